@@ -0,0 +1,59 @@
+//! A plugin adding `repeat(text, times)`. Build it with `cargo build
+//! --example plugin` and load it with
+//! `lox run --plugin=target/debug/examples/libplugin.so prog.lox`
+//! (`.dylib` on macOS).
+
+use std::cell::RefCell;
+use std::ffi::{c_int, c_void};
+use std::slice;
+
+use lox::plugin::{PluginApi, PluginValue, API_VERSION, KIND_NUMBER, KIND_STRING};
+
+thread_local! {
+    /// What the last call returned, which has to outlive it
+    static RESULT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// # Safety
+///
+/// Called by the interpreter, as `plugin::PluginFn` says.
+unsafe extern "C" fn repeat(
+    _data: *mut c_void,
+    args: *const PluginValue,
+    count: usize,
+    result: *mut PluginValue,
+) -> bool {
+    let args = slice::from_raw_parts(args, count);
+    let repeated = match (args[0].kind, args[1].kind) {
+        (KIND_STRING, KIND_NUMBER) if args[1].number >= 0.0 => {
+            let text = args[0].text().unwrap_or_default();
+            Ok(text.repeat(args[1].number as usize))
+        }
+        _ => Err("repeat() takes a string and a count.".to_string()),
+    };
+    let ok = repeated.is_ok();
+    RESULT.with(|last| {
+        *last.borrow_mut() = repeated.unwrap_or_else(|err| err);
+        *result = PluginValue::string(&last.borrow());
+    });
+    ok
+}
+
+/// # Safety
+///
+/// Called by the interpreter with a valid `api`.
+#[no_mangle]
+pub unsafe extern "C" fn lox_plugin_init(api: *const PluginApi) -> c_int {
+    let api = &*api;
+    if api.version != API_VERSION {
+        return 1;
+    }
+    (api.define)(
+        api.host,
+        b"repeat\0".as_ptr().cast(),
+        2,
+        repeat,
+        std::ptr::null_mut(),
+    );
+    0
+}
@@ -0,0 +1,38 @@
+//! Feeds arbitrary bytes through every stage that runs before a program
+//! does: scanning, parsing, resolving and compiling to bytecode. None of
+//! them may panic, whatever the input.
+//!
+//! Run with `cargo fuzz run scan_parse` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox::format::Format;
+use lox::printer::AstStyle;
+use lox::{disassemble, parse, print_ast, tokenize, Options, Output};
+
+fuzz_target!(|data: &[u8]| {
+    // The CLI decodes before scanning, rejecting invalid UTF-8 unless
+    // --encoding=latin1 is given, so the scanner only ever sees text
+    let source = String::from_utf8_lossy(data);
+
+    for format in [Format::Text, Format::Json] {
+        let options = Options {
+            format,
+            ..Options::default()
+        };
+        tokenize(&source, &options, &mut Output::default());
+        parse(&source, &options, &mut Output::default());
+    }
+
+    for ast_style in [AstStyle::Sexpr, AstStyle::Indent] {
+        let options = Options {
+            ast_style,
+            ..Options::default()
+        };
+        print_ast(&source, &options, &mut Output::default());
+    }
+
+    // Resolves and compiles without running, so looping programs are fine
+    disassemble(&source, &Options::default(), &mut Output::default());
+});
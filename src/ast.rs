@@ -0,0 +1,381 @@
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+
+use crate::string::LoxString;
+use crate::token::Token;
+
+/// An expression in an `ExprArena`, which is where the tree keeps its
+/// expressions: a node refers to its operands, and a statement to its
+/// expressions, by their ids rather than owning them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+/// The expressions of everything parsed by one `Parser`, in the order they
+/// were made. Allocating a node is a push onto one vector, not an allocation of its
+/// own, and an expression's operands are usually next to it in memory.
+///
+/// Functions keep the arena of the source that declared them alive, so it
+/// lasts as long as any of its code can still run.
+#[derive(Debug, Clone, Default)]
+pub struct ExprArena {
+    exprs: Vec<Expr>,
+}
+
+impl ExprId {
+    /// Where the expression is in its arena, for tables kept alongside it.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl ExprArena {
+    pub fn new() -> Self {
+        ExprArena::default()
+    }
+
+    /// An arena with room for `capacity` expressions before it grows.
+    pub fn with_capacity(capacity: usize) -> Self {
+        ExprArena {
+            exprs: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Adds `expr` to the arena, returning its id.
+    pub fn alloc(&mut self, expr: Expr) -> ExprId {
+        let id = ExprId(self.exprs.len() as u32);
+        self.exprs.push(expr);
+        id
+    }
+
+    /// Takes the expression at `id` out of the arena to rewrite it, leaving
+    /// `nil` in its place until the rewritten one is put back.
+    pub fn take(&mut self, id: ExprId) -> Expr {
+        std::mem::replace(&mut self[id], Expr::Literal(Literal::Nil))
+    }
+
+    /// How many expressions the arena holds, including any no longer part
+    /// of the tree.
+    pub fn len(&self) -> usize {
+        self.exprs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exprs.is_empty()
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for ExprArena {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0 as usize]
+    }
+}
+
+/// Expression AST. Its operands are in the `ExprArena` it belongs to.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(Literal),
+    Grouping(ExprId),
+    Variable(Token),
+    Assign {
+        name: Token,
+        value: ExprId,
+    },
+    Unary {
+        operator: Token,
+        right: ExprId,
+    },
+    Binary {
+        left: ExprId,
+        operator: Token,
+        right: ExprId,
+    },
+    Call {
+        callee: ExprId,
+        /// The closing parenthesis, whose line runtime errors report
+        paren: Token,
+        arguments: Vec<ExprId>,
+    },
+    /// Property access, `object.name`
+    Get {
+        object: ExprId,
+        name: Token,
+    },
+    /// Property assignment, `object.name = value`
+    Set {
+        object: ExprId,
+        name: Token,
+        value: ExprId,
+    },
+    /// A list literal, `[a, b]`
+    List {
+        /// The opening bracket
+        bracket: Token,
+        elements: Vec<ExprId>,
+    },
+    /// A map literal, `{key: value, ...}`
+    Map {
+        /// The opening brace
+        brace: Token,
+        entries: Vec<(ExprId, ExprId)>,
+    },
+    /// Indexing a list or a map, `object[index]`
+    Index {
+        object: ExprId,
+        /// The closing bracket, like a call's `paren`
+        bracket: Token,
+        index: ExprId,
+    },
+    /// Assignment to an element or entry, `object[index] = value`
+    SetIndex {
+        object: ExprId,
+        bracket: Token,
+        index: ExprId,
+        value: ExprId,
+    },
+    This(Token),
+    /// `super.method`
+    Super {
+        keyword: Token,
+        method: Token,
+    },
+    /// `and` / `or`, kept apart from `Binary` because they short-circuit
+    Logical {
+        left: ExprId,
+        operator: Token,
+        right: ExprId,
+    },
+    /// Stands in for an expression that failed to parse, at the token where
+    /// one was expected. Only trees from a tolerant parse contain it.
+    Error(Token),
+}
+
+impl Expr {
+    /// The token the expression is reported at: its name, operator, closing
+    /// bracket or keyword. `None` for a literal, which keeps no token.
+    pub fn token<'a>(&'a self, arena: &'a ExprArena) -> Option<&'a Token> {
+        match self {
+            Expr::Literal(_) => None,
+            Expr::Grouping(inner) => arena[*inner].token(arena),
+            Expr::Variable(name)
+            | Expr::Assign { name, .. }
+            | Expr::Get { name, .. }
+            | Expr::Set { name, .. } => Some(name),
+            Expr::Unary { operator, .. }
+            | Expr::Binary { operator, .. }
+            | Expr::Logical { operator, .. } => Some(operator),
+            Expr::Call { paren, .. } => Some(paren),
+            Expr::List { bracket, .. }
+            | Expr::Index { bracket, .. }
+            | Expr::SetIndex { bracket, .. } => Some(bracket),
+            Expr::Map { brace, .. } => Some(brace),
+            Expr::This(keyword) | Expr::Super { keyword, .. } => Some(keyword),
+            Expr::Error(token) => Some(token),
+        }
+    }
+}
+
+/// Statement AST. Its expressions are in an `ExprArena`, like those of
+/// `Expr`.
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expression(ExprId),
+    Print {
+        keyword: Token,
+        value: ExprId,
+    },
+    Var {
+        name: Token,
+        /// The type after `var name:`, see `typecheck`
+        annotation: Option<Token>,
+        initializer: Option<ExprId>,
+    },
+    Block(Vec<Stmt>),
+    If {
+        condition: ExprId,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    While {
+        condition: ExprId,
+        body: Box<Stmt>,
+        /// A desugared `for` loop's increment, run after the body even when
+        /// the body ends with `continue`
+        increment: Option<ExprId>,
+    },
+    Break(Token),
+    Continue(Token),
+    Function(Rc<FunctionDecl>),
+    Class {
+        name: Token,
+        /// Always an `Expr::Variable` when present
+        superclass: Option<ExprId>,
+        /// The classes after `with`, whose methods come before the
+        /// superclass's. Always `Expr::Variable`s.
+        mixins: Vec<ExprId>,
+        methods: Vec<Rc<FunctionDecl>>,
+        /// Methods declared with `class`, called on the class itself
+        class_methods: Vec<Rc<FunctionDecl>>,
+    },
+    Return {
+        keyword: Token,
+        value: Option<ExprId>,
+    },
+    /// `import "path";`, which binds the module at `path`, a string
+    /// token, to its file name without the extension
+    Import {
+        keyword: Token,
+        path: Token,
+    },
+    /// `throw value;`, which unwinds to the nearest `catch`
+    Throw {
+        keyword: Token,
+        value: ExprId,
+    },
+    /// `try { ... } catch (e) { ... } finally { ... }`, with at least one
+    /// of `catch` and `finally`
+    Try {
+        keyword: Token,
+        body: Vec<Stmt>,
+        catch: Option<CatchClause>,
+        finally: Option<Vec<Stmt>>,
+    },
+    /// `switch (subject) { case value: ... default: ... }`. Runs the body
+    /// of the first case whose value equals the subject, or of `default`
+    /// if none does; there's no fallthrough.
+    Switch {
+        keyword: Token,
+        subject: ExprId,
+        cases: Vec<SwitchCase>,
+        default: Option<Vec<Stmt>>,
+    },
+    /// Stands in for a declaration that failed to parse, at its first
+    /// token. Only trees from a tolerant parse contain it.
+    Error(Token),
+}
+
+impl Stmt {
+    /// The token the statement starts at, as far as it keeps one: its
+    /// keyword or name, or the token of its expression or condition. `None`
+    /// for a block, or for an expression or condition that's a literal.
+    /// `arena` is the one its expressions are in.
+    pub fn token<'a>(&'a self, arena: &'a ExprArena) -> Option<&'a Token> {
+        match self {
+            Stmt::Expression(expr) => arena[*expr].token(arena),
+            Stmt::If { condition, .. } | Stmt::While { condition, .. } => {
+                arena[*condition].token(arena)
+            }
+            Stmt::Print { keyword, .. }
+            | Stmt::Return { keyword, .. }
+            | Stmt::Import { keyword, .. }
+            | Stmt::Throw { keyword, .. }
+            | Stmt::Try { keyword, .. }
+            | Stmt::Switch { keyword, .. } => Some(keyword),
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } => Some(name),
+            Stmt::Function(declaration) => Some(&declaration.name),
+            Stmt::Break(token) | Stmt::Continue(token) | Stmt::Error(token) => Some(token),
+            Stmt::Block(_) => None,
+        }
+    }
+}
+
+/// The `catch (name) { ... }` of a `try`, which runs with the thrown
+/// value bound to `name`.
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+    pub name: Token,
+    pub body: Vec<Stmt>,
+}
+
+/// One `case value: ...` of a `switch`.
+#[derive(Debug, Clone)]
+pub struct SwitchCase {
+    pub value: ExprId,
+    pub body: Vec<Stmt>,
+}
+
+/// A `fun` declaration. Shared, since every function value created from it
+/// points back at the same declaration.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    /// The type each parameter is annotated with, if any, see `typecheck`
+    pub param_types: Vec<Option<Token>>,
+    /// The type after `->`
+    pub return_type: Option<Token>,
+    pub body: Vec<Stmt>,
+    /// A method declared without a parameter list, which runs when the
+    /// property is read rather than being returned to call
+    pub is_getter: bool,
+}
+
+impl FunctionDecl {
+    /// `name(a, b: number) -> bool`, as declared, or just `name` for a
+    /// getter.
+    pub fn signature(&self) -> String {
+        if self.is_getter {
+            return self.name.lexeme.to_string();
+        }
+        let params: Vec<String> = (0..self.params.len())
+            .map(|index| self.param(index))
+            .collect();
+        let signature = format!("{}({})", self.name.lexeme, params.join(", "));
+        match &self.return_type {
+            Some(return_type) => format!("{} -> {}", signature, return_type.lexeme),
+            None => signature,
+        }
+    }
+
+    /// Parameter `index`, with its annotation if it has one.
+    pub fn param(&self, index: usize) -> String {
+        let name = &self.params[index].lexeme;
+        match &self.param_types[index] {
+            Some(annotation) => format!("{}: {}", name, annotation.lexeme),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// `class Name < Superclass with A, B`, as the class was declared.
+pub fn class_signature(
+    arena: &ExprArena,
+    name: &Token,
+    superclass: Option<ExprId>,
+    mixins: &[ExprId],
+) -> String {
+    let mut signature = match superclass.map(|superclass| &arena[superclass]) {
+        Some(Expr::Variable(superclass)) => {
+            format!("class {} < {}", name.lexeme, superclass.lexeme)
+        }
+        _ => format!("class {}", name.lexeme),
+    };
+    let mixins: Vec<&str> = mixins
+        .iter()
+        .filter_map(|&mixin| match &arena[mixin] {
+            Expr::Variable(mixin) => Some(mixin.lexeme.as_str()),
+            _ => None,
+        })
+        .collect();
+    if !mixins.is_empty() {
+        signature = format!("{} with {}", signature, mixins.join(", "));
+    }
+    signature
+}
+
+/// A constant written in the source. What it evaluates to is a
+/// `value::Value`.
+#[derive(Debug, Clone)]
+pub enum Literal {
+    Bool(bool),
+    Nil,
+    Number(f64),
+    Str(LoxString),
+}
@@ -0,0 +1,53 @@
+//! `captures`: lists what every function captures, the local variables of
+//! the functions around it that it uses, and whether it only reads each
+//! one or assigns it too. Closures keep those variables alive, and the VM
+//! compiles them to upvalues.
+
+use std::fmt::Write;
+
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::status::ExitStatus;
+use crate::{scan_tokens, Options, Output};
+
+/// Writes each function, in the order they're declared, with the variables
+/// it captures in the order it first uses them. A variable a nested
+/// function uses is captured by the functions around it too, which have to
+/// keep it for the nested one. Globals are looked up by name wherever
+/// they're used, so they're never captured.
+pub fn captures(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let tokens = match scan_tokens(source, options) {
+        Ok(tokens) => tokens,
+        Err(status) => return status,
+    };
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let Some(statements) = parser.parse_program() else {
+        return ExitStatus::from_diagnostics(parser.diagnostics);
+    };
+
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.keep_captures();
+    resolver.resolve(&parser.arena, &statements);
+    if resolver.diagnostics.has_errors() {
+        return ExitStatus::from_diagnostics(resolver.diagnostics);
+    }
+
+    for captures in &resolver.captures {
+        let function = &captures.function;
+        let code = if captures.variables.is_empty() {
+            "E0051"
+        } else {
+            "E0052"
+        };
+        writeln!(out.stdout, "{}", msg!(code, function.lexeme, function.line)).unwrap();
+        for capture in &captures.variables {
+            let variable = &capture.declaration;
+            let code = if capture.mutated { "E0054" } else { "E0053" };
+            writeln!(out.stdout, "{}", msg!(code, variable.lexeme, variable.line)).unwrap();
+        }
+    }
+    ExitStatus::Success
+}
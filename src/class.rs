@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::function::{LoxCallable, LoxFunction};
+use crate::gc::{self, Trace};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::suggest;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::value::Value;
+use crate::Output;
+
+/// A class declared in Lox. Calling it creates an instance.
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    /// The classes after `with`, whose methods are found before the
+    /// superclass's
+    mixins: Vec<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+    /// Methods declared with `class`, called on the class itself
+    class_methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        mixins: Vec<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+        class_methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        LoxClass {
+            name,
+            superclass,
+            mixins,
+            methods,
+            class_methods,
+        }
+    }
+
+    /// Finds a method on this class, or else the first of its mixins with
+    /// it, or else the nearest superclass with it.
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        if let Some(method) = self.methods.get(&name) {
+            return Some(Rc::clone(method));
+        }
+        self.mixins
+            .iter()
+            .find_map(|mixin| mixin.find_method(name))
+            .or_else(|| self.superclass.as_ref()?.find_method(name))
+    }
+
+    /// The name of every method `find_method` can find.
+    pub(crate) fn method_names(&self) -> HashSet<Symbol> {
+        let mut names: HashSet<Symbol> = self.methods.keys().copied().collect();
+        for class in self.mixins.iter().chain(&self.superclass) {
+            names.extend(class.method_names());
+        }
+        names
+    }
+
+    /// A method two of the mixins have different versions of, which the
+    /// class doesn't settle by defining its own: its name and the indexes
+    /// of the two mixins. Picking the first mixin's would silently depend
+    /// on the order they're listed in.
+    pub fn mixin_conflict(&self) -> Option<(Symbol, usize, usize)> {
+        let names: Vec<_> = self
+            .mixins
+            .iter()
+            .map(|mixin| mixin.method_names())
+            .collect();
+        let mut conflicts = Vec::new();
+        for first in 0..self.mixins.len() {
+            for second in first + 1..self.mixins.len() {
+                for &name in names[first].intersection(&names[second]) {
+                    let found = |index: usize| self.mixins[index].find_method(name);
+                    let (earlier, later) = (found(first), found(second));
+                    let same = earlier.zip(later).is_some_and(|(a, b)| Rc::ptr_eq(&a, &b));
+                    if !same && !self.methods.contains_key(&name) {
+                        conflicts.push((name, first, second));
+                    }
+                }
+            }
+        }
+        // The same one from run to run, whatever order the sets were in
+        conflicts
+            .into_iter()
+            .min_by_key(|&(name, first, second)| (first, second, name.as_str()))
+    }
+
+    /// Finds a class method on this class, or else the nearest superclass
+    /// with it, so subclasses inherit them too.
+    pub fn find_class_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        match self.class_methods.get(&name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_class_method(name),
+        }
+    }
+
+    /// The name of every method `find_class_method` can find.
+    pub(crate) fn class_method_names(&self) -> HashSet<Symbol> {
+        let mut names: HashSet<Symbol> = self.class_methods.keys().copied().collect();
+        if let Some(superclass) = &self.superclass {
+            names.extend(superclass.class_method_names());
+        }
+        names
+    }
+
+    /// Reads a property of the class itself: a class method bound to it.
+    pub fn get(class: &Rc<LoxClass>, name: &Token) -> Result<Value, RuntimeError> {
+        match class.find_class_method(name.lexeme.symbol()) {
+            Some(method) => Ok(Value::Function(gc::tracked(
+                method.bind(Value::Class(Rc::clone(class))),
+            ))),
+            None => Err(undefined_property(name, class.class_method_names())),
+        }
+    }
+}
+
+/// The error for there being no property `name`, suggesting the closest
+/// of the `names` there are.
+pub(crate) fn undefined_property(
+    name: &Token,
+    names: impl IntoIterator<Item = Symbol>,
+) -> RuntimeError {
+    let message = msg!("E0309", name.lexeme);
+    let candidates = names.into_iter().map(Symbol::as_str);
+    RuntimeError::new(
+        name,
+        suggest::did_you_mean(message, &name.lexeme, candidates),
+    )
+}
+
+impl LoxCallable for Rc<LoxClass> {
+    /// A class takes whatever its `init` method takes, or nothing without one.
+    fn arity(&self) -> usize {
+        self.find_method(Symbol::intern("init"))
+            .map_or(0, |init| init.arity())
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        let instance = gc::tracked(RefCell::new(LoxInstance::new(Rc::clone(self))));
+        if let Some(init) = self.find_method(Symbol::intern("init")) {
+            init.bind(Value::Instance(Rc::clone(&instance))).call(
+                interpreter,
+                arguments,
+                paren,
+                out,
+            )?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An instance of a class, holding its own fields.
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<Symbol, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Reads a property: a field if there is one, otherwise a method bound
+    /// to `instance`. A getter is returned bound too, for the caller to run.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme.symbol()) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.lexeme.symbol());
+        match method {
+            Some(method) => {
+                let this = Value::Instance(Rc::clone(instance));
+                Ok(Value::Function(gc::tracked(method.bind(this))))
+            }
+            None => {
+                let instance = instance.borrow();
+                let fields = instance.fields.keys().copied();
+                Err(undefined_property(
+                    name,
+                    fields.chain(instance.class.method_names()),
+                ))
+            }
+        }
+    }
+
+    /// Sets a field, creating it if needed.
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.symbol(), value);
+    }
+}
+
+// Written by hand: fields can refer back to the instance itself
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoxInstance")
+            .field("class", &self.class.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl Trace for LoxClass {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        for class in self.superclass.iter().chain(&self.mixins) {
+            edge(gc::address(class));
+        }
+        for method in self.methods.values().chain(self.class_methods.values()) {
+            edge(gc::address(method));
+        }
+        true
+    }
+}
+
+impl Trace for RefCell<LoxInstance> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(instance) = self.try_borrow() else {
+            return false;
+        };
+        edge(gc::address(&instance.class));
+        for value in instance.fields.values() {
+            value.trace(edge);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            let fields = std::mem::take(&mut instance.fields);
+            drop(instance);
+            drop(fields);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Line coverage for `run --coverage`. The interpreter counts how many
+//! times each statement runs, keyed by the offset of the token it starts
+//! at, and the counts are grouped by file and line into an lcov or HTML
+//! report.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::ast::{Expr, ExprArena, Stmt};
+use crate::token::Token;
+use crate::visit::{walk_stmt, Visitor};
+
+/// Which report `--coverage` writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageFormat {
+    /// The `.info` tracefile `lcov` and `genhtml` read
+    Lcov,
+    /// A page showing each file with the lines that ran highlighted
+    Html,
+}
+
+/// How many times each line with a statement on it ran, by line number.
+pub type LineHits = BTreeMap<usize, usize>;
+
+/// The statements of the programs being covered, with how many times each
+/// has run.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// By the offset of each statement's token, its line and run count
+    statements: BTreeMap<usize, (usize, usize)>,
+}
+
+impl Coverage {
+    /// Adds the statements of a program, and those nested in them, with a
+    /// count of zero. Blocks themselves aren't counted, only what's in
+    /// them.
+    pub fn add(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        self.visit_stmts(arena, statements);
+    }
+
+    /// Counts a run of the statement at `token`.
+    pub fn hit(&mut self, token: &Token) {
+        self.statements
+            .entry(token.span.start)
+            .or_insert((token.line, 0))
+            .1 += 1;
+    }
+
+    /// The counts by file, naming the file of each offset with `file_of`.
+    /// A line with several statements counts as run as often as the one
+    /// that ran most.
+    pub fn by_file(&self, file_of: impl Fn(usize) -> String) -> BTreeMap<String, LineHits> {
+        let mut files: BTreeMap<String, LineHits> = BTreeMap::new();
+        for (&offset, &(line, hits)) in &self.statements {
+            let lines = files.entry(file_of(offset)).or_default();
+            let count = lines.entry(line).or_default();
+            *count = (*count).max(hits);
+        }
+        files
+    }
+}
+
+impl Visitor for Coverage {
+    fn visit_stmt(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        if let Some(token) = stmt.token(arena) {
+            self.statements
+                .entry(token.span.start)
+                .or_insert((token.line, 0));
+        }
+        walk_stmt(self, arena, stmt);
+    }
+
+    /// Expressions hold no statements.
+    fn visit_expr(&mut self, _arena: &ExprArena, _expr: &Expr) {}
+}
+
+/// The report as an lcov tracefile: a record per file with a `DA:` line
+/// for each line with a statement.
+pub fn lcov(files: &BTreeMap<String, LineHits>) -> String {
+    let mut report = String::from("TN:\n");
+    for (file, lines) in files {
+        writeln!(report, "SF:{}", file).unwrap();
+        for (line, hits) in lines {
+            writeln!(report, "DA:{},{}", line, hits).unwrap();
+        }
+        writeln!(report, "LF:{}", lines.len()).unwrap();
+        writeln!(report, "LH:{}", covered(lines)).unwrap();
+        report.push_str("end_of_record\n");
+    }
+    report
+}
+
+/// The report as an HTML page listing each file's source, with the lines
+/// that ran in green and those that didn't in red. `source_of` gives a
+/// file's text; a file without one is listed by its counts alone.
+pub fn html(
+    files: &BTreeMap<String, LineHits>,
+    source_of: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Lox coverage</title>\n<style>\n\
+         pre { line-height: 1.3; }\n\
+         .hit { background: #dfd; }\n\
+         .miss { background: #fdd; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    for (file, lines) in files {
+        let summary = msg!("E0042", covered(lines), lines.len());
+        writeln!(page, "<h2>{} ({})</h2>", escape(file), summary).unwrap();
+        page.push_str("<pre>\n");
+        let source = source_of(file).unwrap_or_default();
+        let texts: Vec<&str> = source.lines().collect();
+        let last = lines.keys().next_back().copied().unwrap_or(0);
+        for line in 1..=texts.len().max(last) {
+            let (class, hits) = match lines.get(&line) {
+                Some(0) => (" class=\"miss\"", "0".to_string()),
+                Some(hits) => (" class=\"hit\"", hits.to_string()),
+                None => ("", String::new()),
+            };
+            let text = escape(texts.get(line - 1).copied().unwrap_or(""));
+            writeln!(
+                page,
+                "<span{}>{:>5} {:>6} | {}</span>",
+                class, line, hits, text
+            )
+            .unwrap();
+        }
+        page.push_str("</pre>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+/// How many of `lines` ran at least once.
+fn covered(lines: &LineHits) -> usize {
+    lines.values().filter(|&&hits| hits > 0).count()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,516 @@
+//! A debug adapter for editors, speaking the Debug Adapter Protocol over
+//! standard input and output. Started with `lox dap`.
+//!
+//! The editor launches a program by its path, and it runs on the
+//! tree-walker under a `DebugHook` that stops at breakpoints and steps the
+//! way `lox debug` does. While it's stopped, the adapter answers requests
+//! for the calls in progress and the variables in scope. The program runs
+//! on the thread reading requests, so the ones sent while it runs, like
+//! `pause`, are only answered when it next stops or ends. Messages are
+//! framed and written like the language server's, see `lsp`.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, BufRead, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::debugger::{DebugHook, Mode, Stops};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lsp::json::{object, Json};
+use crate::lsp::{read_message, write_message};
+use crate::token::Token;
+use crate::value::Value;
+use crate::{execute_program, resolve_program, ExitStatus, Options, Output};
+
+/// The one thread a Lox program runs on
+const THREAD: usize = 1;
+/// The `variablesReference` of the innermost call's scopes, and of the
+/// globals
+const LOCALS: usize = 1;
+const GLOBALS: usize = 2;
+
+/// The adapter's end of the connection. Requests are taken by `serve`
+/// before the program runs and after it ends, and by `Session` while it's
+/// stopped; what it prints is sent as it's printed.
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    /// The `seq` of the last message sent
+    seq: usize,
+    /// Set when the editor ended the session while the program was stopped
+    disconnected: bool,
+}
+
+impl Connection {
+    /// The next request, skipping messages that aren't JSON. `None` when
+    /// the input has ended.
+    fn read(&mut self) -> io::Result<Option<Json>> {
+        while let Some(body) = read_message(&mut self.input)? {
+            if let Some(message) = Json::parse(&body) {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    fn send(&mut self, kind: &str, members: Vec<(String, Json)>) -> io::Result<()> {
+        self.seq += 1;
+        let mut message = vec![
+            ("seq".to_string(), self.seq.into()),
+            ("type".to_string(), kind.into()),
+        ];
+        message.extend(members);
+        write_message(&mut self.output, &Json::Object(message))
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        let members = response(request, true);
+        self.send(
+            "response",
+            [members, vec![("body".to_string(), body)]].concat(),
+        )
+    }
+
+    /// Answers `request` with a failure showing `message`.
+    fn fail(&mut self, request: &Json, message: String) -> io::Result<()> {
+        let members = response(request, false);
+        self.send(
+            "response",
+            [members, vec![("message".to_string(), message.into())]].concat(),
+        )
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        let members = vec![
+            ("event".to_string(), event.into()),
+            ("body".to_string(), body),
+        ];
+        self.send("event", members)
+    }
+
+    /// Sends `text` for the editor to show as the program's output.
+    fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+        let body = object([("category", category.into()), ("output", text.into())]);
+        self.event("output", body)
+    }
+}
+
+/// The members of a response to `request` besides its body.
+fn response(request: &Json, success: bool) -> Vec<(String, Json)> {
+    let seq = request.get("seq").cloned().unwrap_or(Json::Null);
+    let command = request.get("command").cloned().unwrap_or(Json::Null);
+    vec![
+        ("request_seq".to_string(), seq),
+        ("success".to_string(), success.into()),
+        ("command".to_string(), command),
+    ]
+}
+
+/// The program `launch` asked for: its path and source, and whether to
+/// stop before its first statement.
+struct Launch {
+    path: PathBuf,
+    source: String,
+    stop_on_entry: bool,
+}
+
+/// Serves requests read from `input` until the editor disconnects or the
+/// input ends, writing responses and events to `output`. `options` are
+/// those the program runs with, as for `lox run`.
+pub fn serve(
+    input: impl BufRead + 'static,
+    output: impl Write + 'static,
+    options: &Options,
+) -> io::Result<()> {
+    let shared = Rc::new(RefCell::new(Connection {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+        disconnected: false,
+    }));
+    let mut launch = None;
+    let mut configured = false;
+    let mut ran = false;
+    // By the path the editor gave, until the program starts
+    let mut breakpoints: Vec<(String, BTreeSet<usize>)> = Vec::new();
+    loop {
+        let Some(request) = shared.borrow_mut().read()? else {
+            return Ok(());
+        };
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let arguments = request.get("arguments").unwrap_or(&Json::Null);
+        let mut connection = shared.borrow_mut();
+        match command {
+            "initialize" => {
+                let capabilities = object([
+                    ("supportsConfigurationDoneRequest", true.into()),
+                    ("supportsTerminateRequest", true.into()),
+                ]);
+                connection.respond(&request, capabilities)?;
+                connection.event("initialized", object([]))?;
+            }
+            "launch" => {
+                let Some(path) = arguments.get("program").and_then(Json::as_str) else {
+                    connection.fail(&request, msg!("E0064"))?;
+                    continue;
+                };
+                let Ok(source) = fs::read_to_string(path) else {
+                    connection.fail(&request, msg!("E0006", path))?;
+                    continue;
+                };
+                launch = Some(Launch {
+                    path: PathBuf::from(path),
+                    source,
+                    stop_on_entry: arguments.get("stopOnEntry") == Some(&Json::Bool(true)),
+                });
+                connection.respond(&request, object([]))?;
+            }
+            "setBreakpoints" => {
+                let (path, lines) = requested_breakpoints(arguments);
+                connection.respond(&request, verified(&lines))?;
+                breakpoints.retain(|(other, _)| *other != path);
+                breakpoints.push((path, lines));
+            }
+            "configurationDone" => {
+                configured = true;
+                connection.respond(&request, object([]))?;
+            }
+            "threads" => connection.respond(&request, threads())?,
+            "disconnect" => return connection.respond(&request, object([])),
+            "terminate" => {
+                connection.respond(&request, object([]))?;
+                connection.event("terminated", object([]))?;
+            }
+            "stackTrace" | "scopes" | "variables" | "continue" | "next" | "stepIn" | "stepOut"
+            | "pause" => connection.fail(&request, msg!("E0063", command))?,
+            _ => connection.fail(&request, msg!("E0062", command))?,
+        }
+        drop(connection);
+
+        if let (Some(launch), true, false) = (&launch, configured, ran) {
+            ran = true;
+            let lines = breakpoints
+                .iter()
+                .filter(|(path, _)| same_file(Path::new(path), &launch.path))
+                .flat_map(|(_, lines)| lines.iter().copied())
+                .collect();
+            debug(launch, lines, options, &shared)?;
+            if shared.borrow().disconnected {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Runs the program under a `Session`, sending what it prints and the
+/// errors it ends with, then the events saying it has ended.
+fn debug(
+    launch: &Launch,
+    breakpoints: BTreeSet<usize>,
+    options: &Options,
+    connection: &Rc<RefCell<Connection>>,
+) -> io::Result<()> {
+    let options = Options {
+        path: Some(launch.path.clone()),
+        ..options.clone()
+    };
+    let mut out = Output::default();
+    let status = match resolve_program(&launch.source, &options, &mut out) {
+        Ok((arena, statements, mut interpreter)) => {
+            let mode = if launch.stop_on_entry {
+                Mode::Step
+            } else {
+                Mode::Continue
+            };
+            let mut stops = Stops::new(mode);
+            stops.breakpoints = breakpoints;
+            interpreter.set_debugger(Session {
+                connection: Rc::clone(connection),
+                stops,
+                name: launch.path.display().to_string(),
+                entry: launch.stop_on_entry,
+            });
+            interpreter.set_output(LineWriter::new(ProgramOutput(Rc::clone(connection))));
+            let status = execute_program(&mut interpreter, &arena, &statements, &mut out);
+            // Sending the last of what it printed
+            drop(interpreter);
+            status
+        }
+        Err(status) => status,
+    };
+
+    let mut connection = connection.borrow_mut();
+    if connection.disconnected {
+        return Ok(());
+    }
+    let mut errors = String::new();
+    match &status {
+        ExitStatus::CompileError(diagnostics) | ExitStatus::Warnings(diagnostics) => {
+            for diagnostic in diagnostics {
+                errors.push_str(&format!("{}\n", diagnostic));
+            }
+        }
+        ExitStatus::RuntimeError(error) if error.message != msg!("E0335") => {
+            errors.push_str(&format!("{}\n", error));
+        }
+        _ => {}
+    }
+    if !errors.is_empty() {
+        connection.output("stderr", &errors)?;
+    }
+    let exit_code = object([("exitCode", Json::Number(status.exit_code().into()))]);
+    connection.event("exited", exit_code)?;
+    connection.event("terminated", object([]))
+}
+
+/// Sends what the program prints as `output` events, a line or more at a
+/// time behind a `LineWriter`.
+struct ProgramOutput(Rc<RefCell<Connection>>);
+
+impl Write for ProgramOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.borrow_mut().output("stdout", &text)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stops the program where the editor asks to and answers its requests
+/// while it's stopped.
+struct Session {
+    connection: Rc<RefCell<Connection>>,
+    stops: Stops,
+    /// The name the interpreter knows the program by, which breakpoints
+    /// are in; not the modules it imports
+    name: String,
+    /// Whether the next stop is the one `stopOnEntry` asked for
+    entry: bool,
+}
+
+impl DebugHook for Session {
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        token: &Token,
+    ) -> Result<(), RuntimeError> {
+        let depth = interpreter.depth();
+        let in_program = interpreter.source_name(token.span.start) == self.name;
+        let at_breakpoint = in_program && self.stops.breakpoints.contains(&token.line);
+        if !self.stops.should_stop(token.line, depth, in_program) {
+            return Ok(());
+        }
+        let reason = if std::mem::take(&mut self.entry) {
+            "entry"
+        } else if at_breakpoint {
+            "breakpoint"
+        } else {
+            "step"
+        };
+        let stopped = object([
+            ("reason", reason.into()),
+            ("threadId", THREAD.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        // If the editor can't be told, there's no one to wait for
+        let mut connection = self.connection.borrow_mut();
+        if connection.event("stopped", stopped).is_err() {
+            return self.quit(&mut connection, token);
+        }
+
+        loop {
+            let request = match connection.read() {
+                Ok(Some(request)) => request,
+                Ok(None) | Err(_) => return self.quit(&mut connection, token),
+            };
+            let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+            let arguments = request.get("arguments").unwrap_or(&Json::Null);
+            let resume = match command {
+                "continue" => Some(Mode::Continue),
+                "next" => Some(Mode::Next { depth }),
+                "stepIn" => Some(Mode::Step),
+                "stepOut" => Some(Mode::Next {
+                    depth: depth.saturating_sub(1),
+                }),
+                _ => None,
+            };
+            if let Some(mode) = resume {
+                self.stops.mode = mode;
+                let body = object([("allThreadsContinued", true.into())]);
+                _ = connection.respond(&request, body);
+                return Ok(());
+            }
+
+            let sent = match command {
+                "threads" => connection.respond(&request, threads()),
+                "stackTrace" => connection.respond(&request, stack_trace(interpreter, token)),
+                "scopes" => {
+                    let frame = arguments.get("frameId").and_then(Json::as_usize);
+                    connection.respond(&request, scopes(frame == Some(0)))
+                }
+                "variables" => {
+                    let reference = arguments.get("variablesReference").and_then(Json::as_usize);
+                    connection.respond(&request, variables(interpreter, reference))
+                }
+                "setBreakpoints" => {
+                    let (path, lines) = requested_breakpoints(arguments);
+                    let body = verified(&lines);
+                    if same_file(Path::new(&path), Path::new(&self.name)) {
+                        self.stops.breakpoints = lines;
+                    }
+                    connection.respond(&request, body)
+                }
+                "pause" => connection.respond(&request, object([])),
+                "disconnect" | "terminate" => {
+                    _ = connection.respond(&request, object([]));
+                    connection.disconnected = command == "disconnect";
+                    return Err(RuntimeError::new(token, msg!("E0335")));
+                }
+                _ => connection.fail(&request, msg!("E0062", command)),
+            };
+            if sent.is_err() {
+                return self.quit(&mut connection, token);
+            }
+        }
+    }
+}
+
+impl Session {
+    /// Stops the program when the editor has gone.
+    fn quit(&self, connection: &mut Connection, token: &Token) -> Result<(), RuntimeError> {
+        connection.disconnected = true;
+        Err(RuntimeError::new(token, msg!("E0335")))
+    }
+}
+
+/// The path and lines of a `setBreakpoints` request.
+fn requested_breakpoints(arguments: &Json) -> (String, BTreeSet<usize>) {
+    let path = arguments
+        .get("source")
+        .and_then(|source| source.get("path"))
+        .and_then(Json::as_str)
+        .unwrap_or("")
+        .to_string();
+    let lines = match arguments.get("breakpoints") {
+        Some(Json::Array(breakpoints)) => breakpoints
+            .iter()
+            .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_usize))
+            .collect(),
+        _ => BTreeSet::new(),
+    };
+    (path, lines)
+}
+
+/// The body answering `setBreakpoints`: every line taken as asked.
+fn verified(lines: &BTreeSet<usize>) -> Json {
+    let breakpoints = lines
+        .iter()
+        .map(|&line| object([("verified", true.into()), ("line", line.into())]))
+        .collect::<Vec<_>>();
+    object([("breakpoints", breakpoints.into())])
+}
+
+/// Whether two paths name the same file, comparing them as given when
+/// either can't be found.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn threads() -> Json {
+    let thread = object([("id", THREAD.into()), ("name", "main".into())]);
+    object([("threads", vec![thread].into())])
+}
+
+/// The calls in progress, innermost first, each with the line it's at. A
+/// frame's `id` is its place in the list.
+fn stack_trace(interpreter: &Interpreter, token: &Token) -> Json {
+    let frames: Vec<Json> = interpreter
+        .stack_trace(token)
+        .into_iter()
+        .enumerate()
+        .map(|(id, frame)| {
+            let name = Path::new(&frame.file)
+                .file_name()
+                .map_or_else(|| frame.file.clone(), |name| name.to_string_lossy().into());
+            let source = object([("name", name.into()), ("path", frame.file.into())]);
+            object([
+                ("id", id.into()),
+                ("name", frame.function.into()),
+                ("source", source),
+                ("line", frame.line.into()),
+                ("column", 1.into()),
+            ])
+        })
+        .collect();
+    let total = frames.len();
+    object([
+        ("stackFrames", frames.into()),
+        ("totalFrames", total.into()),
+    ])
+}
+
+/// The scopes of a frame. Only the innermost call's: the tree-walker keeps
+/// no scopes for the calls it's inside of.
+fn scopes(innermost: bool) -> Json {
+    let scope = |name: &str, reference: usize| {
+        object([
+            ("name", name.into()),
+            ("variablesReference", reference.into()),
+            ("expensive", false.into()),
+        ])
+    };
+    let scopes = if innermost {
+        vec![scope("Locals", LOCALS), scope("Globals", GLOBALS)]
+    } else {
+        Vec::new()
+    };
+    object([("scopes", scopes.into())])
+}
+
+/// The variables of the `LOCALS` or `GLOBALS` scope: the locals innermost
+/// first, leaving out those shadowed, and the globals the program
+/// declared rather than the natives.
+fn variables(interpreter: &Interpreter, reference: Option<usize>) -> Json {
+    let mut scopes = Vec::new();
+    let mut scope = Some(interpreter.environment());
+    while let Some(environment) = scope {
+        scopes.push(environment.borrow().variables());
+        scope = environment.borrow().enclosing();
+    }
+    let globals = scopes.pop().unwrap_or_default();
+    let variables: Vec<_> = match reference {
+        Some(LOCALS) => {
+            let mut seen = HashSet::new();
+            scopes
+                .into_iter()
+                .flat_map(|variables| variables.into_iter().rev())
+                .filter(|(name, _)| seen.insert(*name))
+                .collect()
+        }
+        Some(GLOBALS) => globals
+            .into_iter()
+            .filter(|(_, value)| !matches!(value, Value::Native(_)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let variables: Vec<Json> = variables
+        .into_iter()
+        .map(|(name, value)| {
+            object([
+                ("name", name.as_str().into()),
+                ("value", value.to_string().into()),
+                ("type", value.type_name().into()),
+                ("variablesReference", 0.into()),
+            ])
+        })
+        .collect();
+    object([("variables", variables.into())])
+}
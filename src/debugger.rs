@@ -0,0 +1,229 @@
+//! An interactive debugger for the tree-walker, which `lox debug` attaches
+//! with `Interpreter::set_debugger`. The interpreter asks it before every
+//! statement whether to stop; when it does, it reads commands until one
+//! resumes the program. `dap` stops the same way for editors.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::token::Token;
+use crate::value::Value;
+
+/// What the interpreter asks before every statement while debugging: the
+/// `Debugger` here for a terminal, or `dap`'s for an editor.
+pub trait DebugHook {
+    /// Called by the interpreter before the statement at `token`. Returns
+    /// once the program should go on, or fails with an error that stops it.
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        token: &Token,
+    ) -> Result<(), RuntimeError>;
+}
+
+/// How the program should run until the next stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Mode {
+    /// Stop at the next statement, in any function
+    Step,
+    /// Stop at the next statement at most `depth` calls deep, stepping
+    /// over calls
+    Next { depth: usize },
+    /// Stop only at breakpoints
+    Continue,
+}
+
+/// Where to stop next: the breakpoints, by line, and how the program was
+/// last resumed.
+pub(crate) struct Stops {
+    pub breakpoints: BTreeSet<usize>,
+    pub mode: Mode,
+    /// The line and call depth of the last statement reached. Statements
+    /// after it on the same line don't stop again, so a line stops once.
+    last: Option<(usize, usize)>,
+}
+
+impl Stops {
+    pub fn new(mode: Mode) -> Self {
+        Stops {
+            breakpoints: BTreeSet::new(),
+            mode,
+            last: None,
+        }
+    }
+
+    /// Whether to stop before a statement on `line`, `depth` calls deep.
+    /// Breakpoints only count where `breakable`.
+    pub fn should_stop(&mut self, line: usize, depth: usize, breakable: bool) -> bool {
+        let here = (line, depth);
+        if self.last == Some(here) {
+            return false;
+        }
+        self.last = Some(here);
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next { depth: deepest } => depth <= deepest,
+            Mode::Continue => false,
+        };
+        stop || breakable && self.breakpoints.contains(&line)
+    }
+}
+
+/// A debugger reading commands from `input` and writing to `output`.
+pub struct Debugger {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    stops: Stops,
+    /// The lines of the file being debugged, to show where it stopped
+    source: Vec<String>,
+    /// The name the interpreter knows that file by
+    name: String,
+}
+
+impl Debugger {
+    /// A debugger for `source`, read from the file `name`. It stops before
+    /// the first statement.
+    pub fn new(
+        source: &str,
+        name: String,
+        input: impl BufRead + 'static,
+        output: impl Write + 'static,
+    ) -> Self {
+        Debugger {
+            input: Box::new(input),
+            output: Box::new(output),
+            stops: Stops::new(Mode::Step),
+            source: source.lines().map(str::to_string).collect(),
+            name,
+        }
+    }
+
+    fn say(&mut self, text: String) {
+        _ = writeln!(self.output, "{}", text);
+    }
+
+    /// `Stopped at file:line`, then the line itself if it's in the file
+    /// being debugged rather than a module it imported.
+    fn show_location(&mut self, interpreter: &Interpreter, token: &Token) {
+        let file = interpreter.source_name(token.span.start);
+        self.say(msg!("E0034", file, token.line));
+        if file == self.name {
+            if let Some(text) = self.source.get(token.line - 1) {
+                let text = format!("{:>5} | {}", token.line, text);
+                self.say(text);
+            }
+        }
+    }
+
+    /// Every variable in scope, innermost scope first and globals last.
+    fn show_variables(&mut self, interpreter: &Interpreter) {
+        let mut scope = Some(interpreter.environment());
+        while let Some(environment) = scope {
+            for (name, value) in environment.borrow().variables() {
+                self.say(format!("{} = {}", name, value));
+            }
+            scope = environment.borrow().enclosing();
+        }
+    }
+}
+
+impl DebugHook for Debugger {
+    /// Stops if a breakpoint or a step says to, and takes commands until
+    /// one resumes. Fails with an error that stops the program on `quit`.
+    fn before_statement(
+        &mut self,
+        interpreter: &Interpreter,
+        token: &Token,
+    ) -> Result<(), RuntimeError> {
+        let depth = interpreter.depth();
+        if !self.stops.should_stop(token.line, depth, true) {
+            return Ok(());
+        }
+
+        self.show_location(interpreter, token);
+        loop {
+            // A prompt that can't be shown isn't worth stopping for
+            _ = write!(self.output, "(lox) ");
+            _ = self.output.flush();
+            let mut line = String::new();
+            // At the end of the input there's no one to ask, so run on
+            if matches!(self.input.read_line(&mut line), Ok(0) | Err(_)) {
+                self.stops.breakpoints.clear();
+                self.stops.mode = Mode::Continue;
+                return Ok(());
+            }
+
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let argument = words.next();
+            match command {
+                "" => {}
+                "s" | "step" => {
+                    self.stops.mode = Mode::Step;
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    self.stops.mode = Mode::Next { depth };
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.stops.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "b" | "break" => match argument.and_then(|line| line.parse().ok()) {
+                    Some(line) => {
+                        self.stops.breakpoints.insert(line);
+                        self.say(msg!("E0035", line));
+                    }
+                    None => self.say(msg!("E0037")),
+                },
+                "d" | "delete" => match argument.and_then(|line| line.parse().ok()) {
+                    Some(line) if self.stops.breakpoints.remove(&line) => {
+                        self.say(msg!("E0036", line))
+                    }
+                    Some(line) => self.say(msg!("E0038", line)),
+                    None => self.say(msg!("E0037")),
+                },
+                "p" | "print" => match argument {
+                    Some(name) => match find_variable(interpreter.environment(), name) {
+                        Some(value) => self.say(format!("{} = {}", name, value)),
+                        None => self.say(msg!("E0304", name)),
+                    },
+                    None => self.say(msg!("E0039")),
+                },
+                "v" | "vars" => self.show_variables(interpreter),
+                "bt" | "backtrace" => {
+                    for frame in interpreter.stack_trace(token) {
+                        self.say(frame.to_string());
+                    }
+                }
+                "l" | "list" => self.show_location(interpreter, token),
+                "h" | "help" => self.say(msg!("E0040")),
+                "q" | "quit" => return Err(RuntimeError::new(token, msg!("E0335"))),
+                _ => self.say(msg!("E0041", command)),
+            }
+        }
+    }
+}
+
+/// The value of the innermost variable called `name`, starting from
+/// `environment`.
+fn find_variable(environment: Rc<RefCell<Environment>>, name: &str) -> Option<Value> {
+    let mut scope = Some(environment);
+    while let Some(environment) = scope {
+        let variables = environment.borrow().variables();
+        if let Some((_, value)) = variables
+            .into_iter()
+            .rev()
+            .find(|(var, _)| var.as_str() == name)
+        {
+            return Some(value);
+        }
+        scope = environment.borrow().enclosing();
+    }
+    None
+}
@@ -0,0 +1,347 @@
+use std::fmt::{self, Write};
+use std::ops::Range;
+use std::vec;
+
+use crate::line_index::LineIndex;
+use crate::token::{Token, TokenType};
+
+/// The stage of handling a source that reported a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading and decoding the file
+    Read,
+    Scan,
+    Parse,
+    Resolve,
+    /// Checking types, see `typecheck`
+    Typecheck,
+    /// Compiling to bytecode
+    Compile,
+    Runtime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The token a parse or resolve diagnostic names, as in `Error at 'x'`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum At {
+    Lexeme(String),
+    /// The end of the source, where there's no lexeme to show
+    End,
+}
+
+/// One problem found in a source, with where it was found. Messages come
+/// from the catalog without a position; `Display` adds it, so every phase
+/// reports in the same form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    /// Boxed, as a lexeme can be any length and most results that could
+    /// hold a diagnostic don't
+    pub at: Option<Box<At>>,
+    /// Byte offsets of the text at fault, when known. Only `render_pretty`
+    /// uses it, to underline more than the first character.
+    pub span: Option<Range<usize>>,
+    pub message: String,
+    /// Related places worth pointing at, such as where an unclosed
+    /// bracket was opened. Only `render_pretty` shows them.
+    pub notes: Vec<Note>,
+    /// For a runtime error inside a function, the calls it happened in,
+    /// innermost first
+    pub trace: Box<[TraceFrame]>,
+}
+
+/// A call a runtime error happened in: the function, and the line of it
+/// that was running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// The function's name, or `<script>` for the top level
+    pub function: String,
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", msg!("E0019", self.function, self.file, self.line))
+    }
+}
+
+/// A remark attached to a diagnostic, about another place in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub line: usize,
+    pub column: usize,
+    pub span: Option<Range<usize>>,
+    pub message: String,
+}
+
+impl Note {
+    /// A note pointing at `token`.
+    pub fn at_token(token: &Token, message: String) -> Self {
+        Note {
+            line: token.line,
+            column: token.column,
+            span: Some(token.span.clone()),
+            message,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn error(phase: Phase, line: usize, column: usize, message: String) -> Self {
+        Diagnostic {
+            phase,
+            severity: Severity::Error,
+            line,
+            column,
+            at: None,
+            span: None,
+            message,
+            notes: Vec::new(),
+            trace: Box::default(),
+        }
+    }
+
+    /// An error found at `token`, naming it: its lexeme, or the end of the
+    /// source for EOF.
+    pub fn at_token(phase: Phase, token: &Token, message: String) -> Self {
+        let at = match token.token_type {
+            TokenType::Eof => At::End,
+            _ => At::Lexeme(token.lexeme.to_string()),
+        };
+        Diagnostic::error(phase, token.line, token.column, message)
+            .at(at)
+            .with_span(token.span.clone())
+    }
+
+    /// The same diagnostic, naming the token it was found at.
+    pub fn at(self, at: At) -> Self {
+        Diagnostic {
+            at: Some(Box::new(at)),
+            ..self
+        }
+    }
+
+    /// The same diagnostic, underlining the bytes in `span`.
+    pub fn with_span(self, span: Range<usize>) -> Self {
+        Diagnostic {
+            span: Some(span),
+            ..self
+        }
+    }
+
+    /// The same diagnostic, with the calls it happened in.
+    pub fn with_trace(self, trace: Vec<TraceFrame>) -> Self {
+        Diagnostic {
+            trace: trace.into(),
+            ..self
+        }
+    }
+
+    /// The same diagnostic with `note` added.
+    pub fn with_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// Renders the diagnostic the way rustc does: the message, the file and
+    /// position, then the source line with the fault underlined, then any
+    /// notes in the same form. Without `source`, as when it couldn't be
+    /// decoded, only the message and position are shown.
+    pub fn render_pretty(&self, source: Option<&str>, filename: &str) -> String {
+        let severity = match self.severity {
+            Severity::Error => msg!("E0015"),
+            Severity::Warning => msg!("E0016"),
+        };
+        let index = source.map(|source| (source, LineIndex::new(source)));
+        let index = index.as_ref().map(|(source, index)| (*source, index));
+
+        let mut out = String::new();
+        writeln!(out, "{}: {}", severity.to_lowercase(), self.message).unwrap();
+        let place = (self.line, self.column, self.span.as_ref());
+        write_snippet(&mut out, index, filename, place);
+        for note in &self.notes {
+            writeln!(out, "{}: {}", msg!("E0017"), note.message).unwrap();
+            let place = (note.line, note.column, note.span.as_ref());
+            write_snippet(&mut out, index, filename, place);
+        }
+        for line in trace_lines(&self.trace) {
+            writeln!(out, "{}", line).unwrap();
+        }
+        // A blank line between diagnostics, as rustc leaves
+        out.push('\n');
+        out
+    }
+}
+
+/// The lines showing `trace`. A call made over and over from the same
+/// line, as runaway recursion leaves a thousand of, is shown a few times
+/// and then counted.
+fn trace_lines(trace: &[TraceFrame]) -> Vec<String> {
+    const SHOWN: usize = 3;
+    let mut lines = Vec::new();
+    let mut frames = trace.iter().peekable();
+    while let Some(frame) = frames.next() {
+        let mut repeats = 1;
+        while frames.next_if_eq(&frame).is_some() {
+            repeats += 1;
+        }
+        for _ in 0..repeats.min(SHOWN) {
+            lines.push(frame.to_string());
+        }
+        if repeats > SHOWN {
+            lines.push(msg!("E0050", repeats - SHOWN));
+        }
+    }
+    lines
+}
+
+/// Writes ` --> file:line:col` and, when the line is in `source`, the line
+/// with a caret under each character of the span (at least one).
+fn write_snippet(
+    out: &mut String,
+    source: Option<(&str, &LineIndex)>,
+    filename: &str,
+    (line, column, span): (usize, usize, Option<&Range<usize>>),
+) {
+    let gutter = " ".repeat(line.to_string().len());
+    writeln!(out, "{}--> {}:{}:{}", gutter, filename, line, column).unwrap();
+
+    let Some((source, index)) = source else {
+        return;
+    };
+    let Some(start) = index.offset(line, column) else {
+        return;
+    };
+    if !source.is_char_boundary(start) {
+        return;
+    }
+    let line_start = start + 1 - column;
+    let line_end = source[line_start..]
+        .find(['\n', '\r'])
+        .map_or(source.len(), |end| line_start + end);
+    let text = &source[line_start..line_end];
+
+    // Pad with the line's own tabs so the carets line up beneath it
+    let padding: String = source[line_start..start.min(line_end)]
+        .chars()
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end = span.map_or(start, |span| span.end.min(line_end));
+    let carets = source[start.min(line_end)..end.max(start).min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    writeln!(out, "{} |", gutter).unwrap();
+    writeln!(out, "{} | {}", line, text).unwrap();
+    writeln!(out, "{} | {}{}", gutter, padding, "^".repeat(carets)).unwrap();
+}
+
+/// `[line N, col M] Error at 'x': message` for problems found before
+/// running, and jlox's `message` then `[line N, col M]` on its own line for
+/// runtime errors, followed by the calls they happened in.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (line, column, message) = (self.line, self.column, &self.message);
+        if self.phase == Phase::Runtime {
+            write!(f, "{}", msg!("E0014", message, line, column))?;
+            for line in trace_lines(&self.trace) {
+                write!(f, "\n{}", line)?;
+            }
+            return Ok(());
+        }
+
+        let severity = match self.severity {
+            Severity::Error => msg!("E0015"),
+            Severity::Warning => msg!("E0016"),
+        };
+        let text = match self.at.as_deref() {
+            None => msg!("E0011", line, column, severity, message),
+            Some(At::Lexeme(lexeme)) => msg!("E0012", line, column, severity, lexeme, message),
+            Some(At::End) => msg!("E0013", line, column, severity, message),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Where the scanner, parser, resolver and compiler put the diagnostics
+/// they find, in the order they found them.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    list: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.list.push(diagnostic);
+    }
+
+    /// Reports an error in `phase` at the given position.
+    pub fn error(&mut self, phase: Phase, line: usize, column: usize, message: String) {
+        self.report(Diagnostic::error(phase, line, column, message));
+    }
+
+    /// Whether any of the diagnostics is an error rather than a warning.
+    pub fn has_errors(&self) -> bool {
+        self.list
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.list.iter()
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.list
+    }
+}
+
+impl FromIterator<Diagnostic> for Diagnostics {
+    fn from_iter<I: IntoIterator<Item = Diagnostic>>(iter: I) -> Self {
+        Diagnostics {
+            list: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Diagnostic> for Diagnostics {
+    fn extend<I: IntoIterator<Item = Diagnostic>>(&mut self, iter: I) {
+        self.list.extend(iter);
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.list.iter()
+    }
+}
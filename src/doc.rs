@@ -0,0 +1,210 @@
+//! `doc`: documentation for the functions and classes a file declares at
+//! the top level, from the `///` comments right above them. Comments don't
+//! make it into the tokens, so like the formatter this reads them from the
+//! source between a declaration and the token before it.
+
+use std::fmt::Write;
+
+use crate::ast::{class_signature, ExprArena, FunctionDecl, Stmt};
+use crate::format::Format;
+use crate::json;
+use crate::parser::Parser;
+use crate::status::ExitStatus;
+use crate::token::{Token, TokenType};
+use crate::{scan_tokens, Options, Output};
+
+/// Writes the top-level functions and classes in `source`, with their
+/// methods, as Markdown, or as JSON with `--format=json`. Declarations
+/// without a doc comment are listed too.
+pub fn doc(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let tokens = match scan_tokens(source, options) {
+        Ok(tokens) => tokens,
+        Err(status) => return status,
+    };
+    let mut parser = Parser::new(tokens.clone());
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let Some(statements) = parser.parse_program() else {
+        return ExitStatus::from_diagnostics(parser.diagnostics);
+    };
+
+    let comments = Comments {
+        source,
+        tokens: &tokens,
+    };
+    let items: Vec<Item> = statements
+        .iter()
+        .filter_map(|stmt| comments.item(&parser.arena, stmt))
+        .collect();
+
+    match options.format {
+        Format::Json => write_json(out, &items),
+        _ => {
+            let title = options.path.as_ref().and_then(|path| path.file_name());
+            if let Some(title) = title {
+                writeln!(out.stdout, "# {}\n", title.to_string_lossy()).unwrap();
+            }
+            for item in &items {
+                write_markdown(out, item, "##");
+            }
+        }
+    }
+    ExitStatus::Success
+}
+
+/// A documented declaration.
+struct Item {
+    kind: &'static str,
+    name: String,
+    /// As declared, like `fun add(a, b)` or `class A < B`
+    signature: String,
+    line: usize,
+    /// The text of its `///` comment, without the slashes
+    doc: Option<String>,
+    /// A class's methods and class methods, in the order they're declared
+    methods: Vec<Item>,
+}
+
+/// Finds the doc comments of declarations.
+struct Comments<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+}
+
+impl Comments<'_> {
+    fn item(&self, arena: &ExprArena, stmt: &Stmt) -> Option<Item> {
+        match stmt {
+            Stmt::Function(function) => Some(self.function(
+                "function",
+                format!("fun {}", function.signature()),
+                function,
+            )),
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                // In the order they're declared, class methods among the rest
+                let mut members: Vec<(bool, &FunctionDecl)> = methods
+                    .iter()
+                    .map(|method| (false, &**method))
+                    .chain(class_methods.iter().map(|method| (true, &**method)))
+                    .collect();
+                members.sort_by_key(|(_, method)| method.name.span.start);
+                let methods = members
+                    .into_iter()
+                    .map(|(is_class_method, method)| {
+                        if is_class_method {
+                            let signature = format!("class {}", method.signature());
+                            self.function("class method", signature, method)
+                        } else {
+                            self.function("method", method.signature(), method)
+                        }
+                    })
+                    .collect();
+                Some(Item {
+                    kind: "class",
+                    name: name.lexeme.to_string(),
+                    signature: class_signature(arena, name, *superclass, mixins),
+                    line: name.line,
+                    doc: self.comment_before(name),
+                    methods,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn function(&self, kind: &'static str, signature: String, function: &FunctionDecl) -> Item {
+        Item {
+            kind,
+            name: function.name.lexeme.to_string(),
+            signature,
+            line: function.name.line,
+            doc: self.comment_before(&function.name),
+            methods: Vec::new(),
+        }
+    }
+
+    /// The `///` lines right above the declaration named `name`, which
+    /// starts at the `fun` or `class` before the name if there is one. A
+    /// line that isn't one, even a blank one, ends the comment.
+    fn comment_before(&self, name: &Token) -> Option<String> {
+        let mut index = self
+            .tokens
+            .partition_point(|token| token.span.start < name.span.start);
+        if index > 0
+            && matches!(
+                self.tokens[index - 1].token_type,
+                TokenType::Fun | TokenType::Class
+            )
+        {
+            index -= 1;
+        }
+        let start = match index {
+            0 => 0,
+            _ => self.tokens[index - 1].span.end,
+        };
+
+        // Past the end of the line the previous token is on, and up to the
+        // indentation of the declaration's own
+        let gap = &self.source[start..self.tokens[index].span.start];
+        let mut lines: Vec<&str> = gap.split('\n').skip(usize::from(index > 0)).collect();
+        lines.pop();
+        let comment: Vec<&str> = lines
+            .iter()
+            .rev()
+            .map_while(|line| line.trim_start().strip_prefix("///"))
+            .collect();
+        if comment.is_empty() {
+            return None;
+        }
+        let text: Vec<&str> = comment
+            .iter()
+            .rev()
+            .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+            .collect();
+        Some(text.join("\n"))
+    }
+}
+
+/// The item under a heading of `level`, and its methods a level down.
+fn write_markdown(out: &mut Output, item: &Item, level: &str) {
+    writeln!(out.stdout, "{} `{}`\n", level, item.signature).unwrap();
+    writeln!(out.stdout, "{}\n", msg!("E0055", item.line)).unwrap();
+    if let Some(doc) = &item.doc {
+        writeln!(out.stdout, "{}\n", doc).unwrap();
+    }
+    for method in &item.methods {
+        write_markdown(out, method, &format!("{}#", level));
+    }
+}
+
+fn write_json(out: &mut Output, items: &[Item]) {
+    out.stdout.push_str(&json_items(items));
+    out.stdout.push('\n');
+}
+
+fn json_items(items: &[Item]) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|item| {
+            let doc = item.doc.as_deref().map_or("null".to_string(), json::string);
+            let mut object = format!(
+                r#"{{"kind":{},"name":{},"signature":{},"line":{},"doc":{}"#,
+                json::string(item.kind),
+                json::string(&item.name),
+                json::string(&item.signature),
+                item.line,
+                doc
+            );
+            if item.kind == "class" {
+                write!(object, r#","methods":{}"#, json_items(&item.methods)).unwrap();
+            }
+            object.push('}');
+            object
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
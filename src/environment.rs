@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::gc::{self, Trace};
+use crate::interpreter::RuntimeError;
+use crate::suggest;
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::value::Value;
+
+/// Variables of one scope, with a link to the scope it's nested in. Shared
+/// so that blocks and closures can hold on to their enclosing scope.
+///
+/// Globals are kept by their interned name, since they can be redefined and referred to
+/// before they're declared. Locals are kept in declaration order, so the
+/// interpreter reads them by the slot index the resolver gave them rather
+/// than hashing their names.
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    slots: Vec<Value>,
+    /// The name of the local in each slot, for the debugger
+    names: Vec<Symbol>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    /// Whether a function has captured it, and it's registered with the
+    /// collector
+    captured: bool,
+}
+
+impl Environment {
+    /// A new scope nested inside `enclosing`.
+    pub fn new(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            slots: Vec::new(),
+            names: Vec::new(),
+            enclosing: Some(enclosing),
+            captured: false,
+        }
+    }
+
+    /// A new global scope with the natives defined in `globals`, for a
+    /// module to run in.
+    pub fn with_natives(globals: &Environment) -> Self {
+        let natives = globals
+            .values
+            .iter()
+            .filter(|(_, value)| matches!(value, Value::Native(_)))
+            .map(|(name, value)| (*name, value.clone()));
+        Environment {
+            values: natives.collect(),
+            ..Environment::default()
+        }
+    }
+
+    /// The scope `distance` levels out from `environment`, as worked out by
+    /// the resolver.
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(environment);
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("resolver distances stay within the scope chain");
+            environment = enclosing;
+        }
+        environment
+    }
+
+    /// Registers `environment` and the scopes around it with the collector
+    /// the first time a function captures it. Until then only the
+    /// interpreter refers to it, so it can't be part of a cycle.
+    pub(crate) fn capture(environment: &Rc<RefCell<Environment>>) {
+        let mut environment = Rc::clone(environment);
+        loop {
+            let enclosing = {
+                let mut scope = environment.borrow_mut();
+                if scope.captured {
+                    return;
+                }
+                scope.captured = true;
+                scope.enclosing.clone()
+            };
+            gc::track(&environment);
+            match enclosing {
+                Some(enclosing) => environment = enclosing,
+                None => return,
+            }
+        }
+    }
+
+    /// Defines a variable in this scope. In the global scope it's stored
+    /// under `name`, replacing any earlier definition; in a local scope it
+    /// takes the next slot, which is the one the resolver numbered it with
+    /// as long as declarations run in the order they were resolved.
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        if self.enclosing.is_none() {
+            self.values.insert(name, value);
+        } else {
+            self.slots.push(value);
+            self.names.push(name);
+        }
+    }
+
+    /// Looks up the global `name`.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.values.get(&name.lexeme.symbol()) {
+            Some(value) => Ok(value.clone()),
+            None => Err(self.undefined(name)),
+        }
+    }
+
+    /// Assigns to the global `name`. Unlike `define`, fails if the variable
+    /// was never declared.
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match self.values.get_mut(&name.lexeme.symbol()) {
+            Some(variable) => {
+                *variable = value;
+                Ok(())
+            }
+            None => Err(self.undefined(name)),
+        }
+    }
+
+    /// The error for `name` not being defined, suggesting the closest of the
+    /// variables this scope and those around it have, natives included.
+    pub fn undefined(&self, name: &Token) -> RuntimeError {
+        let mut names: Vec<Symbol> = self.values.keys().chain(&self.names).copied().collect();
+        let mut enclosing = self.enclosing.clone();
+        while let Some(scope) = enclosing {
+            let scope = scope.borrow();
+            names.extend(scope.values.keys().chain(&scope.names));
+            enclosing = scope.enclosing.clone();
+        }
+        let message = msg!("E0304", name.lexeme);
+        let candidates = names.into_iter().map(Symbol::as_str);
+        RuntimeError::new(
+            name,
+            suggest::did_you_mean(message, &name.lexeme, candidates),
+        )
+    }
+
+    /// Reads the local in `slot` of this scope.
+    pub fn get_slot(&self, slot: usize) -> Value {
+        self.slots[slot].clone()
+    }
+
+    /// Overwrites the local in `slot` of this scope.
+    pub fn assign_slot(&mut self, slot: usize, value: Value) {
+        self.slots[slot] = value;
+    }
+
+    /// The scope this one is nested in, unless it's a global scope.
+    pub fn enclosing(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.enclosing.clone()
+    }
+
+    /// The variables of this scope: locals in the order they were declared,
+    /// or globals by name, leaving out the natives.
+    pub fn variables(&self) -> Vec<(Symbol, Value)> {
+        if self.enclosing.is_some() {
+            return self.names.iter().copied().zip(self.slots.clone()).collect();
+        }
+        let mut globals: Vec<(Symbol, Value)> = self
+            .values
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Native(_)))
+            .map(|(name, value)| (*name, value.clone()))
+            .collect();
+        globals.sort_by_key(|(name, _)| name.as_str());
+        globals
+    }
+}
+
+impl Trace for RefCell<Environment> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(environment) = self.try_borrow() else {
+            return false;
+        };
+        for value in environment.values.values().chain(&environment.slots) {
+            value.trace(edge);
+        }
+        if let Some(enclosing) = &environment.enclosing {
+            edge(gc::address(enclosing));
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut environment) = self.try_borrow_mut() {
+            let cleared = std::mem::take(&mut *environment);
+            drop(environment);
+            drop(cleared);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+use crate::interpreter::RuntimeError;
+use crate::string::LoxString;
+use crate::token::Token;
+use crate::value::Value;
+
+/// What `catch` gets for a runtime error, and what `Exception(message)`
+/// makes for a program to throw its own.
+#[derive(Debug)]
+pub struct Exception {
+    pub message: LoxString,
+    /// Where it was raised or thrown. `None` until an exception made with
+    /// `Exception()` is thrown.
+    pub line: Option<usize>,
+}
+
+impl Exception {
+    pub fn new(message: LoxString, line: Option<usize>) -> Self {
+        Exception { message, line }
+    }
+
+    /// `exception.message` and `exception.line`, which is `nil` before the
+    /// exception is thrown.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match name.lexeme.as_str() {
+            "message" => Ok(Value::Str(self.message.clone())),
+            "line" => Ok(self
+                .line
+                .map_or(Value::Nil, |line| Value::Number(line as f64))),
+            _ => Err(RuntimeError::new(name, msg!("E0309", name.lexeme))),
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<exception {}>", self.message)
+    }
+}
@@ -0,0 +1,148 @@
+//! `check-tests`: runs a file from the *Crafting Interpreters* test suite
+//! and compares what it did with the expectations in its comments:
+//!
+//! - `// expect: text`: `print` writes `text` as the next line
+//! - `// expect runtime error: message`: the program stops with this
+//!   runtime error, on the comment's line
+//! - `// Error at 'x': message`: a compile error on the comment's line, or
+//!   on line N with a `[line N]` prefix. `[java line N]` and `[c line N]`
+//!   only apply to the tree-walker and to the VM.
+//! - `// nontest`: the file isn't a test, and isn't run
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::{Backend, ExitStatus, Options, Output};
+
+/// What a test file's comments say running it should do.
+#[derive(Debug, Default, PartialEq)]
+pub struct Expectations {
+    /// The lines `print` should write, each with the line of its comment
+    pub output: Vec<(usize, String)>,
+    /// The compile errors, as `[line N] Error at 'x': message`
+    pub errors: Vec<String>,
+    /// The line and message of the runtime error it should stop with
+    pub runtime_error: Option<(usize, String)>,
+    pub nontest: bool,
+}
+
+impl Expectations {
+    /// The expectations in `source`, keeping only the compile errors that
+    /// apply to `backend`.
+    pub fn parse(source: &str, backend: Backend) -> Self {
+        let mut expected = Expectations::default();
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let Some((_, comment)) = text.split_once("// ") else {
+                continue;
+            };
+
+            if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expected.runtime_error = Some((line, message.to_string()));
+            } else if let Some(output) = comment.strip_prefix("expect:") {
+                let output = output.strip_prefix(' ').unwrap_or(output);
+                expected.output.push((line, output.to_string()));
+            } else if comment.starts_with("Error") {
+                expected.errors.push(format!("[line {}] {}", line, comment));
+            } else if let Some(error) = error_on_line(comment, backend) {
+                expected.errors.push(error);
+            } else if comment.starts_with("nontest") {
+                expected.nontest = true;
+            }
+        }
+        expected
+    }
+}
+
+/// `[line N] Error...` from a `[line N] Error...` comment, or from a
+/// `[java line N]` or `[c line N]` one when it's for `backend`.
+fn error_on_line(comment: &str, backend: Backend) -> Option<String> {
+    let (place, error) = comment.strip_prefix('[')?.split_once("] ")?;
+    let line = match place.split_once(" line ") {
+        Some(("java", line)) if backend == Backend::TreeWalk => line,
+        Some(("c", line)) if backend == Backend::Vm => line,
+        Some(_) => return None,
+        None => place.strip_prefix("line ")?,
+    };
+    let line: usize = line.parse().ok()?;
+    error
+        .starts_with("Error")
+        .then(|| format!("[line {}] {}", line, error))
+}
+
+/// `[line N] Error at 'x': message`, the form the test suite expects
+/// compile errors in.
+fn suite_form(diagnostic: &Diagnostic) -> String {
+    // What `Display` shows after its `[line N, col M] ` prefix
+    let text = diagnostic.to_string();
+    let error = text
+        .split_once("] ")
+        .map_or(text.as_str(), |(_, error)| error);
+    format!("[line {}] {}", diagnostic.line, error)
+}
+
+/// ---------------------------------------------------------------------------
+/// check_tests() function: runs a test file => compares it with its comments
+/// Runs the source as `run` would, without showing its output. Returns a
+/// `Mismatch` describing every difference from what its `// expect` and
+/// `// Error` comments say, or `Success` if there are none.
+/// ---------------------------------------------------------------------------
+pub fn check_tests(source: &str, options: &Options, _out: &mut Output) -> ExitStatus {
+    let expected = Expectations::parse(source, options.backend);
+    if expected.nontest {
+        return ExitStatus::Success;
+    }
+
+    let mut actual = Output::default();
+    let status = crate::run(source, options, &mut actual);
+    let mut differences = Vec::new();
+
+    let printed: Vec<&str> = actual.stdout.lines().collect();
+    for index in 0..expected.output.len().max(printed.len()) {
+        match (expected.output.get(index), printed.get(index)) {
+            (Some((_, output)), Some(printed)) if output == printed => {}
+            (Some((line, output)), Some(printed)) => {
+                differences.push(msg!("E0024", output, line, printed))
+            }
+            (Some((line, output)), None) => differences.push(msg!("E0025", output, line)),
+            (None, Some(printed)) => differences.push(msg!("E0026", printed)),
+            (None, None) => unreachable!("index is below one of the lengths"),
+        }
+    }
+
+    let errors: Vec<String> = match &status {
+        ExitStatus::CompileError(diagnostics) => diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(suite_form)
+            .collect(),
+        _ => Vec::new(),
+    };
+    for error in &expected.errors {
+        if !errors.contains(error) {
+            differences.push(msg!("E0027", error));
+        }
+    }
+    for error in &errors {
+        if !expected.errors.contains(error) {
+            differences.push(msg!("E0028", error));
+        }
+    }
+
+    let runtime_error = match &status {
+        ExitStatus::RuntimeError(error) => Some((error.line, error.message.clone())),
+        _ => None,
+    };
+    if runtime_error != expected.runtime_error {
+        if let Some((line, message)) = &expected.runtime_error {
+            differences.push(msg!("E0029", message, line));
+        }
+        if let Some((line, message)) = &runtime_error {
+            differences.push(msg!("E0030", message, line));
+        }
+    }
+
+    if differences.is_empty() {
+        ExitStatus::Success
+    } else {
+        ExitStatus::Mismatch(differences)
+    }
+}
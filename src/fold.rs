@@ -0,0 +1,290 @@
+//! Rewriting the AST. A pass implements `Folder`, which takes each node by
+//! value and returns what replaces it, overriding the methods for the
+//! nodes it changes. The `fold_` functions here rebuild a node from its
+//! folded children, and are what the defaults do, so an override can call
+//! the one for its node to handle the cases it leaves alone.
+//!
+//! A statement can be removed by folding it to `None`. Where the tree needs
+//! one, as the body of an `if` or a loop, an empty block takes its place.
+//! An expression is taken out of its arena to be folded and what replaces
+//! it put back in the same place, so the ids referring to it stay valid.
+
+use std::rc::Rc;
+
+use crate::ast::{CatchClause, Expr, ExprArena, ExprId, FunctionDecl, Stmt, SwitchCase};
+
+/// A pass over the AST that rewrites it. Each method is called for every
+/// node of its kind, with the arena the tree's expressions are in.
+pub trait Folder {
+    /// The statements of a program, block or body, in order.
+    fn fold_stmts(&mut self, arena: &mut ExprArena, statements: Vec<Stmt>) -> Vec<Stmt> {
+        fold_stmts(self, arena, statements)
+    }
+
+    /// `stmt` rewritten, or `None` to remove it.
+    fn fold_stmt(&mut self, arena: &mut ExprArena, stmt: Stmt) -> Option<Stmt> {
+        fold_stmt(self, arena, stmt)
+    }
+
+    fn fold_expr(&mut self, arena: &mut ExprArena, expr: Expr) -> Expr {
+        fold_expr(self, arena, expr)
+    }
+
+    /// A function, method or getter declaration.
+    fn fold_function(
+        &mut self,
+        arena: &mut ExprArena,
+        function: Rc<FunctionDecl>,
+    ) -> Rc<FunctionDecl> {
+        fold_function(self, arena, function)
+    }
+}
+
+/// Folds each statement, leaving out those folded to `None`.
+pub fn fold_stmts<F: Folder + ?Sized>(
+    folder: &mut F,
+    arena: &mut ExprArena,
+    statements: Vec<Stmt>,
+) -> Vec<Stmt> {
+    statements
+        .into_iter()
+        .filter_map(|stmt| folder.fold_stmt(arena, stmt))
+        .collect()
+}
+
+/// `stmt` with its expressions, statements and functions folded. Always
+/// `Some`: only an override removes statements.
+pub fn fold_stmt<F: Folder + ?Sized>(
+    folder: &mut F,
+    arena: &mut ExprArena,
+    stmt: Stmt,
+) -> Option<Stmt> {
+    let stmt = match stmt {
+        Stmt::Expression(expr) => Stmt::Expression(fold_at(folder, arena, expr)),
+        Stmt::Print { keyword, value } => Stmt::Print {
+            keyword,
+            value: fold_at(folder, arena, value),
+        },
+        Stmt::Var {
+            name,
+            annotation,
+            initializer,
+        } => Stmt::Var {
+            name,
+            annotation,
+            initializer: initializer.map(|value| fold_at(folder, arena, value)),
+        },
+        Stmt::Block(statements) => Stmt::Block(folder.fold_stmts(arena, statements)),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Stmt::If {
+            condition: fold_at(folder, arena, condition),
+            then_branch: Box::new(fold_branch(folder, arena, *then_branch)),
+            else_branch: else_branch
+                .and_then(|branch| folder.fold_stmt(arena, *branch).map(Box::new)),
+        },
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => Stmt::While {
+            condition: fold_at(folder, arena, condition),
+            body: Box::new(fold_branch(folder, arena, *body)),
+            increment: increment.map(|increment| fold_at(folder, arena, increment)),
+        },
+        Stmt::Function(declaration) => Stmt::Function(folder.fold_function(arena, declaration)),
+        Stmt::Class {
+            name,
+            superclass,
+            mixins,
+            methods,
+            class_methods,
+        } => Stmt::Class {
+            name,
+            superclass: superclass.map(|superclass| fold_at(folder, arena, superclass)),
+            mixins: mixins
+                .into_iter()
+                .map(|mixin| fold_at(folder, arena, mixin))
+                .collect(),
+            methods: methods
+                .into_iter()
+                .map(|method| folder.fold_function(arena, method))
+                .collect(),
+            class_methods: class_methods
+                .into_iter()
+                .map(|method| folder.fold_function(arena, method))
+                .collect(),
+        },
+        Stmt::Return { keyword, value } => Stmt::Return {
+            keyword,
+            value: value.map(|value| fold_at(folder, arena, value)),
+        },
+        Stmt::Throw { keyword, value } => Stmt::Throw {
+            keyword,
+            value: fold_at(folder, arena, value),
+        },
+        Stmt::Try {
+            keyword,
+            body,
+            catch,
+            finally,
+        } => Stmt::Try {
+            keyword,
+            body: folder.fold_stmts(arena, body),
+            catch: catch.map(|catch| CatchClause {
+                name: catch.name,
+                body: folder.fold_stmts(arena, catch.body),
+            }),
+            finally: finally.map(|finally| folder.fold_stmts(arena, finally)),
+        },
+        Stmt::Switch {
+            keyword,
+            subject,
+            cases,
+            default,
+        } => Stmt::Switch {
+            keyword,
+            subject: fold_at(folder, arena, subject),
+            cases: cases
+                .into_iter()
+                .map(|case| SwitchCase {
+                    value: fold_at(folder, arena, case.value),
+                    body: folder.fold_stmts(arena, case.body),
+                })
+                .collect(),
+            default: default.map(|default| folder.fold_stmts(arena, default)),
+        },
+        stmt @ (Stmt::Break(_) | Stmt::Continue(_) | Stmt::Import { .. } | Stmt::Error(_)) => stmt,
+    };
+    Some(stmt)
+}
+
+/// A statement the tree can't do without, folded: an empty block if it
+/// was removed.
+pub fn fold_branch<F: Folder + ?Sized>(folder: &mut F, arena: &mut ExprArena, stmt: Stmt) -> Stmt {
+    folder
+        .fold_stmt(arena, stmt)
+        .unwrap_or_else(|| Stmt::Block(Vec::new()))
+}
+
+/// `expr` with its operands folded.
+pub fn fold_expr<F: Folder + ?Sized>(folder: &mut F, arena: &mut ExprArena, expr: Expr) -> Expr {
+    match expr {
+        Expr::Grouping(inner) => Expr::Grouping(fold_at(folder, arena, inner)),
+        Expr::Assign { name, value } => Expr::Assign {
+            name,
+            value: fold_at(folder, arena, value),
+        },
+        Expr::Unary { operator, right } => Expr::Unary {
+            operator,
+            right: fold_at(folder, arena, right),
+        },
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => Expr::Binary {
+            left: fold_at(folder, arena, left),
+            operator,
+            right: fold_at(folder, arena, right),
+        },
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => Expr::Logical {
+            left: fold_at(folder, arena, left),
+            operator,
+            right: fold_at(folder, arena, right),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: fold_at(folder, arena, object),
+            name,
+        },
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => Expr::Set {
+            object: fold_at(folder, arena, object),
+            name,
+            value: fold_at(folder, arena, value),
+        },
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => Expr::Index {
+            object: fold_at(folder, arena, object),
+            bracket,
+            index: fold_at(folder, arena, index),
+        },
+        Expr::SetIndex {
+            object,
+            bracket,
+            index,
+            value,
+        } => Expr::SetIndex {
+            object: fold_at(folder, arena, object),
+            bracket,
+            index: fold_at(folder, arena, index),
+            value: fold_at(folder, arena, value),
+        },
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => Expr::Call {
+            callee: fold_at(folder, arena, callee),
+            paren,
+            arguments: arguments
+                .into_iter()
+                .map(|argument| fold_at(folder, arena, argument))
+                .collect(),
+        },
+        Expr::List { bracket, elements } => Expr::List {
+            bracket,
+            elements: elements
+                .into_iter()
+                .map(|element| fold_at(folder, arena, element))
+                .collect(),
+        },
+        Expr::Map { brace, entries } => Expr::Map {
+            brace,
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| (fold_at(folder, arena, key), fold_at(folder, arena, value)))
+                .collect(),
+        },
+        expr @ (Expr::Literal(_)
+        | Expr::Variable(_)
+        | Expr::This(_)
+        | Expr::Super { .. }
+        | Expr::Error(_)) => expr,
+    }
+}
+
+/// Folds the expression at `id` in place, returning `id`.
+pub fn fold_at<F: Folder + ?Sized>(folder: &mut F, arena: &mut ExprArena, id: ExprId) -> ExprId {
+    let expr = arena.take(id);
+    arena[id] = folder.fold_expr(arena, expr);
+    id
+}
+
+/// A new declaration like `function`, with its body folded.
+pub fn fold_function<F: Folder + ?Sized>(
+    folder: &mut F,
+    arena: &mut ExprArena,
+    function: Rc<FunctionDecl>,
+) -> Rc<FunctionDecl> {
+    Rc::new(FunctionDecl {
+        name: function.name.clone(),
+        params: function.params.clone(),
+        param_types: function.param_types.clone(),
+        return_type: function.return_type.clone(),
+        body: folder.fold_stmts(arena, function.body.clone()),
+        is_getter: function.is_getter,
+    })
+}
@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+use crate::json;
+use crate::scanner::Scanner;
+use crate::status::ExitStatus;
+use crate::token::{Token, TokenType};
+use crate::{Options, Output};
+
+/// How `tokenize` lays out each token.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// `TYPE lexeme literal`, the classic space-separated listing
+    #[default]
+    Text,
+    /// Tab-separated type, lexeme, literal, line and column, with a header row
+    Tsv,
+    /// The same fields as comma-separated values
+    Csv,
+    /// A JSON array with one object per token, see `json::token`
+    Json,
+}
+
+/// Writes every token in `input` to `out`, returning any scanning errors.
+pub fn tokenize(input: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let mut scanner = Scanner::new(input, &options.limits);
+    scanner.set_extensions(options.extensions);
+    let tokens = scanner.scan_tokens();
+
+    if options.format == Format::Json {
+        write_json_tokens(out, &tokens);
+        return ExitStatus::from_diagnostics(scanner.diagnostics);
+    }
+
+    if options.format != Format::Text {
+        write_token_row(
+            out,
+            options.format,
+            ["type", "lexeme", "literal", "line", "column"],
+        );
+    }
+    for token in &tokens {
+        write_token(out, options.format, token);
+    }
+
+    ExitStatus::from_diagnostics(scanner.diagnostics)
+}
+
+/// Writes the tokens as a JSON array, one token per line.
+fn write_json_tokens(out: &mut Output, tokens: &[Token]) {
+    out.stdout.push_str("[\n");
+    for (i, token) in tokens.iter().enumerate() {
+        let separator = if i + 1 < tokens.len() { "," } else { "" };
+        writeln!(out.stdout, "  {}{}", json::token(token), separator).unwrap();
+    }
+    out.stdout.push_str("]\n");
+}
+
+/// Writes one token in one of the line-oriented formats.
+fn write_token(out: &mut Output, format: Format, token: &Token) {
+    let token_type = token.token_type.to_string();
+    let literal = match &token.token_type {
+        TokenType::Number(value) => format_float_value(*value),
+        TokenType::StringLit(value) => value.clone(),
+        _ => "null".to_string(),
+    };
+
+    match format {
+        Format::Text => {
+            writeln!(out.stdout, "{} {} {}", token_type, token.lexeme, literal).unwrap()
+        }
+        Format::Tsv | Format::Csv | Format::Json => {
+            let (line, column) = (token.line.to_string(), token.column.to_string());
+            let fields = [token_type.as_str(), &token.lexeme, &literal, &line, &column];
+            write_token_row(out, format, fields);
+        }
+    }
+}
+
+/// Writes a row of delimited fields, escaping them so every row stays on one
+/// line with exactly five fields.
+fn write_token_row(out: &mut Output, format: Format, fields: [&str; 5]) {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match format {
+            // Backslash escapes keep tabs and newlines out of TSV fields
+            Format::Tsv => field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+            // RFC 4180 quoting, only where a field needs it
+            _ if field.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            }
+            _ => field.to_string(),
+        })
+        .collect();
+
+    let separator = if format == Format::Tsv { "\t" } else { "," };
+    writeln!(out.stdout, "{}", fields.join(separator)).unwrap();
+}
+
+/// Ensures floats have at least one digit after the decimal if there's no fractional part.
+pub fn format_float_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}.0", value.trunc())
+    } else {
+        value.to_string() // minimal decimal representation
+    }
+}
@@ -0,0 +1,374 @@
+use std::mem;
+
+use crate::parser::Parser;
+use crate::status::ExitStatus;
+use crate::token::{Token, TokenType};
+use crate::{scan_tokens, Options, Output};
+
+/// One level of indentation, as in *Crafting Interpreters*.
+const INDENT: &str = "  ";
+
+/// Prints `source` canonically formatted, or with `--check` only reports
+/// whether formatting would change it. Sources that don't parse are left
+/// alone and their errors returned.
+pub fn fmt(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let tokens = match scan_tokens(source, options) {
+        Ok(tokens) => tokens,
+        Err(status) => return status,
+    };
+    let mut parser = Parser::new(tokens.clone());
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    if parser.parse_program().is_none() {
+        return ExitStatus::from_diagnostics(parser.diagnostics);
+    }
+
+    let formatted = format_program(source, &tokens);
+    if !options.check {
+        out.stdout.push_str(&formatted);
+    } else if formatted != source {
+        return ExitStatus::Unformatted;
+    }
+    ExitStatus::Success
+}
+
+/// Lays out the tokens scanned from `source` one statement per line,
+/// indented by block, with a space between tokens except where Lox
+/// style has none: inside parentheses, around `.`, before `,` and `;`,
+/// after unary operators and between a callee and its arguments.
+///
+/// Works from the tokens rather than the AST so nothing the parser
+/// desugars or drops is lost: `for` loops stay `for` loops, numbers and
+/// strings keep their spelling, and comments, which the scanner skips, are
+/// taken from the text between tokens. Single blank lines between
+/// statements are kept too. Expressions aren't wrapped, however long.
+pub fn format_program(source: &str, tokens: &[Token]) -> String {
+    let mut formatter = Formatter::default();
+    let mut end = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.token_type == TokenType::Eof {
+            break;
+        }
+        formatter.gap(&source[end..token.span.start]);
+        let next = tokens.get(index + 1).map(|next| &next.token_type);
+        formatter.token(&source[token.span.clone()], &token.token_type, next);
+        end = token.span.end;
+    }
+    formatter.gap(&source[end..]);
+    formatter.finish()
+}
+
+#[derive(Default)]
+struct Formatter {
+    lines: Vec<String>,
+    /// The line being written, with its indentation
+    line: String,
+    /// How many blocks are open
+    depth: usize,
+    /// What each open brace, innermost last, starts
+    braces: Vec<Brace>,
+    /// A `switch` was written whose body hasn't started yet
+    switch_pending: bool,
+    /// How many parentheses are open, inside which `;` doesn't end a line
+    parens: usize,
+    /// A line comment broke the statement being written, so the rest of it
+    /// is indented one level further
+    continuation: bool,
+    /// There was a blank line in the source before what's written next
+    blank_line: bool,
+    previous: Option<TokenType>,
+    /// Whether the previous token was a unary `-` or `!`
+    unary: bool,
+    /// Whether a block comment was the last thing written
+    after_block_comment: bool,
+}
+
+impl Formatter {
+    fn token(&mut self, text: &str, token_type: &TokenType, next: Option<&TokenType>) {
+        use TokenType::*;
+
+        match token_type {
+            // A map literal stays on the line, like a list
+            LeftBrace if self.opens_map() => {
+                let space = self.space_before(token_type);
+                self.write(text, space);
+                self.braces.push(Brace::Map);
+            }
+            RightBrace if self.braces.last() == Some(&Brace::Map) => {
+                self.braces.pop();
+                self.write(text, false);
+            }
+            LeftBrace => {
+                let brace = if mem::take(&mut self.switch_pending) {
+                    Brace::Switch { in_case: false }
+                } else {
+                    Brace::Block
+                };
+                self.braces.push(brace);
+                self.write(text, !self.line.is_empty());
+                self.depth += 1;
+                // An empty block stays on one line: `{}`
+                if next != Some(&RightBrace) {
+                    self.end_statement();
+                }
+            }
+            RightBrace => {
+                if let Some(Brace::Switch { in_case: true }) = self.braces.pop() {
+                    self.depth -= 1;
+                }
+                self.depth = self.depth.saturating_sub(1);
+                if self.line.ends_with('{') {
+                    self.line.push('}');
+                } else {
+                    self.end_line();
+                    self.blank_line = false;
+                    self.write(text, false);
+                }
+                // `} else {`, `} catch (e) {` and `} finally {` are written
+                // on one line
+                if !matches!(next, Some(Else | Catch | Finally)) {
+                    self.end_statement();
+                }
+            }
+            // A label of a switch is on a line of its own, with the
+            // statements it runs indented under it
+            Case | Default if self.in_case() => {
+                self.set_in_case(false);
+                self.depth -= 1;
+                self.write(text, false);
+            }
+            Colon if self.is_label_colon() => {
+                self.write(text, false);
+                self.end_statement();
+                self.set_in_case(true);
+                self.depth += 1;
+            }
+            Semicolon => {
+                self.write(text, false);
+                // The clauses of a `for` are separated, not ended. An `else`
+                // after a body without braces stays on its line, since on
+                // a line of its own it would line up with the outer `if`
+                // of `if (a) if (b) x; else y;` rather than the one it's of.
+                if self.parens == 0 && next != Some(&Else) {
+                    self.end_statement();
+                }
+            }
+            _ => {
+                let space = self.space_before(token_type);
+                self.write(text, space);
+                match token_type {
+                    Switch => self.switch_pending = true,
+                    LeftParen => self.parens += 1,
+                    RightParen => self.parens = self.parens.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        self.after_block_comment = false;
+        self.unary =
+            matches!(token_type, Minus | Bang) && !self.previous.as_ref().is_some_and(ends_operand);
+        self.previous = Some(token_type.clone());
+    }
+
+    fn space_before(&self, token_type: &TokenType) -> bool {
+        use TokenType::*;
+
+        let Some(previous) = &self.previous else {
+            return false;
+        };
+        match token_type {
+            RightParen | RightBracket | Comma | Colon | Dot | Semicolon => return false,
+            // A call or an index, or the parameters of a function
+            LeftParen | LeftBracket if ends_operand(previous) => return false,
+            _ => {}
+        }
+        !(matches!(previous, LeftParen | LeftBracket | LeftBrace | Dot) || self.unary)
+    }
+
+    /// Whether a `{` here starts a map literal: it's in an expression, so
+    /// after an operator, a keyword such as `return`, an opening bracket or
+    /// a map's `:` or `,`. A block's `{` follows the end of an operand, as
+    /// in `if (x) {` or `class A {`, or starts a statement.
+    fn opens_map(&self) -> bool {
+        use TokenType::*;
+
+        match &self.previous {
+            None | Some(Else | Try | Finally | Semicolon | RightBrace) => false,
+            Some(LeftBrace) => self.braces.last() == Some(&Brace::Map),
+            // A block after a switch label
+            Some(Colon) if self.in_case() => false,
+            Some(previous) => !ends_operand(previous),
+        }
+    }
+
+    /// Whether the innermost brace is a switch's, in the statements of one
+    /// of its labels.
+    fn in_case(&self) -> bool {
+        self.braces.last() == Some(&Brace::Switch { in_case: true })
+    }
+
+    fn set_in_case(&mut self, in_case: bool) {
+        if let Some(Brace::Switch { in_case: current }) = self.braces.last_mut() {
+            *current = in_case;
+        }
+    }
+
+    /// Whether a `:` here ends a `case` or `default` label rather than
+    /// separating a map entry's key and value: the innermost brace is a
+    /// switch's, and the label's statements haven't started.
+    fn is_label_colon(&self) -> bool {
+        self.braces.last() == Some(&Brace::Switch { in_case: false })
+    }
+
+    /// Writes the comments in the text between two tokens, keeping each
+    /// one on its own line or after code as it was in the source.
+    fn gap(&mut self, text: &str) {
+        let mut newlines = 0;
+        let mut rest = text;
+        while let Some(ch) = rest.chars().next() {
+            let comment_len = if rest.starts_with("//") {
+                rest.find('\n').unwrap_or(rest.len())
+            } else if rest.starts_with("/*") {
+                block_comment_len(rest)
+            } else {
+                if ch == '\n' {
+                    newlines += 1;
+                    // Code after a block comment starts a new line if it did
+                    if self.after_block_comment {
+                        self.end_line();
+                    }
+                }
+                rest = &rest[ch.len_utf8()..];
+                continue;
+            };
+
+            let comment = &rest[..comment_len];
+            self.comment(comment, newlines);
+            newlines = 0;
+            rest = &rest[comment_len..];
+        }
+
+        if newlines >= 2 {
+            self.blank_line = true;
+        }
+    }
+
+    /// Writes `comment`, which followed `newlines` line breaks.
+    fn comment(&mut self, comment: &str, newlines: usize) {
+        // Whether the comment is in the middle of a statement, which it
+        // breaks across lines if it runs to the end of one
+        let inside = !self.line.is_empty() && !self.after_block_comment;
+        if newlines > 0 || self.previous.is_none() {
+            self.end_line();
+            self.continuation |= inside;
+            self.blank_line |= newlines >= 2;
+            self.write(comment, false);
+        } else if self.line.is_empty() {
+            // After the end of a statement on the same line
+            let last = self.lines.last_mut().expect("a statement was written");
+            last.push(' ');
+            last.push_str(comment);
+            return;
+        } else {
+            self.write(comment, true);
+        }
+
+        if comment.starts_with("//") {
+            self.end_line();
+            self.continuation |= inside;
+        } else {
+            self.after_block_comment = true;
+        }
+    }
+
+    /// Appends `text` to the current line, indenting it first if the line
+    /// is new.
+    fn write(&mut self, text: &str, space: bool) {
+        // Blank lines inside a statement aren't kept
+        let blank_line = mem::take(&mut self.blank_line);
+        if self.line.is_empty() {
+            let follows_open_brace = self.lines.last().is_some_and(|line| line.ends_with('{'));
+            if blank_line && !self.lines.is_empty() && !follows_open_brace {
+                self.lines.push(String::new());
+            }
+            let depth = self.depth + usize::from(self.continuation);
+            self.line = INDENT.repeat(depth);
+        } else if space {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+    }
+
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(mem::take(&mut self.line));
+        }
+    }
+
+    fn end_statement(&mut self) {
+        self.end_line();
+        self.continuation = false;
+    }
+
+    fn finish(mut self) -> String {
+        self.end_line();
+        let mut text = self.lines.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// What an open `{` starts.
+#[derive(PartialEq)]
+enum Brace {
+    Block,
+    Map,
+    /// The body of a `switch`, and whether a label's statements are being
+    /// written, indented under it
+    Switch {
+        in_case: bool,
+    },
+}
+
+/// Whether a token of this type can end an operand, so a `-` after it is
+/// binary and a `(` after it starts a call.
+fn ends_operand(token_type: &TokenType) -> bool {
+    use TokenType::*;
+
+    matches!(
+        token_type,
+        Identifier
+            | Number(_)
+            | StringLit(_)
+            | True
+            | False
+            | Nil
+            | This
+            | RightParen
+            | RightBracket
+    )
+}
+
+/// The length of the `/* ... */` comment `text` starts with, counting the
+/// comments nested in it.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut offset = 0;
+    while offset < text.len() {
+        if text[offset..].starts_with("/*") {
+            depth += 1;
+            offset += 2;
+        } else if text[offset..].starts_with("*/") {
+            depth -= 1;
+            offset += 2;
+            if depth == 0 {
+                return offset;
+            }
+        } else {
+            offset += text[offset..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{ExprArena, FunctionDecl};
+use crate::environment::Environment;
+use crate::gc::{self, Trace};
+use crate::interpreter::{Interpreter, RuntimeError, TailCall, Unwind};
+use crate::symbol::Symbol;
+use crate::token::Token;
+use crate::value::Value;
+use crate::Output;
+
+/// Anything Lox code can call with `()`.
+pub trait LoxCallable: fmt::Debug + fmt::Display {
+    /// Number of arguments the callable expects. Calls are checked against it
+    /// before `call` runs.
+    fn arity(&self) -> usize;
+
+    /// Runs the call. `paren` is the call's closing parenthesis, which any
+    /// error the callee itself reports is blamed on.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError>;
+}
+
+/// A function declared in Lox, along with the scope it was declared in.
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    /// The expressions of the source it was declared in, which its body's
+    /// are among
+    arena: Rc<ExprArena>,
+    closure: Rc<RefCell<Environment>>,
+    /// The global scope of the module it was declared in, which the names
+    /// in its body the resolver left unresolved are looked up in
+    globals: Rc<RefCell<Environment>>,
+    /// Whether this is a class's `init` method, which always returns `this`
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        arena: Rc<ExprArena>,
+        closure: Rc<RefCell<Environment>>,
+        globals: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Environment::capture(&closure);
+        LoxFunction {
+            declaration,
+            arena,
+            closure,
+            globals,
+            is_initializer,
+        }
+    }
+
+    /// Returns this method with `this` bound to `this`, an instance or, for
+    /// a class method, the class, in a scope between the method's closure
+    /// and its body.
+    pub fn bind(&self, this: Value) -> LoxFunction {
+        let mut scope = Environment::new(Rc::clone(&self.closure));
+        scope.define(Symbol::intern("this"), this);
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::clone(&self.arena),
+            Rc::new(RefCell::new(scope)),
+            Rc::clone(&self.globals),
+            self.is_initializer,
+        )
+    }
+
+    /// Whether this is a getter, which runs when the property is read.
+    pub fn is_getter(&self) -> bool {
+        self.declaration.is_getter
+    }
+
+    /// Runs the body with `arguments` in a frame for the call at `paren`.
+    fn run(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+        out: &mut Output,
+    ) -> Result<(), Unwind> {
+        let mut scope = Environment::new(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            scope.define(param.lexeme.symbol(), argument);
+        }
+
+        interpreter.push_frame(self.declaration.name.lexeme.symbol(), paren)?;
+        let body = &self.declaration.body;
+        let mut result = interpreter.in_globals(&self.globals, |interpreter| {
+            interpreter.execute_block(&self.arena, body, scope, out)
+        });
+        // Traced by the innermost call it leaves, while its frames are there
+        if let Err(Unwind::Error(error)) = &mut result {
+            if error.trace.is_empty() {
+                error.trace = interpreter.stack_trace(&error.token);
+            }
+        }
+        interpreter.pop_frame();
+        result
+    }
+
+    /// The `this` an initializer was bound to, the only variable in the
+    /// scope `bind` made.
+    fn this(&self) -> Value {
+        self.closure.borrow().get_slot(0)
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        let mut result = self.run(interpreter, arguments, paren, out);
+        // Each tail call is made once the function that returned it is gone,
+        // in a frame for the call that started them, so the stack stays as
+        // it is however many there are
+        let mut returning = None;
+        while let Err(Unwind::TailCall(tail_call)) = result {
+            let TailCall {
+                function,
+                arguments,
+            } = *tail_call;
+            result = function.run(interpreter, arguments, paren, out);
+            returning = Some(function);
+        }
+
+        let function = returning.as_deref().unwrap_or(self);
+        match result {
+            Ok(()) | Err(Unwind::Return(_)) if function.is_initializer => Ok(function.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+            Err(Unwind::TailCall(_)) => unreachable!("tail calls are all made above"),
+            Err(Unwind::Break | Unwind::Continue) => {
+                unreachable!("resolver keeps break and continue inside loops")
+            }
+        }
+    }
+}
+
+// Written by hand: the closure can refer back to this function, so a derived
+// impl would recurse forever.
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoxFunction")
+            .field("name", &self.declaration.name.lexeme)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+impl Trace for LoxFunction {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        edge(gc::address(&self.closure));
+        edge(gc::address(&self.globals));
+        true
+    }
+}
@@ -0,0 +1,259 @@
+//! Frees reference cycles. Values are reference counted, so an object is
+//! freed as soon as nothing refers to it, except when objects refer to
+//! each other: a local function and the scope it's declared in, or two
+//! instances whose fields hold each other, keep each other alive after the
+//! program can no longer reach either.
+//!
+//! Every object that can be part of a cycle is registered when it's made.
+//! Every so often the collector goes over them all and counts, for each,
+//! how many of the references to it come from the others. One with more
+//! references than that is held from outside them, by a scope the
+//! interpreter is running in, a value on the VM's stack or Rust code, and
+//! everything it refers to is still in use. What's left can only be
+//! reached from itself, so the collector empties it, which breaks its
+//! cycles and lets reference counting free it.
+//!
+//! Most objects die young, so the collector is generational. Objects are
+//! registered in a nursery, which a minor collection goes over alone every
+//! `NURSERY_SIZE` objects made; references from older objects count as
+//! coming from outside it, so nothing they hold is freed. What survives is
+//! promoted to the old generation, which a major collection goes over
+//! together with the nursery once it has doubled since the last one. A
+//! cycle through an old object waits for a major collection to be freed.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+/// How many objects can be promoted before the first major collection.
+const MIN_LIMIT: usize = 4096;
+/// How many objects are made between minor collections.
+const NURSERY_SIZE: usize = 1024;
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::new(Collector {
+        young: Vec::new(),
+        old: Vec::new(),
+        limit: MIN_LIMIT,
+        stats: HeapStats::default(),
+    });
+}
+
+/// An object that can be part of a cycle.
+pub(crate) trait Trace {
+    /// Calls `edge` with the address of each object it holds a reference
+    /// to, once per reference. Returns false, without calling it, if the
+    /// object is borrowed mutably and can't be looked at right now.
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool;
+
+    /// Drops the references it holds, once nothing outside its cycles can
+    /// reach it. Objects that can't be changed after they're made leave
+    /// this to the ones that can, since every cycle goes through one.
+    fn clear(&self) {}
+}
+
+struct Collector {
+    /// The nursery: objects made since the last collection. Includes those
+    /// already freed, until the next one.
+    young: Vec<Weak<dyn Trace>>,
+    /// Objects that survived a collection, and those already freed since
+    old: Vec<Weak<dyn Trace>>,
+    /// How many old objects there are when the next major collection runs:
+    /// twice as many as were left after the last, so the time collecting
+    /// takes stays in proportion to the objects made
+    limit: usize,
+    stats: HeapStats,
+}
+
+/// What the collector has done on this thread since `reset_stats`, and how
+/// many objects are in each generation, for `run --heap-stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HeapStats {
+    pub minor_collections: usize,
+    /// Objects freed by minor collections
+    pub minor_freed: usize,
+    /// Objects moved from the nursery to the old generation
+    pub promoted: usize,
+    pub major_collections: usize,
+    /// Objects freed by major collections
+    pub major_freed: usize,
+    /// Objects alive in the nursery, and in the old generation
+    pub young: usize,
+    pub old: usize,
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            msg!(
+                "E0065",
+                self.minor_collections,
+                self.minor_freed,
+                self.promoted,
+                self.major_collections,
+                self.major_freed,
+                self.young,
+                self.old
+            )
+        )
+    }
+}
+
+/// The address `Trace::trace` reports `object` by.
+pub(crate) fn address<T: ?Sized>(object: &Rc<T>) -> *const () {
+    Rc::as_ptr(object) as *const ()
+}
+
+/// `object`, shared and registered with the collector.
+pub(crate) fn tracked<T: Trace + 'static>(object: T) -> Rc<T> {
+    let object = Rc::new(object);
+    track(&object);
+    object
+}
+
+/// Registers `object` with the collector, in the nursery.
+pub(crate) fn track<T: Trace + 'static>(object: &Rc<T>) {
+    let weak: Weak<dyn Trace> = Rc::downgrade(object) as Weak<T>;
+    let full = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        collector.young.push(weak);
+        collector.young.len() >= NURSERY_SIZE
+    });
+    if full {
+        collect_young();
+    }
+}
+
+/// Frees the objects in the nursery only cycles among themselves keep
+/// alive, and promotes the rest. Runs a major collection after, if the old
+/// generation has grown enough.
+fn collect_young() {
+    let young = COLLECTOR.with(|collector| std::mem::take(&mut collector.borrow_mut().young));
+    let (survivors, freed) = sweep(young);
+    let major = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        collector.stats.minor_collections += 1;
+        collector.stats.minor_freed += freed;
+        collector.stats.promoted += survivors.len();
+        collector.old.extend(survivors);
+        collector.old.len() >= collector.limit
+    });
+    if major {
+        collect();
+    }
+}
+
+/// Frees every registered object only cycles keep alive, old and young,
+/// and returns how many there were. Runs on its own as objects are made;
+/// calling it is only needed to free them at a particular point.
+pub fn collect() -> usize {
+    let objects = COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        let mut objects = std::mem::take(&mut collector.old);
+        objects.append(&mut collector.young);
+        objects
+    });
+    let (survivors, freed) = sweep(objects);
+    COLLECTOR.with(|collector| {
+        let mut collector = collector.borrow_mut();
+        collector.stats.major_collections += 1;
+        collector.stats.major_freed += freed;
+        collector.limit = MIN_LIMIT.max(survivors.len() * 2);
+        collector.old.extend(survivors);
+    });
+    freed
+}
+
+/// Frees those of `registered` that only cycles among them keep alive.
+/// References from objects that aren't among them count as coming from
+/// outside. Returns the ones left alive and how many were freed.
+fn sweep(registered: Vec<Weak<dyn Trace>>) -> (Vec<Weak<dyn Trace>>, usize) {
+    let objects: Vec<Rc<dyn Trace>> = registered.iter().filter_map(Weak::upgrade).collect();
+    drop(registered);
+    let indexes: HashMap<*const (), usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(index, object)| (Rc::as_ptr(object) as *const (), index))
+        .collect();
+
+    // The references to each object from the others. One that can't be
+    // looked at is kept, along with everything it refers to, which doesn't
+    // count its references and so looks held from outside.
+    let mut inside = vec![0; objects.len()];
+    let mut reached = vec![false; objects.len()];
+    for (index, object) in objects.iter().enumerate() {
+        let traced = object.trace(&mut |address| {
+            if let Some(&index) = indexes.get(&address) {
+                inside[index] += 1;
+            }
+        });
+        reached[index] = !traced;
+    }
+    // Less the one `objects` holds
+    let mut pending: Vec<usize> = (0..objects.len())
+        .filter(|&index| reached[index] || Rc::strong_count(&objects[index]) - 1 > inside[index])
+        .collect();
+    for &index in &pending {
+        reached[index] = true;
+    }
+    while let Some(index) = pending.pop() {
+        objects[index].trace(&mut |address| {
+            if let Some(&index) = indexes.get(&address) {
+                if !reached[index] {
+                    reached[index] = true;
+                    pending.push(index);
+                }
+            }
+        });
+    }
+
+    let mut freed = 0;
+    for (object, reached) in objects.iter().zip(&reached) {
+        if !reached {
+            object.clear();
+            freed += 1;
+        }
+    }
+    let live: Vec<Weak<dyn Trace>> = objects
+        .iter()
+        .zip(&reached)
+        .filter(|(_, reached)| **reached)
+        .map(|(object, _)| Rc::downgrade(object))
+        .collect();
+    // Dropped here, freeing what was cleared
+    drop(objects);
+    (live, freed)
+}
+
+/// How many registered objects are still alive, cycles included.
+pub fn live() -> usize {
+    let stats = stats();
+    stats.young + stats.old
+}
+
+/// What the collector has done since `reset_stats`, and the objects alive
+/// in each generation now.
+pub fn stats() -> HeapStats {
+    COLLECTOR.with(|collector| {
+        let collector = collector.borrow();
+        let alive = |objects: &[Weak<dyn Trace>]| {
+            objects
+                .iter()
+                .filter(|object| object.strong_count() > 0)
+                .count()
+        };
+        HeapStats {
+            young: alive(&collector.young),
+            old: alive(&collector.old),
+            ..collector.stats
+        }
+    })
+}
+
+/// Starts counting collections and what they freed and promoted over.
+pub fn reset_stats() {
+    COLLECTOR.with(|collector| collector.borrow_mut().stats = HeapStats::default());
+}
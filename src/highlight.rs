@@ -0,0 +1,182 @@
+//! Colors Lox source for a terminal, as the REPL does with the line being
+//! typed. A line is usually unfinished, so this doesn't use the scanner,
+//! which reports what isn't closed yet as errors: it reads the text
+//! character by character and colors keywords, strings and numbers with
+//! ANSI escapes, and dims a closing bracket that has nothing to close.
+
+use crate::token::TokenType;
+
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const UNMATCHED: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Colors source text.
+#[derive(Default)]
+pub struct Highlighter {
+    extensions: bool,
+}
+
+/// What the text read so far leaves open.
+#[derive(Default)]
+struct State {
+    /// The opening brackets not yet closed, innermost last
+    open: Vec<char>,
+    in_string: bool,
+    /// How many `/*` comments it's inside, since they nest
+    comment_depth: usize,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Highlighter::default()
+    }
+
+    /// Colors the words reserved by `--extensions` as keywords too.
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+    }
+
+    /// `line` with ANSI colors, following `before`, the lines already
+    /// entered of the same statement. Those decide whether the line starts
+    /// inside a string or a comment, and which brackets it can close.
+    pub fn highlight(&self, before: &str, line: &str) -> String {
+        let mut state = State::default();
+        self.paint(&mut state, before);
+        self.paint(&mut state, line)
+    }
+
+    /// Whether `source` closes every bracket, string and comment it opens,
+    /// so the REPL can run it rather than read another line of it.
+    pub fn is_complete(&self, source: &str) -> bool {
+        let mut state = State::default();
+        self.paint(&mut state, source);
+        state.open.is_empty() && !state.in_string && state.comment_depth == 0
+    }
+
+    /// `text` colored, starting in and leaving `state`.
+    fn paint(&self, state: &mut State, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut at = 0;
+        while at < text.len() {
+            let rest = &text[at..];
+            let (len, color) = if state.in_string {
+                (string_rest(state, rest), Some(STRING))
+            } else if state.comment_depth > 0 {
+                (comment_rest(state, rest), None)
+            } else {
+                self.token(state, rest)
+            };
+            match color {
+                Some(color) => {
+                    out.push_str(color);
+                    out.push_str(&rest[..len]);
+                    out.push_str(RESET);
+                }
+                None => out.push_str(&rest[..len]),
+            }
+            at += len;
+        }
+        out
+    }
+
+    /// The length of the token `rest` starts with, or of the character if
+    /// it doesn't start one, and its color.
+    fn token(&self, state: &mut State, rest: &str) -> (usize, Option<&'static str>) {
+        let ch = rest.chars().next().expect("only called with text left");
+        match ch {
+            '"' => {
+                state.in_string = true;
+                (1 + string_rest(state, &rest[1..]), Some(STRING))
+            }
+            '/' if rest.starts_with("//") => (rest.find('\n').unwrap_or(rest.len()), None),
+            '/' if rest.starts_with("/*") => {
+                state.comment_depth = 1;
+                (2 + comment_rest(state, &rest[2..]), None)
+            }
+            '0'..='9' => {
+                let mut len = digits(rest);
+                let fraction = &rest[len..];
+                if fraction.starts_with('.') && digits(&fraction[1..]) > 0 {
+                    len += 1 + digits(&fraction[1..]);
+                }
+                (len, Some(NUMBER))
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let len = rest
+                    .find(|next: char| !(next.is_alphanumeric() || next == '_'))
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                let keyword = TokenType::keyword(word).is_some()
+                    || (self.extensions && TokenType::extension_keyword(word).is_some());
+                (len, keyword.then_some(KEYWORD))
+            }
+            '(' | '[' | '{' => {
+                state.open.push(ch);
+                (1, None)
+            }
+            ')' | ']' | '}' => {
+                let opening = match ch {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if state.open.last() == Some(&opening) {
+                    state.open.pop();
+                    (1, None)
+                } else {
+                    (1, Some(UNMATCHED))
+                }
+            }
+            _ => (ch.len_utf8(), None),
+        }
+    }
+}
+
+/// The length of the rest of a string up to and including its closing
+/// quote, or all of `rest` if it isn't closed there.
+fn string_rest(state: &mut State, rest: &str) -> usize {
+    let mut escaped = false;
+    for (offset, ch) in rest.char_indices() {
+        match ch {
+            '"' if !escaped => {
+                state.in_string = false;
+                return offset + 1;
+            }
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    rest.len()
+}
+
+/// The length of the rest of a block comment up to and including the
+/// `*/` that closes it, or all of `rest` if it isn't closed there.
+fn comment_rest(state: &mut State, rest: &str) -> usize {
+    let bytes = rest.as_bytes();
+    let mut at = 0;
+    while at < bytes.len() {
+        match &bytes[at..] {
+            [b'/', b'*', ..] => {
+                state.comment_depth += 1;
+                at += 2;
+            }
+            [b'*', b'/', ..] => {
+                state.comment_depth -= 1;
+                at += 2;
+                if state.comment_depth == 0 {
+                    return at;
+                }
+            }
+            _ => at += 1,
+        }
+    }
+    rest.len()
+}
+
+/// How many ASCII digits `text` starts with.
+fn digits(text: &str) -> usize {
+    text.find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(text.len())
+}
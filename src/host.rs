@@ -0,0 +1,209 @@
+//! Conversions between Lox values and Rust types, for hosts embedding the
+//! interpreter. `Interpreter::register` uses them to make a plain Rust
+//! closure callable from Lox, converting its arguments and result, and
+//! `Interpreter::call` takes arguments as anything `Into<Value>`.
+
+use std::cell::RefCell;
+
+use crate::gc;
+use crate::value::Value;
+
+/// A Rust type a Lox value can be converted to, as a registered function's
+/// parameter.
+pub trait FromValue: Sized {
+    /// What a value must be to convert, for the error when it isn't, such
+    /// as `a number`
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// A Rust type a registered function can return. An `Err` becomes a
+/// runtime error at the call.
+pub trait IntoValue {
+    fn into_value(self) -> Result<Value, String>;
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "a value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Only whole numbers in range convert, rather than being truncated.
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Some(*n as i64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const EXPECTED: &'static str = "a boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Str(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// A copy of a list whose elements all convert.
+impl<T: FromValue> FromValue for Vec<T> {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(list) => list.borrow().iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// `nil` converts to `None`, so a parameter can be left out by passing it.
+impl<T: FromValue> FromValue for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: Into<Value>> IntoValue for T {
+    fn into_value(self) -> Result<Value, String> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Value>> IntoValue for Result<T, String> {
+    fn into_value(self) -> Result<Value, String> {
+        self.map(Into::into)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.into())
+    }
+}
+
+/// For functions called only for what they do, which return `nil`.
+impl From<()> for Value {
+    fn from((): ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        Value::List(gc::tracked(RefCell::new(values)))
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// A Rust closure `Interpreter::register` can make callable from Lox.
+/// `Args` is the tuple of its parameter types, which only tells the
+/// implementations for each number of parameters apart.
+pub trait HostFunction<Args> {
+    fn arity(&self) -> usize;
+
+    /// Converts `arguments`, of which there are `arity`, calls the closure
+    /// with them and converts what it returns.
+    fn call(&self, arguments: &[Value]) -> Result<Value, String>;
+}
+
+/// The argument at `index` as a `T`, or the error saying what it should be.
+fn argument<T: FromValue>(arguments: &[Value], index: usize) -> Result<T, String> {
+    let value = &arguments[index];
+    T::from_value(value).ok_or_else(|| msg!("E0316", T::EXPECTED, index + 1, value.type_name()))
+}
+
+macro_rules! host_function {
+    ($arity:literal $(, $param:ident $index:tt)*) => {
+        impl<F, R, $($param),*> HostFunction<($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R,
+            R: IntoValue,
+            $($param: FromValue,)*
+        {
+            fn arity(&self) -> usize {
+                $arity
+            }
+
+            #[allow(unused_variables)]
+            fn call(&self, arguments: &[Value]) -> Result<Value, String> {
+                self($(argument::<$param>(arguments, $index)?),*).into_value()
+            }
+        }
+    };
+}
+
+host_function!(0);
+host_function!(1, A 0);
+host_function!(2, A 0, B 1);
+host_function!(3, A 0, B 1, C 2);
+host_function!(4, A 0, B 1, C 2, D 3);
+host_function!(5, A 0, B 1, C 2, D 3, E 4);
+host_function!(6, A 0, B 1, C 2, D 3, E 4, G 5);
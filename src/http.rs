@@ -0,0 +1,141 @@
+//! A blocking HTTP/1.1 client for the `fetch` native, built with the `net`
+//! feature. It speaks plain `http://` over a `TcpStream`, with no TLS, so
+//! an `https://` URL is an error rather than a dependency.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long connecting, or any read or write, can take before the request
+/// fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the server answered.
+pub struct Response {
+    pub status: u16,
+    /// As UTF-8, with what isn't replaced
+    pub body: String,
+}
+
+/// Gets `url`. Redirects aren't followed: a 3xx is returned like any other
+/// response.
+pub fn get(url: &str) -> Result<Response, String> {
+    let (authority, host, port, path) = split_url(url).ok_or_else(|| msg!("E0342", url))?;
+    request(authority, host, port, &path).map_err(|err| msg!("E0341", url, err))
+}
+
+fn request(authority: &str, host: &str, port: u16, path: &str) -> io::Result<Response> {
+    let mut stream = connect(host, port)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    // The server closes the connection after answering, so a body with
+    // neither a length nor chunks still ends
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: lox\r\nConnection: close\r\n\r\n",
+        path, authority
+    )?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let status_line = read_line(&mut reader)?;
+    let status = match status_line.split(' ').collect::<Vec<_>>()[..] {
+        [version, status, ..] if version.starts_with("HTTP/1.") => status.parse().ok(),
+        _ => None,
+    };
+    let status = status.ok_or_else(not_http)?;
+
+    let mut length = None;
+    let mut chunked = false;
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').ok_or_else(not_http)?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = Some(value.parse().map_err(|_| not_http())?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let line = read_line(&mut reader)?;
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| not_http())?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            read_line(&mut reader)?;
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok(Response {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// A connection to the first of `host`'s addresses that takes one.
+fn connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last = io::Error::from(io::ErrorKind::AddrNotAvailable);
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last = err,
+        }
+    }
+    Err(last)
+}
+
+/// A line of the response's head, without its line ending.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+fn not_http() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg!("E0343"))
+}
+
+/// The host and port, as written and apart, and the path of an `http://`
+/// URL, leaving out any fragment. `None` for any other URL.
+fn split_url(url: &str) -> Option<(&str, &str, u16, String)> {
+    let (scheme, rest) = url.split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("http") {
+        return None;
+    }
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(at) => rest.split_at(at),
+        None => (rest, ""),
+    };
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    // An IPv6 address is in brackets, so its colons aren't the port's
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+        _ => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    (!host.is_empty()).then_some((authority, host, port, path))
+}
@@ -0,0 +1,1368 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, Write as _};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ast::{Expr, ExprArena, ExprId, FunctionDecl, Stmt};
+use crate::class::{self, LoxClass, LoxInstance};
+use crate::coverage::{Coverage, LineHits};
+use crate::debugger::DebugHook;
+use crate::diagnostic::{Diagnostic, Diagnostics, Phase, TraceFrame};
+use crate::environment::Environment;
+use crate::exception::Exception;
+use crate::function::{LoxCallable, LoxFunction};
+use crate::gc;
+use crate::host::HostFunction;
+use crate::map::LoxMap;
+use crate::module::{module_name, Module};
+use crate::native::{self, NativeFunction};
+use crate::optimizer::Optimizer;
+use crate::parser::Parser;
+use crate::printer;
+use crate::profiler::Profiler;
+use crate::resolver::Resolver;
+use crate::scanner::{Limits, Scanner};
+use crate::string::LoxString;
+use crate::symbol::Symbol;
+use crate::token::{Lexeme, Token, TokenType};
+use crate::value::Value;
+use crate::Output;
+
+/// Tree-walk interpreter. Holds the global scope, the scope that statements
+/// currently run in, and where the resolver found each local variable.
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// How many scopes out each local variable was declared and its slot in
+    /// that scope, keyed by the byte offset of the token that refers to it.
+    /// An offset identifies one expression, since no two tokens start at the
+    /// same one.
+    locals: HashMap<usize, (usize, usize)>,
+    /// Whether `+` with one string operand stringifies the other, instead of
+    /// that being a runtime error
+    coerce_strings: bool,
+    /// Whether `run` and imports scan `switch`, `case` and `default` as keywords
+    extensions: bool,
+    /// Whether every expression evaluated is written to stderr, see
+    /// `set_trace`
+    trace: bool,
+    /// Whether imported modules are optimized like the program, see
+    /// `set_optimize`
+    optimize: bool,
+    /// Whether calls in tail position reuse the stack, see `set_tco`
+    tco: bool,
+    /// The offsets of the `return` keywords the resolver found return a
+    /// call in tail position
+    tail_calls: HashSet<usize>,
+    /// Asked before each statement whether to stop, see `set_debugger`
+    debugger: Option<Box<dyn DebugHook>>,
+    /// How many times each statement has run, see `set_coverage`
+    coverage: Option<Coverage>,
+    /// How long each function's calls have taken, see `set_profile`
+    profiler: Option<Profiler>,
+    /// Set when the debugger's `quit` stops the program, so no `catch`
+    /// catches the error that unwinds it
+    stopping: bool,
+    /// Where `print` writes, see `set_output`
+    output: Option<Box<dyn io::Write>>,
+    /// Where each source `run` was given starts, in order. The offsets of
+    /// the tokens it scans are moved past the earlier sources, so no two
+    /// share offsets and so keys in `locals`.
+    source_starts: Vec<usize>,
+    /// The file each source was read from, for stack traces, or
+    /// `<script>` for one that wasn't
+    source_names: Vec<String>,
+    next_source_offset: usize,
+    /// The calls in progress, innermost last
+    frames: Vec<CallFrame>,
+    /// Where on the Rust stack the outermost of `frames` started
+    stack_base: usize,
+    /// What the sources it runs and the calls they make can grow to, see
+    /// `set_limits`
+    limits: Limits,
+    /// The file the running code was read from, which its imports are
+    /// relative to. Without one, they're relative to the current directory.
+    path: Option<PathBuf>,
+    /// Every module imported so far, by canonical path, so each runs once
+    modules: HashMap<PathBuf, Rc<Module>>,
+    /// The file running and the modules it's importing, innermost last, to
+    /// catch import cycles
+    importing: Vec<PathBuf>,
+}
+
+/// A call in progress: the function and the token of the call, whose
+/// line is where its caller is at.
+struct CallFrame {
+    function: Symbol,
+    call: Token,
+}
+
+/// An error that stops a running program unless a `catch` catches it,
+/// blamed on the token whose operation failed.
+#[derive(Debug)]
+pub struct RuntimeError {
+    /// Boxed, since every evaluation returns a `Result` this has to fit in
+    pub token: Box<Token>,
+    pub message: String,
+    /// The value a `throw` threw, which is what `catch` gets. Errors the
+    /// interpreter raises itself are caught as an `Exception`.
+    pub thrown: Option<Box<Value>>,
+    /// The calls it happened in, once it has unwound out of one
+    pub trace: Vec<TraceFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: String) -> Self {
+        RuntimeError {
+            token: Box::new(token.clone()),
+            message,
+            thrown: None,
+            trace: Vec::new(),
+        }
+    }
+
+    /// The error `throw value` at `keyword` unwinds with.
+    pub fn thrown(keyword: &Token, value: Value) -> Self {
+        let description = match &value {
+            Value::Exception(exception) => exception.message.to_string(),
+            value => value.to_string(),
+        };
+        RuntimeError {
+            token: Box::new(keyword.clone()),
+            message: msg!("E0332", description),
+            thrown: Some(Box::new(value)),
+            trace: Vec::new(),
+        }
+    }
+
+    /// What a `catch` binds its variable to for this error.
+    fn into_caught(self) -> Value {
+        self.thrown.map(|value| *value).unwrap_or_else(|| {
+            let exception = Exception::new(self.message.into(), Some(self.token.line));
+            Value::Exception(Rc::new(exception))
+        })
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        let (line, column) = (error.token.line, error.token.column);
+        Diagnostic::error(Phase::Runtime, line, column, error.message)
+            .with_span(error.token.span)
+            .with_trace(error.trace)
+    }
+}
+
+/// Why execution stopped before the end of a statement.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    /// A `return` statement, carrying the value back to the call
+    Return(Value),
+    /// A `return` of a call in tail position, with `--tco`, for the call
+    /// being returned from to make in its place
+    TailCall(Box<TailCall>),
+    /// `break`, caught by the innermost loop
+    Break,
+    /// `continue`, caught by the innermost loop
+    Continue,
+}
+
+/// A call to a Lox function whose callee and arguments are evaluated, but
+/// which is left to run until the function returning it has.
+#[derive(Debug)]
+pub struct TailCall {
+    pub function: Rc<LoxFunction>,
+    pub arguments: Vec<Value>,
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            coerce_strings: false,
+            extensions: false,
+            trace: false,
+            debugger: None,
+            optimize: false,
+            tco: false,
+            tail_calls: HashSet::new(),
+            coverage: None,
+            profiler: None,
+            stopping: false,
+            output: None,
+            source_starts: Vec::new(),
+            source_names: Vec::new(),
+            next_source_offset: 0,
+            frames: Vec::new(),
+            stack_base: 0,
+            limits: Limits::default(),
+            path: None,
+            modules: HashMap::new(),
+            importing: Vec::new(),
+        };
+        native::define_standard_library(&mut interpreter);
+        interpreter
+    }
+
+    /// Lets `"n = " + 1` concatenate to `"n = 1"`. Off by default, where
+    /// `+` needs two numbers or two strings.
+    pub fn set_coerce_strings(&mut self, coerce_strings: bool) {
+        self.coerce_strings = coerce_strings;
+    }
+
+    /// Scans the sources given to `run` and the modules they import with
+    /// `--extensions`, so they can use `switch`, exceptions and mixins.
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+    }
+
+    /// Writes every expression evaluated, with its line and value, to the
+    /// `stderr` of the `Output` given to `execute`, or stderr for `run`.
+    /// Nested calls are indented further.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Makes a function that returns a call to a Lox function, outside any
+    /// `try`, end before that call is made rather than waiting for it, so
+    /// tail-recursive programs run in constant stack however deep they go.
+    /// The function that ended is gone from stack traces, the debugger's
+    /// backtrace and the profiler's stacks from then on, as if the function
+    /// it called had been called in its place, and `--trace` doesn't show
+    /// what the call returned.
+    pub fn set_tco(&mut self, tco: bool) {
+        self.tco = tco;
+    }
+
+    /// Applies `limits` to the sources `run` and `import` read, and to
+    /// calls: more of them in progress than `max_call_depth`, or taking
+    /// more than `max_call_stack` of the stack, are a "Stack overflow."
+    /// runtime error.
+    pub fn set_limits(&mut self, limits: &Limits) {
+        self.limits = limits.clone();
+    }
+
+    /// Runs programs under `debugger`, which is asked before every
+    /// statement whether to stop there.
+    pub fn set_debugger(&mut self, debugger: impl DebugHook + 'static) {
+        self.debugger = Some(Box::new(debugger));
+    }
+
+    /// Counts how many times each statement runs, for `coverage`. Only
+    /// statements added with `cover`, and those of the modules they import,
+    /// are reported if they never run.
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = coverage.then(Coverage::default);
+    }
+
+    /// Adds a resolved program's statements to the coverage report, so
+    /// those that never run show up in it.
+    pub fn cover(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.add(arena, statements);
+        }
+    }
+
+    /// How many times each line with a statement on it has run, by file,
+    /// if coverage is being counted.
+    pub fn coverage(&self) -> Option<BTreeMap<String, LineHits>> {
+        let coverage = self.coverage.as_ref()?;
+        Some(coverage.by_file(|offset| self.source_name(offset)))
+    }
+
+    /// Runs modules through the `--opt` pass as they're imported, after
+    /// resolving them. The program itself is optimized by whoever gives
+    /// it to `execute`.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Times every call to a Lox function, for `profile`. The program's
+    /// own time starts counting now.
+    pub fn set_profile(&mut self, profile: bool) {
+        self.profiler = profile.then(Profiler::new);
+    }
+
+    /// Stops timing and returns the profiler, with the times of every
+    /// function called since `set_profile`.
+    pub fn profile(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Sends what `print` writes to `output` rather than the `Output` given
+    /// to `execute`, or stdout for `run`. Lets a host application or a test
+    /// capture a program's output.
+    pub fn set_output(&mut self, output: impl io::Write + 'static) {
+        self.output = Some(Box::new(output));
+    }
+
+    /// Resolves the imports in code given to `run` relative to `path`'s
+    /// directory.
+    pub fn set_path(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.importing = fs::canonicalize(&path).into_iter().collect();
+        self.path = Some(path);
+    }
+
+    /// Makes room for a source of `len` bytes among the offsets of those
+    /// run so far, returning where it starts. Its tokens must be moved
+    /// there before it's resolved. `name` is the file it was read from.
+    pub(crate) fn begin_source(&mut self, len: usize, name: String) -> usize {
+        let base = self.next_source_offset;
+        self.source_starts.push(base);
+        self.source_names.push(name);
+        self.next_source_offset += len + 1;
+        base
+    }
+
+    /// Scans, parses, resolves and runs `source` as a program. Runs in the
+    /// same global scope as earlier calls, so a host can define something
+    /// in one source and use it in the next. Fails with the errors found
+    /// before running, or the runtime error that stopped the program.
+    ///
+    /// Recurses as deeply as the program nests; see `lox::STACK_SIZE` for
+    /// the stack that takes.
+    pub fn run(&mut self, source: &str) -> Result<(), Vec<Diagnostic>> {
+        let limits = self.limits.clone();
+        let mut scanner = Scanner::new(source, &limits);
+        scanner.set_extensions(self.extensions);
+        let mut tokens = scanner.scan_tokens();
+        if scanner.diagnostics.has_errors() {
+            return Err(scanner.diagnostics.into_vec());
+        }
+
+        let base = self.begin_source(source.len(), "<script>".to_string());
+        for token in &mut tokens {
+            token.span = token.span.start + base..token.span.end + base;
+        }
+
+        let mut parser = Parser::new(tokens);
+        parser.set_max_depth(limits.max_nesting_depth);
+        let Some(statements) = parser.parse_program() else {
+            return Err(self.unshift_all(parser.diagnostics.into_vec()));
+        };
+        let arena = Rc::new(parser.arena);
+        let mut resolver = Resolver::new(self);
+        resolver.resolve(&arena, &statements);
+        if resolver.diagnostics.has_errors() {
+            let diagnostics = resolver.diagnostics.into_vec();
+            return Err(self.unshift_all(diagnostics));
+        }
+
+        let mut out = Output::default();
+        for stmt in &statements {
+            let result = self.execute(&arena, stmt, &mut out);
+            pass_on(&mut out);
+            match result {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(vec![self.unshift_spans(error.into())]),
+                Err(Unwind::Return(_) | Unwind::TailCall(_) | Unwind::Break | Unwind::Continue) => {
+                    unreachable!("resolver rejects return, break and continue out of place")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls the global function or class `name`, as Lox code would with
+    /// `name(arguments)`, in the scope earlier calls to `run` set up.
+    /// Errors inside the callee are at their place in the source that
+    /// declared it; those at the call itself, such as an undefined `name`
+    /// or a wrong number of arguments, have no position, so line 0.
+    pub fn call(
+        &mut self,
+        name: &str,
+        arguments: impl IntoIterator<Item = impl Into<Value>>,
+    ) -> Result<Value, Diagnostic> {
+        // Stand for the call's callee and parentheses, which aren't in any
+        // source, so are at line 0
+        let host_token = |token_type, lexeme| Token {
+            token_type,
+            lexeme: Lexeme::Interned(Symbol::intern(lexeme)),
+            line: 0,
+            column: 0,
+            span: 0..0,
+        };
+        let paren = host_token(TokenType::RightParen, ")");
+        let at_call = |message| Diagnostic::error(Phase::Runtime, 0, 0, message);
+
+        let name = host_token(TokenType::Identifier, name);
+        let callee = self.globals.borrow().get(&name);
+        let callee = callee.map_err(|error| at_call(error.message))?;
+        let function: &dyn LoxCallable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::Native(native) => native.as_ref(),
+            Value::Class(class) => class,
+            _ => return Err(at_call(msg!("E0305"))),
+        };
+        let arguments: Vec<Value> = arguments.into_iter().map(Into::into).collect();
+        if arguments.len() != function.arity() {
+            return Err(at_call(msg!("E0306", function.arity(), arguments.len())));
+        }
+
+        let mut out = Output::default();
+        let result = function.call(self, arguments, &paren, &mut out);
+        pass_on(&mut out);
+        result.map_err(|error| match error.token.line {
+            0 => at_call(error.message),
+            _ => self.unshift_spans(error.into()),
+        })
+    }
+
+    /// Makes a Rust closure callable from Lox as the global `name`, with
+    /// its arguments converted from Lox values and its result converted
+    /// back, as `host` describes. An argument of the wrong type is a
+    /// runtime error at the call, as is an `Err` the closure returns.
+    pub fn register<Args>(&mut self, name: &str, function: impl HostFunction<Args> + 'static) {
+        let arity = function.arity();
+        self.define_native(name, arity, move |arguments| function.call(arguments));
+    }
+
+    /// Runs `f` with `globals` as the global scope, as a function declared
+    /// in a module runs in the module's.
+    pub(crate) fn in_globals<T>(
+        &mut self,
+        globals: &Rc<RefCell<Environment>>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let previous = std::mem::replace(&mut self.globals, Rc::clone(globals));
+        let result = f(self);
+        self.globals = previous;
+        result
+    }
+
+    /// Loads the module an `import` of the string token `path` names,
+    /// relative to the running file. Runs it the first time, in a global
+    /// scope of its own with the same natives; later imports of the same
+    /// file share it. Anything that goes wrong inside it is reported at
+    /// `path`, with where in the module it happened.
+    fn import(&mut self, path: &Token) -> Result<Rc<Module>, RuntimeError> {
+        let TokenType::StringLit(file) = &path.token_type else {
+            unreachable!("parser only makes imports of strings");
+        };
+        let directory = self.path.as_deref().and_then(Path::parent);
+        let relative_path = directory.unwrap_or(Path::new("")).join(file);
+        let unreadable = |_| RuntimeError::new(path, msg!("E0317", file));
+        let full_path = fs::canonicalize(&relative_path).map_err(unreadable)?;
+        if let Some(module) = self.modules.get(&full_path) {
+            return Ok(Rc::clone(module));
+        }
+        if self.importing.contains(&full_path) {
+            return Err(RuntimeError::new(path, msg!("E0318", file)));
+        }
+        let source = fs::read_to_string(&full_path).map_err(unreadable)?;
+
+        let in_module = |diagnostic: Diagnostic| {
+            let (line, column) = (diagnostic.line, diagnostic.column);
+            let msg = msg!("E0319", file, line, column, diagnostic.message);
+            RuntimeError::new(path, msg)
+        };
+        let first_error = |diagnostics: Diagnostics| {
+            let diagnostic = diagnostics.into_vec().into_iter().next();
+            in_module(diagnostic.expect("only called with errors"))
+        };
+
+        let limits = self.limits.clone();
+        let mut scanner = Scanner::new(&source, &limits);
+        scanner.set_extensions(self.extensions);
+        let mut tokens = scanner.scan_tokens();
+        if scanner.diagnostics.has_errors() {
+            return Err(first_error(scanner.diagnostics));
+        }
+        let base = self.begin_source(source.len(), relative_path.display().to_string());
+        for token in &mut tokens {
+            token.span = token.span.start + base..token.span.end + base;
+        }
+        let mut parser = Parser::new(tokens);
+        parser.set_max_depth(limits.max_nesting_depth);
+        let Some(statements) = parser.parse_program() else {
+            return Err(first_error(parser.diagnostics));
+        };
+        let mut arena = parser.arena;
+        let mut resolver = Resolver::new(self);
+        resolver.resolve(&arena, &statements);
+        if resolver.diagnostics.has_errors() {
+            return Err(first_error(resolver.diagnostics));
+        }
+        let unused = std::mem::take(&mut resolver.unused);
+        let statements = match self.optimize {
+            true => {
+                let mut optimizer = Optimizer::new(self.coerce_strings);
+                optimizer.set_unused(&unused);
+                let statements = optimizer.optimize(&mut arena, statements);
+                Resolver::new(self).resolve(&arena, &statements);
+                statements
+            }
+            false => statements,
+        };
+        self.cover(&arena, &statements);
+        let arena = Rc::new(arena);
+
+        let globals = Rc::new(RefCell::new(Environment::with_natives(
+            &self.globals.borrow(),
+        )));
+        let previous_environment = std::mem::replace(&mut self.environment, Rc::clone(&globals));
+        let previous_path = self.path.replace(full_path.clone());
+        self.importing.push(full_path.clone());
+        let mut out = Output::default();
+        let result = self.in_globals(&globals, |interpreter| {
+            statements.iter().try_for_each(|stmt| {
+                let result = interpreter.execute(&arena, stmt, &mut out);
+                pass_on(&mut out);
+                result
+            })
+        });
+        self.importing.pop();
+        self.path = previous_path;
+        self.environment = previous_environment;
+        match result {
+            Ok(()) => {}
+            Err(Unwind::Error(error)) => return Err(in_module(error.into())),
+            Err(Unwind::Return(_) | Unwind::TailCall(_) | Unwind::Break | Unwind::Continue) => {
+                unreachable!("resolver rejects return, break and continue out of place")
+            }
+        }
+
+        let name = module_name(file).expect("resolver checks module names");
+        let module = Rc::new(Module::new(name.to_string(), globals));
+        self.modules.insert(full_path, Rc::clone(&module));
+        Ok(module)
+    }
+
+    /// Notes that `function` was called at `call`, until `pop_frame`, or
+    /// fails with a "Stack overflow." if there are already as many calls in
+    /// progress as `set_limits` allows, or they've taken as much of the
+    /// stack.
+    pub(crate) fn push_frame(
+        &mut self,
+        function: Symbol,
+        call: &Token,
+    ) -> Result<(), RuntimeError> {
+        // The stack grows down, from about here for the outermost call
+        let here = &function as *const Symbol as usize;
+        if self.frames.is_empty() {
+            self.stack_base = here;
+        }
+        let stack = self.stack_base.saturating_sub(here);
+        if self.frames.len() >= self.limits.max_call_depth || stack > self.limits.max_call_stack {
+            return Err(RuntimeError::new(call, msg!("E0314")));
+        }
+        self.frames.push(CallFrame {
+            function,
+            call: call.clone(),
+        });
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(function);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn pop_frame(&mut self) {
+        self.frames.pop();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+    }
+
+    /// The calls in progress, innermost first, for an error at `at` in the
+    /// innermost. Each is shown at the line running in it: where the error
+    /// happened, or else where it called the one inside it.
+    pub(crate) fn stack_trace(&self, at: &Token) -> Vec<TraceFrame> {
+        let functions = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| frame.function.as_str());
+        let lines = std::iter::once(at).chain(self.frames.iter().rev().map(|frame| &frame.call));
+        functions
+            .chain(std::iter::once("<script>"))
+            .zip(lines)
+            // A host's `call` has no line to show, and nothing outside it
+            .take_while(|(_, token)| token.line > 0)
+            .map(|(function, token)| TraceFrame {
+                function: function.to_string(),
+                file: self.source_name(token.span.start),
+                line: token.line,
+            })
+            .collect()
+    }
+
+    /// How many calls are in progress.
+    pub(crate) fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The innermost scope of the code running.
+    pub(crate) fn environment(&self) -> Rc<RefCell<Environment>> {
+        Rc::clone(&self.environment)
+    }
+
+    /// The name of the source the offset `at` is in.
+    pub(crate) fn source_name(&self, at: usize) -> String {
+        let source = self.source_starts.partition_point(|&start| start <= at);
+        self.source_names
+            .get(source.max(1) - 1)
+            .map_or_else(|| "<script>".to_string(), Clone::clone)
+    }
+
+    /// Takes the offset of the source each span in a diagnostic is in off
+    /// it, so the spans are offsets into the source given to `run`, like
+    /// the diagnostic's line and column.
+    fn unshift_spans(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let unshift = |span: Option<Range<usize>>| {
+            span.map(|span| {
+                let source = self
+                    .source_starts
+                    .partition_point(|&start| start <= span.start);
+                let base = self.source_starts[source.max(1) - 1];
+                span.start - base..span.end - base
+            })
+        };
+        diagnostic.span = unshift(diagnostic.span.take());
+        for note in &mut diagnostic.notes {
+            note.span = unshift(note.span.take());
+        }
+        diagnostic
+    }
+
+    fn unshift_all(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| self.unshift_spans(diagnostic))
+            .collect()
+    }
+
+    /// Makes a Rust function callable from Lox as the global `name`. Calls
+    /// with anything but `arity` arguments fail before it runs; an `Err` it
+    /// returns becomes a runtime error at the call.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), Value::Native(Rc::new(native)));
+    }
+
+    /// Records that the variable `name` refers to is `depth` scopes out, in
+    /// that scope's `slot`. Called by the resolver.
+    pub fn resolve(&mut self, name: &Token, depth: usize, slot: usize) {
+        self.locals.insert(name.span.start, (depth, slot));
+    }
+
+    /// Records that the `return` at `keyword` returns a call in tail
+    /// position, for `set_tco`. Called by the resolver.
+    pub fn resolve_tail_call(&mut self, keyword: &Token) {
+        self.tail_calls.insert(keyword.span.start);
+    }
+
+    /// Executes one statement, writing anything it prints to `out`. Its
+    /// expressions are in `arena`, which the functions it declares keep.
+    pub fn execute(
+        &mut self,
+        arena: &Rc<ExprArena>,
+        stmt: &Stmt,
+        out: &mut Output,
+    ) -> Result<(), Unwind> {
+        if let (Some(coverage), Some(token)) = (&mut self.coverage, stmt.token(arena)) {
+            coverage.hit(token);
+        }
+        if self.debugger.is_some() {
+            self.debug(arena, stmt)?;
+        }
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.evaluate(arena, *expr, out)?;
+            }
+            Stmt::Print { value, .. } => {
+                let value = self.evaluate(arena, *value, out)?;
+                match &mut self.output {
+                    // Like jlox, which doesn't check for errors printing
+                    Some(output) => _ = writeln!(output, "{}", value),
+                    None => writeln!(out.stdout, "{}", value).unwrap(),
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(expr) => self.evaluate(arena, *expr, out)?,
+                    None => Value::Nil,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.symbol(), value);
+            }
+            Stmt::Block(statements) => {
+                let scope = Environment::new(Rc::clone(&self.environment));
+                self.execute_block(arena, statements, scope, out)?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(arena, *condition, out)?.is_truthy() {
+                    self.execute(arena, then_branch, out)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(arena, else_branch, out)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                while self.evaluate(arena, *condition, out)?.is_truthy() {
+                    match self.execute(arena, body, out) {
+                        Ok(()) | Err(Unwind::Continue) => {}
+                        Err(Unwind::Break) => break,
+                        Err(unwind) => return Err(unwind),
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(arena, *increment, out)?;
+                    }
+                }
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                default,
+                ..
+            } => {
+                let subject = self.evaluate(arena, *subject, out)?;
+                let mut body = default.as_ref();
+                for case in cases {
+                    if self.evaluate(arena, case.value, out)?.equals(&subject) {
+                        body = Some(&case.body);
+                        break;
+                    }
+                }
+                if let Some(body) = body {
+                    let scope = Environment::new(Rc::clone(&self.environment));
+                    self.execute_block(arena, body, scope, out)?;
+                }
+            }
+            Stmt::Throw { keyword, value } => {
+                let value = match self.evaluate(arena, *value, out)? {
+                    // Made with `Exception()`, so it's only now known where
+                    Value::Exception(exception) if exception.line.is_none() => {
+                        let message = exception.message.clone();
+                        Value::Exception(Rc::new(Exception::new(message, Some(keyword.line))))
+                    }
+                    value => value,
+                };
+                return Err(RuntimeError::thrown(keyword, value).into());
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                let scope = Environment::new(Rc::clone(&self.environment));
+                let mut result = self.execute_block(arena, body, scope, out);
+                if let Some(catch) = catch {
+                    match result {
+                        // Nothing catches the debugger stopping the program
+                        Err(Unwind::Error(error)) if !self.stopping => {
+                            let mut scope = Environment::new(Rc::clone(&self.environment));
+                            scope.define(catch.name.lexeme.symbol(), error.into_caught());
+                            result = self.execute_block(arena, &catch.body, scope, out);
+                        }
+                        _ => {}
+                    }
+                }
+                // Runs however the rest ended. If it unwinds itself, say
+                // with a `return`, that replaces how the rest ended.
+                if let Some(finally) = finally {
+                    let scope = Environment::new(Rc::clone(&self.environment));
+                    self.execute_block(arena, finally, scope, out)?;
+                }
+                result?;
+            }
+            Stmt::Break(_) => return Err(Unwind::Break),
+            Stmt::Continue(_) => return Err(Unwind::Continue),
+            Stmt::Import { path, .. } => {
+                let module = self.import(path)?;
+                let name = Symbol::intern(&module.name);
+                self.environment
+                    .borrow_mut()
+                    .define(name, Value::Module(module));
+            }
+            Stmt::Function(declaration) => {
+                let closure = Rc::clone(&self.environment);
+                let globals = Rc::clone(&self.globals);
+                let arena = Rc::clone(arena);
+                let function =
+                    LoxFunction::new(Rc::clone(declaration), arena, closure, globals, false);
+                self.environment.borrow_mut().define(
+                    declaration.name.lexeme.symbol(),
+                    Value::Function(gc::tracked(function)),
+                );
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => Some(self.evaluate_class(arena, *expr, "E0310", out)?),
+                    None => None,
+                };
+                let mixin_classes = mixins
+                    .iter()
+                    .map(|&mixin| self.evaluate_class(arena, mixin, "E0330", out))
+                    .collect::<Result<_, _>>()?;
+
+                // Methods of a subclass see `super` in a scope of their own
+                let mut closure = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut scope = Environment::new(closure);
+                    scope.define(Symbol::intern("super"), Value::Class(Rc::clone(superclass)));
+                    closure = Rc::new(RefCell::new(scope));
+                }
+
+                let function = |method: &Rc<FunctionDecl>, is_initializer| {
+                    let closure = Rc::clone(&closure);
+                    let globals = Rc::clone(&self.globals);
+                    let arena = Rc::clone(arena);
+                    let function = LoxFunction::new(
+                        Rc::clone(method),
+                        arena,
+                        closure,
+                        globals,
+                        is_initializer,
+                    );
+                    (method.name.lexeme.symbol(), gc::tracked(function))
+                };
+                let methods: HashMap<_, _> = methods
+                    .iter()
+                    .map(|method| function(method, method.name.lexeme == "init"))
+                    .collect();
+                let class_methods: HashMap<_, _> = class_methods
+                    .iter()
+                    .map(|method| function(method, false))
+                    .collect();
+
+                let class = LoxClass::new(
+                    name.lexeme.to_string(),
+                    superclass,
+                    mixin_classes,
+                    methods,
+                    class_methods,
+                );
+                if let Some((method, first, second)) = class.mixin_conflict() {
+                    let [first, second] =
+                        [first, second].map(|index| variable_name(&arena[mixins[index]]));
+                    let msg = msg!("E0331", method, first.lexeme, second.lexeme, name.lexeme);
+                    return Err(RuntimeError::new(second, msg).into());
+                }
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme.symbol(), Value::Class(gc::tracked(class)));
+            }
+            Stmt::Return { keyword, value } => {
+                let value = match value {
+                    Some(expr) if self.tco && self.tail_calls.contains(&keyword.span.start) => {
+                        return Err(self.tail_call(arena, *expr, out)?);
+                    }
+                    Some(expr) => self.evaluate(arena, *expr, out)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Error(token) => {
+                return Err(RuntimeError::new(token, msg!("E0315")).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `statements` in `scope`, restoring the current scope afterwards
+    /// even if one of them fails.
+    pub(crate) fn execute_block(
+        &mut self,
+        arena: &Rc<ExprArena>,
+        statements: &[Stmt],
+        scope: Environment,
+        out: &mut Output,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(scope)));
+        let result = statements
+            .iter()
+            .try_for_each(|stmt| self.execute(arena, stmt, out));
+        self.environment = previous;
+        result
+    }
+
+    /// Hands control to the debugger before `stmt`. Statements without a
+    /// token, which are blocks, are stepped into rather than stopped at.
+    fn debug(&mut self, arena: &ExprArena, stmt: &Stmt) -> Result<(), RuntimeError> {
+        let Some(token) = stmt.token(arena) else {
+            return Ok(());
+        };
+        let Some(mut debugger) = self.debugger.take() else {
+            return Ok(());
+        };
+        let result = debugger.before_statement(self, token);
+        match result {
+            Ok(()) => self.debugger = Some(debugger),
+            Err(_) => self.stopping = true,
+        }
+        result
+    }
+
+    /// Evaluates an expression in `arena` to a value, or fails with a
+    /// runtime error message.
+    pub fn evaluate(
+        &mut self,
+        arena: &ExprArena,
+        expr: ExprId,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        let expr = &arena[expr];
+        let value = self.evaluate_expr(arena, expr, out)?;
+        if self.trace {
+            self.trace_expr(arena, expr, &value, out);
+        }
+        Ok(value)
+    }
+
+    fn evaluate_arguments(
+        &mut self,
+        arena: &ExprArena,
+        arguments: &[ExprId],
+        out: &mut Output,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.evaluate(arena, *argument, out)?);
+        }
+        Ok(values)
+    }
+
+    /// What `return` unwinds with for the call `expr` in tail position: a
+    /// call to a Lox function, for the caller to make, or the value of
+    /// calling anything else.
+    fn tail_call(
+        &mut self,
+        arena: &ExprArena,
+        expr: ExprId,
+        out: &mut Output,
+    ) -> Result<Unwind, RuntimeError> {
+        let Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } = &arena[expr]
+        else {
+            unreachable!("resolver only finds calls in tail position")
+        };
+        let callee = self.evaluate(arena, *callee, out)?;
+        let arguments = self.evaluate_arguments(arena, arguments, out)?;
+        let function = callable(&callee, arguments.len(), paren)?;
+        match &callee {
+            Value::Function(function) => Ok(Unwind::TailCall(Box::new(TailCall {
+                function: Rc::clone(function),
+                arguments,
+            }))),
+            _ => Ok(Unwind::Return(function.call(self, arguments, paren, out)?)),
+        }
+    }
+
+    /// Writes `expr` and what it evaluated to to `out.stderr`, indented by
+    /// the depth of calls. Literals and groupings are left out, since they
+    /// say nothing their surroundings don't.
+    fn trace_expr(&self, arena: &ExprArena, expr: &Expr, value: &Value, out: &mut Output) {
+        let Some(token) = expr.token(arena) else {
+            return;
+        };
+        if let Expr::Grouping(_) = expr {
+            return;
+        }
+        let indent = "  ".repeat(self.frames.len());
+        let text = msg!("E0033", token.line, printer::print_ast(arena, expr), value);
+        writeln!(out.stderr, "{}{}", indent, text).unwrap();
+    }
+
+    fn evaluate_expr(
+        &mut self,
+        arena: &ExprArena,
+        expr: &Expr,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        match expr {
+            Expr::Literal(literal) => Ok(Value::from(literal)),
+            Expr::Grouping(inner) => self.evaluate(arena, *inner, out),
+            Expr::Variable(name) => self.look_up_variable(name),
+            Expr::Assign { name, value } => {
+                let value = self.evaluate(arena, *value, out)?;
+                match self.locals.get(&name.span.start) {
+                    Some(&(distance, slot)) => Environment::ancestor(&self.environment, distance)
+                        .borrow_mut()
+                        .assign_slot(slot, value.clone()),
+                    None => {
+                        // Not holding the globals while suggesting a name,
+                        // since they may be the current scope
+                        let assigned = self.globals.borrow_mut().assign(name, value.clone());
+                        assigned.map_err(|_| self.environment.borrow().undefined(name))?
+                    }
+                }
+                Ok(value)
+            }
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(arena, *right, out)?;
+                match operator.token_type {
+                    TokenType::Minus => match right {
+                        Value::Number(n) => Ok(Value::Number(-n)),
+                        _ => Err(RuntimeError::new(operator, msg!("E0301"))),
+                    },
+                    TokenType::Bang => Ok(Value::Bool(!right.is_truthy())),
+                    _ => unreachable!("parser only builds unary '-' and '!'"),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(arena, *left, out)?;
+                let right = self.evaluate(arena, *right, out)?;
+                evaluate_binary(operator, left, right, self.coerce_strings)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.evaluate(arena, *callee, out)?;
+                let values = self.evaluate_arguments(arena, arguments, out)?;
+                let function = callable(&callee, values.len(), paren)?;
+                function.call(self, values, paren, out)
+            }
+            Expr::Get { object, name } => match self.evaluate(arena, *object, out)? {
+                Value::Instance(instance) => {
+                    let property = LoxInstance::get(&instance, name)?;
+                    self.run_getter(property, name, out)
+                }
+                Value::Class(class) => {
+                    let property = LoxClass::get(&class, name)?;
+                    self.run_getter(property, name, out)
+                }
+                Value::Module(module) => module.get(name),
+                Value::Exception(exception) => exception.get(name),
+                Value::List(list) => native::list_method(&list, name),
+                Value::Map(map) => native::map_method(&map, name),
+                Value::Str(s) => native::string_method(&s, name),
+                _ => Err(RuntimeError::new(name, msg!("E0307"))),
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let Value::Instance(instance) = self.evaluate(arena, *object, out)? else {
+                    return Err(RuntimeError::new(name, msg!("E0308")));
+                };
+                let value = self.evaluate(arena, *value, out)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::List { elements, .. } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(arena, *element, out)?);
+                }
+                Ok(Value::List(gc::tracked(RefCell::new(values))))
+            }
+            Expr::Map { brace, entries } => {
+                let mut map = LoxMap::default();
+                for (key, value) in entries {
+                    let key = self.evaluate(arena, *key, out)?;
+                    let key = native::map_key(&key).map_err(|msg| RuntimeError::new(brace, msg))?;
+                    map.insert(key, self.evaluate(arena, *value, out)?);
+                }
+                Ok(Value::Map(gc::tracked(RefCell::new(map))))
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(arena, *object, out)?;
+                let index = self.evaluate(arena, *index, out)?;
+                match object {
+                    Value::List(list) => {
+                        let list = list.borrow();
+                        let index = element_index(bracket, index, list.len(), "E0322")?;
+                        Ok(list[index].clone())
+                    }
+                    // Characters, not bytes, so every index is a whole one
+                    Value::Str(s) => {
+                        let len = s.chars().count();
+                        let index = element_index(bracket, index, len, "E0327")?;
+                        let ch = s.chars().nth(index).expect("index is in bounds");
+                        Ok(Value::Str(ch.to_string().into()))
+                    }
+                    Value::Map(map) => {
+                        let key = native::map_key(&index)
+                            .map_err(|msg| RuntimeError::new(bracket, msg))?;
+                        let value = map.borrow().get(&key).cloned();
+                        value.ok_or_else(|| RuntimeError::new(bracket, msg!("E0326", index)))
+                    }
+                    _ => Err(RuntimeError::new(bracket, msg!("E0320"))),
+                }
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = self.evaluate(arena, *object, out)?;
+                let index = self.evaluate(arena, *index, out)?;
+                let value = self.evaluate(arena, *value, out)?;
+                match object {
+                    Value::List(list) => {
+                        // Checked after evaluating `value`, which could
+                        // change the list's length
+                        let len = list.borrow().len();
+                        let index = element_index(bracket, index, len, "E0322")?;
+                        list.borrow_mut()[index] = value.clone();
+                    }
+                    Value::Map(map) => {
+                        let key = native::map_key(&index)
+                            .map_err(|msg| RuntimeError::new(bracket, msg))?;
+                        map.borrow_mut().insert(key, value.clone());
+                    }
+                    Value::Str(_) => return Err(RuntimeError::new(bracket, msg!("E0328"))),
+                    _ => return Err(RuntimeError::new(bracket, msg!("E0320"))),
+                }
+                Ok(value)
+            }
+            Expr::This(keyword) => self.look_up_variable(keyword),
+            Expr::Error(token) => Err(RuntimeError::new(token, msg!("E0315"))),
+            Expr::Super { keyword, method } => {
+                let Some(&(distance, slot)) = self.locals.get(&keyword.span.start) else {
+                    // Unresolved, as in `evaluate`: fails as an undefined variable
+                    return self.globals.borrow().get(keyword);
+                };
+                let scope = Environment::ancestor(&self.environment, distance);
+                let Value::Class(superclass) = scope.borrow().get_slot(slot) else {
+                    unreachable!("`super` is only ever defined as a class")
+                };
+
+                // `this` is always alone in the scope just inside the one with
+                // `super`. It's the class itself in a class method.
+                let scope = Environment::ancestor(&self.environment, distance - 1);
+                let this = scope.borrow().get_slot(0);
+                let class_method = matches!(this, Value::Class(_));
+                let found = if class_method {
+                    superclass.find_class_method(method.lexeme.symbol())
+                } else {
+                    superclass.find_method(method.lexeme.symbol())
+                };
+
+                match found {
+                    Some(found) => {
+                        let property = Value::Function(gc::tracked(found.bind(this)));
+                        self.run_getter(property, method, out)
+                    }
+                    None if class_method => Err(class::undefined_property(
+                        method,
+                        superclass.class_method_names(),
+                    )),
+                    None => Err(class::undefined_property(method, superclass.method_names())),
+                }
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(arena, *left, out)?;
+                // The result is whichever operand decided it, not a boolean
+                let decided = match operator.token_type {
+                    TokenType::Or => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                if decided {
+                    Ok(left)
+                } else {
+                    self.evaluate(arena, *right, out)
+                }
+            }
+        }
+    }
+
+    /// The class `expr`, a superclass or mixin, evaluates to, or the error
+    /// `code` if it's something else.
+    fn evaluate_class(
+        &mut self,
+        arena: &ExprArena,
+        expr: ExprId,
+        code: &str,
+        out: &mut Output,
+    ) -> Result<Rc<LoxClass>, RuntimeError> {
+        match self.evaluate(arena, expr, out)? {
+            Value::Class(class) => Ok(class),
+            _ => Err(RuntimeError::new(variable_name(&arena[expr]), msg!(code))),
+        }
+    }
+
+    /// What reading a method `property` gives: the bound method, or what
+    /// it returns if it's a getter. `name` is blamed for errors in it.
+    fn run_getter(
+        &mut self,
+        property: Value,
+        name: &Token,
+        out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        match property {
+            Value::Function(getter) if getter.is_getter() => {
+                getter.call(self, Vec::new(), name, out)
+            }
+            property => Ok(property),
+        }
+    }
+
+    /// Reads a variable from the scope the resolver found it in, or from the
+    /// globals if it wasn't resolved as a local. Failing, the names it
+    /// suggests include the locals in scope.
+    fn look_up_variable(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match self.locals.get(&name.span.start) {
+            Some(&(distance, slot)) => Ok(Environment::ancestor(&self.environment, distance)
+                .borrow()
+                .get_slot(slot)),
+            None => {
+                let value = self.globals.borrow().get(name);
+                value.map_err(|_| self.environment.borrow().undefined(name))
+            }
+        }
+    }
+}
+
+/// The name in a superclass or mixin, which the parser only ever makes
+/// variables.
+fn variable_name(expr: &Expr) -> &Token {
+    let Expr::Variable(name) = expr else {
+        unreachable!("parser only builds variable superclasses and mixins")
+    };
+    name
+}
+
+/// What can be called in `callee`, or the error a call to it at `paren`
+/// with `count` arguments fails with.
+fn callable<'a>(
+    callee: &'a Value,
+    count: usize,
+    paren: &Token,
+) -> Result<&'a dyn LoxCallable, RuntimeError> {
+    let function: &dyn LoxCallable = match callee {
+        Value::Function(function) => function.as_ref(),
+        Value::Native(native) => native.as_ref(),
+        Value::Class(class) => class,
+        _ => return Err(RuntimeError::new(paren, msg!("E0305"))),
+    };
+    if count != function.arity() {
+        let msg = msg!("E0306", function.arity(), count);
+        return Err(RuntimeError::new(paren, msg));
+    }
+    Ok(function)
+}
+
+/// Prints what `out` has collected to stdout, where `print` writes when
+/// the host hasn't set an output, and any trace to stderr, and empties it.
+fn pass_on(out: &mut Output) {
+    print!("{}", out.stdout);
+    out.stdout.clear();
+    eprint!("{}", out.stderr);
+    out.stderr.clear();
+}
+
+/// The position in a list or string of `len` elements that `index` refers
+/// to, or the error saying why it doesn't refer to one. `out_of_bounds` is
+/// the code of the message for an index past the end, which names what's
+/// indexed.
+fn element_index(
+    bracket: &Token,
+    index: Value,
+    len: usize,
+    out_of_bounds: &str,
+) -> Result<usize, RuntimeError> {
+    match index {
+        Value::Number(n) if n.fract() != 0.0 => Err(RuntimeError::new(bracket, msg!("E0321"))),
+        Value::Number(n) if n < 0.0 || n >= len as f64 => {
+            Err(RuntimeError::new(bracket, msg!(out_of_bounds, n, len)))
+        }
+        Value::Number(n) => Ok(n as usize),
+        _ => Err(RuntimeError::new(bracket, msg!("E0321"))),
+    }
+}
+
+/// Applies a binary operator to two already evaluated operands.
+pub(crate) fn evaluate_binary(
+    operator: &Token,
+    left: Value,
+    right: Value,
+    coerce_strings: bool,
+) -> Result<Value, RuntimeError> {
+    use Value::{Bool, Number, Str};
+
+    match (&operator.token_type, left, right) {
+        (TokenType::EqualEqual, l, r) => Ok(Bool(l.equals(&r))),
+        (TokenType::BangEqual, l, r) => Ok(Bool(!l.equals(&r))),
+        (TokenType::Plus, Number(l), Number(r)) => Ok(Number(l + r)),
+        (TokenType::Plus, Str(l), Str(r)) => Ok(Str(l.concat(&r))),
+        (TokenType::Plus, Str(l), r) if coerce_strings => Ok(Str(l.concat(&r.to_string().into()))),
+        (TokenType::Plus, l, Str(r)) if coerce_strings => {
+            Ok(Str(LoxString::from(l.to_string()).concat(&r)))
+        }
+        (TokenType::Plus, _, _) => Err(RuntimeError::new(operator, msg!("E0303"))),
+        (TokenType::Minus, Number(l), Number(r)) => Ok(Number(l - r)),
+        (TokenType::Star, Number(l), Number(r)) => Ok(Number(l * r)),
+        (TokenType::Slash | TokenType::Percent, Number(_), Number(0.0)) => {
+            Err(RuntimeError::new(operator, msg!("E0313")))
+        }
+        (TokenType::Slash, Number(l), Number(r)) => Ok(Number(l / r)),
+        // Truncated like C's fmod: the result takes the sign of the dividend
+        (TokenType::Percent, Number(l), Number(r)) => Ok(Number(l % r)),
+        (TokenType::Greater, Number(l), Number(r)) => Ok(Bool(l > r)),
+        (TokenType::GreaterEqual, Number(l), Number(r)) => Ok(Bool(l >= r)),
+        (TokenType::Less, Number(l), Number(r)) => Ok(Bool(l < r)),
+        (TokenType::LessEqual, Number(l), Number(r)) => Ok(Bool(l <= r)),
+        // Strings compare by their bytes, which for UTF-8 is the order of
+        // their characters' code points
+        (TokenType::Greater, Str(l), Str(r)) => Ok(Bool(l.as_str() > r.as_str())),
+        (TokenType::GreaterEqual, Str(l), Str(r)) => Ok(Bool(l.as_str() >= r.as_str())),
+        (TokenType::Less, Str(l), Str(r)) => Ok(Bool(l.as_str() < r.as_str())),
+        (TokenType::LessEqual, Str(l), Str(r)) => Ok(Bool(l.as_str() <= r.as_str())),
+        _ => Err(RuntimeError::new(operator, msg!("E0302"))),
+    }
+}
@@ -0,0 +1,221 @@
+//! JSON renderings of tokens and the AST, for `--format=json`. Written by
+//! hand since the output is small and fixed in shape.
+
+use crate::ast::{Expr, ExprArena, ExprId, Literal};
+use crate::token::{Token, TokenType};
+
+/// A token as a JSON object: its type, lexeme, literal value (or `null`),
+/// line and the span of source it covers.
+pub fn token(token: &Token) -> String {
+    let literal = match &token.token_type {
+        TokenType::Number(value) => number(*value),
+        TokenType::StringLit(value) => string(value),
+        _ => "null".to_string(),
+    };
+    format!(
+        r#"{{"type":{},"lexeme":{},"literal":{},"line":{},"span":{}}}"#,
+        string(&token.token_type.to_string()),
+        string(&token.lexeme),
+        literal,
+        token.line,
+        span(token)
+    )
+}
+
+/// Where `token` starts and the position just past its end, each as a
+/// 1-based line and column (counted in bytes, like the scanner does) and a
+/// byte offset into the source.
+fn span(token: &Token) -> String {
+    let (mut line, mut column) = (token.line, token.column);
+    for byte in token.lexeme.bytes() {
+        if byte == b'\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    format!(
+        r#"{{"start":{},"end":{}}}"#,
+        position(token.line, token.column, token.span.start),
+        position(line, column, token.span.end)
+    )
+}
+
+fn position(line: usize, column: usize, offset: usize) -> String {
+    format!(
+        r#"{{"line":{},"column":{},"offset":{}}}"#,
+        line, column, offset
+    )
+}
+
+/// An expression as a JSON object tagged with its `kind`. Operators, names
+/// and keywords are included as full tokens so tools can map nodes back to
+/// the source. Its operands are in `arena`.
+pub fn expr(arena: &ExprArena, expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(value) => format!(r#"{{"kind":"Literal","value":{}}}"#, literal(value)),
+        Expr::Grouping(inner) => {
+            format!(
+                r#"{{"kind":"Grouping","expression":{}}}"#,
+                self::expr(arena, &arena[*inner])
+            )
+        }
+        Expr::Variable(name) => format!(r#"{{"kind":"Variable","name":{}}}"#, token(name)),
+        Expr::Assign { name, value } => format!(
+            r#"{{"kind":"Assign","name":{},"value":{}}}"#,
+            token(name),
+            self::expr(arena, &arena[*value])
+        ),
+        Expr::Unary { operator, right } => format!(
+            r#"{{"kind":"Unary","operator":{},"right":{}}}"#,
+            token(operator),
+            self::expr(arena, &arena[*right])
+        ),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => binary(arena, "Binary", *left, operator, *right),
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => binary(arena, "Logical", *left, operator, *right),
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => {
+            let arguments: Vec<String> = arguments
+                .iter()
+                .map(|&argument| self::expr(arena, &arena[argument]))
+                .collect();
+            format!(
+                r#"{{"kind":"Call","callee":{},"paren":{},"arguments":[{}]}}"#,
+                self::expr(arena, &arena[*callee]),
+                token(paren),
+                arguments.join(",")
+            )
+        }
+        Expr::Get { object, name } => format!(
+            r#"{{"kind":"Get","object":{},"name":{}}}"#,
+            self::expr(arena, &arena[*object]),
+            token(name)
+        ),
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => format!(
+            r#"{{"kind":"Set","object":{},"name":{},"value":{}}}"#,
+            self::expr(arena, &arena[*object]),
+            token(name),
+            self::expr(arena, &arena[*value])
+        ),
+        Expr::List { bracket, elements } => {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|&element| self::expr(arena, &arena[element]))
+                .collect();
+            format!(
+                r#"{{"kind":"List","bracket":{},"elements":[{}]}}"#,
+                token(bracket),
+                elements.join(",")
+            )
+        }
+        Expr::Map { brace, entries } => {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        r#"{{"key":{},"value":{}}}"#,
+                        self::expr(arena, &arena[*key]),
+                        self::expr(arena, &arena[*value])
+                    )
+                })
+                .collect();
+            format!(
+                r#"{{"kind":"Map","brace":{},"entries":[{}]}}"#,
+                token(brace),
+                entries.join(",")
+            )
+        }
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => format!(
+            r#"{{"kind":"Index","object":{},"bracket":{},"index":{}}}"#,
+            self::expr(arena, &arena[*object]),
+            token(bracket),
+            self::expr(arena, &arena[*index])
+        ),
+        Expr::SetIndex {
+            object,
+            bracket,
+            index,
+            value,
+        } => format!(
+            r#"{{"kind":"SetIndex","object":{},"bracket":{},"index":{},"value":{}}}"#,
+            self::expr(arena, &arena[*object]),
+            token(bracket),
+            self::expr(arena, &arena[*index]),
+            self::expr(arena, &arena[*value])
+        ),
+        Expr::This(keyword) => format!(r#"{{"kind":"This","keyword":{}}}"#, token(keyword)),
+        Expr::Super { keyword, method } => format!(
+            r#"{{"kind":"Super","keyword":{},"method":{}}}"#,
+            token(keyword),
+            token(method)
+        ),
+        Expr::Error(at) => format!(r#"{{"kind":"Error","token":{}}}"#, token(at)),
+    }
+}
+
+fn binary(arena: &ExprArena, kind: &str, left: ExprId, operator: &Token, right: ExprId) -> String {
+    format!(
+        r#"{{"kind":"{}","left":{},"operator":{},"right":{}}}"#,
+        kind,
+        expr(arena, &arena[left]),
+        token(operator),
+        expr(arena, &arena[right])
+    )
+}
+
+fn literal(value: &Literal) -> String {
+    match value {
+        Literal::Bool(b) => b.to_string(),
+        Literal::Nil => "null".to_string(),
+        Literal::Number(n) => number(*n),
+        Literal::Str(s) => string(s),
+    }
+}
+
+/// JSON has no infinity, which an overlong number literal parses to.
+pub(crate) fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// `text` as a quoted JSON string.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
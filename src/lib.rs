@@ -0,0 +1,749 @@
+//! A tree-walk interpreter for the Lox language from *Crafting Interpreters*.
+//!
+//! The scanner, parser and interpreter modules can be used on their own. The
+//! functions at the crate root run one CLI command over a source string and
+//! collect what it prints, which is what the `lox` binary is built on.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[macro_use]
+pub mod messages;
+pub mod ast;
+pub mod captures;
+pub mod class;
+#[cfg(feature = "track-allocations")]
+pub mod counting_alloc;
+pub mod coverage;
+pub mod dap;
+pub mod debugger;
+pub mod diagnostic;
+pub mod doc;
+pub mod environment;
+pub mod exception;
+pub mod expect;
+pub mod fold;
+pub mod format;
+pub mod formatter;
+pub mod function;
+pub mod gc;
+pub mod highlight;
+pub mod host;
+#[cfg(feature = "net")]
+pub mod http;
+pub mod interpreter;
+pub mod json;
+pub mod line_index;
+pub mod lint;
+pub mod lsp;
+pub mod map;
+pub mod module;
+pub mod native;
+pub mod optimizer;
+pub mod parser;
+pub mod plugin;
+pub mod printer;
+pub mod profiler;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod status;
+pub mod string;
+pub mod suggest;
+pub mod symbol;
+pub mod terminal;
+pub mod token;
+pub mod typecheck;
+pub mod value;
+pub mod visit;
+pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use ast::{ExprArena, ExprId};
+use coverage::CoverageFormat;
+use debugger::Debugger;
+use diagnostic::Diagnostics;
+use format::Format;
+use interpreter::{Interpreter, Unwind};
+use lint::LintLevels;
+use optimizer::Optimizer;
+use parser::Parser;
+use printer::AstStyle;
+use resolver::Resolver;
+use scanner::{Limits, Scanner};
+use token::Token;
+use vm::Vm;
+
+pub use captures::captures;
+pub use doc::doc;
+pub use expect::check_tests;
+pub use format::tokenize;
+pub use formatter::fmt;
+pub use lint::lint;
+pub use status::ExitStatus;
+pub use typecheck::typecheck;
+
+/// The stack to run the commands and `Interpreter` on. Parsing, checking
+/// and running a program all recurse as deeply as it nests, a few
+/// kilobytes a level and several times that in an unoptimized build, so
+/// the 2 MiB a thread gets by default runs out well before the default
+/// `--max-nesting-depth` in a debug build. A host that lets programs nest
+/// that deeply should give the thread it runs them on this much.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Which engine `run` executes programs with.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// Walks the AST directly, see `interpreter`
+    #[default]
+    TreeWalk,
+    /// Compiles to bytecode and runs that, see `vm`
+    Vm,
+}
+
+/// How source files are decoded into text.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    /// Invalid sequences are an error, reported with their offset
+    #[default]
+    Utf8,
+    /// ISO 8859-1: every byte is the character with the same code point
+    Latin1,
+}
+
+/// Command-line options shared by every command.
+#[derive(Default, Clone)]
+pub struct Options {
+    pub limits: Limits,
+    pub format: Format,
+    pub ast_style: AstStyle,
+    /// `--coerce-strings`: see `Interpreter::set_coerce_strings`
+    pub coerce_strings: bool,
+    pub backend: Backend,
+    /// `--extensions`: makes `switch`, `case`, `default`, `try`, `catch`,
+    /// `finally`, `throw` and `with` keywords, for the statements and
+    /// mixins strict Lox doesn't have
+    pub extensions: bool,
+    pub encoding: Encoding,
+    /// `--pretty-errors`: show diagnostics with the source line they're
+    /// about, see `Diagnostic::render_pretty`
+    pub pretty_errors: bool,
+    /// `--tolerant`: `parse` and `print-ast` print the tree even when the
+    /// source has syntax errors, with error nodes where parsing failed
+    pub tolerant: bool,
+    /// `--check`: `fmt` only reports whether files are formatted
+    pub check: bool,
+    /// `--trace`: `run` and `evaluate` write what they evaluate to stderr, see
+    /// `Interpreter::set_trace` and `Vm::set_trace`
+    pub trace: bool,
+    /// `--opt`: `run` and `print-ast` fold constants and drop dead
+    /// branches first, see `optimizer`
+    pub optimize: bool,
+    /// `--opt-verbose`: `--opt`, also noting on stderr what it removed
+    pub opt_verbose: bool,
+    /// `--tco`: `run` doesn't grow the stack for calls in tail position, see
+    /// `Interpreter::set_tco`
+    pub tco: bool,
+    /// `--coverage`: `run` writes a report of which lines ran next to the
+    /// file, see `coverage`
+    pub coverage: Option<CoverageFormat>,
+    /// `--profile`: `run` prints how long each function took to stderr,
+    /// see `profiler`
+    pub profile: bool,
+    /// `--profile=FILE`: also writes the time in each stack of calls to
+    /// `FILE`, for a flame graph
+    pub folded_stacks: Option<PathBuf>,
+    /// `--op-histogram`: `run --backend=vm` counts the opcodes it runs and
+    /// prints them to stderr, see `vm::histogram`
+    pub op_histogram: bool,
+    /// `--heap-stats`: `run` prints what the cycle collector did to stderr
+    /// when the program ends, see `gc::HeapStats`
+    pub heap_stats: bool,
+    /// `--iterations`: how many times `bench` runs the program, if not
+    /// `DEFAULT_ITERATIONS`
+    pub iterations: Option<usize>,
+    /// `--allow`, `--warn` and `--deny`: how `lint` reports each lint
+    pub lint_levels: LintLevels,
+    /// `--strict`: `typecheck` reports what it finds as errors rather than
+    /// warnings
+    pub strict: bool,
+    /// `--allow-fs`: `run` gives the program natives that list, create and
+    /// remove files, see `native::define_fs`
+    pub allow_fs: bool,
+    /// `--allow-exec`: `run` gives the program a native that runs other
+    /// programs, see `native::define_exec`
+    pub allow_exec: bool,
+    /// `--allow-net`: `run` gives the program a native that makes HTTP
+    /// requests, see `native::define_net`. Only in a build with the `net`
+    /// feature.
+    pub allow_net: bool,
+    /// `--plugin=path`, once for each: shared libraries `run` loads natives
+    /// from, see `plugin`
+    pub plugins: Vec<PathBuf>,
+    /// The file the source was read from, which `import`s in it are
+    /// relative to. Set per file rather than by an option.
+    pub path: Option<PathBuf>,
+}
+
+/// Text a command produced for one file. Buffered rather than printed so
+/// files can be processed on worker threads without interleaving.
+#[derive(Default)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// ---------------------------------------------------------------------------
+/// parse() function: Scans => runs parser => prints AST
+/// `--format=json` prints it as a JSON object instead of an s-expression.
+/// Returns the parse errors, if any, as a `CompileError`.
+/// ---------------------------------------------------------------------------
+pub fn parse(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let (arena, expr, status) = if options.tolerant {
+        let (arena, expr, diagnostics) = parse_tolerant(source, options, Parser::parse_tolerant);
+        (arena, expr, ExitStatus::from_diagnostics(diagnostics))
+    } else {
+        match parse_expression(source, options) {
+            Ok((arena, expr)) => (arena, expr, ExitStatus::Success),
+            Err(status) => return status,
+        }
+    };
+
+    let ast = match options.format {
+        Format::Json => json::expr(&arena, &arena[expr]),
+        _ => printer::print_ast(&arena, &arena[expr]),
+    };
+    writeln!(out.stdout, "{}", ast).unwrap();
+    status
+}
+
+/// ---------------------------------------------------------------------------
+/// print_ast() function: parses a whole program => prints its AST
+/// Uses `options.ast_style`. Returns the parse errors, if any, as a `CompileError`.
+/// ---------------------------------------------------------------------------
+pub fn print_ast(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let (mut arena, statements, status) = if options.tolerant {
+        let (arena, statements, diagnostics) =
+            parse_tolerant(source, options, Parser::parse_program_tolerant);
+        (arena, statements, ExitStatus::from_diagnostics(diagnostics))
+    } else {
+        let tokens = match scan_tokens(source, options) {
+            Ok(tokens) => tokens,
+            Err(status) => return status,
+        };
+        let mut parser = Parser::new(tokens);
+        parser.set_max_depth(options.limits.max_nesting_depth);
+        match parser.parse_program() {
+            Some(statements) => (parser.arena, statements, ExitStatus::Success),
+            None => return ExitStatus::from_diagnostics(parser.diagnostics),
+        }
+    };
+
+    let statements = match options.optimize {
+        true => {
+            // Resolved only to find the unused locals
+            let mut interpreter = Interpreter::new();
+            let mut resolver = Resolver::new(&mut interpreter);
+            resolver.resolve(&arena, &statements);
+            optimize(&mut arena, statements, &resolver.unused, options, out)
+        }
+        false => statements,
+    };
+    match options.ast_style {
+        AstStyle::Sexpr => {
+            for stmt in &statements {
+                writeln!(out.stdout, "{}", printer::print_stmt(&arena, stmt)).unwrap();
+            }
+        }
+        AstStyle::Indent => out
+            .stdout
+            .push_str(&printer::print_tree(&arena, &statements)),
+    }
+    status
+}
+
+/// ---------------------------------------------------------------------------
+/// evaluate() function: parses like parse() => evaluates the AST => prints the value
+/// Returns a `CompileError` for parse errors and a `RuntimeError` if evaluation fails.
+/// ---------------------------------------------------------------------------
+pub fn evaluate(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let (arena, expr) = match parse_expression(source, options) {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_coerce_strings(options.coerce_strings);
+    interpreter.set_extensions(options.extensions);
+    interpreter.set_trace(options.trace);
+    interpreter.set_limits(&options.limits);
+    match interpreter.evaluate(&arena, expr, out) {
+        Ok(value) => {
+            writeln!(out.stdout, "{}", value).unwrap();
+            ExitStatus::Success
+        }
+        Err(error) => ExitStatus::RuntimeError(error.into()),
+    }
+}
+
+/// ---------------------------------------------------------------------------
+/// run() function: parses a whole program => resolves it => executes its statements
+/// Returns a `CompileError` for parse and resolution errors and a `RuntimeError`
+/// if execution fails.
+/// ---------------------------------------------------------------------------
+pub fn run(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    if options.heap_stats {
+        gc::reset_stats();
+    }
+    let (arena, statements, mut interpreter) = match resolve_program(source, options, out) {
+        Ok(resolved) => resolved,
+        Err(status) => return status,
+    };
+
+    if options.backend == Backend::Vm {
+        if options.coverage.is_some() {
+            return ExitStatus::UsageError(msg!("E0043", "--coverage"));
+        }
+        if options.profile {
+            return ExitStatus::UsageError(msg!("E0043", "--profile"));
+        }
+        if options.tco {
+            return ExitStatus::UsageError(msg!("E0043", "--tco"));
+        }
+        if options.allow_fs {
+            return ExitStatus::UsageError(msg!("E0043", "--allow-fs"));
+        }
+        if options.allow_exec {
+            return ExitStatus::UsageError(msg!("E0043", "--allow-exec"));
+        }
+        if options.allow_net {
+            return ExitStatus::UsageError(msg!("E0043", "--allow-net"));
+        }
+        if !options.plugins.is_empty() {
+            return ExitStatus::UsageError(msg!("E0043", "--plugin"));
+        }
+        return run_vm(&arena, &statements, options, out);
+    }
+
+    if options.op_histogram {
+        return ExitStatus::UsageError(msg!("E0058", "--op-histogram"));
+    }
+    interpreter.set_coverage(options.coverage.is_some());
+    interpreter.cover(&arena, &statements);
+    interpreter.set_profile(options.profile);
+    let mut status = execute_program(&mut interpreter, &arena, &statements, out);
+
+    // Reported however the program ended, since a failing one's reports
+    // show how far it got
+    if let Some(mut profiler) = interpreter.profile() {
+        out.stderr.push_str(&profiler::table(&profiler.finish()));
+        if let Some(path) = &options.folded_stacks {
+            match fs::write(path, profiler.folded()) {
+                Ok(()) => writeln!(out.stderr, "{}", msg!("E0046", path.display())).unwrap(),
+                Err(err) if matches!(status, ExitStatus::Success) => status = ExitStatus::Io(err),
+                Err(_) => {}
+            }
+        }
+    }
+    if let Some(format) = options.coverage {
+        match write_coverage(&interpreter, source, options, format) {
+            Ok(path) => writeln!(out.stderr, "{}", msg!("E0044", path.display())).unwrap(),
+            Err(err) if matches!(status, ExitStatus::Success) => status = ExitStatus::Io(err),
+            Err(_) => {}
+        }
+    }
+    if options.heap_stats {
+        writeln!(out.stderr, "{}", gc::stats()).unwrap();
+    }
+    status
+}
+
+/// Writes the coverage report next to the file the program was read from,
+/// with the extension of `format`, and returns where it went.
+fn write_coverage(
+    interpreter: &Interpreter,
+    source: &str,
+    options: &Options,
+    format: CoverageFormat,
+) -> io::Result<PathBuf> {
+    let files = interpreter.coverage().unwrap_or_default();
+    let (report, extension) = match format {
+        CoverageFormat::Lcov => (coverage::lcov(&files), "lcov"),
+        CoverageFormat::Html => {
+            let name = source_name(options);
+            let report = coverage::html(&files, |file| match file == name {
+                true => Some(source.to_string()),
+                false => fs::read_to_string(file).ok(),
+            });
+            (report, "html")
+        }
+    };
+    let path = match &options.path {
+        Some(path) => path.with_extension(extension),
+        None => PathBuf::from("coverage").with_extension(extension),
+    };
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// ---------------------------------------------------------------------------
+/// debug() function: runs a program under the interactive debugger
+/// Like `run` on the tree-walker, but stops before the first statement and
+/// reads debugger commands from stdin, see `debugger`. What the program
+/// prints goes straight to stdout, so it shows between the prompts.
+/// ---------------------------------------------------------------------------
+pub fn debug(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    if options.backend == Backend::Vm {
+        return ExitStatus::UsageError(msg!("E0043", "debug"));
+    }
+    let (arena, statements, mut interpreter) = match resolve_program(source, options, out) {
+        Ok(resolved) => resolved,
+        Err(status) => return status,
+    };
+
+    let debugger = Debugger::new(
+        source,
+        source_name(options),
+        io::stdin().lock(),
+        io::stdout(),
+    );
+    interpreter.set_debugger(debugger);
+    interpreter.set_output(io::stdout());
+    execute_program(&mut interpreter, &arena, &statements, out)
+}
+
+/// Runs a resolved program's statements on the tree-walker.
+pub(crate) fn execute_program(
+    interpreter: &mut Interpreter,
+    arena: &Rc<ExprArena>,
+    statements: &[ast::Stmt],
+    out: &mut Output,
+) -> ExitStatus {
+    for stmt in statements {
+        match interpreter.execute(arena, stmt, out) {
+            Ok(()) => {}
+            Err(Unwind::Error(error)) => return ExitStatus::RuntimeError(error.into()),
+            Err(Unwind::Return(_) | Unwind::TailCall(_) | Unwind::Break | Unwind::Continue) => {
+                unreachable!("resolver rejects return, break and continue out of place")
+            }
+        }
+    }
+    ExitStatus::Success
+}
+
+/// How many times `bench` runs a program without `--iterations`.
+pub const DEFAULT_ITERATIONS: usize = 10;
+
+/// ---------------------------------------------------------------------------
+/// bench() function: runs a program repeatedly => prints timing statistics
+/// Times every step of `run`, from scanning to the end of the program, with
+/// what it prints thrown away. Prints the fastest, median and mean time, and
+/// with the `track-allocations` feature how much each run allocated. Stops
+/// at the first run that fails and returns its status.
+/// ---------------------------------------------------------------------------
+pub fn bench(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let iterations = options.iterations.unwrap_or(DEFAULT_ITERATIONS);
+    #[cfg(feature = "track-allocations")]
+    let (allocations, bytes) = counting_alloc::allocations();
+
+    let times = match time_runs(source, options) {
+        Ok(times) => times,
+        Err(status) => return status,
+    };
+    let median = median(&times);
+    let mean = times.iter().sum::<Duration>() / iterations as u32;
+    // Such as `1.234ms`, to at most three decimals
+    let [min, median, mean] = [times[0], median, mean].map(|time| format!("{:.3?}", time));
+    writeln!(
+        out.stdout,
+        "{}",
+        msg!("E0031", bench_name(options), iterations, min, median, mean)
+    )
+    .unwrap();
+
+    #[cfg(feature = "track-allocations")]
+    {
+        let (allocations_after, bytes_after) = counting_alloc::allocations();
+        let per_run = |before: usize, after: usize| (after - before) / iterations;
+        writeln!(
+            out.stdout,
+            "{}",
+            msg!(
+                "E0032",
+                per_run(allocations, allocations_after),
+                per_run(bytes, bytes_after)
+            )
+        )
+        .unwrap();
+    }
+    ExitStatus::Success
+}
+
+/// ---------------------------------------------------------------------------
+/// compare_backends() function: times a program on both backends => prints a
+/// table row
+/// What `bench` does for each program in a directory: times `run` on the
+/// tree-walker and then on the VM, and prints a row of the table
+/// `compare_backends_header` heads, with the median time on each and how
+/// many times faster the VM was. Stops at the first run that fails on
+/// either and returns its status.
+/// ---------------------------------------------------------------------------
+pub fn compare_backends(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let mut medians = Vec::new();
+    for backend in [Backend::TreeWalk, Backend::Vm] {
+        let options = Options {
+            backend,
+            ..options.clone()
+        };
+        match time_runs(source, &options) {
+            Ok(times) => medians.push(median(&times)),
+            Err(status) => return status,
+        }
+    }
+
+    let (tree_walk, vm) = (medians[0], medians[1]);
+    let speedup = format!("{:.2}x", tree_walk.as_secs_f64() / vm.as_secs_f64());
+    let name = bench_name(options);
+    let [tree_walk, vm] = [tree_walk, vm].map(|time| format!("{:.3?}", time));
+    out.stdout
+        .push_str(&comparison_row(&name, &tree_walk, &vm, &speedup));
+    ExitStatus::Success
+}
+
+/// The header of the table `compare_backends` prints the rows of.
+pub fn compare_backends_header() -> String {
+    let header = msg!("E0057");
+    let columns: Vec<&str> = header.split_whitespace().collect();
+    comparison_row(columns[0], columns[1], columns[2], columns[3])
+}
+
+fn comparison_row(name: &str, tree_walk: &str, vm: &str, speedup: &str) -> String {
+    format!("{:<28} {:>12} {:>12} {:>8}\n", name, tree_walk, vm, speedup)
+}
+
+/// How long each of `--iterations` runs of `source` took, or the status of
+/// the first that failed.
+fn time_runs(source: &str, options: &Options) -> Result<Vec<Duration>, ExitStatus> {
+    let iterations = options.iterations.unwrap_or(DEFAULT_ITERATIONS);
+    let mut times = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let status = run(source, options, &mut Output::default());
+        times.push(start.elapsed());
+        if !matches!(status, ExitStatus::Success) {
+            return Err(status);
+        }
+    }
+    times.sort();
+    Ok(times)
+}
+
+/// The middle of the sorted `times`, or the mean of the middle two.
+fn median(times: &[Duration]) -> Duration {
+    let middle = times.len() / 2;
+    if times.len() % 2 == 0 {
+        (times[middle - 1] + times[middle]) / 2
+    } else {
+        times[middle]
+    }
+}
+
+/// What `bench` calls the program it times.
+fn bench_name(options: &Options) -> String {
+    options
+        .path
+        .as_ref()
+        .map_or_else(|| "<stdin>".to_string(), |path| path.display().to_string())
+}
+
+/// ---------------------------------------------------------------------------
+/// disassemble() function: compiles a whole program to bytecode => prints it
+/// Lists the script's chunk and then every function's, clox style. Returns a
+/// `CompileError` for parse, resolution and compile errors.
+/// ---------------------------------------------------------------------------
+pub fn disassemble(source: &str, options: &Options, out: &mut Output) -> ExitStatus {
+    let (arena, statements, _) = match resolve_program(source, options, out) {
+        Ok(resolved) => resolved,
+        Err(status) => return status,
+    };
+
+    match vm::compiler::compile(&arena, &statements) {
+        Ok(script) => {
+            vm::debug::disassemble_function(&script, &mut out.stdout);
+            ExitStatus::Success
+        }
+        Err(diagnostics) => ExitStatus::from_diagnostics(diagnostics),
+    }
+}
+
+/// Scans, parses and resolves a whole program, returning the arena of its
+/// expressions, its statements and the interpreter the resolver told where
+/// its locals are.
+pub(crate) fn resolve_program(
+    source: &str,
+    options: &Options,
+    out: &mut Output,
+) -> Result<(Rc<ExprArena>, Vec<ast::Stmt>, Interpreter), ExitStatus> {
+    let tokens = scan_tokens(source, options)?;
+
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let Some(statements) = parser.parse_program() else {
+        return Err(ExitStatus::from_diagnostics(parser.diagnostics));
+    };
+    let mut arena = parser.arena;
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_coerce_strings(options.coerce_strings);
+    interpreter.set_extensions(options.extensions);
+    interpreter.set_trace(options.trace);
+    interpreter.set_tco(options.tco);
+    interpreter.set_limits(&options.limits);
+    if options.allow_fs {
+        native::define_fs(&mut interpreter);
+    }
+    if options.allow_exec {
+        native::define_exec(&mut interpreter);
+    }
+    #[cfg(feature = "net")]
+    if options.allow_net {
+        native::define_net(&mut interpreter);
+    }
+    for path in &options.plugins {
+        plugin::load(&mut interpreter, path).map_err(ExitStatus::UsageError)?;
+    }
+    if let Some(path) = &options.path {
+        interpreter.set_path(path);
+    }
+    // The first source, so its tokens keep their offsets
+    interpreter.begin_source(source.len(), source_name(options));
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve(&arena, &statements);
+    if resolver.diagnostics.has_errors() {
+        return Err(ExitStatus::from_diagnostics(resolver.diagnostics));
+    }
+    let unused = std::mem::take(&mut resolver.unused);
+    interpreter.set_optimize(options.optimize);
+    if !options.optimize {
+        return Ok((Rc::new(arena), statements, interpreter));
+    }
+    let statements = optimize(&mut arena, statements, &unused, options, out);
+    // A dropped declaration moves the slots of those after it
+    Resolver::new(&mut interpreter).resolve(&arena, &statements);
+    Ok((Rc::new(arena), statements, interpreter))
+}
+
+/// Runs the `--opt` pass over a program whose locals `unused` are never
+/// used, noting what it removed on stderr with `--opt-verbose`.
+fn optimize(
+    arena: &mut ExprArena,
+    statements: Vec<ast::Stmt>,
+    unused: &[Token],
+    options: &Options,
+    out: &mut Output,
+) -> Vec<ast::Stmt> {
+    let mut optimizer = Optimizer::new(options.coerce_strings);
+    optimizer.set_unused(unused);
+    let statements = optimizer.optimize(arena, statements);
+    if options.opt_verbose {
+        for removed in &optimizer.removed {
+            writeln!(out.stderr, "{}", removed).unwrap();
+        }
+    }
+    statements
+}
+
+/// The name stack traces give the file the source was read from.
+fn source_name(options: &Options) -> String {
+    options
+        .path
+        .as_ref()
+        .map_or_else(|| "<script>".to_string(), |path| path.display().to_string())
+}
+
+/// Compiles an already resolved program to bytecode and runs it on the VM.
+fn run_vm(
+    arena: &ExprArena,
+    statements: &[ast::Stmt],
+    options: &Options,
+    out: &mut Output,
+) -> ExitStatus {
+    let script = match vm::compiler::compile(arena, statements) {
+        Ok(script) => script,
+        Err(diagnostics) => return ExitStatus::from_diagnostics(diagnostics),
+    };
+
+    let mut vm = Vm::new();
+    vm.set_coerce_strings(options.coerce_strings);
+    vm.set_max_frames(options.limits.max_call_depth);
+    vm.set_trace(options.trace);
+    vm.set_histogram(options.op_histogram);
+    let status = match vm.interpret(script, out) {
+        Ok(()) => ExitStatus::Success,
+        Err(error) => ExitStatus::RuntimeError(error),
+    };
+    // However the program ended, like `--profile`'s table
+    if let Some(histogram) = vm.histogram() {
+        out.stderr.push_str(&histogram);
+    }
+    if options.heap_stats {
+        writeln!(out.stderr, "{}", gc::stats()).unwrap();
+    }
+    status
+}
+
+/// Scans and parses `source` as a single expression, returning it with the
+/// arena it and its operands are in.
+pub fn parse_expression(
+    source: &str,
+    options: &Options,
+) -> Result<(ExprArena, ExprId), ExitStatus> {
+    let tokens = scan_tokens(source, options)?;
+
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    match parser.parse() {
+        Some(expr) => Ok((parser.arena, expr)),
+        // If parse error, hand back the diagnostics
+        None => Err(ExitStatus::from_diagnostics(parser.diagnostics)),
+    }
+}
+
+/// Scans and parses `source` with `parse`, one of the parser's tolerant
+/// entry points, carrying on past errors in both. Returns what was parsed
+/// and the arena of its expressions, along with every diagnostic, the
+/// scanner's first.
+fn parse_tolerant<T>(
+    source: &str,
+    options: &Options,
+    parse: impl FnOnce(&mut Parser) -> T,
+) -> (ExprArena, T, Diagnostics) {
+    let mut scanner = Scanner::new(source, &options.limits);
+    scanner.set_extensions(options.extensions);
+    let tokens = scanner.scan_tokens();
+
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let parsed = parse(&mut parser);
+    let mut diagnostics = scanner.diagnostics;
+    diagnostics.extend(parser.diagnostics);
+    (parser.arena, parsed, diagnostics)
+}
+
+/// Scans `source` into the tokens the parser works on.
+pub fn scan_tokens(source: &str, options: &Options) -> Result<Vec<Token>, ExitStatus> {
+    let mut scanner = Scanner::new(source, &options.limits);
+    scanner.set_extensions(options.extensions);
+    let tokens = scanner.scan_tokens();
+
+    if scanner.diagnostics.has_errors() {
+        Err(ExitStatus::from_diagnostics(scanner.diagnostics))
+    } else {
+        Ok(tokens)
+    }
+}
@@ -0,0 +1,53 @@
+/// Precomputed line start offsets for a source, used to convert between byte
+/// offsets and 1-based (line, column) positions without rescanning for
+/// newlines. Columns are counted in bytes from the start of the line.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (offset, byte) in source.bytes().enumerate() {
+            if byte == b'\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+
+        LineIndex {
+            line_starts,
+            len: source.len(),
+        }
+    }
+
+    /// Number of lines in the source. An empty source has one (empty) line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the 1-based line and column of `offset`. Offsets past the end
+    /// of the source are clamped to the end.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len);
+        // Number of lines starting at or before `offset` is the line number
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = offset - self.line_starts[line - 1] + 1;
+        (line, column)
+    }
+
+    /// Returns the byte offset of a 1-based line and column, or `None` if the
+    /// position is outside the source.
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).copied().unwrap_or(self.len + 1);
+        let offset = start + column.checked_sub(1)?;
+
+        // A line's valid columns run up to and including its newline
+        if offset < end {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
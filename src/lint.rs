@@ -0,0 +1,123 @@
+use crate::diagnostic::{Diagnostics, Severity};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::status::ExitStatus;
+use crate::{scan_tokens, Options, Output};
+
+/// Reports the mistakes the resolver finds and the lints
+/// `options.lint_levels` asks for, ordered by where they are.
+pub fn lint(source: &str, options: &Options, _out: &mut Output) -> ExitStatus {
+    let tokens = match scan_tokens(source, options) {
+        Ok(tokens) => tokens,
+        Err(status) => return status,
+    };
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let Some(statements) = parser.parse_program() else {
+        return ExitStatus::from_diagnostics(parser.diagnostics);
+    };
+
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.set_lints(options.lint_levels);
+    resolver.resolve(&parser.arena, &statements);
+
+    // Unused locals are only found at the end of their scope
+    let mut diagnostics = resolver.diagnostics.into_vec();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    ExitStatus::from_diagnostics(diagnostics.into_iter().collect::<Diagnostics>())
+}
+
+/// A check the resolver can make for the `lint` command, beyond the errors
+/// it always reports. Those about variables only look at locals: a global
+/// can be used by code anywhere, even code that runs later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A local variable, function or class that's never read
+    UnusedVariable,
+    /// A parameter that's never read
+    UnusedParameter,
+    /// Statements after a `return`, `break` or `continue` in the same block
+    UnreachableCode,
+    /// A local declared with the name of one in an enclosing scope
+    Shadowing,
+    /// A value stored in a local that's never read before the local goes
+    /// out of scope
+    UnusedAssignment,
+    /// A comparison like `a < b < c`, which compares the boolean `a < b`
+    /// with `c` rather than checking `b` is between the two
+    ChainedComparison,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UnreachableCode,
+        Lint::Shadowing,
+        Lint::UnusedAssignment,
+        Lint::ChainedComparison,
+    ];
+
+    /// The name `--allow`, `--warn` and `--deny` take, shown after each
+    /// message.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::Shadowing => "shadowing",
+            Lint::UnusedAssignment => "unused-assignment",
+            Lint::ChainedComparison => "chained-comparison",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    /// Reported as an error, failing the command
+    Deny,
+}
+
+impl Level {
+    /// The severity findings are reported with, or `None` when allowed.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            Level::Allow => None,
+            Level::Warn => Some(Severity::Warning),
+            Level::Deny => Some(Severity::Error),
+        }
+    }
+}
+
+/// The level of every lint. All are warnings unless configured otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LintLevels {
+    levels: [Level; Lint::ALL.len()],
+}
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        LintLevels {
+            levels: [Level::Warn; Lint::ALL.len()],
+        }
+    }
+}
+
+impl LintLevels {
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels[lint as usize]
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels[lint as usize] = level;
+    }
+}
@@ -0,0 +1,260 @@
+use std::slice;
+
+use crate::ast::{class_signature, ExprArena, FunctionDecl, Stmt};
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::interpreter::Interpreter;
+use crate::lint::LintLevels;
+use crate::parser::Parser;
+use crate::resolver::{Binding, Resolver};
+use crate::scanner::{Limits, Scanner};
+use crate::token::{Token, TokenType};
+
+/// What the server works out from one version of a document. Built from
+/// a tolerant parse, so a document being edited still has most of its
+/// declarations and bindings.
+pub struct Analysis {
+    pub tokens: Vec<Token>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The declarations at the top level, each with those nested in it
+    pub declarations: Vec<Declaration>,
+    bindings: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclarationKind {
+    Variable,
+    Parameter,
+    Function,
+    Method,
+    Class,
+}
+
+/// A name the source declares.
+pub struct Declaration {
+    pub name: Token,
+    pub kind: DeclarationKind,
+    /// How it's declared, such as `fun add(a, b)`, for hovers
+    pub signature: String,
+    /// Declared at the top level rather than in a block or function
+    pub global: bool,
+    /// Parameters, methods and the locals of a function's body
+    pub children: Vec<Declaration>,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let limits = Limits::default();
+        let mut scanner = Scanner::new(source, &limits);
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens.clone());
+        let statements = parser.parse_program_tolerant();
+
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.set_lints(LintLevels::default());
+        resolver.keep_bindings();
+        resolver.resolve(&parser.arena, &statements);
+
+        let mut diagnostics = scanner.diagnostics;
+        diagnostics.extend(parser.diagnostics);
+        // The resolver rejects the error nodes of code that didn't parse,
+        // which would only repeat the syntax errors
+        if !diagnostics.has_errors() {
+            diagnostics.extend(sorted(resolver.diagnostics));
+        }
+
+        let mut declarations = Vec::new();
+        collect(&parser.arena, &statements, true, &mut declarations);
+        Analysis {
+            tokens,
+            diagnostics: diagnostics.into_vec(),
+            declarations,
+            bindings: resolver.bindings,
+        }
+    }
+
+    /// The index of the identifier at byte `offset`, or just before it so
+    /// a cursor at the end of a name still finds it.
+    pub fn identifier_at(&self, offset: usize) -> Option<usize> {
+        self.tokens.iter().position(|token| {
+            token.token_type == TokenType::Identifier
+                && token.span.start <= offset
+                && offset <= token.span.end
+        })
+    }
+
+    /// The declaration the identifier at `index` names: itself when it's
+    /// the name being declared, the local it's bound to, or otherwise the
+    /// global of that name. Property names after a `.` have none.
+    pub fn definition(&self, index: usize) -> Option<&Declaration> {
+        let token = &self.tokens[index];
+        let all = self.all_declarations();
+        if let Some(declaration) = all
+            .iter()
+            .find(|declaration| declaration.name.span == token.span)
+        {
+            return Some(declaration);
+        }
+
+        if let Some(binding) = self
+            .bindings
+            .iter()
+            .find(|binding| binding.usage.span == token.span)
+        {
+            let span = &binding.declaration.span;
+            return all
+                .into_iter()
+                .find(|declaration| declaration.name.span == *span);
+        }
+
+        let after_dot = index > 0 && self.tokens[index - 1].token_type == TokenType::Dot;
+        if after_dot {
+            return None;
+        }
+        all.into_iter()
+            .find(|declaration| declaration.global && declaration.name.lexeme == token.lexeme)
+    }
+
+    /// Every declaration, parents before their children.
+    fn all_declarations(&self) -> Vec<&Declaration> {
+        let mut all = Vec::new();
+        let mut pending: Vec<&Declaration> = self.declarations.iter().rev().collect();
+        while let Some(declaration) = pending.pop() {
+            all.push(declaration);
+            pending.extend(declaration.children.iter().rev());
+        }
+        all
+    }
+}
+
+/// The resolver finds unused locals at the end of their scope, after what
+/// follows them, so its diagnostics are put back in source order.
+fn sorted(diagnostics: Diagnostics) -> Vec<Diagnostic> {
+    let mut diagnostics = diagnostics.into_vec();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    diagnostics
+}
+
+/// Adds the declarations in `statements` to `out`, looking into blocks
+/// and the bodies of loops and `if`s, which declare into the same list.
+fn collect(arena: &ExprArena, statements: &[Stmt], global: bool, out: &mut Vec<Declaration>) {
+    for stmt in statements {
+        match stmt {
+            Stmt::Var {
+                name, annotation, ..
+            } => out.push(Declaration {
+                name: name.clone(),
+                kind: DeclarationKind::Variable,
+                signature: match annotation {
+                    Some(annotation) => format!("var {}: {}", name.lexeme, annotation.lexeme),
+                    None => format!("var {}", name.lexeme),
+                },
+                global,
+                children: Vec::new(),
+            }),
+            Stmt::Function(function) => {
+                let signature = format!("fun {}", function.signature());
+                out.push(function_declaration(
+                    arena,
+                    function,
+                    DeclarationKind::Function,
+                    signature,
+                    global,
+                ));
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                let signature = class_signature(arena, name, *superclass, mixins);
+                let class_methods = class_methods.iter().map(|method| {
+                    let signature = format!("class {}.{}", name.lexeme, method.signature());
+                    (method, signature)
+                });
+                let children = methods
+                    .iter()
+                    .map(|method| {
+                        let signature = format!("{}.{}", name.lexeme, method.signature());
+                        (method, signature)
+                    })
+                    .chain(class_methods)
+                    .map(|(method, signature)| {
+                        function_declaration(
+                            arena,
+                            method,
+                            DeclarationKind::Method,
+                            signature,
+                            false,
+                        )
+                    })
+                    .collect();
+                out.push(Declaration {
+                    name: name.clone(),
+                    kind: DeclarationKind::Class,
+                    signature,
+                    global,
+                    children,
+                });
+            }
+            Stmt::Block(statements) => collect(arena, statements, false, out),
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect(arena, slice::from_ref(then_branch), false, out);
+                if let Some(else_branch) = else_branch {
+                    collect(arena, slice::from_ref(else_branch), false, out);
+                }
+            }
+            Stmt::While { body, .. } => collect(arena, slice::from_ref(body), false, out),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                collect(arena, body, false, out);
+                if let Some(catch) = catch {
+                    collect(arena, &catch.body, false, out);
+                }
+                if let Some(finally) = finally {
+                    collect(arena, finally, false, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn function_declaration(
+    arena: &ExprArena,
+    function: &FunctionDecl,
+    kind: DeclarationKind,
+    signature: String,
+    global: bool,
+) -> Declaration {
+    let mut children: Vec<Declaration> = function
+        .params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| Declaration {
+            name: param.clone(),
+            kind: DeclarationKind::Parameter,
+            signature: format!("(parameter) {}", function.param(index)),
+            global: false,
+            children: Vec::new(),
+        })
+        .collect();
+    collect(arena, &function.body, false, &mut children);
+    Declaration {
+        name: function.name.clone(),
+        kind,
+        signature,
+        global,
+        children,
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A JSON value, as the messages of the protocol are made of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they were written
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses `text` as a single JSON value, or `None` if it isn't one.
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        chars.peek().is_none().then_some(value)
+    }
+
+    /// The member `key` of an object. `None` for a missing member and for
+    /// values that aren't objects, so lookups can be chained.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find_map(|(name, value)| (name == key).then_some(value)),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+}
+
+/// An object with the given members.
+pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+    Json::Object(
+        members
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self {
+        Json::Array(values)
+    }
+}
+
+/// Compact JSON, with no whitespace between tokens.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", crate::json::number(*n)),
+            Json::String(s) => write!(f, "{}", crate::json::string(s)),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", crate::json::string(name), value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars
+        .next_if(|ch| matches!(ch, ' ' | '\t' | '\n' | '\r'))
+        .is_some()
+    {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_whitespace(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Some(Json::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                chars.next_if_eq(&'"')?;
+                let name = parse_string(chars)?;
+                skip_whitespace(chars);
+                chars.next_if_eq(&':')?;
+                members.push((name, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    '}' => return Some(Json::Object(members)),
+                    _ => return None,
+                }
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Some(Json::Array(values));
+            }
+            loop {
+                values.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next()? {
+                    ',' => continue,
+                    ']' => return Some(Json::Array(values)),
+                    _ => return None,
+                }
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        't' => keyword(chars, "true", Json::Bool(true)),
+        'f' => keyword(chars, "false", Json::Bool(false)),
+        'n' => keyword(chars, "null", Json::Null),
+        _ => {
+            let mut number = String::new();
+            while let Some(ch) =
+                chars.next_if(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(ch);
+            }
+            number.parse().ok().map(Json::Number)
+        }
+    }
+}
+
+fn keyword(chars: &mut Peekable<Chars>, word: &str, value: Json) -> Option<Json> {
+    for expected in word.chars() {
+        chars.next_if_eq(&expected)?;
+    }
+    Some(value)
+}
+
+/// Parses the rest of a string whose opening quote has been consumed.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(text),
+            '\\' => match chars.next()? {
+                '"' => text.push('"'),
+                '\\' => text.push('\\'),
+                '/' => text.push('/'),
+                'b' => text.push('\u{8}'),
+                'f' => text.push('\u{c}'),
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                'u' => {
+                    let unit = hex4(chars)?;
+                    // Characters outside the BMP come as a surrogate pair
+                    let code = if (0xD800..0xDC00).contains(&unit) {
+                        chars.next_if_eq(&'\\')?;
+                        chars.next_if_eq(&'u')?;
+                        let low = hex4(chars)?;
+                        0x10000 + ((unit - 0xD800) << 10) + (low.checked_sub(0xDC00)? & 0x3FF)
+                    } else {
+                        unit
+                    };
+                    text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                _ => return None,
+            },
+            ch => text.push(ch),
+        }
+    }
+}
+
+fn hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..4 {
+        value = value * 16 + chars.next()?.to_digit(16)?;
+    }
+    Some(value)
+}
@@ -0,0 +1,352 @@
+//! A language server for editors, speaking the Language Server Protocol
+//! over standard input and output. Started with `lox lsp`.
+//!
+//! It reports diagnostics when a document is opened or saved, and answers
+//! go-to-definition, hover and document symbol requests. Every request
+//! reanalyzes the document, see `analysis`; sources are small enough.
+
+pub mod analysis;
+pub mod json;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::line_index::LineIndex;
+use analysis::{Analysis, Declaration, DeclarationKind};
+use json::{object, Json};
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// Serves requests read from `input` until the client says to exit, writing
+/// responses and notifications to `output`. Returns the code to exit with:
+/// 0 when the client asked to shut down first, as the protocol says, and
+/// 1 when it didn't or `input` ended.
+pub fn serve(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+    let mut server = Server::default();
+    while let Some(body) = read_message(input)? {
+        let Some(message) = Json::parse(&body) else {
+            let error = error_response(Json::Null, PARSE_ERROR, "Parse error");
+            write_message(output, &error)?;
+            continue;
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        if method == "exit" {
+            return Ok(if server.shut_down { 0 } else { 1 });
+        }
+        server.handle(method, &message, output)?;
+    }
+    Ok(1)
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of every open document, by URI
+    documents: HashMap<String, String>,
+    shut_down: bool,
+}
+
+impl Server {
+    fn handle(&mut self, method: &str, message: &Json, output: &mut impl Write) -> io::Result<()> {
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or("")
+            .to_string();
+
+        // Notifications, which have no id and get no response
+        let Some(id) = message.get("id").cloned() else {
+            match method {
+                "textDocument/didOpen" => {
+                    let text = params.get("textDocument").and_then(|doc| doc.get("text"));
+                    let text = text.and_then(Json::as_str).unwrap_or("").to_string();
+                    self.documents.insert(uri.clone(), text);
+                    self.publish_diagnostics(&uri, output)?;
+                }
+                "textDocument/didChange" => {
+                    // Synced in full, so the last change is the whole text
+                    let changes = match params.get("contentChanges") {
+                        Some(Json::Array(changes)) => changes.as_slice(),
+                        _ => &[],
+                    };
+                    let text = changes.last().and_then(|change| change.get("text"));
+                    if let Some(text) = text.and_then(Json::as_str) {
+                        self.documents.insert(uri, text.to_string());
+                    }
+                }
+                "textDocument/didSave" => {
+                    if let Some(text) = params.get("text").and_then(Json::as_str) {
+                        self.documents.insert(uri.clone(), text.to_string());
+                    }
+                    self.publish_diagnostics(&uri, output)?;
+                }
+                "textDocument/didClose" => {
+                    self.documents.remove(&uri);
+                    write_message(output, &diagnostics_notification(&uri, Vec::new()))?;
+                }
+                _ => {}
+            }
+            return Ok(());
+        };
+
+        let result = match method {
+            "initialize" => object([
+                ("capabilities", capabilities()),
+                ("serverInfo", object([("name", "lox".into())])),
+            ]),
+            "shutdown" => {
+                self.shut_down = true;
+                Json::Null
+            }
+            "textDocument/definition" | "textDocument/hover" => {
+                self.at_position(method, &uri, params).unwrap_or(Json::Null)
+            }
+            "textDocument/documentSymbol" => {
+                let source = self.documents.get(&uri).map_or("", String::as_str);
+                let analysis = Analysis::new(source);
+                let index = LineIndex::new(source);
+                let symbols = analysis
+                    .declarations
+                    .iter()
+                    .map(|declaration| document_symbol(source, &index, declaration))
+                    .collect();
+                Json::Array(symbols)
+            }
+            _ => {
+                let error = error_response(id, METHOD_NOT_FOUND, "Method not found");
+                return write_message(output, &error);
+            }
+        };
+        let response = object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]);
+        write_message(output, &response)
+    }
+
+    /// Answers a definition or hover request about the identifier at the
+    /// request's position, if there's one there that's declared somewhere.
+    fn at_position(&self, method: &str, uri: &str, params: &Json) -> Option<Json> {
+        let source = self.documents.get(uri)?;
+        let index = LineIndex::new(source);
+        let position = params.get("position")?;
+        let line = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+        let offset = to_offset(source, &index, line, character)?;
+
+        let analysis = Analysis::new(source);
+        let token_index = analysis.identifier_at(offset)?;
+        let declaration = analysis.definition(token_index)?;
+        if method == "textDocument/definition" {
+            let range = range(source, &index, &declaration.name.span);
+            return Some(object([("uri", uri.into()), ("range", range)]));
+        }
+
+        let contents = format!("```lox\n{}\n```", declaration.signature);
+        let span = &analysis.tokens[token_index].span;
+        Some(object([
+            (
+                "contents",
+                object([("kind", "markdown".into()), ("value", contents.into())]),
+            ),
+            ("range", range(source, &index, span)),
+        ]))
+    }
+
+    fn publish_diagnostics(&self, uri: &str, output: &mut impl Write) -> io::Result<()> {
+        let source = self.documents.get(uri).map_or("", String::as_str);
+        let index = LineIndex::new(source);
+        let diagnostics = Analysis::new(source)
+            .diagnostics
+            .iter()
+            .map(|diagnostic| lsp_diagnostic(source, &index, uri, diagnostic))
+            .collect();
+        write_message(output, &diagnostics_notification(uri, diagnostics))
+    }
+}
+
+/// What the server can do, for the `initialize` response.
+fn capabilities() -> Json {
+    object([
+        (
+            "textDocumentSync",
+            object([
+                ("openClose", true.into()),
+                // Full: every change sends the whole text
+                ("change", 1.into()),
+                ("save", object([("includeText", true.into())])),
+            ]),
+        ),
+        ("definitionProvider", true.into()),
+        ("hoverProvider", true.into()),
+        ("documentSymbolProvider", true.into()),
+    ])
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Json>) -> Json {
+    object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+        ),
+    ])
+}
+
+fn lsp_diagnostic(source: &str, index: &LineIndex, uri: &str, diagnostic: &Diagnostic) -> Json {
+    let span = diagnostic_span(
+        source,
+        index,
+        diagnostic.line,
+        diagnostic.column,
+        &diagnostic.span,
+    );
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    let related = diagnostic
+        .notes
+        .iter()
+        .map(|note| {
+            let span = diagnostic_span(source, index, note.line, note.column, &note.span);
+            let location = object([("uri", uri.into()), ("range", range(source, index, &span))]);
+            object([
+                ("location", location),
+                ("message", note.message.clone().into()),
+            ])
+        })
+        .collect::<Vec<_>>();
+    object([
+        ("range", range(source, index, &span)),
+        ("severity", severity.into()),
+        ("source", "lox".into()),
+        ("message", diagnostic.message.clone().into()),
+        ("relatedInformation", related.into()),
+    ])
+}
+
+/// The bytes a diagnostic or note is about: its span, or else the
+/// character at its position.
+fn diagnostic_span(
+    source: &str,
+    index: &LineIndex,
+    line: usize,
+    column: usize,
+    span: &Option<Range<usize>>,
+) -> Range<usize> {
+    if let Some(span) = span {
+        return span.clone();
+    }
+    let start = index.offset(line, column).unwrap_or(source.len());
+    let len = source
+        .get(start..)
+        .and_then(|rest| rest.chars().next())
+        .map_or(0, char::len_utf8);
+    start..start + len
+}
+
+fn document_symbol(source: &str, index: &LineIndex, declaration: &Declaration) -> Json {
+    // The protocol's SymbolKind numbers
+    let kind = match declaration.kind {
+        DeclarationKind::Class => 5,
+        DeclarationKind::Method => 6,
+        DeclarationKind::Function => 12,
+        DeclarationKind::Variable | DeclarationKind::Parameter => 13,
+    };
+    let children = declaration
+        .children
+        .iter()
+        .filter(|child| child.kind != DeclarationKind::Parameter)
+        .map(|child| document_symbol(source, index, child))
+        .collect::<Vec<_>>();
+    // Only the name's position is known, so it's the whole range too
+    let range = range(source, index, &declaration.name.span);
+    object([
+        ("name", declaration.name.lexeme.as_str().into()),
+        ("detail", declaration.signature.clone().into()),
+        ("kind", kind.into()),
+        ("range", range.clone()),
+        ("selectionRange", range),
+        ("children", children.into()),
+    ])
+}
+
+/// A protocol range for a span of bytes.
+fn range(source: &str, index: &LineIndex, span: &Range<usize>) -> Json {
+    object([
+        ("start", to_position(source, index, span.start)),
+        ("end", to_position(source, index, span.end)),
+    ])
+}
+
+/// A byte offset as a protocol position: a 0-based line and a column
+/// counted in UTF-16 code units.
+fn to_position(source: &str, index: &LineIndex, offset: usize) -> Json {
+    let offset = offset.min(source.len());
+    let (line, column) = index.line_col(offset);
+    let line_start = offset + 1 - column;
+    let character = source
+        .get(line_start..offset)
+        .map_or(0, |text| text.encode_utf16().count());
+    object([("line", (line - 1).into()), ("character", character.into())])
+}
+
+/// The byte offset of a protocol position, clamped to the end of its line.
+fn to_offset(source: &str, index: &LineIndex, line: usize, character: usize) -> Option<usize> {
+    let line_start = index.offset(line + 1, 1)?;
+    let mut units = 0;
+    for (offset, ch) in source[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return Some(line_start + offset);
+        }
+        units += ch.len_utf16();
+    }
+    Some(source.len())
+}
+
+fn error_response(id: Json, code: i32, message: &str) -> Json {
+    let error = object([
+        ("code", Json::Number(code.into())),
+        ("message", message.into()),
+    ]);
+    object([("jsonrpc", "2.0".into()), ("id", id), ("error", error)])
+}
+
+/// Reads the body of the next message, after its headers. `None` when
+/// `input` has ended.
+pub(crate) fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub(crate) fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
@@ -1,608 +1,658 @@
-use std::collections::HashMap;
 use std::env;
+use std::fmt::Write;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use lox::coverage::CoverageFormat;
+use lox::dap;
+use lox::diagnostic::{Diagnostic, Phase, Severity};
+use lox::format::Format;
+use lox::lint::{Level, Lint};
+use lox::lsp;
+use lox::messages::{self, Lang};
+use lox::printer::AstStyle;
+use lox::repl;
+use lox::{
+    bench, captures, check_tests, compare_backends, debug, disassemble, doc, evaluate, fmt, lint,
+    msg, parse, print_ast, run, tokenize, typecheck, Backend, Encoding, ExitStatus, Options,
+    Output,
+};
+
+/// With `track-allocations`, `bench` reports how much each run allocated.
+#[cfg(feature = "track-allocations")]
+#[global_allocator]
+static ALLOCATOR: lox::counting_alloc::CountingAllocator = lox::counting_alloc::CountingAllocator;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    // Pick the message language before anything can be reported. An unknown
+    // LOX_LANG falls back to English, an unknown --lang is a usage error.
+    if let Some(code) = args.iter().find_map(|arg| arg.strip_prefix("--lang=")) {
+        match Lang::from_code(code) {
+            Some(lang) => messages::set_lang(lang),
+            None => {
+                eprintln!("{}", msg!("E0008", code));
+                process::exit(64); // Usage error
+            }
+        }
+    } else if let Some(lang) = env::var("LOX_LANG")
+        .ok()
+        .and_then(|code| Lang::from_code(&code))
+    {
+        messages::set_lang(lang);
+    }
+
+    // The language server talks over stdio, so it takes no files
+    if args.get(1).is_some_and(|command| command == "lsp") {
+        let code =
+            lsp::serve(&mut io::stdin().lock(), &mut io::stdout().lock()).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                74 // I/O error
+            });
+        process::exit(code);
+    }
+
+    // So does the debug adapter, which is told what to run by the editor
+    if args.get(1).is_some_and(|command| command == "dap") {
+        let (options, filenames) = parse_args(&args[2..]).unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            process::exit(ExitStatus::UsageError(msg).exit_code());
+        });
+        if let Some(filename) = filenames.first() {
+            eprintln!("{}", msg!("E0061", filename));
+            process::exit(64); // Usage error
+        }
+        // On a thread with room for programs that nest deeply
+        let result = thread::Builder::new()
+            .stack_size(lox::STACK_SIZE)
+            .spawn(move || dap::serve(io::stdin().lock(), io::stdout(), &options))
+            .expect("debug adapter thread should start")
+            .join()
+            .expect("debug adapter thread shouldn't panic");
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(74); // I/O error
+        }
+        process::exit(0);
+    }
+
+    // The REPL reads statements from stdin too, so it takes only options
+    if args.get(1).is_some_and(|command| command == "repl") {
+        let (options, filenames) = parse_args(&args[2..]).unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            process::exit(ExitStatus::UsageError(msg).exit_code());
+        });
+        if let Some(filename) = filenames.first() {
+            eprintln!("{}", msg!("E0056", filename));
+            process::exit(64); // Usage error
+        }
+        // On a thread with room for programs that nest deeply
+        let result = thread::Builder::new()
+            .stack_size(lox::STACK_SIZE)
+            .spawn(move || repl::repl(&options))
+            .expect("REPL thread should start")
+            .join()
+            .expect("REPL thread shouldn't panic");
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(74); // I/O error
+        }
+        process::exit(0);
+    }
+
     if args.len() < 3 {
-        eprintln!("Usage: {} <tokenize|parse> <filename>", args[0]);
+        eprintln!("{}", msg!("E0001", args[0]));
         process::exit(64); // Usage error
     }
 
     let command = &args[1];
-    let filename = &args[2];
-
-    match command.as_str() {
-        "tokenize" => {
-            // Attempt to read file
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                eprintln!("Failed to read file {}", filename);
-                process::exit(65); // File read error
-            });
+    let (options, mut filenames) = parse_args(&args[2..]).unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        process::exit(ExitStatus::UsageError(msg).exit_code());
+    });
+    if filenames.is_empty() {
+        let msg = msg!("E0005");
+        eprintln!("{}", msg);
+        process::exit(ExitStatus::UsageError(msg).exit_code());
+    }
+    // Directories given to `test` and `check-tests` stand for the test
+    // files in them, and those given to `bench` for the programs in them,
+    // which it runs on both backends to compare them
+    let comparing = command == "bench" && filenames.iter().any(|path| Path::new(path).is_dir());
+    let test_suffix = match command.as_str() {
+        "test" => Some("_test.lox"),
+        "check-tests" => Some(".lox"),
+        "bench" if comparing => Some(".lox"),
+        _ => None,
+    };
+    let testing = matches!(command.as_str(), "test" | "check-tests");
+    if let Some(suffix) = test_suffix {
+        filenames = test_files(&filenames, suffix).unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            process::exit(ExitStatus::UsageError(msg).exit_code());
+        });
+    }
 
-            // Tokenize file contents
-            let had_error = tokenize(&file_contents);
+    // Each command writes its output and reports how it went for a given source
+    let run: fn(&str, &Options, &mut Output) -> ExitStatus = match command.as_str() {
+        "tokenize" => tokenize,
+        "parse" => parse,
+        "print-ast" => print_ast,
+        "evaluate" => evaluate,
+        "run" | "test" => run,
+        "check-tests" => check_tests,
+        "bench" if comparing => compare_backends,
+        "bench" => bench,
+        "debug" => debug,
+        "disassemble" => disassemble,
+        "fmt" => fmt,
+        "lint" => lint,
+        "captures" => captures,
+        "typecheck" => typecheck,
+        "doc" => doc,
+        _ => {
+            eprintln!("{}", msg!("E0002", command));
+            process::exit(64); // Usage error
+        }
+    };
 
-            // If any scanning errors occurred, exit code 65
-            if had_error {
-                process::exit(65);
-            }
+    // Benchmarks run one file at a time, so they don't slow each other
+    // down, and debugging sessions so they don't share the terminal
+    let workers = match command.as_str() {
+        "bench" | "debug" => 1,
+        _ => available_workers(),
+    };
+    if comparing {
+        print!("{}", lox::compare_backends_header());
+    }
+    let results = run_files(run, &options, &filenames, workers);
+
+    // Output stays grouped per file and in the order the files were given
+    for result in &results {
+        print!("{}", result.output.stdout);
+        eprint!("{}", result.output.stderr);
+        if testing {
+            println!("{}", result.verdict());
         }
+    }
 
-        "parse" => {
-            // Same file read
-            let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-                eprintln!("Failed to read file {}", filename);
-                process::exit(65); // File read error
-            });
+    // With several files, show how each one fared before exiting
+    if testing {
+        print_test_summary(&results);
+    } else if results.len() > 1 && !comparing {
+        print_summary(&results);
+    }
 
-            // Parse returns true if there's an error
-            let had_error = parse(&file_contents);
+    // The worst result decides the exit code
+    let exit_code = results
+        .iter()
+        .map(|result| result.status.exit_code())
+        .max()
+        .unwrap_or(0);
+    if exit_code != 0 {
+        process::exit(exit_code);
+    }
+}
 
-            // If any parse errors occurred, exit code 65
-            if had_error {
-                process::exit(65);
-            }
+/// The filename that stands for standard input, which `--stdin` also adds.
+const STDIN: &str = "-";
+
+/// The files to test: every file whose name ends with `suffix` under each
+/// directory given, in name order, and each file given as it is. A
+/// directory without tests is an error, since it's most likely the wrong
+/// one.
+fn test_files(paths: &[String], suffix: &str) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            files.push(path.clone());
+            continue;
         }
-
-        _ => {
-            eprintln!("Unknown command: {}", command);
-            process::exit(64); // Usage error
+        let found = files.len();
+        find_tests(Path::new(path), suffix, &mut files).map_err(|_| msg!("E0006", path))?;
+        if files.len() == found {
+            return Err(msg!("E0020", format!("*{}", suffix), path));
         }
     }
+    Ok(files)
 }
 
+/// Adds the files in `dir` and its subdirectories whose names end with
+/// `suffix` to `files`.
+fn find_tests(dir: &Path, suffix: &str, files: &mut Vec<String>) -> io::Result<()> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        let is_test = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(suffix));
+        if path.is_dir() {
+            find_tests(&path, suffix, files)?;
+        } else if is_test {
+            files.push(path.display().to_string());
+        }
+    }
+    Ok(())
+}
 
-fn tokenize(input: &str) -> bool {
-    let mut had_error = false;
-    let mut chars = input.chars().peekable();
-    let mut line = 1;
-
-    // Keywords in Lox
-    let keywords: HashMap<&str, &str> = [
-        ("and", "AND"),
-        ("class", "CLASS"),
-        ("else", "ELSE"),
-        ("false", "FALSE"),
-        ("for", "FOR"),
-        ("fun", "FUN"),
-        ("if", "IF"),
-        ("nil", "NIL"),
-        ("or", "OR"),
-        ("print", "PRINT"),
-        ("return", "RETURN"),
-        ("super", "SUPER"),
-        ("this", "THIS"),
-        ("true", "TRUE"),
-        ("var", "VAR"),
-        ("while", "WHILE"),
-    ]
-    .iter()
-    .cloned()
-    .collect();
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            '(' => println!("LEFT_PAREN ( null"),
-            ')' => println!("RIGHT_PAREN ) null"),
-            '{' => println!("LEFT_BRACE {{ null"),
-            '}' => println!("RIGHT_BRACE }} null"),
-            '*' => println!("STAR * null"),
-            '.' => println!("DOT . null"),
-            '+' => println!("PLUS + null"),
-            ',' => println!("COMMA , null"),
-            '-' => println!("MINUS - null"),
-            ';' => println!("SEMICOLON ; null"),
-            '/' => {
-                // Look ahead for comment
-                if let Some('/') = chars.peek() {
-                    // This is a comment; consume '//'
-                    chars.next();
-                    // Skip until newline
-                    while let Some(&comment_char) = chars.peek() {
-                        if comment_char == '\n' {
-                            break;
-                        }
-                        chars.next();
-                    }
-                } else {
-                    println!("SLASH / null");
-                }
+/// Splits the arguments after the command into options and filenames.
+/// The filenames can be empty, which only `repl` allows.
+fn parse_args(args: &[String]) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut filenames = Vec::new();
+
+    for arg in args {
+        let Some(flag) = arg.strip_prefix("--") else {
+            filenames.push(arg.clone());
+            continue;
+        };
+
+        let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+        let limit = match name {
+            // Already applied in main, before anything could be reported
+            "lang" => continue,
+            "format" => {
+                options.format = match value {
+                    "text" => Format::Text,
+                    "tsv" => Format::Tsv,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(msg!("E0004", name, value)),
+                };
+                continue;
             }
-
-            // Number literal (integer or float)
-            '0'..='9' => {
-                let mut number = String::new();
-                number.push(ch);
-                let mut is_float = false;
-
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch.is_ascii_digit() {
-                        number.push(next_ch);
-                        chars.next();
-                    } else if next_ch == '.' && !is_float {
-                        is_float = true;
-                        number.push(next_ch);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-
-                if is_float {
-                    let parsed = number.parse::<f64>().unwrap();
-                    println!("NUMBER {} {}", number, format_float_value(parsed));
-                } else {
-                    // No decimal point => integer
-                    println!("NUMBER {} {}.0", number, number);
-                }
+            "pretty-errors" => {
+                options.pretty_errors = true;
+                continue;
             }
-
-            // Identifiers or keywords
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let mut identifier = String::new();
-                identifier.push(ch);
-
-                while let Some(&next_char) = chars.peek() {
-                    if next_char.is_alphanumeric() || next_char == '_' {
-                        identifier.push(next_char);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-
-                // Check if it matches a known keyword
-                if let Some(token_type) = keywords.get(identifier.as_str()) {
-                    println!("{} {} null", token_type, identifier);
-                } else {
-                    println!("IDENTIFIER {} null", identifier);
-                }
+            "tolerant" => {
+                options.tolerant = true;
+                continue;
             }
-
-            // String literal
-            '"' => {
-                let mut string_literal = String::new();
-                let mut unterminated = true;
-
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch == '"' {
-                        // Closing quote
-                        chars.next(); // consume it
-                        unterminated = false;
-                        println!("STRING \"{}\" {}", string_literal, string_literal);
-                        break;
-                    } else if next_ch == '\n' {
-                        eprintln!("[line {}] Error: Unterminated string.", line);
-                        had_error = true;
-                        break;
-                    } else {
-                        string_literal.push(next_ch);
-                        chars.next();
-                    }
-                }
-
-                if unterminated {
-                    // We never found a closing quote
-                    eprintln!("[line {}] Error: Unterminated string.", line);
-                    had_error = true;
-                }
+            "check" => {
+                options.check = true;
+                continue;
             }
-
-            // Comparison operators
-            '<' => {
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    println!("LESS_EQUAL <= null");
-                } else {
-                    println!("LESS < null");
-                }
+            "strict" => {
+                options.strict = true;
+                continue;
             }
-            '>' => {
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    println!("GREATER_EQUAL >= null");
-                } else {
-                    println!("GREATER > null");
-                }
+            "trace" => {
+                options.trace = true;
+                continue;
             }
-            '!' => {
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    println!("BANG_EQUAL != null");
-                } else {
-                    println!("BANG ! null");
-                }
+            "opt" => {
+                options.optimize = true;
+                continue;
             }
-            '=' => {
-                if let Some('=') = chars.peek() {
-                    chars.next();
-                    println!("EQUAL_EQUAL == null");
-                } else {
-                    println!("EQUAL = null");
-                }
+            "tco" => {
+                options.tco = true;
+                continue;
             }
-
-            // Newline
-            '\n' => line += 1,
-
-            // Whitespace
-            ' ' | '\t' | '\r' => {},
-
-            // Unknown character => error
-            _ => {
-                eprintln!("[line {}] Error: Unexpected character: {}", line, ch);
-                had_error = true;
+            "heap-stats" => {
+                options.heap_stats = true;
+                continue;
             }
-        }
+            "op-histogram" => {
+                options.op_histogram = true;
+                continue;
+            }
+            "allow-fs" => {
+                options.allow_fs = true;
+                continue;
+            }
+            "allow-exec" => {
+                options.allow_exec = true;
+                continue;
+            }
+            "plugin" => {
+                options.plugins.push(PathBuf::from(value));
+                continue;
+            }
+            "allow-net" if !cfg!(feature = "net") => return Err(msg!("E0066")),
+            "allow-net" => {
+                options.allow_net = true;
+                continue;
+            }
+            "opt-verbose" => {
+                options.optimize = true;
+                options.opt_verbose = true;
+                continue;
+            }
+            "profile" => {
+                options.profile = true;
+                options.folded_stacks = (!value.is_empty()).then(|| PathBuf::from(value));
+                continue;
+            }
+            "coverage" => {
+                options.coverage = match value {
+                    "" | "lcov" => Some(CoverageFormat::Lcov),
+                    "html" => Some(CoverageFormat::Html),
+                    _ => return Err(msg!("E0004", name, value)),
+                };
+                continue;
+            }
+            // `bench` couldn't report times for no runs
+            "iterations" => {
+                let iterations = value.parse().ok().filter(|&n| n > 0);
+                options.iterations = Some(iterations.ok_or_else(|| msg!("E0004", name, value))?);
+                continue;
+            }
+            "allow" | "warn" | "deny" => {
+                let lint = Lint::from_name(value).ok_or_else(|| msg!("E0004", name, value))?;
+                let level = match name {
+                    "allow" => Level::Allow,
+                    "warn" => Level::Warn,
+                    _ => Level::Deny,
+                };
+                options.lint_levels.set(lint, level);
+                continue;
+            }
+            "stdin" => {
+                filenames.push(STDIN.to_string());
+                continue;
+            }
+            "extensions" => {
+                options.extensions = true;
+                continue;
+            }
+            "coerce-strings" => {
+                options.coerce_strings = true;
+                continue;
+            }
+            "backend" => {
+                options.backend = match value {
+                    "tree" => Backend::TreeWalk,
+                    "vm" => Backend::Vm,
+                    _ => return Err(msg!("E0004", name, value)),
+                };
+                continue;
+            }
+            "encoding" => {
+                options.encoding = match value {
+                    "utf8" | "utf-8" => Encoding::Utf8,
+                    "latin1" | "latin-1" | "iso-8859-1" => Encoding::Latin1,
+                    _ => return Err(msg!("E0004", name, value)),
+                };
+                continue;
+            }
+            "ast-style" => {
+                options.ast_style = match value {
+                    "sexpr" => AstStyle::Sexpr,
+                    "indent" => AstStyle::Indent,
+                    _ => return Err(msg!("E0004", name, value)),
+                };
+                continue;
+            }
+            "max-source-size" => &mut options.limits.max_source_size,
+            "max-tokens" => &mut options.limits.max_tokens,
+            "max-literal-length" => &mut options.limits.max_literal_length,
+            "max-nesting-depth" => &mut options.limits.max_nesting_depth,
+            _ => return Err(msg!("E0003", arg)),
+        };
+        *limit = value.parse().map_err(|_| msg!("E0004", name, value))?;
     }
 
-    // End of file
-    println!("EOF  null");
-
-    had_error
-}
-
-/// Ensures floats have at least one digit after the decimal if there's no fractional part.
-fn format_float_value(value: f64) -> String {
-    if value.fract() == 0.0 {
-        format!("{}.0", value.trunc())
-    } else {
-        value.to_string() // minimal decimal representation
+    if filenames.iter().filter(|name| *name == STDIN).count() > 1 {
+        return Err(msg!("E0009"));
     }
-}
 
-/// ---------------------------------------------------------------------------
-/// PARSER
-/// ---------------------------------------------------------------------------
-
-/// Minimal token type used for parsing.
-#[derive(Debug, Clone, PartialEq)]
-enum TokenType {
-    // Single chars
-    LeftParen, RightParen,
-    // Literals
-    Number(f64),
-    StringLit(String),
-    True, False, Nil,
-   
-    Eof,
+    Ok((options, filenames))
 }
 
-/// A Token for the parser
-#[derive(Debug, Clone)]
-struct Token {
-    token_type: TokenType,
-    lexeme: String,  // the exact text
-    line: usize,
+/// Outcome of running a command over a single file.
+struct FileResult {
+    filename: String,
+    status: ExitStatus,
+    output: Output,
 }
 
-/// A minimal expression AST for demonstration.
-#[derive(Debug, Clone)]
-enum Expr {
-    Literal(LitValue),
-    Grouping(Box<Expr>),
-}
-
-/// Literal values we care about
-#[derive(Debug, Clone)]
-enum LitValue {
-    Boolean(bool),
-    Nil,
-    Number(f64),
-    Str(String),
-}
-
-/// The parser itself
-struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
-    had_error: bool,
-}
-
-impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Parser {
-            tokens,
-            current: 0,
-            had_error: false,
+impl FileResult {
+    fn errors(&self) -> usize {
+        match &self.status {
+            ExitStatus::RuntimeError(_) | ExitStatus::Io(_) => 1,
+            ExitStatus::Mismatch(differences) => differences.len(),
+            _ => self.count(Severity::Error),
         }
     }
 
-   
-    fn parse(&mut self) -> Option<Expr> {
-        let expr = self.expression();
-
-        if self.had_error {
-            None
-        } else {
-            Some(expr)
-        }
-    }
-
-    /
-    fn expression(&mut self) -> Expr {
-        self.primary()
+    fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
     }
 
-    /// primary -> "true" | "false" | "nil" | NUMBER | STRING | "(" expression ")"
-    fn primary(&mut self) -> Expr {
-        // Peek current token
-        let token = self.peek_token();
-
-        match token.token_type {
-            TokenType::True => {
-                self.advance();
-                Expr::Literal(LitValue::Boolean(true))
-            }
-            TokenType::False => {
-                self.advance();
-                Expr::Literal(LitValue::Boolean(false))
-            }
-            TokenType::Nil => {
-                self.advance();
-                Expr::Literal(LitValue::Nil)
-            }
-            TokenType::Number(n) => {
-                self.advance();
-                Expr::Literal(LitValue::Number(n))
-            }
-            TokenType::StringLit(ref s) => {
-                // clone s
-                let lit_string = s.clone();
-                self.advance();
-                Expr::Literal(LitValue::Str(lit_string))
-            }
-            TokenType::LeftParen => {
-                self.advance(); // consume '('
-                let expr = self.expression();
-                // Expect a right paren
-                if self.peek_token().token_type == TokenType::RightParen {
-                    self.advance(); // consume it
-                } else {
-                    self.error("Expected ')' after expression.");
-                }
-                Expr::Grouping(Box::new(expr))
-            }
-            TokenType::RightParen | TokenType::Eof => {
-                // Error: we expected an expression but got a right paren or end
-                self.error("Expected expression.");
-                // Return something to keep going
-                Expr::Literal(LitValue::Nil)
+    /// How many of the diagnostics reported have `severity`.
+    fn count(&self, severity: Severity) -> usize {
+        match &self.status {
+            ExitStatus::CompileError(diagnostics) | ExitStatus::Warnings(diagnostics) => {
+                diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == severity)
+                    .count()
             }
+            _ => 0,
         }
     }
 
-    /// If there's an error, print message and set had_error.
-    fn error(&mut self, msg: &str) {
-        eprintln!("Parse error: {}", msg);
-        self.had_error = true;
+    /// Whether the file ran as a test without failing an assertion or
+    /// hitting any other error.
+    fn passed(&self) -> bool {
+        matches!(self.status, ExitStatus::Success | ExitStatus::Warnings(_))
     }
 
-    /// Return the current token
-    fn peek_token(&self) -> &Token {
-        if self.current >= self.tokens.len() {
-            // In case we're out of range
-            &self.tokens[self.tokens.len() - 1]
-        } else {
-            &self.tokens[self.current]
-        }
+    /// `PASS file` or `FAIL file`, for `test`.
+    fn verdict(&self) -> String {
+        let code = if self.passed() { "E0021" } else { "E0022" };
+        msg!(code, self.filename)
     }
 
-    /// Advance the parser by one token
-    fn advance(&mut self) {
-        if self.current < self.tokens.len() {
-            self.current += 1;
+    fn describe(&self) -> &'static str {
+        match self.status {
+            ExitStatus::Success | ExitStatus::Warnings(_) => "ok",
+            ExitStatus::UsageError(_)
+            | ExitStatus::CompileError(_)
+            | ExitStatus::RuntimeError(_) => "failed",
+            ExitStatus::Io(_) => "unreadable",
+            ExitStatus::Unformatted => "unformatted",
+            ExitStatus::Mismatch(_) => "failed",
         }
     }
 }
 
-/// A small "AST printer" that prints expressions in a Lisp-like style:
-/// - `true`, `false`, `nil` for booleans/nil
-/// - e.g. `123.0` for numbers
-/// - e.g. `("hello")` for strings
-/// - `(group <expr>)` or just `(<expr>)` if you prefer
-fn print_ast(expr: &Expr) -> String {
-    match expr {
-        Expr::Literal(value) => match value {
-            LitValue::Boolean(b) => b.to_string(), // "true" or "false"
-            LitValue::Nil => "nil".to_string(),
-            LitValue::Number(n) => format_float_value(*n), // e.g. "3.0"
-            LitValue::Str(s) => format!("\"{}\"", s),
-        },
-        Expr::Grouping(sub) => format!("(group {})", print_ast(sub)),
-    }
+/// How many worker threads to run files on: one per core.
+fn available_workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
 }
 
-/// ---------------------------------------------------------------------------
-/// parse() function: Scans => converts to simpler tokens => runs parser => prints AST
-/// Returns `true` on error, `false` if success.
-/// ---------------------------------------------------------------------------
-fn parse(source: &str) -> bool {
-    // 1) Scan to "raw" tokens with your existing scanning logic,
-    //    but we won't print them. We'll convert them into the `TokenType`
-    //    used by the parser (`TokenType::True, TokenType::Nil`, etc.)
-
-    let raw_tokens = scan_raw_tokens(source);
-
-    // 2) Build the parser tokens
-    let mut parser_tokens = Vec::new();
-    for rtok in raw_tokens {
-        parser_tokens.push(convert_token(rtok));
-    }
-
-    // 3) Parse
-    let mut parser = Parser::new(parser_tokens);
-    let ast = parser.parse();
-
-    // 4) If parse error, return true
-    if parser.had_error || ast.is_none() {
-        return true;
-    }
+/// Runs the command over every file on a pool of up to `workers` threads,
+/// returning the results in the same order as `filenames`.
+fn run_files(
+    run: fn(&str, &Options, &mut Output) -> ExitStatus,
+    options: &Options,
+    filenames: &[String],
+    workers: usize,
+) -> Vec<FileResult> {
+    let workers = workers.min(filenames.len());
+
+    // Workers claim the next unprocessed file until none are left
+    let next = AtomicUsize::new(0);
+    let slots: Mutex<Vec<Option<FileResult>>> =
+        Mutex::new(filenames.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            thread::Builder::new()
+                .stack_size(lox::STACK_SIZE)
+                .spawn_scoped(scope, || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= filenames.len() {
+                        break;
+                    }
+                    let result = run_file(run, options, &filenames[index]);
+                    slots.lock().unwrap()[index] = Some(result);
+                })
+                .expect("worker thread should start");
+        }
+    });
 
-    // 5) Otherwise, print the AST
-    let expr = ast.unwrap();
-    println!("{}", print_ast(&expr));
-    false
+    slots
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|slot| slot.expect("every file is processed"))
+        .collect()
 }
 
-/// A minimal "raw" token representation from your existing scanner output.
-/// We only store the type as a string for now, plus the lexeme and line.
-#[derive(Debug)]
-struct RawToken {
-    token_type: String,
-    lexeme: String,
-    line: usize,
-}
+/// Reads one file and runs the command over it, without exiting on failure.
+fn run_file(
+    run: fn(&str, &Options, &mut Output) -> ExitStatus,
+    options: &Options,
+    filename: &str,
+) -> FileResult {
+    let mut output = Output::default();
+    let display_name = if filename == STDIN {
+        "<stdin>"
+    } else {
+        filename
+    };
 
-/// We'll do a custom scanning that returns `Vec<RawToken>` instead of printing.
-/// This is a simplified version of `tokenize()`, but just collects tokens.
-fn scan_raw_tokens(source: &str) -> Vec<RawToken> {
-    let mut tokens = Vec::new();
-    let mut chars = source.chars().peekable();
-    let mut line = 1;
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            '(' => {
-                tokens.push(RawToken {
-                    token_type: "LEFT_PAREN".into(),
-                    lexeme: "(".into(),
-                    line,
-                });
-            }
-            ')' => {
-                tokens.push(RawToken {
-                    token_type: "RIGHT_PAREN".into(),
-                    lexeme: ")".into(),
-                    line,
-                });
-            }
-            '"' => {
-                // String
-                let mut string_literal = String::new();
-                let mut unterminated = true;
-
-                while let Some(&nc) = chars.peek() {
-                    if nc == '"' {
-                        chars.next(); // consume closing "
-                        unterminated = false;
-                        break;
-                    } else if nc == '\n' {
-                        line += 1;
-                    }
-                    string_literal.push(nc);
-                    chars.next();
-                }
-
-                if unterminated {
-                    // We didn't find a closing quote
-                    // We'll still record it, but note it might be invalid
-                }
-                tokens.push(RawToken {
-                    token_type: "STRING".into(),
-                    lexeme: string_literal,
-                    line,
-                });
-            }
-            '0'..='9' => {
-                // number
-                let mut number_str = ch.to_string();
-                let mut is_float = false;
-                while let Some(&nc) = chars.peek() {
-                    if nc.is_ascii_digit() {
-                        number_str.push(nc);
-                        chars.next();
-                    } else if nc == '.' && !is_float {
-                        is_float = true;
-                        number_str.push(nc);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(RawToken {
-                    token_type: "NUMBER".into(),
-                    lexeme: number_str,
-                    line,
-                });
-            }
-            'a'..='z' | 'A'..='Z' | '_' => {
-                // identifier or keyword
-                let mut ident = ch.to_string();
-                while let Some(&nc) = chars.peek() {
-                    if nc.is_alphanumeric() || nc == '_' {
-                        ident.push(nc);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(RawToken {
-                    token_type: identify_keyword(&ident),
-                    lexeme: ident,
-                    line,
-                });
-            }
-            '\n' => {
-                line += 1;
-            }
-            ' ' | '\r' | '\t' => {
-                // ignore
+    let (source, status) = match read_source(filename, display_name, options) {
+        Ok(file_contents) => {
+            // Imports are relative to the file, or for stdin the current
+            // directory
+            let options = &Options {
+                path: (filename != STDIN).then(|| filename.into()),
+                ..options.clone()
+            };
+            let status = run(&file_contents, options, &mut output);
+            (Some(file_contents), status)
+        }
+        Err(status) => (None, status),
+    };
+
+    // Render whatever went wrong after the command's own output
+    let render = |diagnostic: &Diagnostic| {
+        if options.pretty_errors {
+            diagnostic.render_pretty(source.as_deref(), display_name)
+        } else {
+            format!("{}\n", diagnostic)
+        }
+    };
+    match &status {
+        ExitStatus::CompileError(diagnostics) | ExitStatus::Warnings(diagnostics) => {
+            for diagnostic in diagnostics {
+                output.stderr.push_str(&render(diagnostic));
             }
-            _ => {
-                // ignore or handle error
+        }
+        ExitStatus::Io(_) => writeln!(output.stderr, "{}", msg!("E0006", display_name)).unwrap(),
+        ExitStatus::Unformatted => {
+            writeln!(output.stderr, "{}", msg!("E0018", display_name)).unwrap()
+        }
+        ExitStatus::RuntimeError(error) => output.stderr.push_str(&render(error)),
+        ExitStatus::Mismatch(differences) => {
+            for difference in differences {
+                writeln!(output.stderr, "{}: {}", display_name, difference).unwrap();
             }
         }
+        ExitStatus::UsageError(msg) => writeln!(output.stderr, "{}", msg).unwrap(),
+        ExitStatus::Success => {}
     }
 
-    // push an EOF
-    tokens.push(RawToken {
-        token_type: "EOF".into(),
-        lexeme: "".into(),
-        line,
-    });
-
-    tokens
+    FileResult {
+        filename: display_name.to_string(),
+        status,
+        output,
+    }
 }
 
-/// Determine if the given identifier is 'true', 'false', or 'nil'.
-fn identify_keyword(s: &str) -> String {
-    match s {
-        "true" => "TRUE".into(),
-        "false" => "FALSE".into(),
-        "nil" => "NIL".into(),
-        _ => "IDENTIFIER".into(),
+/// Reads a source file, or standard input for `-`, and decodes it with
+/// `options.encoding`. Never reads more than one byte past the size limit.
+/// Failures come back as the status to report: `Io` if the file couldn't
+/// be read, a `CompileError` if it's too large or isn't valid UTF-8.
+fn read_source(
+    filename: &str,
+    display_name: &str,
+    options: &Options,
+) -> Result<String, ExitStatus> {
+    let max_size = options.limits.max_source_size;
+    let limits = &options.limits;
+    let bytes = if filename == STDIN {
+        limits.read_source(io::stdin().lock())
+    } else {
+        fs::File::open(filename).and_then(|file| limits.read_source(file))
+    }
+    .map_err(ExitStatus::Io)?;
+
+    // Reported at the first byte past the limit
+    if bytes.len() > max_size {
+        let (line, column) = position(&bytes, max_size);
+        let msg = msg!("E0007", display_name, max_size);
+        let diagnostic = Diagnostic::error(Phase::Read, line, column, msg);
+        return Err(ExitStatus::CompileError(vec![diagnostic]));
+    }
+
+    match options.encoding {
+        Encoding::Latin1 => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+        Encoding::Utf8 => String::from_utf8(bytes).map_err(|err| {
+            let offset = err.utf8_error().valid_up_to();
+            let (line, column) = position(err.as_bytes(), offset);
+            let msg = msg!("E0010", display_name, offset);
+            let diagnostic = Diagnostic::error(Phase::Read, line, column, msg);
+            ExitStatus::CompileError(vec![diagnostic])
+        }),
     }
 }
 
-/// Converts the "raw" token (which only has a string type) into a parser `Token`.
-fn convert_token(rtok: RawToken) -> Token {
-    use TokenType::*;
-    let token_type = match rtok.token_type.as_str() {
-        "LEFT_PAREN" => LeftParen,
-        "RIGHT_PAREN" => RightParen,
-        "STRING" => StringLit(rtok.lexeme.clone()),
-        "TRUE" => True,
-        "FALSE" => False,
-        "NIL" => Nil,
-        "NUMBER" => {
-            // parse float
-            let val = rtok.lexeme.parse::<f64>().unwrap_or(0.0);
-            Number(val)
-        }
-        "EOF" => Eof,
-        _ => {
-           
-            Nil
-        }
-    };
+/// The 1-based line and column of byte `offset` in `bytes`, which needn't
+/// be valid text.
+fn position(bytes: &[u8], offset: usize) -> (usize, usize) {
+    let before = &bytes[..offset];
+    let line = 1 + before.iter().filter(|&&byte| byte == b'\n').count();
+    let line_start = before
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    (line, offset - line_start + 1)
+}
 
-    Token {
-        token_type,
-        lexeme: rtok.lexeme,
-        line: rtok.line,
+/// Prints a per-file table of errors, warnings and status to stderr.
+fn print_summary(results: &[FileResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.filename.len())
+        .chain(std::iter::once("file".len()))
+        .max()
+        .unwrap_or(0);
+
+    eprintln!();
+    eprintln!(
+        "{:<width$}  {:>6}  {:>8}  status",
+        "file", "errors", "warnings"
+    );
+    for r in results {
+        eprintln!(
+            "{:<width$}  {:>6}  {:>8}  {}",
+            r.filename,
+            r.errors(),
+            r.warnings(),
+            r.describe()
+        );
     }
 }
+
+/// Prints how many of the tests passed and failed.
+fn print_test_summary(results: &[FileResult]) {
+    let passed = results.iter().filter(|result| result.passed()).count();
+    println!();
+    println!("{}", msg!("E0023", passed, results.len() - passed));
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::gc::Trace;
+use crate::string::LoxString;
+use crate::value::{print_once, Value};
+
+/// A key of a map, compared like `==` compares values: `nil`, booleans,
+/// numbers and strings by value, so `0` and `-0` are the same key, and
+/// everything else by identity, so an instance is only ever its own key
+/// however its fields change.
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    /// The bits of the number, with `-0` stored as `0`
+    Number(u64),
+    Str(LoxString),
+    /// Any other value, which the map keeps alive
+    Object(Value),
+}
+
+impl MapKey {
+    /// The key for `value`, or `None` if it can't be one. NaN can't, since
+    /// it isn't equal to itself.
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::Number(n) if n.is_nan() => None,
+            Value::Number(n) if *n == 0.0 => Some(MapKey::Number(0.0f64.to_bits())),
+            Value::Number(n) => Some(MapKey::Number(n.to_bits())),
+            Value::Str(s) => Some(MapKey::Str(s.clone())),
+            Value::Nil => Some(MapKey::Nil),
+            Value::Bool(b) => Some(MapKey::Bool(*b)),
+            object => Some(MapKey::Object(object.clone())),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Str(s) => Value::Str(s.clone()),
+            MapKey::Object(object) => object.clone(),
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &MapKey) -> bool {
+        match (self, other) {
+            (MapKey::Nil, MapKey::Nil) => true,
+            (MapKey::Bool(l), MapKey::Bool(r)) => l == r,
+            (MapKey::Number(l), MapKey::Number(r)) => l == r,
+            (MapKey::Str(l), MapKey::Str(r)) => l == r,
+            (MapKey::Object(l), MapKey::Object(r)) => l.equals(r),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MapKey {}
+
+impl Hash for MapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            MapKey::Nil => {}
+            MapKey::Bool(b) => b.hash(state),
+            MapKey::Number(bits) => bits.hash(state),
+            MapKey::Str(s) => s.hash(state),
+            MapKey::Object(object) => object.identity().hash(state),
+        }
+    }
+}
+
+/// The entries of a map, kept in the order their keys were first added so
+/// printing and `keys()` are the same from run to run.
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, Value)>,
+    /// Where each key's entry is in `entries`
+    positions: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    pub fn get(&self, key: &MapKey) -> Option<&Value> {
+        let &position = self.positions.get(key)?;
+        Some(&self.entries[position].1)
+    }
+
+    /// Sets the value of `key`, keeping its place if it's already there.
+    pub fn insert(&mut self, key: MapKey, value: Value) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// Removes `key` and returns its value, if it was there.
+    pub fn remove(&mut self, key: &MapKey) -> Option<Value> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            *self
+                .positions
+                .get_mut(key)
+                .expect("every entry has a position") -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &MapKey> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+/// `{a: 1, 2: b}`, with keys and values printed as `print` would.
+impl fmt::Display for LoxMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        print_once(f, self as *const LoxMap as *const (), "{...}", |f| {
+            write!(f, "{{")?;
+            for (index, (key, value)) in self.entries.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", key.to_value(), value)?;
+            }
+            write!(f, "}}")
+        })
+    }
+}
+
+impl Trace for RefCell<LoxMap> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(map) = self.try_borrow() else {
+            return false;
+        };
+        // Every key is held twice, by its entry and its position
+        let keys = map.keys().chain(map.positions.keys());
+        for key in keys {
+            if let MapKey::Object(object) = key {
+                object.trace(edge);
+            }
+        }
+        for value in map.values() {
+            value.trace(edge);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut map) = self.try_borrow_mut() {
+            let entries = std::mem::take(&mut *map);
+            drop(map);
+            drop(entries);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use std::fmt::{Display, Write};
+use std::sync::OnceLock;
+
+/// Formats the catalog message for an error code in the current language,
+/// filling its `{}` placeholders with the remaining arguments in order.
+#[macro_export]
+macro_rules! msg {
+    ($code:expr $(, $arg:expr)* $(,)?) => {
+        $crate::messages::message($code, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+/// Languages diagnostics can be shown in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    En,
+    Es,
+}
+
+impl Lang {
+    /// Accepts a plain language code (`es`) or a locale name (`es_ES.UTF-8`).
+    pub fn from_code(code: &str) -> Option<Lang> {
+        let language = code.split(['_', '-', '.']).next()?.to_lowercase();
+        match language.as_str() {
+            "en" => Some(Lang::En),
+            "es" => Some(Lang::Es),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::Es => ES,
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Selects the language for every message from now on. Only the first call
+/// has an effect.
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// Looks up `code` in the current language, falling back to English for
+/// untranslated codes, and substitutes `args` for its placeholders.
+pub fn message(code: &str, args: &[&dyn Display]) -> String {
+    let lang = LANG.get().copied().unwrap_or(Lang::En);
+    let template = lookup(lang, code)
+        .or_else(|| lookup(Lang::En, code))
+        .unwrap_or(code);
+
+    let mut text = String::new();
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        text.push_str(&rest[..pos]);
+        if let Some(arg) = args.next() {
+            write!(text, "{}", arg).unwrap();
+        }
+        rest = &rest[pos + 2..];
+    }
+    text.push_str(rest);
+    text
+}
+
+fn lookup(lang: Lang, code: &str) -> Option<&'static str> {
+    lang.catalog()
+        .iter()
+        .find(|&&(key, _)| key == code)
+        .map(|&(_, text)| text)
+}
+
+/// English messages. Every code must have an entry here.
+/// E00xx: command line, files and the frames diagnostics are shown in,
+/// E01xx: scanner, E02xx: parser, E03xx: runtime, E04xx: resolver,
+/// E05xx: bytecode compiler, E06xx: type checker.
+const EN: &[(&str, &str)] = &[
+    ("E0001", "Usage: {} <tokenize|parse|print-ast|evaluate|run|debug|test|check-tests|bench|disassemble|fmt|lint|captures|typecheck|doc|lsp|dap|repl> [options] <filename|->..."),
+    ("E0002", "Unknown command: {}"),
+    ("E0003", "Unknown option: {}"),
+    ("E0004", "Invalid value for --{}: '{}'"),
+    ("E0005", "No input files given."),
+    ("E0006", "Failed to read file {}"),
+    ("E0007", "Source file {} is too large (limit is {} bytes)."),
+    ("E0008", "Unknown language: {}"),
+    ("E0009", "Standard input can only be read once; give '-' or --stdin only once."),
+    ("E0010", "{} is not valid UTF-8 (invalid byte at offset {}). Use --encoding=latin1 for Latin-1 files."),
+    ("E0011", "[line {}, col {}] {}: {}"),
+    ("E0012", "[line {}, col {}] {} at '{}': {}"),
+    ("E0013", "[line {}, col {}] {} at end: {}"),
+    ("E0014", "{}\n[line {}, col {}]"),
+    ("E0015", "Error"),
+    ("E0016", "Warning"),
+    ("E0017", "note"),
+    ("E0018", "{} is not formatted."),
+    ("E0019", "  at {} ({}:{})"),
+    ("E0020", "No test files ({}) found in {}."),
+    ("E0021", "PASS {}"),
+    ("E0022", "FAIL {}"),
+    ("E0023", "{} passed, {} failed"),
+    ("E0024", "Expected output '{}' (line {}), got '{}'."),
+    ("E0025", "Expected output '{}' (line {}), got nothing."),
+    ("E0026", "Unexpected output '{}'."),
+    ("E0027", "Expected error not reported: {}"),
+    ("E0028", "Unexpected error: {}"),
+    ("E0029", "Expected runtime error '{}' (line {}) did not happen."),
+    ("E0030", "Unexpected runtime error '{}' (line {})."),
+    ("E0031", "{}: {} iterations, min {}, median {}, mean {}"),
+    ("E0032", "  {} allocations ({} bytes) per iteration"),
+    ("E0033", "[line {}] {} => {}"),
+    ("E0034", "Stopped at {}:{}"),
+    ("E0035", "Breakpoint set at line {}."),
+    ("E0036", "Breakpoint at line {} deleted."),
+    ("E0037", "Expected a line number."),
+    ("E0038", "No breakpoint at line {}."),
+    ("E0039", "Expected a variable name."),
+    ("E0040", "Commands:\n  s, step         run to the next statement, into calls\n  n, next         run to the next statement, over calls\n  c, continue     run to the next breakpoint\n  b, break LINE   stop at LINE\n  d, delete LINE  remove the breakpoint at LINE\n  p, print NAME   show the variable NAME\n  v, vars         show every variable in scope\n  bt, backtrace   show the calls in progress\n  l, list         show where the program stopped\n  q, quit         stop the program"),
+    ("E0041", "Unknown command: {}. Type 'help' for the list."),
+    ("E0042", "{} of {} lines run"),
+    ("E0043", "{} only works with the tree-walker backend."),
+    ("E0044", "Wrote the coverage report to {}."),
+    ("E0045", "function calls total self"),
+    ("E0046", "Wrote the folded stacks to {}."),
+    ("E0047", "[line {}] Removed the unreachable code after '{}'."),
+    ("E0048", "[line {}] Removed the unused function '{}'."),
+    ("E0049", "[line {}] Removed code that a constant condition means never runs."),
+    ("E0050", "  ... the call above repeated {} more times"),
+    ("E0051", "{} (line {}) captures nothing."),
+    ("E0052", "{} (line {}) captures:"),
+    ("E0053", "  {}, declared on line {}, read-only"),
+    ("E0054", "  {}, declared on line {}, mutated"),
+    ("E0055", "Line {}"),
+    ("E0056", "The REPL reads from standard input and takes no files: {}"),
+    ("E0057", "benchmark tree-walker vm speedup"),
+    ("E0058", "{} only works with the VM backend."),
+    ("E0059", "opcode count share"),
+    ("E0060", "sequence count share"),
+    ("E0061", "The debug adapter talks over standard input and takes no files: {}"),
+    ("E0062", "Unknown request: {}"),
+    ("E0063", "{} needs the program stopped."),
+    ("E0064", "launch needs the path of a program."),
+    ("E0065", "Heap: {} minor collections freed {} objects and promoted {}; {} major collections freed {}; {} young and {} old objects alive."),
+    ("E0066", "--allow-net needs lox built with the net feature."),
+    ("E0067", "Can't load plugin '{}': {}"),
+    ("E0068", "its {} returned {}"),
+    ("E0069", "plugins can only be loaded on Unix"),
+    ("E0101", "Unexpected character: {}"),
+    ("E0102", "Unterminated string."),
+    ("E0103", "Number literal is too long (limit is {} characters)."),
+    ("E0104", "String literal is too long (limit is {} characters)."),
+    ("E0105", "Too many tokens (limit is {})."),
+    ("E0106", "Unterminated block comment."),
+    ("E0107", "Invalid escape sequence '\\{}'."),
+    ("E0108", "Invalid number literal '{}'."),
+    ("E0201", "Expected expression."),
+    ("E0202", "Expected ')' after expression."),
+    ("E0203", "Did you mean '{}'?"),
+    ("E0204", "Expected ';' after value."),
+    ("E0205", "Expected ';' after expression."),
+    ("E0206", "Expected variable name."),
+    ("E0207", "Expected ';' after variable declaration."),
+    ("E0208", "Expected '}' after block."),
+    ("E0209", "Expected '(' after 'if'."),
+    ("E0210", "Expected ')' after if condition."),
+    ("E0211", "Expected '(' after 'while'."),
+    ("E0212", "Expected ')' after condition."),
+    ("E0213", "Expected '(' after 'for'."),
+    ("E0214", "Expected ';' after loop condition."),
+    ("E0215", "Expected ')' after for clauses."),
+    ("E0216", "Expected function name."),
+    ("E0217", "Expected '(' after function name."),
+    ("E0218", "Can't have more than 255 parameters."),
+    ("E0219", "Expected parameter name."),
+    ("E0220", "Expected ')' after parameters."),
+    ("E0221", "Expected '{' before function body."),
+    ("E0222", "Can't have more than 255 arguments."),
+    ("E0223", "Expected ')' after arguments."),
+    ("E0224", "Expected ';' after return value."),
+    ("E0225", "Expected class name."),
+    ("E0226", "Expected '{' before class body."),
+    ("E0227", "Expected '}' after class body."),
+    ("E0228", "Expected property name after '.'."),
+    ("E0229", "Invalid assignment target."),
+    ("E0230", "Expected method name."),
+    ("E0231", "Expected superclass name."),
+    ("E0232", "A class can't inherit from itself."),
+    ("E0233", "Expected '.' after 'super'."),
+    ("E0234", "Expected superclass method name."),
+    ("E0236", "Expected ';' after 'break'."),
+    ("E0237", "Expected ';' after 'continue'."),
+    ("E0238", "'{}' opened here."),
+    ("E0239", "Expected a module path string after 'import'."),
+    ("E0240", "Expected ';' after import."),
+    ("E0241", "Expected ']' after index."),
+    ("E0242", "Expected ']' after list elements."),
+    ("E0243", "Expected ':' after map key."),
+    ("E0244", "Expected '}' after map entries."),
+    ("E0245", "Expected '(' after 'switch'."),
+    ("E0246", "Expected ')' after switch value."),
+    ("E0247", "Expected '{' before switch body."),
+    ("E0248", "Expected ':' after case value."),
+    ("E0249", "Expected ':' after 'default'."),
+    ("E0250", "A switch can only have one 'default'."),
+    ("E0251", "Expected 'case', 'default' or '}' in switch body."),
+    ("E0252", "Expected mixin name."),
+    ("E0253", "Expected ';' after thrown value."),
+    ("E0254", "Expected '{' after 'try'."),
+    ("E0255", "Expected '(' after 'catch'."),
+    ("E0256", "Expected exception variable name."),
+    ("E0257", "Expected ')' after exception variable."),
+    ("E0258", "Expected '{' before catch body."),
+    ("E0259", "Expected '{' after 'finally'."),
+    ("E0260", "Expected 'catch' or 'finally' after try block."),
+    ("E0261", "Expression too deeply nested."),
+    ("E0262", "Statement too deeply nested."),
+    ("E0263", "Expected a type name."),
+    ("E0301", "Operand must be a number."),
+    ("E0302", "Operands must be numbers."),
+    ("E0303", "Operands must be two numbers or two strings."),
+    ("E0304", "Undefined variable '{}'."),
+    ("E0305", "Can only call functions and classes."),
+    ("E0306", "Expected {} arguments but got {}."),
+    ("E0307", "Only instances have properties."),
+    ("E0308", "Only instances have fields."),
+    ("E0309", "Undefined property '{}'."),
+    ("E0310", "Superclass must be a class."),
+    ("E0311", "Argument to len() must be a string."),
+    ("E0312", "Argument to num() must be a string or a number."),
+    ("E0313", "Division by zero."),
+    ("E0314", "Stack overflow."),
+    ("E0315", "Can't run code that failed to parse."),
+    ("E0316", "Expected {} for argument {} but got {}."),
+    ("E0317", "Can't read module '{}'."),
+    ("E0318", "Circular import of module '{}'."),
+    ("E0319", "In module '{}' at [line {}, col {}]: {}"),
+    ("E0320", "Only lists, maps and strings can be indexed."),
+    ("E0321", "Index must be a whole number."),
+    ("E0322", "Index {} is out of bounds for a list of length {}."),
+    ("E0323", "Can't pop from an empty list."),
+    ("E0324", "Argument to len() must be a string or a list."),
+    ("E0325", "NaN can't be a map key, since it isn't equal to itself. Any other value can: nil, booleans, numbers and strings are the same key when they're equal, and anything else only when it's the same object."),
+    ("E0326", "Undefined key '{}'."),
+    ("E0327", "Index {} is out of bounds for a string of length {}."),
+    ("E0328", "Strings can't be changed; build a new one instead."),
+    ("E0329", "Length can't be negative."),
+    ("E0330", "Mixin must be a class."),
+    ("E0331", "Method '{}' comes from both '{}' and '{}'; define it in '{}' to choose one."),
+    ("E0332", "Uncaught exception: {}"),
+    ("E0333", "Exception message must be a string."),
+    ("E0334", "Assertion failed: {}"),
+    ("E0335", "Program stopped by the debugger."),
+    ("E0336", "Can't list directory '{}': {}"),
+    ("E0337", "Can't remove '{}': {}"),
+    ("E0338", "Can't create directory '{}': {}"),
+    ("E0339", "Can't run '{}': {}"),
+    ("E0340", "Invalid regular expression '{}': {}"),
+    ("E0341", "Can't fetch '{}': {}"),
+    ("E0342", "Can't fetch '{}': only http:// URLs are supported."),
+    ("E0343", "the response isn't HTTP"),
+    ("E0344", "A plugin function returned a string that isn't UTF-8."),
+    ("E0345", "A plugin function returned a value of unknown kind {}."),
+    ("E0401", "Already a variable with this name in this scope."),
+    ("E0402", "Can't read local variable in its own initializer."),
+    ("E0403", "Can't return from top-level code."),
+    ("E0404", "Can't return a value from an initializer."),
+    ("E0405", "Can't use 'this' outside of a class."),
+    ("E0406", "Can't use 'super' outside of a class."),
+    ("E0407", "Can't use 'super' in a class with no superclass."),
+    ("E0408", "Can't use 'break' outside of a loop."),
+    ("E0409", "Can't use 'continue' outside of a loop."),
+    ("E0410", "Can't resolve code that failed to parse."),
+    ("E0411", "'{}' is never used."),
+    ("E0412", "Parameter '{}' is never used."),
+    ("E0413", "Code after '{}' is never run."),
+    ("E0414", "'{}' shadows a variable in an enclosing scope."),
+    ("E0415", "Value assigned to '{}' is never read."),
+    ("E0416", "The shadowed variable is declared here."),
+    ("E0417", "Can only import at the top level."),
+    ("E0418", "Module name '{}' isn't a valid identifier."),
+    ("E0419", "Module name '{}' is already imported from \"{}\"."),
+    ("E0420", "The first import is here."),
+    ("E0421", "'{}' after '{}' compares the result of the first comparison, true or false, rather than the value before it. Write 'a {} b and b {} c' to check both."),
+    ("E0501", "Too many constants in one chunk."),
+    ("E0502", "Too many local variables in function."),
+    ("E0503", "Too many closure variables in function."),
+    ("E0504", "Too much code to jump over."),
+    ("E0505", "Loop body too large."),
+    ("E0506", "The bytecode VM can't import modules."),
+    ("E0507", "The bytecode VM doesn't support {}."),
+    ("E0601", "Operand of '{}' must be a number, not {}."),
+    ("E0602", "Operands of '{}' must be numbers, not {} and {}."),
+    ("E0603", "Operands of '+' must be two numbers or two strings, not {} and {}."),
+    ("E0604", "Can only call functions and classes, not {}."),
+    ("E0605", "Expected {} arguments but got {}."),
+    ("E0606", "Can't get property '{}' of {}; only instances have properties."),
+    ("E0607", "Only lists, maps and strings can be indexed, not {}."),
+    ("E0608", "'{}' is declared as {} but is given {}."),
+    ("E0609", "Expected {} for argument {} but got {}."),
+    ("E0610", "Can't return {} from a function declared to return {}."),
+    ("E0611", "Operands of '{}' must be two numbers or two strings, not {} and {}."),
+];
+
+/// Spanish messages.
+const ES: &[(&str, &str)] = &[
+    ("E0001", "Uso: {} <tokenize|parse|print-ast|evaluate|run|debug|test|check-tests|bench|disassemble|fmt|lint|captures|typecheck|doc|lsp|dap|repl> [opciones] <archivo|->..."),
+    ("E0002", "Comando desconocido: {}"),
+    ("E0003", "Opción desconocida: {}"),
+    ("E0004", "Valor no válido para --{}: '{}'"),
+    ("E0005", "No se indicó ningún archivo de entrada."),
+    ("E0006", "No se pudo leer el archivo {}"),
+    ("E0007", "El archivo fuente {} es demasiado grande (el límite es {} bytes)."),
+    ("E0008", "Idioma desconocido: {}"),
+    ("E0009", "La entrada estándar solo se puede leer una vez; indique '-' o --stdin una sola vez."),
+    ("E0010", "{} no es UTF-8 válido (byte no válido en la posición {}). Use --encoding=latin1 para archivos Latin-1."),
+    ("E0011", "[línea {}, col. {}] {}: {}"),
+    ("E0012", "[línea {}, col. {}] {} en '{}': {}"),
+    ("E0013", "[línea {}, col. {}] {} al final: {}"),
+    ("E0014", "{}\n[línea {}, col. {}]"),
+    ("E0015", "Error"),
+    ("E0016", "Advertencia"),
+    ("E0017", "nota"),
+    ("E0018", "{} no tiene el formato canónico."),
+    ("E0019", "  en {} ({}:{})"),
+    ("E0020", "No se encontraron archivos de prueba ({}) en {}."),
+    ("E0021", "BIEN {}"),
+    ("E0022", "FALLO {}"),
+    ("E0023", "{} correctas, {} fallidas"),
+    ("E0024", "Se esperaba la salida '{}' (línea {}) y se obtuvo '{}'."),
+    ("E0025", "Se esperaba la salida '{}' (línea {}) y no se obtuvo nada."),
+    ("E0026", "Salida inesperada '{}'."),
+    ("E0027", "No se informó del error esperado: {}"),
+    ("E0028", "Error inesperado: {}"),
+    ("E0029", "No se produjo el error de ejecución esperado '{}' (línea {})."),
+    ("E0030", "Error de ejecución inesperado '{}' (línea {})."),
+    ("E0031", "{}: {} iteraciones, mínimo {}, mediana {}, media {}"),
+    ("E0032", "  {} reservas de memoria ({} bytes) por iteración"),
+    ("E0033", "[línea {}] {} => {}"),
+    ("E0034", "Detenido en {}:{}"),
+    ("E0035", "Punto de interrupción en la línea {}."),
+    ("E0036", "Se eliminó el punto de interrupción de la línea {}."),
+    ("E0037", "Se esperaba un número de línea."),
+    ("E0038", "No hay ningún punto de interrupción en la línea {}."),
+    ("E0039", "Se esperaba un nombre de variable."),
+    ("E0040", "Comandos:\n  s, step         ejecuta hasta la siguiente sentencia, entrando en llamadas\n  n, next         ejecuta hasta la siguiente sentencia, sin entrar en llamadas\n  c, continue     ejecuta hasta el siguiente punto de interrupción\n  b, break LÍNEA  se detiene en LÍNEA\n  d, delete LÍNEA quita el punto de interrupción de LÍNEA\n  p, print NOMBRE muestra la variable NOMBRE\n  v, vars         muestra todas las variables visibles\n  bt, backtrace   muestra las llamadas en curso\n  l, list         muestra dónde se detuvo el programa\n  q, quit         detiene el programa"),
+    ("E0041", "Comando desconocido: {}. Escriba 'help' para ver la lista."),
+    ("E0042", "{} de {} líneas ejecutadas"),
+    ("E0043", "{} solo funciona con el intérprete de árbol."),
+    ("E0044", "Se escribió el informe de cobertura en {}."),
+    ("E0045", "función llamadas total propio"),
+    ("E0046", "Se escribieron las pilas plegadas en {}."),
+    ("E0047", "[línea {}] Se eliminó el código inalcanzable después de '{}'."),
+    ("E0048", "[línea {}] Se eliminó la función sin usar '{}'."),
+    ("E0049", "[línea {}] Se eliminó código que una condición constante impide ejecutar."),
+    ("E0050", "  ... la llamada anterior se repite {} veces más"),
+    ("E0051", "{} (línea {}) no captura nada."),
+    ("E0052", "{} (línea {}) captura:"),
+    ("E0053", "  {}, declarada en la línea {}, solo lectura"),
+    ("E0054", "  {}, declarada en la línea {}, modificada"),
+    ("E0055", "Línea {}"),
+    ("E0056", "El REPL lee de la entrada estándar y no admite archivos: {}"),
+    ("E0057", "programa intérprete vm aceleración"),
+    ("E0058", "{} solo funciona con la máquina virtual."),
+    ("E0059", "instrucción veces parte"),
+    ("E0060", "secuencia veces parte"),
+    ("E0061", "El adaptador de depuración usa la entrada estándar y no recibe archivos: {}"),
+    ("E0062", "Petición desconocida: {}"),
+    ("E0063", "{} necesita el programa detenido."),
+    ("E0064", "launch necesita la ruta de un programa."),
+    ("E0065", "Montón: {} recolecciones menores liberaron {} objetos y promovieron {}; {} recolecciones mayores liberaron {}; {} objetos jóvenes y {} viejos vivos."),
+    ("E0066", "--allow-net necesita lox compilado con la característica net."),
+    ("E0067", "No se pudo cargar el complemento '{}': {}"),
+    ("E0068", "su {} devolvió {}"),
+    ("E0069", "los complementos solo se pueden cargar en Unix"),
+    ("E0101", "Carácter inesperado: {}"),
+    ("E0102", "Cadena sin terminar."),
+    ("E0103", "Literal numérico demasiado largo (el límite es {} caracteres)."),
+    ("E0104", "Literal de cadena demasiado largo (el límite es {} caracteres)."),
+    ("E0105", "Demasiados tokens (el límite es {})."),
+    ("E0106", "Comentario de bloque sin terminar."),
+    ("E0107", "Secuencia de escape no válida '\\{}'."),
+    ("E0108", "Literal numérico no válido '{}'."),
+    ("E0201", "Se esperaba una expresión."),
+    ("E0202", "Se esperaba ')' después de la expresión."),
+    ("E0203", "¿Quisiste decir '{}'?"),
+    ("E0204", "Se esperaba ';' después del valor."),
+    ("E0205", "Se esperaba ';' después de la expresión."),
+    ("E0206", "Se esperaba un nombre de variable."),
+    ("E0207", "Se esperaba ';' después de la declaración de variable."),
+    ("E0208", "Se esperaba '}' después del bloque."),
+    ("E0209", "Se esperaba '(' después de 'if'."),
+    ("E0210", "Se esperaba ')' después de la condición del if."),
+    ("E0211", "Se esperaba '(' después de 'while'."),
+    ("E0212", "Se esperaba ')' después de la condición."),
+    ("E0213", "Se esperaba '(' después de 'for'."),
+    ("E0214", "Se esperaba ';' después de la condición del bucle."),
+    ("E0215", "Se esperaba ')' después de las cláusulas del for."),
+    ("E0216", "Se esperaba el nombre de la función."),
+    ("E0217", "Se esperaba '(' después del nombre de la función."),
+    ("E0218", "No puede haber más de 255 parámetros."),
+    ("E0219", "Se esperaba un nombre de parámetro."),
+    ("E0220", "Se esperaba ')' después de los parámetros."),
+    ("E0221", "Se esperaba '{' antes del cuerpo de la función."),
+    ("E0222", "No puede haber más de 255 argumentos."),
+    ("E0223", "Se esperaba ')' después de los argumentos."),
+    ("E0224", "Se esperaba ';' después del valor de retorno."),
+    ("E0225", "Se esperaba el nombre de la clase."),
+    ("E0226", "Se esperaba '{' antes del cuerpo de la clase."),
+    ("E0227", "Se esperaba '}' después del cuerpo de la clase."),
+    ("E0228", "Se esperaba un nombre de propiedad después de '.'."),
+    ("E0229", "Destino de asignación no válido."),
+    ("E0230", "Se esperaba el nombre del método."),
+    ("E0231", "Se esperaba el nombre de la superclase."),
+    ("E0232", "Una clase no puede heredar de sí misma."),
+    ("E0233", "Se esperaba '.' después de 'super'."),
+    ("E0234", "Se esperaba el nombre de un método de la superclase."),
+    ("E0236", "Se esperaba ';' después de 'break'."),
+    ("E0237", "Se esperaba ';' después de 'continue'."),
+    ("E0238", "'{}' abierto aquí."),
+    ("E0239", "Se esperaba la ruta de un módulo como cadena después de 'import'."),
+    ("E0240", "Se esperaba ';' después de import."),
+    ("E0241", "Se esperaba ']' después del índice."),
+    ("E0242", "Se esperaba ']' después de los elementos de la lista."),
+    ("E0243", "Se esperaba ':' después de la clave del mapa."),
+    ("E0244", "Se esperaba '}' después de las entradas del mapa."),
+    ("E0245", "Se esperaba '(' después de 'switch'."),
+    ("E0246", "Se esperaba ')' después del valor del switch."),
+    ("E0247", "Se esperaba '{' antes del cuerpo del switch."),
+    ("E0248", "Se esperaba ':' después del valor del case."),
+    ("E0249", "Se esperaba ':' después de 'default'."),
+    ("E0250", "Un switch solo puede tener un 'default'."),
+    ("E0251", "Se esperaba 'case', 'default' o '}' en el cuerpo del switch."),
+    ("E0252", "Se esperaba el nombre del mixin."),
+    ("E0253", "Se esperaba ';' después del valor lanzado."),
+    ("E0254", "Se esperaba '{' después de 'try'."),
+    ("E0255", "Se esperaba '(' después de 'catch'."),
+    ("E0256", "Se esperaba el nombre de la variable de la excepción."),
+    ("E0257", "Se esperaba ')' después de la variable de la excepción."),
+    ("E0258", "Se esperaba '{' antes del cuerpo del catch."),
+    ("E0259", "Se esperaba '{' después de 'finally'."),
+    ("E0260", "Se esperaba 'catch' o 'finally' después del bloque try."),
+    ("E0261", "Expresión anidada a demasiada profundidad."),
+    ("E0262", "Sentencia anidada a demasiada profundidad."),
+    ("E0263", "Se esperaba un nombre de tipo."),
+    ("E0301", "El operando debe ser un número."),
+    ("E0302", "Los operandos deben ser números."),
+    ("E0303", "Los operandos deben ser dos números o dos cadenas."),
+    ("E0304", "Variable no definida '{}'."),
+    ("E0305", "Solo se pueden llamar funciones y clases."),
+    ("E0306", "Se esperaban {} argumentos pero se recibieron {}."),
+    ("E0307", "Solo las instancias tienen propiedades."),
+    ("E0308", "Solo las instancias tienen campos."),
+    ("E0309", "Propiedad no definida '{}'."),
+    ("E0310", "La superclase debe ser una clase."),
+    ("E0311", "El argumento de len() debe ser una cadena."),
+    ("E0312", "El argumento de num() debe ser una cadena o un número."),
+    ("E0313", "División entre cero."),
+    ("E0314", "Desbordamiento de pila."),
+    ("E0315", "No se puede ejecutar código que no se pudo analizar."),
+    ("E0316", "Se esperaba {} como argumento {} pero se recibió {}."),
+    ("E0317", "No se puede leer el módulo '{}'."),
+    ("E0318", "Importación circular del módulo '{}'."),
+    ("E0319", "En el módulo '{}' en [línea {}, col {}]: {}"),
+    ("E0320", "Solo se pueden indexar listas, mapas y cadenas."),
+    ("E0321", "El índice debe ser un número entero."),
+    ("E0322", "El índice {} está fuera de los límites de una lista de longitud {}."),
+    ("E0323", "No se puede sacar de una lista vacía."),
+    ("E0324", "El argumento de len() debe ser una cadena o una lista."),
+    ("E0325", "NaN no puede ser una clave de un mapa, ya que no es igual a sí mismo. Cualquier otro valor puede serlo: nil, los booleanos, los números y las cadenas son la misma clave cuando son iguales, y lo demás solo cuando es el mismo objeto."),
+    ("E0326", "Clave no definida '{}'."),
+    ("E0327", "El índice {} está fuera de los límites de una cadena de longitud {}."),
+    ("E0328", "Las cadenas no se pueden modificar; construye una nueva."),
+    ("E0329", "La longitud no puede ser negativa."),
+    ("E0330", "El mixin debe ser una clase."),
+    ("E0331", "El método '{}' viene tanto de '{}' como de '{}'; defínelo en '{}' para elegir uno."),
+    ("E0332", "Excepción no capturada: {}"),
+    ("E0333", "El mensaje de la excepción debe ser una cadena."),
+    ("E0334", "La aserción falló: {}"),
+    ("E0335", "El depurador detuvo el programa."),
+    ("E0336", "No se pudo listar el directorio '{}': {}"),
+    ("E0337", "No se pudo eliminar '{}': {}"),
+    ("E0338", "No se pudo crear el directorio '{}': {}"),
+    ("E0339", "No se pudo ejecutar '{}': {}"),
+    ("E0340", "Expresión regular no válida '{}': {}"),
+    ("E0341", "No se pudo obtener '{}': {}"),
+    ("E0342", "No se pudo obtener '{}': solo se admiten URL http://."),
+    ("E0343", "la respuesta no es HTTP"),
+    ("E0344", "Una función de complemento devolvió una cadena que no es UTF-8."),
+    ("E0345", "Una función de complemento devolvió un valor de tipo desconocido {}."),
+    ("E0401", "Ya hay una variable con este nombre en este ámbito."),
+    ("E0402", "No se puede leer una variable local en su propio inicializador."),
+    ("E0403", "No se puede usar return fuera de una función."),
+    ("E0404", "No se puede devolver un valor desde un inicializador."),
+    ("E0405", "No se puede usar 'this' fuera de una clase."),
+    ("E0406", "No se puede usar 'super' fuera de una clase."),
+    ("E0407", "No se puede usar 'super' en una clase sin superclase."),
+    ("E0408", "No se puede usar 'break' fuera de un bucle."),
+    ("E0409", "No se puede usar 'continue' fuera de un bucle."),
+    ("E0410", "No se puede resolver código que no se pudo analizar."),
+    ("E0411", "'{}' nunca se usa."),
+    ("E0412", "El parámetro '{}' nunca se usa."),
+    ("E0413", "El código después de '{}' nunca se ejecuta."),
+    ("E0414", "'{}' oculta una variable de un ámbito exterior."),
+    ("E0415", "El valor asignado a '{}' nunca se lee."),
+    ("E0416", "La variable oculta se declara aquí."),
+    ("E0417", "Solo se puede importar en el nivel superior."),
+    ("E0418", "El nombre de módulo '{}' no es un identificador válido."),
+    ("E0419", "El nombre de módulo '{}' ya se importa desde \"{}\"."),
+    ("E0420", "La primera importación está aquí."),
+    ("E0421", "'{}' después de '{}' compara el resultado de la primera comparación, verdadero o falso, en lugar del valor anterior. Escribe 'a {} b and b {} c' para comprobar ambas."),
+    ("E0501", "Demasiadas constantes en un fragmento."),
+    ("E0502", "Demasiadas variables locales en la función."),
+    ("E0503", "Demasiadas variables capturadas en la función."),
+    ("E0504", "Demasiado código que saltar."),
+    ("E0505", "Cuerpo del bucle demasiado grande."),
+    ("E0506", "La VM de bytecode no puede importar módulos."),
+    ("E0507", "La VM de bytecode no admite {}."),
+    ("E0601", "El operando de '{}' debe ser un número, no {}."),
+    ("E0602", "Los operandos de '{}' deben ser números, no {} y {}."),
+    ("E0603", "Los operandos de '+' deben ser dos números o dos cadenas, no {} y {}."),
+    ("E0604", "Solo se pueden llamar funciones y clases, no {}."),
+    ("E0605", "Se esperaban {} argumentos pero se recibieron {}."),
+    ("E0606", "No se puede obtener la propiedad '{}' de {}; solo las instancias tienen propiedades."),
+    ("E0607", "Solo se pueden indexar listas, mapas y cadenas, no {}."),
+    ("E0608", "'{}' está declarada como {} pero recibe {}."),
+    ("E0609", "Se esperaba {} para el argumento {} pero se recibió {}."),
+    ("E0610", "No se puede devolver {} desde una función declarada para devolver {}."),
+    ("E0611", "Los operandos de '{}' deben ser dos números o dos cadenas, no {} y {}."),
+];
@@ -0,0 +1,59 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::interpreter::RuntimeError;
+use crate::token::{Token, TokenType};
+use crate::value::Value;
+
+/// A file loaded with `import`, as the value bound to its name. It ran in
+/// its own global scope, whose variables are its properties.
+pub struct Module {
+    pub name: String,
+    globals: Rc<RefCell<Environment>>,
+}
+
+impl Module {
+    pub fn new(name: String, globals: Rc<RefCell<Environment>>) -> Self {
+        Module { name, globals }
+    }
+
+    /// The global `name` of the module, for `module.name`.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        self.globals
+            .borrow()
+            .get(name)
+            .map_err(|_| RuntimeError::new(name, msg!("E0309", name.lexeme)))
+    }
+}
+
+/// The name an import of `path` binds: the file name without its
+/// extension, if that's an identifier Lox code can refer to.
+pub fn module_name(path: &str) -> Option<&str> {
+    let name = Path::new(path).file_stem()?.to_str()?;
+    let mut chars = name.chars();
+    let starts_well = chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_');
+    let identifier = starts_well && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    let keyword = TokenType::keyword(name).is_some();
+    (identifier && !keyword).then_some(name)
+}
+
+// Written by hand: the module's functions refer back to its globals, so a
+// derived impl would recurse forever.
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Module")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
@@ -0,0 +1,399 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::process::Command;
+use std::rc::Rc;
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exception::Exception;
+use crate::function::LoxCallable;
+use crate::gc;
+use crate::host::HostFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::map::{LoxMap, MapKey};
+use crate::token::Token;
+use crate::value::Value;
+use crate::Output;
+
+/// The Rust side of a native function. Gets the already arity-checked
+/// arguments and returns the result, or an error message.
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+/// A function implemented in Rust and callable from Lox.
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        paren: &Token,
+        _out: &mut Output,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments).map_err(|msg| RuntimeError::new(paren, msg))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// Defines the standard library's natives as globals of `interpreter`.
+pub fn define_standard_library(interpreter: &mut Interpreter) {
+    interpreter.define_native("clock", 0, |_| Ok(Value::Number(clock())));
+
+    interpreter.define_native("len", 1, |args| match &args[0] {
+        Value::Str(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::List(list) => Ok(Value::Number(list.borrow().len() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+        _ => Err(msg!("E0324")),
+    });
+
+    interpreter.define_native("str", 1, |args| Ok(Value::Str(args[0].to_string().into())));
+
+    interpreter.define_native("num", 1, |args| match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Str(s) => Ok(parse_number(s).map_or(Value::Nil, Value::Number)),
+        _ => Err(msg!("E0312")),
+    });
+
+    interpreter.define_native("type", 1, |args| Ok(Value::Str(args[0].type_name().into())));
+
+    interpreter.define_native("input", 0, |_| {
+        Ok(read_line().map_or(Value::Nil, |line| Value::Str(line.into())))
+    });
+
+    interpreter.define_native("assert", 2, |args| {
+        if args[0].is_truthy() {
+            Ok(Value::Nil)
+        } else {
+            Err(msg!("E0334", args[1]))
+        }
+    });
+
+    // Its line is filled in by the `throw` that throws it
+    interpreter.define_native("Exception", 1, |args| match &args[0] {
+        Value::Str(message) => {
+            let exception = Exception::new(message.clone(), None);
+            Ok(Value::Exception(Rc::new(exception)))
+        }
+        _ => Err(msg!("E0333")),
+    });
+
+    #[cfg(feature = "regex")]
+    define_regex(interpreter);
+}
+
+/// Defines the regular expression natives, with the `regex` crate's syntax:
+/// `match(pattern, s)`, a list of the first match in `s` and its groups,
+/// `nil` for a group that took no part, or `nil` if there's no match;
+/// `findAll(pattern, s)`, a list of every match; and `replace(pattern, s,
+/// replacement)`, `s` with every match replaced, where `$1` or `${name}`
+/// in `replacement` stands for a group.
+#[cfg(feature = "regex")]
+pub fn define_regex(interpreter: &mut Interpreter) {
+    interpreter.register(
+        "match",
+        |pattern: String, s: String| -> Result<Option<Vec<Option<String>>>, String> {
+            let captures = compile(&pattern)?.captures(&s);
+            Ok(captures.map(|captures| {
+                let groups = captures.iter();
+                groups
+                    .map(|group| group.map(|group| group.as_str().to_string()))
+                    .collect()
+            }))
+        },
+    );
+
+    interpreter.register(
+        "findAll",
+        |pattern: String, s: String| -> Result<Vec<String>, String> {
+            let regex = compile(&pattern)?;
+            let matches = regex.find_iter(&s);
+            Ok(matches.map(|found| found.as_str().to_string()).collect())
+        },
+    );
+
+    interpreter.register(
+        "replace",
+        |pattern: String, s: String, replacement: String| -> Result<String, String> {
+            let regex = compile(&pattern)?;
+            Ok(regex.replace_all(&s, replacement.as_str()).into_owned())
+        },
+    );
+}
+
+/// `pattern` compiled, or an error giving on one line what's wrong with it,
+/// leaving out the drawing the `regex` crate's errors start with.
+#[cfg(feature = "regex")]
+fn compile(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern).map_err(|err| {
+        let err = err.to_string();
+        let reason = err.lines().last().unwrap_or_default();
+        msg!("E0340", pattern, reason.trim_start_matches("error: "))
+    })
+}
+
+/// Defines `listDir(path)`, `exists(path)`, `remove(path)` and
+/// `mkdir(path)`, which only a program run with `--allow-fs` can use.
+/// Relative paths are from the working directory.
+pub fn define_fs(interpreter: &mut Interpreter) {
+    // Sorted, so a listing is the same from run to run
+    interpreter.register("listDir", |path: String| -> Result<Vec<String>, String> {
+        let names: io::Result<Vec<String>> = fs::read_dir(&path).and_then(|entries| {
+            entries
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                .collect()
+        });
+        let mut names = names.map_err(|err| msg!("E0336", path, err))?;
+        names.sort();
+        Ok(names)
+    });
+
+    interpreter.register("exists", |path: String| Path::new(&path).exists());
+
+    // A directory only if it's empty, so a wrong path can't take a whole
+    // tree with it
+    interpreter.register("remove", |path: String| -> Result<(), String> {
+        let removed = if Path::new(&path).is_dir() {
+            fs::remove_dir(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.map_err(|err| msg!("E0337", path, err))
+    });
+
+    // Along with any missing parents, and fine if it's already there
+    interpreter.register("mkdir", |path: String| -> Result<(), String> {
+        fs::create_dir_all(&path).map_err(|err| msg!("E0338", path, err))
+    });
+}
+
+/// Defines `exec(command, args)`, which only a program run with
+/// `--allow-exec` can use. It runs `command` with the list of strings
+/// `args`, not through a shell, and returns a map of what it wrote to
+/// `stdout` and `stderr` and its exit `code`, `nil` if a signal ended it.
+pub fn define_exec(interpreter: &mut Interpreter) {
+    interpreter.register(
+        "exec",
+        |command: String, args: Vec<String>| -> Result<Value, String> {
+            // Its standard input is empty, and what it writes collected
+            let output = Command::new(&command)
+                .args(&args)
+                .output()
+                .map_err(|err| msg!("E0339", command, err))?;
+            let text = |bytes: Vec<u8>| Value::from(String::from_utf8_lossy(&bytes).into_owned());
+            let mut result = LoxMap::default();
+            result.insert(MapKey::Str("stdout".into()), text(output.stdout));
+            result.insert(MapKey::Str("stderr".into()), text(output.stderr));
+            let code = output.status.code().map(i64::from);
+            result.insert(MapKey::Str("code".into()), code.into());
+            Ok(Value::Map(gc::tracked(RefCell::new(result))))
+        },
+    );
+}
+
+/// Defines `fetch(url)`, which only a program run with `--allow-net` can
+/// use. It makes a GET request to the `http://` URL and returns a map of the
+/// response's `status` and its `body` as a string, whatever the status.
+#[cfg(feature = "net")]
+pub fn define_net(interpreter: &mut Interpreter) {
+    interpreter.register("fetch", |url: String| -> Result<Value, String> {
+        let response = crate::http::get(&url)?;
+        let mut result = LoxMap::default();
+        result.insert(
+            MapKey::Str("status".into()),
+            i64::from(response.status).into(),
+        );
+        result.insert(MapKey::Str("body".into()), response.body.into());
+        Ok(Value::Map(gc::tracked(RefCell::new(result))))
+    });
+}
+
+/// The method `name` of the string `s`, bound to it: `len()`, `upper()`,
+/// `lower()`, `contains(part)`, `replace(from, to)`, `split(separator)` or
+/// `substr(start, length)`. Positions and lengths count characters, not
+/// bytes.
+pub fn string_method(s: &str, name: &Token) -> Result<Value, RuntimeError> {
+    let s = s.to_string();
+    let method = match name.lexeme.as_str() {
+        "len" => method("len", move || s.chars().count() as f64),
+        "upper" => method("upper", move || s.to_uppercase()),
+        "lower" => method("lower", move || s.to_lowercase()),
+        "contains" => method("contains", move |part: String| s.contains(&part)),
+        "replace" => method("replace", move |from: String, to: String| {
+            s.replace(&from, &to)
+        }),
+        // An empty separator splits into characters
+        "split" => method("split", move |separator: String| -> Value {
+            let parts: Vec<&str> = match separator.as_str() {
+                "" => s
+                    .char_indices()
+                    .map(|(i, ch)| &s[i..i + ch.len_utf8()])
+                    .collect(),
+                separator => s.split(separator).collect(),
+            };
+            parts.into()
+        }),
+        // Stops at the end of the string if `length` runs past it
+        "substr" => method("substr", move |start: i64, length: i64| {
+            let len = s.chars().count();
+            if start < 0 || start as usize > len {
+                return Err(msg!("E0327", start, len));
+            }
+            if length < 0 {
+                return Err(msg!("E0329"));
+            }
+            Ok(s.chars()
+                .skip(start as usize)
+                .take(length as usize)
+                .collect::<String>())
+        }),
+        _ => return Err(RuntimeError::new(name, msg!("E0309", name.lexeme))),
+    };
+    Ok(Value::Native(Rc::new(method)))
+}
+
+/// A native method taking and returning Rust types, converted as for
+/// `Interpreter::register`.
+fn method<Args>(name: &str, function: impl HostFunction<Args> + 'static) -> NativeFunction {
+    NativeFunction::new(name, function.arity(), move |args| function.call(args))
+}
+
+/// The method `name` of `list`, bound to it: `push(value)`, `pop()` or
+/// `len()`.
+pub fn list_method(list: &Rc<RefCell<Vec<Value>>>, name: &Token) -> Result<Value, RuntimeError> {
+    let list = Rc::clone(list);
+    let method = match name.lexeme.as_str() {
+        "push" => NativeFunction::new("push", 1, move |args| {
+            list.borrow_mut().push(args[0].clone());
+            Ok(Value::Nil)
+        }),
+        "pop" => NativeFunction::new("pop", 0, move |_| {
+            list.borrow_mut().pop().ok_or_else(|| msg!("E0323"))
+        }),
+        "len" => NativeFunction::new("len", 0, move |_| {
+            Ok(Value::Number(list.borrow().len() as f64))
+        }),
+        _ => return Err(RuntimeError::new(name, msg!("E0309", name.lexeme))),
+    };
+    Ok(Value::Native(Rc::new(method)))
+}
+
+/// The method `name` of `map`, bound to it: `keys()`, `values()`,
+/// `has(key)`, `remove(key)` or `len()`.
+pub fn map_method(map: &Rc<RefCell<LoxMap>>, name: &Token) -> Result<Value, RuntimeError> {
+    let map = Rc::clone(map);
+    let method = match name.lexeme.as_str() {
+        "keys" => NativeFunction::new("keys", 0, move |_| {
+            let keys = map.borrow().keys().map(MapKey::to_value).collect();
+            Ok(Value::List(gc::tracked(RefCell::new(keys))))
+        }),
+        "values" => NativeFunction::new("values", 0, move |_| {
+            let values = map.borrow().values().cloned().collect();
+            Ok(Value::List(gc::tracked(RefCell::new(values))))
+        }),
+        "has" => NativeFunction::new("has", 1, move |args| {
+            let key = map_key(&args[0])?;
+            Ok(Value::Bool(map.borrow().get(&key).is_some()))
+        }),
+        "remove" => NativeFunction::new("remove", 1, move |args| {
+            let key = map_key(&args[0])?;
+            Ok(map.borrow_mut().remove(&key).unwrap_or(Value::Nil))
+        }),
+        "len" => NativeFunction::new("len", 0, move |_| {
+            Ok(Value::Number(map.borrow().len() as f64))
+        }),
+        _ => return Err(RuntimeError::new(name, msg!("E0309", name.lexeme))),
+    };
+    Ok(Value::Native(Rc::new(method)))
+}
+
+/// `value` as a map key, or the error saying it can't be one.
+pub fn map_key(value: &Value) -> Result<MapKey, String> {
+    MapKey::from_value(value).ok_or_else(|| msg!("E0325"))
+}
+
+// The parts of the natives that don't depend on the value representation,
+// shared with the bytecode VM's own definitions of them
+
+/// Seconds since the Unix epoch, for `clock()`.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn clock() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// In the browser, where there's no system clock to read, from `Date`.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn clock() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+/// The number `num()` reads from `text`: a number literal as Lox writes
+/// one, like `12` or `0.5`, optionally negative and with space around it.
+/// Anything else, including what Rust would read like `inf`, `NaN` or
+/// `1e3`, gives `None`, which `num()` returns as nil rather than an error
+/// so it's easy to check input with.
+pub(crate) fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// One line from stdin without its line ending, or `None` at end of input,
+/// for `input()`.
+pub(crate) fn read_line() -> Option<String> {
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => {
+            let trimmed = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(trimmed);
+            Some(line)
+        }
+    }
+}
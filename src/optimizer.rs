@@ -0,0 +1,245 @@
+//! The `--opt` pass, run on a resolved program before it's executed.
+//! Folds expressions whose operands are all literals into the literal
+//! they evaluate to, and drops the code that can never run or do
+//! anything: the branches of `if` and the `while` loops that a literal
+//! condition rules out, statements after a `return`, `throw`, `break` or
+//! `continue` in the same block, and local functions that are never used.
+//!
+//! An operation that would fail at runtime, like `1 / 0`, is left for the
+//! interpreter to report. Only literals are replaced and only whole
+//! statements removed, so the tokens the resolver looked up variables by
+//! are untouched, though dropping a declaration means resolving again to
+//! number the slots of the others. The operands of a folded expression
+//! are left in the arena, no longer part of the tree.
+
+use crate::ast::{Expr, ExprArena, Literal, Stmt};
+use crate::fold::{self, Folder};
+use crate::interpreter::evaluate_binary;
+use crate::token::{Token, TokenType};
+use crate::value::Value;
+use std::collections::HashSet;
+
+/// Folds constants and removes dead code in programs run with the given
+/// options.
+pub struct Optimizer {
+    /// Whether `+` stringifies a non-string operand, as
+    /// `Interpreter::set_coerce_strings` says the program runs with
+    coerce_strings: bool,
+    /// The offsets of the names of locals that are never used, see
+    /// `set_unused`
+    unused: HashSet<usize>,
+    /// What `optimize` removed, a line each, for `--opt-verbose`
+    pub removed: Vec<String>,
+}
+
+impl Optimizer {
+    pub fn new(coerce_strings: bool) -> Self {
+        Optimizer {
+            coerce_strings,
+            unused: HashSet::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Lets `optimize` drop the functions declared at these names, the
+    /// locals `Resolver::unused` found are never read or assigned.
+    pub fn set_unused(&mut self, unused: &[Token]) {
+        self.unused = unused.iter().map(|name| name.span.start).collect();
+    }
+
+    /// The statements of a program with their constants folded, and
+    /// without the code that can't run or matter. Their expressions are
+    /// rewritten where they are in `arena`.
+    pub fn optimize(&mut self, arena: &mut ExprArena, statements: Vec<Stmt>) -> Vec<Stmt> {
+        self.fold_stmts(arena, statements)
+    }
+
+    /// Notes that `stmt` was removed because a condition rules it out.
+    /// One with no tokens to place it, like `{}`, isn't worth a note.
+    fn note_dead(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        if let Some(line) = first_line(arena, stmt) {
+            self.removed.push(msg!("E0049", line));
+        }
+    }
+}
+
+impl Folder for Optimizer {
+    /// Also drops unused local functions, and everything after a statement
+    /// that jumps out of the block.
+    fn fold_stmts(&mut self, arena: &mut ExprArena, statements: Vec<Stmt>) -> Vec<Stmt> {
+        let mut optimized = Vec::with_capacity(statements.len());
+        let mut statements = statements.into_iter().peekable();
+        while let Some(stmt) = statements.next() {
+            if let Stmt::Function(declaration) = &stmt {
+                let name = &declaration.name;
+                if self.unused.contains(&name.span.start) {
+                    self.removed.push(msg!("E0048", name.line, name.lexeme));
+                    continue;
+                }
+            }
+            let Some(stmt) = self.fold_stmt(arena, stmt) else {
+                continue;
+            };
+            let jump = match &stmt {
+                Stmt::Return { keyword, .. }
+                | Stmt::Throw { keyword, .. }
+                | Stmt::Break(keyword)
+                | Stmt::Continue(keyword) => Some(keyword.clone()),
+                _ => None,
+            };
+            optimized.push(stmt);
+            if let Some(keyword) = jump.filter(|_| statements.peek().is_some()) {
+                self.removed
+                    .push(msg!("E0047", keyword.line, keyword.lexeme));
+                break;
+            }
+        }
+        optimized
+    }
+
+    /// Replaces an `if` whose condition is a literal with the branch that
+    /// runs, and drops a `while` whose condition is a falsey literal.
+    fn fold_stmt(&mut self, arena: &mut ExprArena, stmt: Stmt) -> Option<Stmt> {
+        match stmt {
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = fold::fold_at(self, arena, condition);
+                match truthiness(&arena[condition]) {
+                    Some(true) => {
+                        if let Some(branch) = &else_branch {
+                            self.note_dead(arena, branch);
+                        }
+                        self.fold_stmt(arena, *then_branch)
+                    }
+                    Some(false) => {
+                        self.note_dead(arena, &then_branch);
+                        else_branch.and_then(|branch| self.fold_stmt(arena, *branch))
+                    }
+                    None => fold::fold_stmt(
+                        self,
+                        arena,
+                        Stmt::If {
+                            condition,
+                            then_branch,
+                            else_branch,
+                        },
+                    ),
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                let condition = fold::fold_at(self, arena, condition);
+                if truthiness(&arena[condition]) == Some(false) {
+                    self.note_dead(arena, &body);
+                    return None;
+                }
+                fold::fold_stmt(
+                    self,
+                    arena,
+                    Stmt::While {
+                        condition,
+                        body,
+                        increment,
+                    },
+                )
+            }
+            stmt => fold::fold_stmt(self, arena, stmt),
+        }
+    }
+
+    /// Replaces an operation on literals with its result.
+    fn fold_expr(&mut self, arena: &mut ExprArena, expr: Expr) -> Expr {
+        match fold::fold_expr(self, arena, expr) {
+            Expr::Grouping(inner) if matches!(arena[inner], Expr::Literal(_)) => arena.take(inner),
+            Expr::Unary { operator, right } => {
+                let folded = match (&operator.token_type, &arena[right]) {
+                    (TokenType::Minus, Expr::Literal(Literal::Number(n))) => {
+                        Some(Literal::Number(-n))
+                    }
+                    (TokenType::Bang, Expr::Literal(literal)) => {
+                        Some(Literal::Bool(!Value::from(literal).is_truthy()))
+                    }
+                    _ => None,
+                };
+                match folded {
+                    Some(literal) => Expr::Literal(literal),
+                    None => Expr::Unary { operator, right },
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                if let (Expr::Literal(l), Expr::Literal(r)) = (&arena[left], &arena[right]) {
+                    let value = evaluate_binary(&operator, l.into(), r.into(), self.coerce_strings);
+                    if let Some(literal) = value.ok().as_ref().and_then(literal) {
+                        return Expr::Literal(literal);
+                    }
+                }
+                Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                }
+            }
+            // `and` gives its left operand if that's falsey and its right
+            // one otherwise, and `or` the other way around
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let gives_left = operator.token_type == TokenType::Or;
+                match truthiness(&arena[left]) {
+                    Some(truthy) if truthy == gives_left => arena.take(left),
+                    Some(_) => arena.take(right),
+                    None => Expr::Logical {
+                        left,
+                        operator,
+                        right,
+                    },
+                }
+            }
+            expr => expr,
+        }
+    }
+}
+
+/// Whether `expr` is truthy, if it's a literal.
+pub(crate) fn truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(literal) => Some(Value::from(literal).is_truthy()),
+        _ => None,
+    }
+}
+
+/// The literal that evaluates to `value`, if there is one.
+fn literal(value: &Value) -> Option<Literal> {
+    match value {
+        Value::Number(n) => Some(Literal::Number(*n)),
+        Value::Bool(b) => Some(Literal::Bool(*b)),
+        Value::Nil => Some(Literal::Nil),
+        Value::Str(s) => Some(Literal::Str(s.clone())),
+        _ => None,
+    }
+}
+
+/// The line of the first token `stmt` keeps, looking inside it if it
+/// keeps none itself.
+fn first_line(arena: &ExprArena, stmt: &Stmt) -> Option<usize> {
+    stmt.token(arena)
+        .map(|token| token.line)
+        .or_else(|| match stmt {
+            Stmt::Block(statements) => statements.iter().find_map(|stmt| first_line(arena, stmt)),
+            Stmt::If { then_branch, .. } => first_line(arena, then_branch),
+            Stmt::While { body, .. } => first_line(arena, body),
+            _ => None,
+        })
+}
@@ -0,0 +1,1116 @@
+use std::rc::Rc;
+
+use crate::ast::{CatchClause, Expr, ExprArena, ExprId, FunctionDecl, Literal, Stmt, SwitchCase};
+use crate::diagnostic::{Diagnostic, Diagnostics, Note, Phase};
+use crate::scanner::Limits;
+use crate::suggest;
+use crate::token::{Lexeme, Token, TokenType, KEYWORDS};
+
+/// The parser itself
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    /// How many levels deep the parser is, up to `max_depth`
+    depth: usize,
+    /// See `set_max_depth`
+    max_depth: usize,
+    /// Set by the first error in a declaration, so the errors that follow
+    /// from it aren't reported until the parser has resynchronized
+    panic_mode: bool,
+    /// Set once something was nested too deeply to parse, after which the
+    /// rest of the tokens are skipped and no more errors reported
+    gave_up: bool,
+    /// Every syntax error found so far
+    pub diagnostics: Diagnostics,
+    /// The expressions of everything parsed so far, which the trees the
+    /// `parse` methods return refer to. Take it once parsing is done.
+    pub arena: ExprArena,
+    /// How many levels each expression in `arena` takes up, itself and the
+    /// deepest of its operands, indexed like the arena
+    heights: Vec<usize>,
+}
+
+impl Parser {
+    /// A parser over `tokens`, which the scanner always ends with an EOF.
+    /// Tokens from anywhere else get one added if they lack it, since the
+    /// parser stops at EOF and has nothing to point errors at without it.
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        if !tokens
+            .last()
+            .is_some_and(|token| token.token_type == TokenType::Eof)
+        {
+            let (line, column, end) = tokens.last().map_or((1, 1, 0), |token| {
+                (token.line, token.column, token.span.end)
+            });
+            tokens.push(Token {
+                token_type: TokenType::Eof,
+                lexeme: Lexeme::new(&TokenType::Eof, ""),
+                line,
+                column,
+                span: end..end,
+            });
+        }
+        // Every expression but an error takes up a token of its own, so
+        // there's always room for them without growing
+        let tokens_len = tokens.len();
+        let arena = ExprArena::with_capacity(tokens_len);
+        Parser {
+            tokens,
+            current: 0,
+            depth: 0,
+            max_depth: Limits::default().max_nesting_depth,
+            panic_mode: false,
+            gave_up: false,
+            diagnostics: Diagnostics::default(),
+            arena,
+            heights: Vec::with_capacity(tokens_len),
+        }
+    }
+
+    /// Makes statements and expressions nested more than `max_depth` levels
+    /// deep an error, counting every body, block, operand and argument the
+    /// parser has to recurse into, and every link of a chain of operators,
+    /// calls or properties like `a + b + c`, which the parser loops over but
+    /// the tree nests. Everything that walks the tree later recurses into it
+    /// the same way, so this is what keeps a program like a thousand `(`
+    /// from overflowing the stack, provided it has `lox::STACK_SIZE`.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Parses a single expression, or `None` if there were errors. It and
+    /// its operands are in `arena`.
+    pub fn parse(&mut self) -> Option<ExprId> {
+        let expr = self.parse_tolerant();
+
+        if self.diagnostics.has_errors() {
+            None
+        } else {
+            Some(expr)
+        }
+    }
+
+    /// Parses a single expression even if it has errors, with an
+    /// `Expr::Error` wherever an expression couldn't be parsed. The errors
+    /// are in `diagnostics`.
+    pub fn parse_tolerant(&mut self) -> ExprId {
+        self.expression()
+    }
+
+    /// program -> declaration* EOF
+    ///
+    /// The expressions of the statements are in `arena`.
+    pub fn parse_program(&mut self) -> Option<Vec<Stmt>> {
+        let statements = self.parse_program_tolerant();
+
+        if self.diagnostics.has_errors() {
+            None
+        } else {
+            Some(statements)
+        }
+    }
+
+    /// Parses the whole program even if it has errors, for tools that want
+    /// to look at the rest of it. Declarations that couldn't be parsed are
+    /// `Stmt::Error`, expressions `Expr::Error`; the errors themselves are in
+    /// `diagnostics`.
+    pub fn parse_program_tolerant(&mut self) -> Vec<Stmt> {
+        let mut statements = Vec::new();
+
+        while self.peek_token().token_type != TokenType::Eof {
+            statements.push(self.declaration());
+        }
+        statements
+    }
+
+    /// declaration -> classDecl | funDecl | varDecl | importDecl | statement
+    ///
+    /// After a syntax error, skips ahead to the next statement so later
+    /// errors in the file are reported too, and returns a `Stmt::Error` in
+    /// place of the broken declaration.
+    fn declaration(&mut self) -> Stmt {
+        let start = self.current;
+        let first = self.peek_token().clone();
+        let stmt = self
+            .nested(|parser| {
+                if parser.match_any(&[TokenType::Class]).is_some() {
+                    parser.class_declaration()
+                } else if parser.match_any(&[TokenType::Fun]).is_some() {
+                    Stmt::Function(parser.function())
+                } else if parser.match_any(&[TokenType::Var]).is_some() {
+                    parser.var_declaration()
+                } else if let Some(keyword) = parser.match_any(&[TokenType::Import]) {
+                    parser.import_declaration(keyword)
+                } else {
+                    parser.statement()
+                }
+            })
+            .unwrap_or_else(|| self.too_deep_statement());
+
+        if self.panic_mode {
+            // Always move on, or the same error would be found again forever
+            if self.current == start {
+                self.advance();
+            }
+            self.synchronize();
+            return Stmt::Error(first);
+        }
+        stmt
+    }
+
+    /// classDecl -> "class" IDENTIFIER ( "<" IDENTIFIER )?
+    ///              ( "with" IDENTIFIER ( "," IDENTIFIER )* )? "{" method* "}"
+    ///
+    /// `with` is only scanned as a keyword with `--extensions`.
+    fn class_declaration(&mut self) -> Stmt {
+        let name = self.consume(TokenType::Identifier, &msg!("E0225"));
+
+        let superclass = if self.match_any(&[TokenType::Less]).is_some() {
+            let superclass = self.consume(TokenType::Identifier, &msg!("E0231"));
+            if superclass.lexeme == name.lexeme {
+                self.error_at(&superclass, &msg!("E0232"));
+            }
+            Some(self.alloc(Expr::Variable(superclass)))
+        } else {
+            None
+        };
+
+        let mut mixins = Vec::new();
+        if self.match_any(&[TokenType::With]).is_some() {
+            loop {
+                let mixin = self.consume(TokenType::Identifier, &msg!("E0252"));
+                if mixin.lexeme == name.lexeme {
+                    self.error_at(&mixin, &msg!("E0232"));
+                }
+                mixins.push(self.alloc(Expr::Variable(mixin)));
+                if self.match_any(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        let brace = self.consume(TokenType::LeftBrace, &msg!("E0226"));
+
+        let mut methods = Vec::new();
+        let mut class_methods = Vec::new();
+        while !matches!(
+            self.peek_token().token_type,
+            TokenType::RightBrace | TokenType::Eof
+        ) && !self.panic_mode
+        {
+            if self.match_any(&[TokenType::Class]).is_some() {
+                class_methods.push(self.method());
+            } else {
+                methods.push(self.method());
+            }
+        }
+        self.consume_closing(TokenType::RightBrace, &msg!("E0227"), &brace);
+
+        Stmt::Class {
+            name,
+            superclass,
+            mixins,
+            methods,
+            class_methods,
+        }
+    }
+
+    /// method -> "class"? IDENTIFIER ( "(" parameters? ")" )? block
+    ///
+    /// The `class` is already consumed. A method without a parameter list
+    /// is a getter.
+    fn method(&mut self) -> Rc<FunctionDecl> {
+        let name = self.consume(TokenType::Identifier, &msg!("E0230"));
+        if let Some(brace) = self.match_any(&[TokenType::LeftBrace]) {
+            let body = self.block(&brace);
+            return Rc::new(FunctionDecl {
+                name,
+                params: Vec::new(),
+                param_types: Vec::new(),
+                return_type: None,
+                body,
+                is_getter: true,
+            });
+        }
+        self.function_rest(name)
+    }
+
+    /// funDecl -> "fun" function
+    /// function -> IDENTIFIER "(" parameters? ")" ( "->" type )? block
+    /// parameters -> parameter ( "," parameter )*
+    /// parameter -> IDENTIFIER ( ":" type )?
+    fn function(&mut self) -> Rc<FunctionDecl> {
+        let name = self.consume(TokenType::Identifier, &msg!("E0216"));
+        self.function_rest(name)
+    }
+
+    /// The parameters and body of a function or method named `name`.
+    fn function_rest(&mut self, name: Token) -> Rc<FunctionDecl> {
+        let paren = self.consume(TokenType::LeftParen, &msg!("E0217"));
+
+        let mut params = Vec::new();
+        let mut param_types = Vec::new();
+        if self.peek_token().token_type != TokenType::RightParen {
+            loop {
+                if params.len() >= 255 {
+                    // Reported, but the parser isn't confused, so carry on
+                    self.error(&msg!("E0218"));
+                }
+                params.push(self.consume(TokenType::Identifier, &msg!("E0219")));
+                param_types.push(self.annotation(TokenType::Colon));
+                if self.match_any(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume_closing(TokenType::RightParen, &msg!("E0220"), &paren);
+        let return_type = self.annotation(TokenType::Arrow);
+
+        let brace = self.consume(TokenType::LeftBrace, &msg!("E0221"));
+        let body = self.block(&brace);
+        Rc::new(FunctionDecl {
+            name,
+            params,
+            param_types,
+            return_type,
+            body,
+            is_getter: false,
+        })
+    }
+
+    /// importDecl -> "import" STRING ";"
+    fn import_declaration(&mut self, keyword: Token) -> Stmt {
+        let path = self.peek_token().clone();
+        if matches!(path.token_type, TokenType::StringLit(_)) {
+            self.advance();
+        } else {
+            self.error(&msg!("E0239"));
+        }
+        self.consume(TokenType::Semicolon, &msg!("E0240"));
+        Stmt::Import { keyword, path }
+    }
+
+    /// varDecl -> "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";"
+    fn var_declaration(&mut self) -> Stmt {
+        let name = self.consume(TokenType::Identifier, &msg!("E0206"));
+        let annotation = self.annotation(TokenType::Colon);
+
+        let initializer = if self.match_any(&[TokenType::Equal]).is_some() {
+            Some(self.expression())
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, &msg!("E0207"));
+        Stmt::Var {
+            name,
+            annotation,
+            initializer,
+        }
+    }
+
+    /// type -> IDENTIFIER | "nil"
+    ///
+    /// The type after `before`, a `:` or `->`, if there is one. Types are
+    /// only checked by `typecheck`, so any name parses.
+    fn annotation(&mut self, before: TokenType) -> Option<Token> {
+        self.match_any(&[before])?;
+        let token = self.peek_token().clone();
+        if matches!(token.token_type, TokenType::Identifier | TokenType::Nil) {
+            self.advance();
+        } else {
+            self.error(&msg!("E0263"));
+        }
+        Some(token)
+    }
+
+    /// statement -> forStmt | ifStmt | printStmt | returnStmt | whileStmt
+    ///            | switchStmt | tryStmt | throwStmt | block | "break" ";"
+    ///            | "continue" ";" | exprStmt
+    fn statement(&mut self) -> Stmt {
+        if self.match_any(&[TokenType::For]).is_some() {
+            return self.for_statement();
+        }
+
+        if self.match_any(&[TokenType::If]).is_some() {
+            return self.if_statement();
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Print]) {
+            let value = self.expression();
+            self.consume(TokenType::Semicolon, &msg!("E0204"));
+            return Stmt::Print { keyword, value };
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Return]) {
+            return self.return_statement(keyword);
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Break]) {
+            self.consume(TokenType::Semicolon, &msg!("E0236"));
+            return Stmt::Break(keyword);
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Continue]) {
+            self.consume(TokenType::Semicolon, &msg!("E0237"));
+            return Stmt::Continue(keyword);
+        }
+
+        if self.match_any(&[TokenType::While]).is_some() {
+            return self.while_statement();
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Switch]) {
+            return self.switch_statement(keyword);
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Try]) {
+            return self.try_statement(keyword);
+        }
+
+        if let Some(keyword) = self.match_any(&[TokenType::Throw]) {
+            let value = self.expression();
+            self.consume(TokenType::Semicolon, &msg!("E0253"));
+            return Stmt::Throw { keyword, value };
+        }
+
+        if let Some(brace) = self.match_any(&[TokenType::LeftBrace]) {
+            return Stmt::Block(self.block(&brace));
+        }
+
+        self.expression_statement()
+    }
+
+    /// exprStmt -> expression ";"
+    fn expression_statement(&mut self) -> Stmt {
+        let expr = self.expression();
+        if self.peek_token().token_type == TokenType::Semicolon {
+            self.advance();
+        } else if let Some(keyword) = keyword_hint(&self.arena, expr) {
+            // `whle (x) ...` reads as an expression; it was probably a keyword
+            self.error(&format!("{} {}", msg!("E0205"), msg!("E0203", keyword)));
+        } else {
+            self.error(&msg!("E0205"));
+        }
+        Stmt::Expression(expr)
+    }
+
+    /// forStmt -> "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement
+    ///
+    /// There's no loop node for `for`: it's desugared into the equivalent
+    /// block around a `while` loop, which runs the increment itself.
+    fn for_statement(&mut self) -> Stmt {
+        let paren = self.consume(TokenType::LeftParen, &msg!("E0213"));
+
+        let initializer = if self.match_any(&[TokenType::Semicolon]).is_some() {
+            None
+        } else if self.match_any(&[TokenType::Var]).is_some() {
+            Some(self.var_declaration())
+        } else {
+            Some(self.expression_statement())
+        };
+
+        let condition = if self.peek_token().token_type != TokenType::Semicolon {
+            self.expression()
+        } else {
+            self.alloc(Expr::Literal(Literal::Bool(true)))
+        };
+        self.consume(TokenType::Semicolon, &msg!("E0214"));
+
+        let increment = if self.peek_token().token_type != TokenType::RightParen {
+            Some(self.expression())
+        } else {
+            None
+        };
+        self.consume_closing(TokenType::RightParen, &msg!("E0215"), &paren);
+
+        let mut body = Stmt::While {
+            condition,
+            body: Box::new(self.body()),
+            increment,
+        };
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+        body
+    }
+
+    /// returnStmt -> "return" expression? ";"
+    fn return_statement(&mut self, keyword: Token) -> Stmt {
+        let value = if self.peek_token().token_type != TokenType::Semicolon {
+            Some(self.expression())
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, &msg!("E0224"));
+
+        Stmt::Return { keyword, value }
+    }
+
+    /// whileStmt -> "while" "(" expression ")" statement
+    fn while_statement(&mut self) -> Stmt {
+        let paren = self.consume(TokenType::LeftParen, &msg!("E0211"));
+        let condition = self.expression();
+        self.consume_closing(TokenType::RightParen, &msg!("E0212"), &paren);
+        let body = Box::new(self.body());
+
+        Stmt::While {
+            condition,
+            body,
+            increment: None,
+        }
+    }
+
+    /// tryStmt -> "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )?
+    ///
+    /// At least one of `catch` and `finally` is required. Like `throw`,
+    /// only scanned with `--extensions`.
+    fn try_statement(&mut self, keyword: Token) -> Stmt {
+        let brace = self.consume(TokenType::LeftBrace, &msg!("E0254"));
+        let body = self.block(&brace);
+
+        let catch = if self.match_any(&[TokenType::Catch]).is_some() {
+            let paren = self.consume(TokenType::LeftParen, &msg!("E0255"));
+            let name = self.consume(TokenType::Identifier, &msg!("E0256"));
+            self.consume_closing(TokenType::RightParen, &msg!("E0257"), &paren);
+            let brace = self.consume(TokenType::LeftBrace, &msg!("E0258"));
+            let body = self.block(&brace);
+            Some(CatchClause { name, body })
+        } else {
+            None
+        };
+
+        let finally = if self.match_any(&[TokenType::Finally]).is_some() {
+            let brace = self.consume(TokenType::LeftBrace, &msg!("E0259"));
+            Some(self.block(&brace))
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            self.error(&msg!("E0260"));
+        }
+        Stmt::Try {
+            keyword,
+            body,
+            catch,
+            finally,
+        }
+    }
+
+    /// switchStmt -> "switch" "(" expression ")" "{" ( caseClause | defaultClause )* "}"
+    /// caseClause -> "case" expression ":" declaration*
+    /// defaultClause -> "default" ":" declaration*
+    ///
+    /// Only scanned with `--extensions`, since `switch`, `case` and
+    /// `default` are otherwise identifiers.
+    fn switch_statement(&mut self, keyword: Token) -> Stmt {
+        let paren = self.consume(TokenType::LeftParen, &msg!("E0245"));
+        let subject = self.expression();
+        self.consume_closing(TokenType::RightParen, &msg!("E0246"), &paren);
+        let brace = self.consume(TokenType::LeftBrace, &msg!("E0247"));
+
+        let mut cases = Vec::new();
+        let mut default = None;
+        loop {
+            if self.match_any(&[TokenType::Case]).is_some() {
+                let value = self.expression();
+                self.consume(TokenType::Colon, &msg!("E0248"));
+                let body = self.case_body();
+                cases.push(SwitchCase { value, body });
+            } else if let Some(token) = self.match_any(&[TokenType::Default]) {
+                if default.is_some() {
+                    self.error_at(&token, &msg!("E0250"));
+                }
+                self.consume(TokenType::Colon, &msg!("E0249"));
+                default = Some(self.case_body());
+            } else {
+                break;
+            }
+        }
+        self.consume_closing(TokenType::RightBrace, &msg!("E0251"), &brace);
+
+        Stmt::Switch {
+            keyword,
+            subject,
+            cases,
+            default,
+        }
+    }
+
+    /// The declarations of a `case` or `default`, up to the next one or the
+    /// end of the switch.
+    fn case_body(&mut self) -> Vec<Stmt> {
+        let mut statements = Vec::new();
+        while !matches!(
+            self.peek_token().token_type,
+            TokenType::Case | TokenType::Default | TokenType::RightBrace | TokenType::Eof
+        ) && !self.panic_mode
+        {
+            statements.push(self.declaration());
+        }
+        statements
+    }
+
+    /// ifStmt -> "if" "(" expression ")" statement ( "else" statement )?
+    fn if_statement(&mut self) -> Stmt {
+        let paren = self.consume(TokenType::LeftParen, &msg!("E0209"));
+        let condition = self.expression();
+        self.consume_closing(TokenType::RightParen, &msg!("E0210"), &paren);
+
+        let then_branch = Box::new(self.body());
+        // A dangling else belongs to the nearest if, which is this one
+        let else_branch = if self.match_any(&[TokenType::Else]).is_some() {
+            Some(Box::new(self.body()))
+        } else {
+            None
+        };
+
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        }
+    }
+
+    /// block -> "{" declaration* "}"
+    ///
+    /// `brace` is the `{`, already consumed.
+    fn block(&mut self, brace: &Token) -> Vec<Stmt> {
+        let mut statements = Vec::new();
+
+        while !matches!(
+            self.peek_token().token_type,
+            TokenType::RightBrace | TokenType::Eof
+        ) && !self.panic_mode
+        {
+            statements.push(self.declaration());
+        }
+
+        self.consume_closing(TokenType::RightBrace, &msg!("E0208"), brace);
+        statements
+    }
+
+    /// The statement an `if`, `while` or `for` runs, one level further in.
+    fn body(&mut self) -> Stmt {
+        self.nested(Self::statement)
+            .unwrap_or_else(|| self.too_deep_statement())
+    }
+
+    /// expression -> assignment
+    ///
+    /// One level further in than the expression it's part of, if any.
+    fn expression(&mut self) -> ExprId {
+        self.nested(Self::assignment)
+            .unwrap_or_else(|| self.too_deep_expression())
+    }
+
+    /// assignment -> ( call "." )? IDENTIFIER "=" assignment | logic_or
+    ///
+    /// The target is parsed as an ordinary expression and only checked once
+    /// the `=` shows up, since it can be arbitrarily long. Assignment is
+    /// right-associative, so `a = b = c` assigns `c` to both.
+    fn assignment(&mut self) -> ExprId {
+        let expr = self.or();
+
+        if let Some(equals) = self.match_any(&[TokenType::Equal]) {
+            let value = self
+                .nested(Self::assignment)
+                .unwrap_or_else(|| self.too_deep_expression());
+            // The assignment takes the place of its target in the arena
+            self.arena[expr] = match self.arena.take(expr) {
+                Expr::Variable(name) => Expr::Assign { name, value },
+                Expr::Get { object, name } => Expr::Set {
+                    object,
+                    name,
+                    value,
+                },
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value,
+                },
+                target => {
+                    self.error_at(&equals, &msg!("E0229"));
+                    target
+                }
+            };
+            self.measure(expr);
+        }
+        expr
+    }
+
+    /// logic_or -> logic_and ( "or" logic_and )*
+    fn or(&mut self) -> ExprId {
+        let mut expr = self.and();
+        while let Some(operator) = self.match_any(&[TokenType::Or]) {
+            let right = self.and();
+            expr = self.alloc(Expr::Logical {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// logic_and -> equality ( "and" equality )*
+    fn and(&mut self) -> ExprId {
+        let mut expr = self.equality();
+        while let Some(operator) = self.match_any(&[TokenType::And]) {
+            let right = self.equality();
+            expr = self.alloc(Expr::Logical {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// equality -> comparison ( ( "!=" | "==" ) comparison )*
+    fn equality(&mut self) -> ExprId {
+        let mut expr = self.comparison();
+        while let Some(operator) = self.match_any(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+            let right = self.comparison();
+            expr = self.alloc(Expr::Binary {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )*
+    fn comparison(&mut self) -> ExprId {
+        let mut expr = self.term();
+        while let Some(operator) = self.match_any(&[
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ]) {
+            let right = self.term();
+            expr = self.alloc(Expr::Binary {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// term -> factor ( ( "-" | "+" ) factor )*
+    fn term(&mut self) -> ExprId {
+        let mut expr = self.factor();
+        while let Some(operator) = self.match_any(&[TokenType::Minus, TokenType::Plus]) {
+            let right = self.factor();
+            expr = self.alloc(Expr::Binary {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// factor -> unary ( ( "/" | "*" | "%" ) unary )*
+    fn factor(&mut self) -> ExprId {
+        let mut expr = self.unary();
+        while let Some(operator) =
+            self.match_any(&[TokenType::Slash, TokenType::Star, TokenType::Percent])
+        {
+            let right = self.unary();
+            expr = self.alloc(Expr::Binary {
+                left: expr,
+                operator,
+                right,
+            });
+        }
+        expr
+    }
+
+    /// unary -> ( "!" | "-" ) unary | call
+    fn unary(&mut self) -> ExprId {
+        if let Some(operator) = self.match_any(&[TokenType::Bang, TokenType::Minus]) {
+            let right = self
+                .nested(Self::unary)
+                .unwrap_or_else(|| self.too_deep_expression());
+            return self.alloc(Expr::Unary { operator, right });
+        }
+        self.call()
+    }
+
+    /// call -> primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )*
+    fn call(&mut self) -> ExprId {
+        let mut expr = self.primary();
+        while let Some(token) =
+            self.match_any(&[TokenType::LeftParen, TokenType::Dot, TokenType::LeftBracket])
+        {
+            match token.token_type {
+                TokenType::LeftParen => expr = self.finish_call(expr, &token),
+                TokenType::Dot => {
+                    let name = self.consume(TokenType::Identifier, &msg!("E0228"));
+                    expr = self.alloc(Expr::Get { object: expr, name });
+                }
+                _ => {
+                    let index = self.expression();
+                    let bracket =
+                        self.consume_closing(TokenType::RightBracket, &msg!("E0241"), &token);
+                    expr = self.alloc(Expr::Index {
+                        object: expr,
+                        bracket,
+                        index,
+                    });
+                }
+            }
+        }
+        expr
+    }
+
+    /// arguments -> expression ( "," expression )*
+    ///
+    /// `open` is the call's `(`, already consumed.
+    fn finish_call(&mut self, callee: ExprId, open: &Token) -> ExprId {
+        let mut arguments = Vec::new();
+        if self.peek_token().token_type != TokenType::RightParen {
+            loop {
+                if arguments.len() >= 255 {
+                    self.error(&msg!("E0222"));
+                }
+                arguments.push(self.expression());
+                if self.match_any(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume_closing(TokenType::RightParen, &msg!("E0223"), open);
+
+        self.alloc(Expr::Call {
+            callee,
+            paren,
+            arguments,
+        })
+    }
+
+    /// primary -> "true" | "false" | "nil" | "this" | NUMBER | STRING | IDENTIFIER
+    ///          | "(" expression ")" | "[" arguments? "]" | "{" entries? "}"
+    ///          | "super" "." IDENTIFIER
+    /// entries -> expression ":" expression ( "," expression ":" expression )*
+    ///
+    /// A `{` only starts a map here, in an expression: at the start of a
+    /// statement it's a block.
+    fn primary(&mut self) -> ExprId {
+        // Peek current token
+        let token = self.peek_token();
+
+        let expr = match token.token_type {
+            TokenType::True => {
+                self.advance();
+                Expr::Literal(Literal::Bool(true))
+            }
+            TokenType::False => {
+                self.advance();
+                Expr::Literal(Literal::Bool(false))
+            }
+            TokenType::Nil => {
+                self.advance();
+                Expr::Literal(Literal::Nil)
+            }
+            TokenType::Number(n) => {
+                self.advance();
+                Expr::Literal(Literal::Number(n))
+            }
+            TokenType::StringLit(ref s) => {
+                // clone s
+                let lit_string = s.clone();
+                self.advance();
+                Expr::Literal(Literal::Str(lit_string.into()))
+            }
+            TokenType::LeftParen => {
+                let paren = token.clone();
+                self.advance(); // consume '('
+                let expr = self.expression();
+                self.consume_closing(TokenType::RightParen, &msg!("E0202"), &paren);
+                Expr::Grouping(expr)
+            }
+            TokenType::LeftBracket => {
+                let bracket = token.clone();
+                self.advance();
+                let mut elements = Vec::new();
+                if self.peek_token().token_type != TokenType::RightBracket {
+                    loop {
+                        elements.push(self.expression());
+                        if self.match_any(&[TokenType::Comma]).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.consume_closing(TokenType::RightBracket, &msg!("E0242"), &bracket);
+                Expr::List { bracket, elements }
+            }
+            TokenType::LeftBrace => {
+                let brace = token.clone();
+                self.advance();
+                let mut entries = Vec::new();
+                if self.peek_token().token_type != TokenType::RightBrace {
+                    loop {
+                        let key = self.expression();
+                        self.consume(TokenType::Colon, &msg!("E0243"));
+                        entries.push((key, self.expression()));
+                        if self.match_any(&[TokenType::Comma]).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.consume_closing(TokenType::RightBrace, &msg!("E0244"), &brace);
+                Expr::Map { brace, entries }
+            }
+            TokenType::This => {
+                let keyword = token.clone();
+                self.advance();
+                Expr::This(keyword)
+            }
+            TokenType::Super => {
+                let keyword = token.clone();
+                self.advance();
+                self.consume(TokenType::Dot, &msg!("E0233"));
+                let method = self.consume(TokenType::Identifier, &msg!("E0234"));
+                Expr::Super { keyword, method }
+            }
+            TokenType::Identifier => {
+                let name = token.clone();
+                self.advance();
+                Expr::Variable(name)
+            }
+            _ => {
+                // Error: we expected an expression but got an operator, a right paren or end
+                let token = token.clone();
+                self.error(&msg!("E0201"));
+                // Return something to keep going
+                Expr::Error(token)
+            }
+        };
+        self.alloc(expr)
+    }
+
+    /// Adds `expr` to the arena, checking it doesn't nest too deeply.
+    fn alloc(&mut self, expr: Expr) -> ExprId {
+        let id = self.arena.alloc(expr);
+        self.heights.resize(self.arena.len(), 0);
+        self.measure(id);
+        id
+    }
+
+    /// Records how many levels the expression at `id` takes up, and reports
+    /// it as nested too deeply if that reaches past `max_depth` from where
+    /// the parser is. The check at the top of an expression covers it all;
+    /// the ones further in just find the problem sooner.
+    fn measure(&mut self, id: ExprId) {
+        let height = |operand: &ExprId| self.heights[operand.index()];
+        let operands = match &self.arena[id] {
+            Expr::Literal(_)
+            | Expr::Variable(_)
+            | Expr::This(_)
+            | Expr::Super { .. }
+            | Expr::Error(_) => 0,
+            Expr::Grouping(inner) => height(inner),
+            Expr::Assign { value, .. } => height(value),
+            Expr::Unary { right, .. } => height(right),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                height(left).max(height(right))
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => arguments
+                .iter()
+                .map(height)
+                .fold(height(callee), usize::max),
+            Expr::Get { object, .. } => height(object),
+            Expr::Set { object, value, .. } => height(object).max(height(value)),
+            Expr::List { elements, .. } => elements.iter().map(height).max().unwrap_or(0),
+            Expr::Map { entries, .. } => entries
+                .iter()
+                .map(|(key, value)| height(key).max(height(value)))
+                .max()
+                .unwrap_or(0),
+            Expr::Index { object, index, .. } => height(object).max(height(index)),
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => height(object).max(height(index)).max(height(value)),
+        };
+        self.heights[id.index()] = operands + 1;
+        // The expression itself is at `depth`, its operands below
+        if self.depth + operands > self.max_depth && !self.gave_up {
+            self.too_deep("E0261");
+        }
+    }
+
+    /// What `parse` parses one level deeper than the parser is, or `None`
+    /// without parsing anything if that's deeper than `max_depth`.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if self.depth >= self.max_depth {
+            return None;
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        Some(result)
+    }
+
+    /// Reports that the statement at the current token is nested too deeply
+    /// to parse, and returns a `Stmt::Error` for it.
+    fn too_deep_statement(&mut self) -> Stmt {
+        let token = self.peek_token().clone();
+        self.too_deep("E0262");
+        Stmt::Error(token)
+    }
+
+    /// Reports that the expression at the current token is nested too
+    /// deeply to parse, and returns an `Expr::Error` for it.
+    fn too_deep_expression(&mut self) -> ExprId {
+        let token = self.peek_token().clone();
+        self.too_deep("E0261");
+        self.alloc(Expr::Error(token))
+    }
+
+    /// Reports the error `code` at the current token and gives up on the
+    /// rest. Resynchronizing would only land somewhere in the middle of
+    /// whatever is nested so deeply, and find it too deep again and again,
+    /// or missing all the closing tokens skipped.
+    fn too_deep(&mut self, code: &str) {
+        self.error(&msg!(code));
+        self.gave_up = true;
+        self.current = self.tokens.len() - 1;
+    }
+
+    /// Records an error at the current token for the caller to report.
+    fn error(&mut self, msg: &str) {
+        let token = self.peek_token().clone();
+        self.error_at(&token, msg);
+    }
+
+    /// Records an error at `token`, naming it. Ignored while already
+    /// recovering from an earlier error.
+    fn error_at(&mut self, token: &Token, msg: &str) {
+        self.report(Diagnostic::at_token(Phase::Parse, token, msg.to_string()));
+    }
+
+    /// Records `diagnostic` unless already recovering from an earlier error
+    /// or given up, and starts recovering.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.panic_mode || self.gave_up {
+            return;
+        }
+        self.panic_mode = true;
+        self.diagnostics.report(diagnostic);
+    }
+
+    /// Discards tokens until the start of what's probably the next
+    /// statement: just past a `;`, or at a keyword that begins a statement.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.peek_token().token_type != TokenType::Eof {
+            if self.current > 0 && self.tokens[self.current - 1].token_type == TokenType::Semicolon
+            {
+                return;
+            }
+            match self.peek_token().token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::Import
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Switch
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Return the current token
+    fn peek_token(&self) -> &Token {
+        if self.current >= self.tokens.len() {
+            // In case we're out of range
+            &self.tokens[self.tokens.len() - 1]
+        } else {
+            &self.tokens[self.current]
+        }
+    }
+
+    /// Advance the parser by one token
+    fn advance(&mut self) {
+        if self.current < self.tokens.len() {
+            self.current += 1;
+        }
+    }
+
+    /// Consume a token of the expected type, or record `msg` as an error.
+    /// Either way returns the current token so parsing can carry on.
+    fn consume(&mut self, token_type: TokenType, msg: &str) -> Token {
+        let token = self.peek_token().clone();
+        if token.token_type == token_type {
+            self.advance();
+        } else {
+            self.error(msg);
+        }
+        token
+    }
+
+    /// Like `consume`, for the bracket closing `open`. A missing one is
+    /// reported with a note pointing back at `open`.
+    fn consume_closing(&mut self, token_type: TokenType, msg: &str, open: &Token) -> Token {
+        let token = self.peek_token().clone();
+        if token.token_type == token_type {
+            self.advance();
+        } else {
+            let note = Note::at_token(open, msg!("E0238", open.lexeme));
+            self.report(
+                Diagnostic::at_token(Phase::Parse, &token, msg.to_string()).with_note(note),
+            );
+        }
+        token
+    }
+
+    /// If the current token has one of the given types, consume and return it
+    fn match_any(&mut self, types: &[TokenType]) -> Option<Token> {
+        let token = self.peek_token();
+        if types.contains(&token.token_type) {
+            let token = token.clone();
+            self.advance();
+            Some(token)
+        } else {
+            None
+        }
+    }
+}
+
+/// If a statement that failed to parse is just a name close to a keyword,
+/// or a call to one, returns that keyword: `retrun` -> `return`, or
+/// `whle (x)` -> `while`, which parses as calling `whle`.
+fn keyword_hint(arena: &ExprArena, expr: ExprId) -> Option<&'static str> {
+    let name = match &arena[expr] {
+        Expr::Variable(name) => name,
+        Expr::Call { callee, .. } => match &arena[*callee] {
+            Expr::Variable(name) => name,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let keywords = KEYWORDS.iter().map(|&(keyword, _)| keyword);
+    suggest::closest(&name.lexeme, keywords)
+}
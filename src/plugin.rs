@@ -0,0 +1,261 @@
+//! Natives from shared libraries, loaded with `--plugin=path`. A plugin
+//! exports `lox_plugin_init`, an `extern "C"` function that's given a
+//! `PluginApi` and calls its `define` for each native it adds. Natives get
+//! and return `PluginValue`s rather than this crate's Rust types, so a
+//! plugin can be written in C, or anything with a C ABI, and keeps working
+//! across builds of the interpreter as long as `API_VERSION` stays the
+//! same. `examples/plugin.rs` is one written in Rust.
+//!
+//! The library stays loaded until the process exits, since the natives it
+//! defined can be called until then.
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::path::Path;
+use std::{mem, ptr, slice};
+
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+
+/// The name of the function a plugin exports.
+pub const INIT_SYMBOL: &str = "lox_plugin_init";
+
+/// Changes whenever any of the types here do, so a plugin built for other
+/// ones can refuse to load rather than misread them.
+pub const API_VERSION: u32 = 1;
+
+/// `PluginValue::kind` for `nil`
+pub const KIND_NIL: u32 = 0;
+pub const KIND_BOOL: u32 = 1;
+pub const KIND_NUMBER: u32 = 2;
+pub const KIND_STRING: u32 = 3;
+/// For any other value, like a list or an instance, given as `print` shows
+/// it. Only in arguments.
+pub const KIND_OTHER: u32 = 4;
+
+/// A Lox value as a plugin sees it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginValue {
+    /// One of the `KIND_` constants
+    pub kind: u32,
+    /// The number, or 1 for `true` and 0 for `false`
+    pub number: f64,
+    /// UTF-8 text, not nul-terminated, for `KIND_STRING` and `KIND_OTHER`
+    pub string: *const u8,
+    pub len: usize,
+}
+
+impl PluginValue {
+    pub const NIL: PluginValue = PluginValue {
+        kind: KIND_NIL,
+        number: 0.0,
+        string: ptr::null(),
+        len: 0,
+    };
+
+    /// `text` as a string, which is only valid while `text` is.
+    pub fn string(text: &str) -> PluginValue {
+        PluginValue {
+            kind: KIND_STRING,
+            string: text.as_ptr(),
+            len: text.len(),
+            ..PluginValue::NIL
+        }
+    }
+
+    /// The text of a `KIND_STRING` or `KIND_OTHER` value.
+    ///
+    /// # Safety
+    ///
+    /// `string` has to point to `len` bytes of UTF-8, or be null.
+    pub unsafe fn text(&self) -> Option<&str> {
+        if self.string.is_null() {
+            return Some("");
+        }
+        std::str::from_utf8(slice::from_raw_parts(self.string, self.len)).ok()
+    }
+}
+
+/// A native a plugin defines. It gets the call's `count` arguments, which
+/// are only valid until it returns, and sets `result`. It returns `false`
+/// for a runtime error, with `result` the string saying what went wrong.
+/// A string in `result` is copied as soon as it returns, so it only has to
+/// live until then.
+pub type PluginFn = unsafe extern "C" fn(
+    data: *mut c_void,
+    args: *const PluginValue,
+    count: usize,
+    result: *mut PluginValue,
+) -> bool;
+
+/// What `lox_plugin_init` is given.
+#[repr(C)]
+pub struct PluginApi {
+    /// `API_VERSION`
+    pub version: u32,
+    /// Passed back to `define`
+    pub host: *mut c_void,
+    /// Defines the global `name`, a nul-terminated UTF-8 string, as a native
+    /// taking `arity` arguments that calls `function` with `data`. Only
+    /// while `lox_plugin_init` runs.
+    pub define: unsafe extern "C" fn(
+        host: *mut c_void,
+        name: *const c_char,
+        arity: usize,
+        function: PluginFn,
+        data: *mut c_void,
+    ),
+}
+
+/// The function a plugin exports as `lox_plugin_init`. It returns 0 if it
+/// loaded, and anything else if it didn't, say for an `API_VERSION` it
+/// wasn't built for.
+pub type PluginInit = unsafe extern "C" fn(api: *const PluginApi) -> c_int;
+
+/// Loads the plugin at `path`, relative to the working directory rather
+/// than searched for, and defines its natives in `interpreter`.
+pub fn load(interpreter: &mut Interpreter, path: &Path) -> Result<(), String> {
+    let fail = |reason: String| msg!("E0067", path.display(), reason);
+    let address = sys::open(&Path::new(".").join(path), INIT_SYMBOL).map_err(fail)?;
+    // SAFETY: a plugin's `lox_plugin_init` is a `PluginInit`; trusting the
+    // library to be a plugin is what `--plugin` asks for
+    let init = unsafe { mem::transmute::<*mut c_void, PluginInit>(address) };
+    unsafe { register(interpreter, init) }.map_err(fail)
+}
+
+/// Runs a plugin's `init` function, defining the natives it asks for in
+/// `interpreter`. For hosts linking a plugin in rather than loading it.
+///
+/// # Safety
+///
+/// `init`, and the natives it defines, have to keep to the contract of
+/// the types here.
+pub unsafe fn register(interpreter: &mut Interpreter, init: PluginInit) -> Result<(), String> {
+    let api = PluginApi {
+        version: API_VERSION,
+        host: (interpreter as *mut Interpreter).cast(),
+        define,
+    };
+    match init(&api) {
+        0 => Ok(()),
+        code => Err(msg!("E0068", INIT_SYMBOL, code)),
+    }
+}
+
+unsafe extern "C" fn define(
+    host: *mut c_void,
+    name: *const c_char,
+    arity: usize,
+    function: PluginFn,
+    data: *mut c_void,
+) {
+    let interpreter = &mut *host.cast::<Interpreter>();
+    let name = CStr::from_ptr(name).to_string_lossy();
+    interpreter.define_native(&name, arity, move |args| call(function, data, args));
+}
+
+fn call(function: PluginFn, data: *mut c_void, args: &[Value]) -> Result<Value, String> {
+    // The text of strings and other values, kept until the call returns
+    let texts: Vec<String> = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Nil | Value::Bool(_) | Value::Number(_) => String::new(),
+            Value::Str(s) => s.as_str().to_string(),
+            other => other.to_string(),
+        })
+        .collect();
+    let values: Vec<PluginValue> = args
+        .iter()
+        .zip(&texts)
+        .map(|(arg, text)| match arg {
+            Value::Nil => PluginValue::NIL,
+            Value::Bool(b) => PluginValue {
+                kind: KIND_BOOL,
+                number: f64::from(u8::from(*b)),
+                ..PluginValue::NIL
+            },
+            Value::Number(n) => PluginValue {
+                kind: KIND_NUMBER,
+                number: *n,
+                ..PluginValue::NIL
+            },
+            Value::Str(_) => PluginValue::string(text),
+            _ => PluginValue {
+                kind: KIND_OTHER,
+                ..PluginValue::string(text)
+            },
+        })
+        .collect();
+
+    let mut result = PluginValue::NIL;
+    // SAFETY: `register`'s caller vouched for the plugin's natives
+    let ok = unsafe { function(data, values.as_ptr(), values.len(), &mut result) };
+    let value = match result.kind {
+        KIND_NIL => Value::Nil,
+        KIND_BOOL => Value::Bool(result.number != 0.0),
+        KIND_NUMBER => Value::Number(result.number),
+        KIND_STRING => {
+            let text = unsafe { result.text() }.ok_or_else(|| msg!("E0344"))?;
+            Value::Str(text.into())
+        }
+        kind => return Err(msg!("E0345", kind)),
+    };
+    if ok {
+        Ok(value)
+    } else {
+        Err(value.to_string())
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// Resolves every symbol on loading, so a missing one fails `--plugin`
+    /// rather than a call
+    const RTLD_NOW: c_int = 2;
+
+    extern "C" {
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *const c_char;
+    }
+
+    /// Loads the library at `path` and finds `symbol` in it, or says why it
+    /// couldn't. The library is never unloaded.
+    pub fn open(path: &Path, symbol: &str) -> Result<*mut c_void, String> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|err| err.to_string())?;
+        let symbol = CString::new(symbol).map_err(|err| err.to_string())?;
+        unsafe {
+            let handle = dlopen(path.as_ptr(), RTLD_NOW);
+            if handle.is_null() {
+                return Err(error());
+            }
+            let address = dlsym(handle, symbol.as_ptr());
+            if address.is_null() {
+                return Err(error());
+            }
+            Ok(address)
+        }
+    }
+
+    unsafe fn error() -> String {
+        let message = dlerror();
+        if message.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(message).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::ffi::c_void;
+    use std::path::Path;
+
+    pub fn open(_: &Path, _: &str) -> Result<*mut c_void, String> {
+        Err(msg!("E0069"))
+    }
+}
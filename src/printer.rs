@@ -0,0 +1,472 @@
+use std::fmt::Write;
+
+use crate::ast::{Expr, ExprArena, ExprId, FunctionDecl, Literal, Stmt};
+use crate::format::format_float_value;
+use crate::token::Token;
+use crate::visit::{walk_expr, walk_stmt, Visitor};
+
+/// How `print-ast` lays out the tree.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum AstStyle {
+    /// One s-expression per statement, e.g. `(print (+ 1.0 2.0))`
+    #[default]
+    Sexpr,
+    /// One node per line, children indented under their parent
+    Indent,
+}
+
+/// A small "AST printer" that prints expressions in a Lisp-like style:
+/// - `true`, `false`, `nil` for booleans/nil
+/// - e.g. `123.0` for numbers
+/// - e.g. `"hello"` for strings
+/// - `(group <expr>)` or just `(<expr>)` if you prefer
+/// - `(<op> <operands>)` for unary, binary and logical expressions, e.g. `(- 1.0)`
+/// - `(call <callee> <arguments>)`, `(. <object> <name>)` and `(= <target> <value>)`
+/// - `(list <elements>)`, `(map (<key> <value>)...)` and `(index <object> <index>)`
+pub fn print_ast(arena: &ExprArena, expr: &Expr) -> String {
+    match expr {
+        Expr::Literal(value) => literal(value),
+        Expr::Grouping(sub) => format!("(group {})", print_ast(arena, &arena[*sub])),
+        Expr::Variable(name) => name.lexeme.to_string(),
+        Expr::Assign { name, value } => {
+            format!("(= {} {})", name.lexeme, print_ast(arena, &arena[*value]))
+        }
+        Expr::Unary { operator, right } => {
+            format!("({} {})", operator.lexeme, print_ast(arena, &arena[*right]))
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let mut call = format!("(call {}", print_ast(arena, &arena[*callee]));
+            for argument in arguments {
+                call.push(' ');
+                call.push_str(&print_ast(arena, &arena[*argument]));
+            }
+            call + ")"
+        }
+        Expr::Get { object, name } => {
+            format!("(. {} {})", print_ast(arena, &arena[*object]), name.lexeme)
+        }
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => format!(
+            "(= (. {} {}) {})",
+            print_ast(arena, &arena[*object]),
+            name.lexeme,
+            print_ast(arena, &arena[*value])
+        ),
+        Expr::List { elements, .. } => list(
+            "list",
+            elements
+                .iter()
+                .map(|&element| print_ast(arena, &arena[element])),
+        ),
+        Expr::Map { entries, .. } => list(
+            "map",
+            entries.iter().map(|(key, value)| {
+                format!(
+                    "({} {})",
+                    print_ast(arena, &arena[*key]),
+                    print_ast(arena, &arena[*value])
+                )
+            }),
+        ),
+        Expr::Index { object, index, .. } => {
+            format!(
+                "(index {} {})",
+                print_ast(arena, &arena[*object]),
+                print_ast(arena, &arena[*index])
+            )
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => format!(
+            "(= (index {} {}) {})",
+            print_ast(arena, &arena[*object]),
+            print_ast(arena, &arena[*index]),
+            print_ast(arena, &arena[*value])
+        ),
+        Expr::This(_) => "this".to_string(),
+        Expr::Super { method, .. } => format!("(super {})", method.lexeme),
+        Expr::Error(_) => "(error)".to_string(),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        }
+        | Expr::Logical {
+            left,
+            operator,
+            right,
+        } => format!(
+            "({} {} {})",
+            operator.lexeme,
+            print_ast(arena, &arena[*left]),
+            print_ast(arena, &arena[*right])
+        ),
+    }
+}
+
+/// Prints a statement as an s-expression, in the same style as `print_ast`.
+pub fn print_stmt(arena: &ExprArena, stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Expression(expr) => format!("(expr {})", print_ast(arena, &arena[*expr])),
+        Stmt::Print { value, .. } => format!("(print {})", print_ast(arena, &arena[*value])),
+        Stmt::Var {
+            name, initializer, ..
+        } => match initializer {
+            Some(initializer) => format!(
+                "(var {} {})",
+                name.lexeme,
+                print_ast(arena, &arena[*initializer])
+            ),
+            None => format!("(var {})", name.lexeme),
+        },
+        Stmt::Block(statements) => list(
+            "block",
+            statements.iter().map(|stmt| print_stmt(arena, stmt)),
+        ),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => match else_branch {
+            Some(else_branch) => format!(
+                "(if {} {} {})",
+                print_ast(arena, &arena[*condition]),
+                print_stmt(arena, then_branch),
+                print_stmt(arena, else_branch)
+            ),
+            None => format!(
+                "(if {} {})",
+                print_ast(arena, &arena[*condition]),
+                print_stmt(arena, then_branch)
+            ),
+        },
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => match increment {
+            Some(increment) => format!(
+                "(while {} {} {})",
+                print_ast(arena, &arena[*condition]),
+                print_stmt(arena, body),
+                print_ast(arena, &arena[*increment])
+            ),
+            None => format!(
+                "(while {} {})",
+                print_ast(arena, &arena[*condition]),
+                print_stmt(arena, body)
+            ),
+        },
+        Stmt::Break(_) => "(break)".to_string(),
+        Stmt::Continue(_) => "(continue)".to_string(),
+        Stmt::Function(function) => print_function(arena, function),
+        Stmt::Class {
+            name,
+            superclass,
+            mixins,
+            methods,
+            class_methods,
+        } => {
+            let head = class_head(arena, "class", name, *superclass, mixins);
+            let class_methods = class_methods
+                .iter()
+                .map(|method| format!("(class {})", print_function(arena, method)));
+            let methods = methods.iter().map(|method| print_function(arena, method));
+            list(&head, methods.chain(class_methods))
+        }
+        Stmt::Return { value, .. } => match value {
+            Some(value) => format!("(return {})", print_ast(arena, &arena[*value])),
+            None => "(return)".to_string(),
+        },
+        Stmt::Import { path, .. } => format!("(import {})", path.lexeme),
+        Stmt::Throw { value, .. } => format!("(throw {})", print_ast(arena, &arena[*value])),
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+            ..
+        } => {
+            let body = list("block", body.iter().map(|stmt| print_stmt(arena, stmt)));
+            let catch = catch.iter().map(|catch| {
+                let head = format!("catch {}", catch.name.lexeme);
+                list(&head, catch.body.iter().map(|stmt| print_stmt(arena, stmt)))
+            });
+            let finally = finally.iter().map(|finally| {
+                list(
+                    "finally",
+                    finally.iter().map(|stmt| print_stmt(arena, stmt)),
+                )
+            });
+            list("try", [body].into_iter().chain(catch).chain(finally))
+        }
+        Stmt::Switch {
+            subject,
+            cases,
+            default,
+            ..
+        } => {
+            let cases = cases.iter().map(|case| {
+                let head = format!("case {}", print_ast(arena, &arena[case.value]));
+                list(&head, case.body.iter().map(|stmt| print_stmt(arena, stmt)))
+            });
+            let default = default
+                .iter()
+                .map(|body| list("default", body.iter().map(|stmt| print_stmt(arena, stmt))));
+            list(
+                &format!("switch {}", print_ast(arena, &arena[*subject])),
+                cases.chain(default),
+            )
+        }
+        Stmt::Error(_) => "(error)".to_string(),
+    }
+}
+
+/// `class A < B with C, D`, starting with `keyword`
+fn class_head(
+    arena: &ExprArena,
+    keyword: &str,
+    name: &Token,
+    superclass: Option<ExprId>,
+    mixins: &[ExprId],
+) -> String {
+    let mut head = format!("{} {}", keyword, name.lexeme);
+    if let Some(superclass) = superclass {
+        write!(head, " < {}", print_ast(arena, &arena[superclass])).unwrap();
+    }
+    if !mixins.is_empty() {
+        let mixins: Vec<String> = mixins
+            .iter()
+            .map(|&mixin| print_ast(arena, &arena[mixin]))
+            .collect();
+        write!(head, " with {}", mixins.join(", ")).unwrap();
+    }
+    head
+}
+
+/// `(fun name (params) body...)`, or `(getter name body...)`
+fn print_function(arena: &ExprArena, function: &FunctionDecl) -> String {
+    if function.is_getter {
+        let head = format!("getter {}", function.name.lexeme);
+        return list(
+            &head,
+            function.body.iter().map(|stmt| print_stmt(arena, stmt)),
+        );
+    }
+    let params: Vec<&str> = function
+        .params
+        .iter()
+        .map(|param| param.lexeme.as_str())
+        .collect();
+    let head = format!("fun {} ({})", function.name.lexeme, params.join(" "));
+    list(
+        &head,
+        function.body.iter().map(|stmt| print_stmt(arena, stmt)),
+    )
+}
+
+/// `(head item item ...)`
+fn list(head: &str, items: impl Iterator<Item = String>) -> String {
+    let mut list = format!("({}", head);
+    for item in items {
+        list.push(' ');
+        list.push_str(&item);
+    }
+    list + ")"
+}
+
+/// Prints statements as an indented tree, one node per line with its
+/// children two spaces further in.
+pub fn print_tree(arena: &ExprArena, statements: &[Stmt]) -> String {
+    let mut printer = TreePrinter::default();
+    printer.visit_stmts(arena, statements);
+    printer.tree
+}
+
+/// Writes the tree for `print_tree`.
+#[derive(Default)]
+struct TreePrinter {
+    tree: String,
+    /// How many nodes the one being written is inside
+    depth: usize,
+}
+
+impl TreePrinter {
+    /// Writes one line of the tree at the current depth.
+    fn node(&mut self, label: &str) {
+        writeln!(
+            self.tree,
+            "{:indent$}{}",
+            "",
+            label,
+            indent = self.depth * 2
+        )
+        .unwrap();
+    }
+
+    /// Writes a node, then what `children` writes one level further in.
+    fn nested(&mut self, label: &str, children: impl FnOnce(&mut Self)) {
+        self.node(label);
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    /// `prefix` is `Class ` for a class method.
+    fn function(&mut self, arena: &ExprArena, function: &FunctionDecl, prefix: &str) {
+        let params: Vec<&str> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let label = if function.is_getter {
+            format!("{}Getter {}", prefix, function.name.lexeme)
+        } else {
+            format!(
+                "{}Function {}({})",
+                prefix,
+                function.name.lexeme,
+                params.join(", ")
+            )
+        };
+        self.nested(&label, |printer| printer.visit_stmts(arena, &function.body));
+    }
+}
+
+impl Visitor for TreePrinter {
+    fn visit_stmt(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        let walk = |printer: &mut Self| walk_stmt(printer, arena, stmt);
+        match stmt {
+            Stmt::Expression(_) => self.nested("Expression", walk),
+            Stmt::Print { .. } => self.nested("Print", walk),
+            Stmt::Var { name, .. } => self.nested(&format!("Var {}", name.lexeme), walk),
+            Stmt::Block(_) => self.nested("Block", walk),
+            // `Else` is the `If`'s sibling rather than its child
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.nested("If", |printer| {
+                    printer.visit_expr(arena, &arena[*condition]);
+                    printer.visit_stmt(arena, then_branch);
+                });
+                if let Some(else_branch) = else_branch {
+                    self.nested("Else", |printer| printer.visit_stmt(arena, else_branch));
+                }
+            }
+            Stmt::While { .. } => self.nested("While", walk),
+            Stmt::Break(_) => self.node("Break"),
+            Stmt::Continue(_) => self.node("Continue"),
+            Stmt::Function(function) => self.visit_function(arena, function),
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                let label = class_head(arena, "Class", name, *superclass, mixins);
+                self.nested(&label, |printer| {
+                    for method in methods {
+                        printer.function(arena, method, "");
+                    }
+                    for method in class_methods {
+                        printer.function(arena, method, "Class ");
+                    }
+                });
+            }
+            Stmt::Return { .. } => self.nested("Return", walk),
+            Stmt::Import { path, .. } => self.node(&format!("Import {}", path.lexeme)),
+            Stmt::Throw { .. } => self.nested("Throw", walk),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.nested("Try", |printer| printer.visit_stmts(arena, body));
+                if let Some(catch) = catch {
+                    let label = format!("Catch {}", catch.name.lexeme);
+                    self.nested(&label, |printer| printer.visit_stmts(arena, &catch.body));
+                }
+                if let Some(finally) = finally {
+                    self.nested("Finally", |printer| printer.visit_stmts(arena, finally));
+                }
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                default,
+                ..
+            } => self.nested("Switch", |printer| {
+                printer.visit_expr(arena, &arena[*subject]);
+                for case in cases {
+                    printer.nested("Case", |printer| {
+                        printer.visit_expr(arena, &arena[case.value]);
+                        printer.visit_stmts(arena, &case.body);
+                    });
+                }
+                if let Some(default) = default {
+                    printer.nested("Default", |printer| printer.visit_stmts(arena, default));
+                }
+            }),
+            Stmt::Error(_) => self.node("Error"),
+        }
+    }
+
+    fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+        let label = match expr {
+            Expr::Literal(value) => format!("Literal {}", literal(value)),
+            Expr::Grouping(_) => "Grouping".to_string(),
+            Expr::Variable(name) => format!("Variable {}", name.lexeme),
+            Expr::Assign { name, .. } => format!("Assign {}", name.lexeme),
+            Expr::Unary { operator, .. } => format!("Unary {}", operator.lexeme),
+            Expr::Binary { operator, .. } => format!("Binary {}", operator.lexeme),
+            Expr::Logical { operator, .. } => format!("Logical {}", operator.lexeme),
+            Expr::Call { .. } => "Call".to_string(),
+            Expr::Get { name, .. } => format!("Get {}", name.lexeme),
+            Expr::Set { name, .. } => format!("Set {}", name.lexeme),
+            Expr::List { .. } => "List".to_string(),
+            Expr::Map { .. } => "Map".to_string(),
+            Expr::Index { .. } => "Index".to_string(),
+            Expr::SetIndex { .. } => "SetIndex".to_string(),
+            Expr::This(_) => "This".to_string(),
+            Expr::Super { method, .. } => format!("Super {}", method.lexeme),
+            Expr::Error(_) => "Error".to_string(),
+        };
+        match expr {
+            // Each key and its value under an `Entry`
+            Expr::Map { entries, .. } => self.nested(&label, |printer| {
+                for (key, value) in entries {
+                    printer.nested("Entry", |printer| {
+                        printer.visit_expr(arena, &arena[*key]);
+                        printer.visit_expr(arena, &arena[*value]);
+                    });
+                }
+            }),
+            _ => self.nested(&label, |printer| walk_expr(printer, arena, expr)),
+        }
+    }
+
+    fn visit_function(&mut self, arena: &ExprArena, function: &FunctionDecl) {
+        self.function(arena, function, "");
+    }
+}
+
+/// A literal as it appears in source, with numbers always showing a fraction.
+fn literal(value: &Literal) -> String {
+    match value {
+        Literal::Bool(b) => b.to_string(), // "true" or "false"
+        Literal::Nil => "nil".to_string(),
+        Literal::Number(n) => format_float_value(*n), // e.g. "3.0"
+        Literal::Str(s) => format!("\"{}\"", s),
+    }
+}
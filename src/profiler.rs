@@ -0,0 +1,162 @@
+//! A profiler for `run --profile`. The interpreter tells it when each call
+//! to a Lox function starts and ends, and it adds up how many calls each
+//! function had and how long they took, with and without the calls they
+//! made, and how long was spent in each distinct stack of calls.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::symbol::Symbol;
+
+/// What the program as a whole is called, as the outermost frame
+const SCRIPT: &str = "<script>";
+
+/// The calls a program has made so far, see `Interpreter::set_profile`.
+pub struct Profiler {
+    /// The calls in progress, starting with the program itself
+    stack: Vec<Frame>,
+    functions: HashMap<Symbol, FunctionTimes>,
+    /// The time spent in each stack of calls, outermost first, and not in
+    /// a call inside it
+    stacks: HashMap<Vec<Symbol>, Duration>,
+    /// The functions of `stack`, as the key into `stacks`
+    names: Vec<Symbol>,
+}
+
+struct Frame {
+    started: Instant,
+    /// How long the calls it made took
+    in_calls: Duration,
+}
+
+/// The calls to one function.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionTimes {
+    pub calls: usize,
+    /// The time from each call to its return. A recursive call is counted
+    /// in the call outside it, not again.
+    pub total: Duration,
+    /// The time in the function's own code, not in the calls it made
+    pub own: Duration,
+    /// How many calls to it are in progress
+    active: usize,
+}
+
+impl Profiler {
+    /// A profiler whose program starts now.
+    pub fn new() -> Self {
+        let mut profiler = Profiler {
+            stack: Vec::new(),
+            functions: HashMap::new(),
+            stacks: HashMap::new(),
+            names: Vec::new(),
+        };
+        profiler.enter(Symbol::intern(SCRIPT));
+        profiler
+    }
+
+    /// A call to `function` starts.
+    pub fn enter(&mut self, function: Symbol) {
+        let times = self.functions.entry(function).or_default();
+        times.calls += 1;
+        times.active += 1;
+        self.names.push(function);
+        self.stack.push(Frame {
+            started: Instant::now(),
+            in_calls: Duration::ZERO,
+        });
+    }
+
+    /// The innermost call returns, or unwinds with an error.
+    pub fn exit(&mut self) {
+        let (Some(frame), Some(function)) = (self.stack.pop(), self.names.last().copied()) else {
+            return;
+        };
+        let elapsed = frame.started.elapsed();
+        let own = elapsed.saturating_sub(frame.in_calls);
+        match self.stacks.get_mut(&self.names[..]) {
+            Some(time) => *time += own,
+            None => {
+                self.stacks.insert(self.names.clone(), own);
+            }
+        }
+        self.names.pop();
+
+        let times = self.functions.get_mut(&function).expect("entered before");
+        times.active -= 1;
+        times.own += own;
+        if times.active == 0 {
+            times.total += elapsed;
+        }
+        if let Some(caller) = self.stack.last_mut() {
+            caller.in_calls += elapsed;
+        }
+    }
+
+    /// Ends the program's frame, and any calls still in progress, and
+    /// returns the times of every function, the most time in its own code
+    /// first.
+    pub fn finish(&mut self) -> Vec<(Symbol, FunctionTimes)> {
+        while !self.stack.is_empty() {
+            self.exit();
+        }
+        let mut functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(&function, times)| (function, times.clone()))
+            .collect();
+        functions.sort_by(|(a, a_times), (b, b_times)| {
+            b_times
+                .own
+                .cmp(&a_times.own)
+                .then(a.as_str().cmp(b.as_str()))
+        });
+        functions
+    }
+
+    /// The time in each stack of calls, in the folded form flame graph
+    /// tools read: the functions outermost first separated by `;`, then the
+    /// microseconds spent in the innermost. Call after `finish`.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, time)| {
+                let names: Vec<&str> = stack.iter().map(|name| name.as_str()).collect();
+                format!("{} {}", names.join(";"), time.as_micros())
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+/// The times of `functions` as a table, a row per function under a header.
+pub fn table(functions: &[(Symbol, FunctionTimes)]) -> String {
+    let header = msg!("E0045");
+    let columns: Vec<&str> = header.split_whitespace().collect();
+    let mut table = String::new();
+    let mut row = |function: &str, calls: &str, total: &str, own: &str| {
+        writeln!(
+            table,
+            "{:<24} {:>8} {:>12} {:>12}",
+            function, calls, total, own
+        )
+        .unwrap();
+    };
+    row(columns[0], columns[1], columns[2], columns[3]);
+    for (function, times) in functions {
+        // Such as `1.234ms`, to at most three decimals
+        let total = format!("{:.3?}", times.total);
+        let own = format!("{:.3?}", times.own);
+        row(function, &times.calls.to_string(), &total, &own);
+    }
+    table
+}
@@ -0,0 +1,374 @@
+//! `repl`: runs statements as they're entered, in one interpreter, so what
+//! one line declares the next can use. A statement that leaves a bracket,
+//! string or comment open continues on the next line. On a terminal the
+//! REPL edits the line itself rather than leaving it to the terminal, so it
+//! can color it with `highlight` as it's typed; piped input is read a line
+//! at a time, without prompts.
+//!
+//! Before the first prompt it runs the startup file, `.loxrc` in the home
+//! directory or the file `LOX_RC` names, so every session can start with
+//! the same functions declared.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::highlight::Highlighter;
+use crate::interpreter::Interpreter;
+use crate::native;
+use crate::plugin;
+use crate::terminal::Terminal;
+use crate::Options;
+
+/// What errors in what's entered say they're in
+const NAME: &str = "<repl>";
+const PROMPT: &str = "> ";
+/// Shown instead of the prompt on the lines that continue a statement
+const CONTINUATION: &str = ". ";
+
+const CTRL_A: u8 = 0x01;
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const BACKSPACE: u8 = 0x08;
+const ESCAPE: u8 = 0x1b;
+const DELETE: u8 = 0x7f;
+
+/// A REPL reading from `input` and writing prompts and the line being
+/// edited to `output`, and errors to stderr.
+pub struct Repl {
+    interpreter: Interpreter,
+    highlighter: Highlighter,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    errors: Box<dyn Write>,
+    /// Whether `input` is keys pressed on a terminal, which the REPL echoes,
+    /// rather than lines
+    editing: bool,
+    /// The terminal `input` is, switched back to its own settings while a
+    /// statement runs
+    terminal: Option<Terminal>,
+    /// The lines entered, oldest first, which the up and down arrows bring
+    /// back
+    history: Vec<String>,
+    pretty_errors: bool,
+}
+
+/// What reading a line ended with.
+enum Entry {
+    Line(String),
+    /// Ctrl-C, which drops the statement being entered
+    Cancel,
+    /// The end of the input, or Ctrl-D on an empty line
+    End,
+}
+
+/// Runs the REPL on standard input and output, editing lines if they're a
+/// terminal whose settings it can change, and reading them whole if not.
+pub fn repl(options: &Options) -> io::Result<()> {
+    let stdin = io::stdin();
+    let terminal = (stdin.is_terminal() && io::stdout().is_terminal())
+        .then(Terminal::new)
+        .flatten();
+    let mut repl = Repl::new(options, stdin.lock(), io::stdout());
+    // One that fails to load is reported, and the REPL starts without it
+    for path in &options.plugins {
+        if let Err(msg) = plugin::load(&mut repl.interpreter, path) {
+            eprintln!("{}", msg);
+        }
+    }
+    // One named by `LOX_RC` has to be there, the default needn't be
+    if let Some((path, required)) = startup_file() {
+        match repl.run_file(&path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {}
+            Err(_) => eprintln!("{}", msg!("E0006", path.display())),
+            Ok(()) => {}
+        }
+    }
+    if let Some(mut terminal) = terminal {
+        if terminal.set_raw(true).is_ok() {
+            repl.set_editing(true);
+            repl.terminal = Some(terminal);
+        }
+    }
+    repl.run()
+}
+
+impl Repl {
+    pub fn new(
+        options: &Options,
+        input: impl BufRead + 'static,
+        output: impl Write + 'static,
+    ) -> Self {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_coerce_strings(options.coerce_strings);
+        interpreter.set_extensions(options.extensions);
+        interpreter.set_trace(options.trace);
+        interpreter.set_tco(options.tco);
+        interpreter.set_limits(&options.limits);
+        if options.allow_fs {
+            native::define_fs(&mut interpreter);
+        }
+        if options.allow_exec {
+            native::define_exec(&mut interpreter);
+        }
+        #[cfg(feature = "net")]
+        if options.allow_net {
+            native::define_net(&mut interpreter);
+        }
+        let mut highlighter = Highlighter::new();
+        highlighter.set_extensions(options.extensions);
+        Repl {
+            interpreter,
+            highlighter,
+            input: Box::new(input),
+            output: Box::new(output),
+            errors: Box::new(io::stderr()),
+            editing: false,
+            terminal: None,
+            history: Vec::new(),
+            pretty_errors: options.pretty_errors,
+        }
+    }
+
+    /// Reads `input` as keys pressed rather than lines, prompting for each
+    /// line and echoing it colored as it's edited. The terminal has to be
+    /// set not to echo them itself.
+    pub fn set_editing(&mut self, editing: bool) {
+        self.editing = editing;
+    }
+
+    /// Where `print` writes, stdout if not set.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.interpreter.set_output(output);
+    }
+
+    /// Where errors are reported, stderr if not set.
+    pub fn set_errors(&mut self, errors: impl Write + 'static) {
+        self.errors = Box::new(errors);
+    }
+
+    /// Runs the file at `path` in the REPL's interpreter, so what it
+    /// declares can be used from then on. Errors in it are reported like
+    /// those of what's entered; only failing to read it fails.
+    pub fn run_file(&mut self, path: &Path) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        self.execute(&source, &path.display().to_string())
+    }
+
+    /// Runs what's entered until the input ends. Errors are reported and
+    /// the REPL goes on; what ran before them stays declared.
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut source = String::new();
+        loop {
+            let entry = if self.editing {
+                let prompt = if source.is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION
+                };
+                self.edit_line(prompt, &source)?
+            } else {
+                self.read_line()?
+            };
+            match entry {
+                Entry::Line(line) => {
+                    source.push_str(&line);
+                    source.push('\n');
+                    if self.highlighter.is_complete(&source) {
+                        self.execute(&mem::take(&mut source), NAME)?;
+                    }
+                }
+                Entry::Cancel => source.clear(),
+                Entry::End => break,
+            }
+        }
+        // What's left unfinished still runs, to report what's missing
+        self.execute(&source, NAME)
+    }
+
+    /// Runs `source`, read from the file `name`, and reports its errors.
+    fn execute(&mut self, source: &str, name: &str) -> io::Result<()> {
+        if source.trim().is_empty() {
+            return Ok(());
+        }
+        // In the terminal's own settings, so the program can be stopped
+        // with Ctrl-C. If they can't be switched back, lines are read whole
+        // from then on.
+        if let Some(terminal) = &mut self.terminal {
+            _ = terminal.set_raw(false);
+        }
+        let result = self.interpreter.run(source);
+        if let Some(terminal) = &mut self.terminal {
+            if terminal.set_raw(true).is_err() {
+                self.editing = false;
+            }
+        }
+
+        for diagnostic in result.err().unwrap_or_default() {
+            let text = if self.pretty_errors {
+                diagnostic.render_pretty(Some(source), name)
+            } else {
+                format!("{}\n", diagnostic)
+            };
+            self.errors.write_all(text.as_bytes())?;
+        }
+        self.errors.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<Entry> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(Entry::End);
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(Entry::Line(line))
+    }
+
+    /// Reads a line key by key, redrawing it colored after each. `before`
+    /// is the statement's earlier lines, which decide how it's colored.
+    fn edit_line(&mut self, prompt: &str, before: &str) -> io::Result<Entry> {
+        let mut line = String::new();
+        // In bytes
+        let mut cursor = 0;
+        // The entry of `history` shown, or its length for the line typed
+        let mut recalled = self.history.len();
+        loop {
+            self.redraw(prompt, before, &line, cursor)?;
+            let Some(byte) = self.read_byte()? else {
+                writeln!(self.output)?;
+                return Ok(Entry::End);
+            };
+            match byte {
+                b'\r' | b'\n' => {
+                    writeln!(self.output)?;
+                    if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                        self.history.push(line.clone());
+                    }
+                    return Ok(Entry::Line(line));
+                }
+                CTRL_C => {
+                    writeln!(self.output, "^C")?;
+                    return Ok(Entry::Cancel);
+                }
+                CTRL_D if line.is_empty() => {
+                    writeln!(self.output)?;
+                    return Ok(Entry::End);
+                }
+                CTRL_A => cursor = 0,
+                CTRL_E => cursor = line.len(),
+                BACKSPACE | DELETE => {
+                    if let Some(ch) = line[..cursor].chars().next_back() {
+                        cursor -= ch.len_utf8();
+                        line.remove(cursor);
+                    }
+                }
+                ESCAPE => match self.escape_sequence()? {
+                    Some(b'D') => {
+                        let ch = line[..cursor].chars().next_back();
+                        cursor -= ch.map_or(0, char::len_utf8);
+                    }
+                    Some(b'C') => {
+                        let ch = line[cursor..].chars().next();
+                        cursor += ch.map_or(0, char::len_utf8);
+                    }
+                    Some(b'A') if recalled > 0 => {
+                        recalled -= 1;
+                        line = self.history[recalled].clone();
+                        cursor = line.len();
+                    }
+                    Some(b'B') if recalled < self.history.len() => {
+                        recalled += 1;
+                        line = self.history.get(recalled).cloned().unwrap_or_default();
+                        cursor = line.len();
+                    }
+                    Some(b'H') => cursor = 0,
+                    Some(b'F') => cursor = line.len(),
+                    _ => {}
+                },
+                // Other control keys do nothing
+                ..=0x1f => {}
+                first => {
+                    if let Some(ch) = self.read_char(first)? {
+                        line.insert(cursor, ch);
+                        cursor += ch.len_utf8();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Clears the line on screen and writes it again, colored, with the
+    /// cursor back where it was.
+    fn redraw(&mut self, prompt: &str, before: &str, line: &str, cursor: usize) -> io::Result<()> {
+        let colored = self.highlighter.highlight(before, line);
+        write!(self.output, "\r\x1b[K{}{}", prompt, colored)?;
+        let after = line[cursor..].chars().count();
+        if after > 0 {
+            write!(self.output, "\x1b[{}D", after)?;
+        }
+        self.output.flush()
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        loop {
+            match self.input.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// The rest of the sequence a key like an arrow sends after the escape,
+    /// and its last byte, which says which key it was. `None` for the
+    /// escape key itself, or at the end of the input.
+    fn escape_sequence(&mut self) -> io::Result<Option<u8>> {
+        if !matches!(self.read_byte()?, Some(b'[' | b'O')) {
+            return Ok(None);
+        }
+        // Parameters, like the 3 of `ESC [ 3 ~`, then the final byte
+        while let Some(byte) = self.read_byte()? {
+            if (0x40..=0x7e).contains(&byte) {
+                return Ok(Some(byte));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The character whose UTF-8 encoding starts with `first`, reading the
+    /// rest of it. `None` if it isn't valid UTF-8.
+    fn read_char(&mut self, first: u8) -> io::Result<Option<char>> {
+        let len = match first {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            match self.read_byte()? {
+                Some(byte) => bytes.push(byte),
+                None => return Ok(None),
+            }
+        }
+        let text = std::str::from_utf8(&bytes).ok();
+        Ok(text.and_then(|text| text.chars().next()))
+    }
+}
+
+/// The file to run on startup, and whether it has to be there: the one
+/// `LOX_RC` names, none if it's set but empty, or else `.loxrc` in the
+/// home directory.
+fn startup_file() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("LOX_RC") {
+        return (!path.is_empty()).then(|| (PathBuf::from(path), true));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    Some((Path::new(&home).join(".loxrc"), false))
+}
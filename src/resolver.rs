@@ -0,0 +1,799 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expr, ExprArena, FunctionDecl, Stmt};
+use crate::diagnostic::{Diagnostic, Diagnostics, Note, Phase};
+use crate::interpreter::Interpreter;
+use crate::lint::{Lint, LintLevels};
+use crate::module::module_name;
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+use crate::visit::{walk_expr, walk_stmt, Visitor};
+
+/// What kind of function the resolver is inside, for checking `return`.
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// What kind of class the resolver is inside, for checking `this` and `super`.
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// A local variable as the resolver tracks it.
+struct Local {
+    /// Whether its initializer has finished, so it can't be read in its own
+    /// initializer
+    defined: bool,
+    /// Its index among the variables of its scope, in declaration order
+    slot: usize,
+    /// Where it was declared, or `None` for `this` and `super`
+    name: Option<Token>,
+    parameter: bool,
+    read: bool,
+    /// Whether it's been read or assigned at all
+    accessed: bool,
+    /// Used by a function declared in its scope, which can run at any time
+    captured: bool,
+    /// The last value stored in it, if it hasn't been read since
+    unread_store: Option<Store>,
+    /// When it was last read, as counted by `Resolver::accesses`
+    last_read: usize,
+}
+
+impl Local {
+    fn new(name: Option<Token>, slot: usize) -> Self {
+        Local {
+            defined: false,
+            slot,
+            name,
+            parameter: false,
+            read: false,
+            accessed: false,
+            captured: false,
+            unread_store: None,
+            last_read: 0,
+        }
+    }
+}
+
+/// A value stored in a local by its initializer or an assignment.
+struct Store {
+    /// The name it was stored by
+    token: Token,
+    /// When, as counted by `Resolver::accesses`
+    at: usize,
+    /// The conditional parts of the code it was stored in, see
+    /// `Resolver::regions`
+    regions: Vec<usize>,
+    /// How many jumps were resolved before it, see `Resolver::jumps`
+    jumps: usize,
+}
+
+/// A use of a local variable and the declaration it refers to.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub usage: Token,
+    pub declaration: Token,
+}
+
+/// The variables a function uses from the functions around it, which a
+/// closure of it has to keep alive.
+#[derive(Debug, Clone)]
+pub struct Captures {
+    /// The function's name
+    pub function: Token,
+    /// In the order it first uses them
+    pub variables: Vec<Capture>,
+}
+
+/// A variable a function captures.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// Where the variable was declared
+    pub declaration: Token,
+    /// Whether the function, or one nested in it, assigns the variable
+    pub mutated: bool,
+}
+
+/// Whether a use of a variable reads or assigns it.
+#[derive(Clone, Copy, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// Static pass between parsing and running. Works out which scope every
+/// local variable refers to, telling the interpreter how many scopes up to
+/// look, and reports the mistakes that don't need running the program.
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    /// One map per enclosing local scope, from each name declared in it to
+    /// the variable
+    scopes: Vec<HashMap<Symbol, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    /// Number of loops around the code being resolved, within the current
+    /// function
+    loop_depth: usize,
+    /// Number of `try` statements around the code being resolved, within
+    /// the current function
+    try_depth: usize,
+    /// Index in `scopes` of the current function's outermost scope
+    function_scope: usize,
+    /// How many times a local has been read or assigned so far
+    accesses: usize,
+    /// The parts of the code around the code being resolved that may not
+    /// run, innermost last: branches, loops, the right of `and` and `or`,
+    /// cases and the parts of a `try`. Each has its own number.
+    regions: Vec<usize>,
+    /// The number the next of `regions` gets
+    next_region: usize,
+    /// How many `return`, `throw`, `break` and `continue` statements have
+    /// been resolved so far
+    jumps: usize,
+    /// The path of the first import of each module name, which an import
+    /// of another file with the same name would rebind
+    imports: HashMap<String, Token>,
+    /// The lints to check for, see `set_lints`
+    lints: Option<LintLevels>,
+    keep_bindings: bool,
+    keep_captures: bool,
+    /// For each function being resolved, innermost last, the index in
+    /// `scopes` of its outermost scope and its entry in `captures`
+    functions: Vec<(usize, usize)>,
+    /// Every static error found so far
+    pub diagnostics: Diagnostics,
+    /// Every use of a local resolved so far, if `keep_bindings` was called
+    pub bindings: Vec<Binding>,
+    /// What every function resolved so far captures, in the order they're
+    /// declared, if `keep_captures` was called
+    pub captures: Vec<Captures>,
+    /// Where each local that's never read or assigned was declared, once
+    /// its scope has ended, for `--opt` to drop
+    pub unused: Vec<Token>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Resolver {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            loop_depth: 0,
+            try_depth: 0,
+            function_scope: 0,
+            accesses: 0,
+            regions: Vec::new(),
+            next_region: 0,
+            jumps: 0,
+            imports: HashMap::new(),
+            lints: None,
+            keep_bindings: false,
+            keep_captures: false,
+            functions: Vec::new(),
+            diagnostics: Diagnostics::default(),
+            bindings: Vec::new(),
+            captures: Vec::new(),
+            unused: Vec::new(),
+        }
+    }
+
+    /// Also records in `bindings` where each use of a local was declared,
+    /// for tools that navigate the source.
+    pub fn keep_bindings(&mut self) {
+        self.keep_bindings = true;
+    }
+
+    /// Also records in `captures` the variables each function uses from
+    /// the functions around it.
+    pub fn keep_captures(&mut self) {
+        self.keep_captures = true;
+    }
+
+    /// Also reports the lints `levels` doesn't allow, as warnings or errors.
+    /// They're off by default, so programs run whatever they find.
+    pub fn set_lints(&mut self, levels: LintLevels) {
+        self.lints = Some(levels);
+    }
+
+    /// Resolves the statements of a program, or of code run after it,
+    /// whose expressions are in `arena`.
+    pub fn resolve(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        self.visit_stmts(arena, statements);
+    }
+
+    fn resolve_function(&mut self, arena: &ExprArena, function: &FunctionDecl, kind: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+        // A loop outside the function doesn't make break valid inside it
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
+        let enclosing_try_depth = std::mem::take(&mut self.try_depth);
+        let enclosing_function_scope = self.function_scope;
+        self.function_scope = self.scopes.len();
+        if self.keep_captures {
+            self.functions
+                .push((self.scopes.len(), self.captures.len()));
+            self.captures.push(Captures {
+                function: function.name.clone(),
+                variables: Vec::new(),
+            });
+        }
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare_local(param, true);
+            self.define(param);
+        }
+        self.visit_stmts(arena, &function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        self.loop_depth = enclosing_loop_depth;
+        self.try_depth = enclosing_try_depth;
+        self.function_scope = enclosing_function_scope;
+        if self.keep_captures {
+            self.functions.pop();
+        }
+    }
+
+    /// Tells the interpreter how many scopes out `name` was declared and in
+    /// which slot, if it's a local. Names found in no scope are left to be
+    /// looked up as globals.
+    fn resolve_local(&mut self, name: &Token, access: Access) {
+        let Some(index) = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&name.lexeme.symbol()))
+        else {
+            return;
+        };
+        let depth = self.scopes.len() - 1 - index;
+        let captured = index < self.function_scope;
+        if captured && self.keep_captures {
+            self.capture(name, index, access);
+        }
+        self.accesses += 1;
+        let accesses = self.accesses;
+
+        let local = self.scopes[index].get_mut(&name.lexeme.symbol()).unwrap();
+        self.interpreter.resolve(name, depth, local.slot);
+        local.captured |= captured;
+        local.accessed = true;
+        if let Some(declaration) = local.name.as_ref().filter(|_| self.keep_bindings) {
+            self.bindings.push(Binding {
+                usage: name.clone(),
+                declaration: declaration.clone(),
+            });
+        }
+        match access {
+            Access::Read => {
+                local.read = true;
+                local.last_read = accesses;
+                local.unread_store = None;
+            }
+            Access::Write => {
+                let store = Store {
+                    token: name.clone(),
+                    at: accesses,
+                    regions: self.regions.clone(),
+                    jumps: self.jumps,
+                };
+                let overwritten = local.unread_store.replace(store);
+                let captured = local.captured;
+                if let Some(overwritten) = overwritten.filter(|_| !captured) {
+                    self.overwrite(overwritten);
+                }
+            }
+        }
+    }
+
+    /// Records the value `name`'s initializer stores in the local it
+    /// declares in the innermost scope.
+    fn initialize(&mut self, name: &Token) {
+        self.accesses += 1;
+        let store = Store {
+            token: name.clone(),
+            at: self.accesses,
+            regions: self.regions.clone(),
+            jumps: self.jumps,
+        };
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme.symbol()))
+        {
+            local.unread_store = Some(store);
+        }
+    }
+
+    /// Reports the unread value `overwritten` if the store that replaced it
+    /// always runs after it, with nothing in between that could jump past
+    /// it or, inside a `try`, throw to code that reads the old value.
+    fn overwrite(&mut self, overwritten: Store) {
+        let always_replaced = overwritten.regions.starts_with(&self.regions)
+            && overwritten.jumps == self.jumps
+            && self.try_depth == 0;
+        // A leading underscore says it's unused on purpose
+        if !always_replaced || overwritten.token.lexeme.starts_with('_') {
+            return;
+        }
+        let msg = msg!("E0415", overwritten.token.lexeme);
+        let diagnostic = Diagnostic::at_token(Phase::Resolve, &overwritten.token, msg);
+        self.lint(Lint::UnusedAssignment, diagnostic);
+    }
+
+    /// Resolves what `visit` does as a part of the code that may not run.
+    fn in_region(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.regions.push(self.next_region);
+        self.next_region += 1;
+        visit(self);
+        self.regions.pop();
+    }
+
+    /// Records that the functions declared inside scope `index` capture
+    /// the variable `name` refers to there.
+    fn capture(&mut self, name: &Token, index: usize, access: Access) {
+        // `this` and `super` are no variables of the source's
+        let Some(declaration) = &self.scopes[index][&name.lexeme.symbol()].name else {
+            return;
+        };
+        let inside = self.functions.iter().rev();
+        for &(_, function) in inside.take_while(|&&(scope, _)| scope > index) {
+            let variables = &mut self.captures[function].variables;
+            let existing = variables
+                .iter_mut()
+                .find(|capture| capture.declaration.span == declaration.span);
+            let capture = match existing {
+                Some(capture) => capture,
+                None => {
+                    variables.push(Capture {
+                        declaration: declaration.clone(),
+                        mutated: false,
+                    });
+                    variables.last_mut().unwrap()
+                }
+            };
+            capture.mutated |= access == Access::Write;
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leaves the innermost scope, noting its locals that were never used
+    /// in `unused`, and reporting them and those whose last value was
+    /// never read if linting.
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("a scope was begun");
+        let unused = scope
+            .values()
+            .filter(|local| !local.accessed && !local.parameter)
+            .filter_map(|local| local.name.clone());
+        self.unused.extend(unused);
+        if self.lints.is_none() {
+            return;
+        }
+
+        let mut locals: Vec<Local> = scope.into_values().collect();
+        locals.sort_by_key(|local| local.slot);
+        for local in locals {
+            // A leading underscore says it's unused on purpose
+            let Some(name) = local.name.filter(|name| !name.lexeme.starts_with('_')) else {
+                continue;
+            };
+            if !local.read {
+                let (lint, msg) = if local.parameter {
+                    (Lint::UnusedParameter, msg!("E0412", name.lexeme))
+                } else {
+                    (Lint::UnusedVariable, msg!("E0411", name.lexeme))
+                };
+                self.lint(lint, Diagnostic::at_token(Phase::Resolve, &name, msg));
+            } else if let Some(store) = local.unread_store.filter(|_| !local.captured) {
+                let msg = msg!("E0415", store.token.lexeme);
+                let diagnostic = Diagnostic::at_token(Phase::Resolve, &store.token, msg);
+                self.lint(Lint::UnusedAssignment, diagnostic);
+            }
+        }
+    }
+
+    /// Called after a loop that began when `accesses` was `start`. A value
+    /// stored in the loop can be read in its next iteration, by code before
+    /// the assignment.
+    fn end_loop(&mut self, start: usize) {
+        for local in self.scopes.iter_mut().flat_map(|scope| scope.values_mut()) {
+            let stored_in_loop = local
+                .unread_store
+                .as_ref()
+                .is_some_and(|store| store.at > start);
+            if stored_in_loop && local.last_read > start {
+                local.unread_store = None;
+            }
+        }
+    }
+
+    /// Adds `name` to the innermost scope, not yet ready to be read.
+    fn declare(&mut self, name: &Token) {
+        self.declare_local(name, false);
+    }
+
+    fn declare_local(&mut self, name: &Token, parameter: bool) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme.symbol()) {
+            self.error(name, msg!("E0401"));
+            return;
+        }
+        let slot = scope.len();
+        let local = Local {
+            parameter,
+            ..Local::new(Some(name.clone()), slot)
+        };
+        scope.insert(name.lexeme.symbol(), local);
+
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+        let shadowed = enclosing
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme.symbol())?.name.clone());
+        if let Some(shadowed) = shadowed {
+            let msg = msg!("E0414", name.lexeme);
+            let diagnostic = Diagnostic::at_token(Phase::Resolve, name, msg)
+                .with_note(Note::at_token(&shadowed, msg!("E0416")));
+            self.lint(Lint::Shadowing, diagnostic);
+        }
+    }
+
+    /// Marks `name` as fully initialized in the innermost scope.
+    fn define(&mut self, name: &Token) {
+        self.define_name(name.lexeme.symbol());
+    }
+
+    /// Marks `name` as initialized, declaring it first if it isn't yet.
+    fn define_name(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.len();
+            scope.entry(name).or_insert(Local::new(None, slot)).defined = true;
+        }
+    }
+
+    fn error(&mut self, token: &Token, msg: String) {
+        let diagnostic = Diagnostic::at_token(Phase::Resolve, token, msg);
+        self.diagnostics.report(diagnostic);
+    }
+
+    /// Reports `diagnostic` for `lint` at the level `set_lints` gave it,
+    /// naming the lint after the message.
+    fn lint(&mut self, lint: Lint, diagnostic: Diagnostic) {
+        let Some(severity) = self.lints.and_then(|lints| lints.get(lint).severity()) else {
+            return;
+        };
+        self.diagnostics.report(Diagnostic {
+            severity,
+            message: format!("{} [{}]", diagnostic.message, lint.name()),
+            ..diagnostic
+        });
+    }
+}
+
+/// The resolver handles every statement that declares a name or opens a
+/// scope itself, and leaves the rest to the walk.
+impl Visitor for Resolver<'_> {
+    /// Also reports code after a `return`, `throw`, `break` or `continue`
+    /// in the same block, if linting.
+    fn visit_stmts(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        let mut unreachable_reported = false;
+        for (index, stmt) in statements.iter().enumerate() {
+            self.visit_stmt(arena, stmt);
+
+            let (Stmt::Return { keyword, .. }
+            | Stmt::Throw { keyword, .. }
+            | Stmt::Break(keyword)
+            | Stmt::Continue(keyword)) = stmt
+            else {
+                continue;
+            };
+            // Once per block is enough
+            if index + 1 < statements.len() && !unreachable_reported {
+                let msg = msg!("E0413", keyword.lexeme);
+                self.lint(
+                    Lint::UnreachableCode,
+                    Diagnostic::at_token(Phase::Resolve, keyword, msg),
+                );
+                unreachable_reported = true;
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        match stmt {
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.visit_expr(arena, &arena[*initializer]);
+                }
+                self.define(name);
+                if initializer.is_some() {
+                    self.initialize(name);
+                }
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.visit_stmts(arena, statements);
+                self.end_scope();
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                let start = self.accesses;
+                self.in_region(|resolver| {
+                    resolver.visit_expr(arena, &arena[*condition]);
+                    resolver.loop_depth += 1;
+                    resolver.visit_stmt(arena, body);
+                    resolver.loop_depth -= 1;
+                    if let Some(increment) = increment {
+                        resolver.visit_expr(arena, &arena[*increment]);
+                    }
+                });
+                self.end_loop(start);
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                default,
+                ..
+            } => {
+                self.visit_expr(arena, &arena[*subject]);
+                for case in cases {
+                    self.in_region(|resolver| {
+                        resolver.visit_expr(arena, &arena[case.value]);
+                        resolver.begin_scope();
+                        resolver.visit_stmts(arena, &case.body);
+                        resolver.end_scope();
+                    });
+                }
+                if let Some(default) = default {
+                    self.in_region(|resolver| {
+                        resolver.begin_scope();
+                        resolver.visit_stmts(arena, default);
+                        resolver.end_scope();
+                    });
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.try_depth += 1;
+                self.in_region(|resolver| {
+                    resolver.begin_scope();
+                    resolver.visit_stmts(arena, body);
+                    resolver.end_scope();
+                });
+                if let Some(catch) = catch {
+                    self.in_region(|resolver| {
+                        // The thrown value is the first variable of the body's scope
+                        resolver.begin_scope();
+                        resolver.declare(&catch.name);
+                        resolver.define(&catch.name);
+                        resolver.visit_stmts(arena, &catch.body);
+                        resolver.end_scope();
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.visit_stmts(arena, finally);
+                    self.end_scope();
+                }
+                self.try_depth -= 1;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.visit_expr(arena, &arena[*condition]);
+                self.in_region(|resolver| resolver.visit_stmt(arena, then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.in_region(|resolver| resolver.visit_stmt(arena, else_branch));
+                }
+            }
+            Stmt::Break(keyword) => {
+                self.jumps += 1;
+                if self.loop_depth == 0 {
+                    self.error(keyword, msg!("E0408"));
+                }
+            }
+            Stmt::Continue(keyword) => {
+                self.jumps += 1;
+                if self.loop_depth == 0 {
+                    self.error(keyword, msg!("E0409"));
+                }
+            }
+            Stmt::Error(token) => self.error(token, msg!("E0410")),
+            Stmt::Import { keyword, path } => {
+                if !self.scopes.is_empty() {
+                    self.error(keyword, msg!("E0417"));
+                }
+                let TokenType::StringLit(file) = &path.token_type else {
+                    unreachable!("parser only makes imports of strings");
+                };
+                match module_name(file) {
+                    None => self.error(path, msg!("E0418", file)),
+                    Some(name) => match self.imports.get(name) {
+                        Some(first) if first.token_type != path.token_type => {
+                            let TokenType::StringLit(first_file) = &first.token_type else {
+                                unreachable!("parser only makes imports of strings");
+                            };
+                            let msg = msg!("E0419", name, first_file);
+                            let diagnostic = Diagnostic::at_token(Phase::Resolve, path, msg)
+                                .with_note(Note::at_token(first, msg!("E0420")));
+                            self.diagnostics.report(diagnostic);
+                        }
+                        Some(_) => {}
+                        None => {
+                            self.imports.insert(name.to_string(), path.clone());
+                        }
+                    },
+                }
+            }
+            Stmt::Function(declaration) => {
+                // Defined before the body so the function can call itself
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(arena, declaration, FunctionType::Function);
+            }
+            Stmt::Return { keyword, value } => {
+                self.jumps += 1;
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, msg!("E0403"));
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, msg!("E0404"));
+                    }
+                    self.visit_expr(arena, &arena[*value]);
+                    // Nothing is left for the function to do once the call
+                    // returns, unless a `try` has to catch what it throws
+                    // or run a `finally` after it
+                    if self.try_depth == 0 && matches!(arena[*value], Expr::Call { .. }) {
+                        self.interpreter.resolve_tail_call(keyword);
+                    }
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+                self.declare(name);
+                self.define(name);
+
+                for mixin in mixins {
+                    self.visit_expr(arena, &arena[*mixin]);
+                }
+                if let Some(superclass) = superclass {
+                    self.current_class = ClassType::Subclass;
+                    self.visit_expr(arena, &arena[*superclass]);
+                    self.begin_scope();
+                    self.define_name(Symbol::intern("super"));
+                }
+
+                self.begin_scope();
+                self.define_name(Symbol::intern("this"));
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(arena, method, kind);
+                }
+                // `this` in a class method is the class, and none of them
+                // is an initializer, whatever its name
+                for method in class_methods {
+                    self.resolve_function(arena, method, FunctionType::Method);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.current_class = enclosing_class;
+            }
+            Stmt::Throw { .. } => {
+                self.jumps += 1;
+                walk_stmt(self, arena, stmt);
+            }
+            Stmt::Expression(_) | Stmt::Print { .. } => walk_stmt(self, arena, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+        match expr {
+            Expr::Variable(name) => {
+                let in_own_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.lexeme.symbol()))
+                    .is_some_and(|local| !local.defined);
+                if in_own_initializer {
+                    self.error(name, msg!("E0402"));
+                }
+                self.resolve_local(name, Access::Read);
+            }
+            Expr::Assign { name, value } => {
+                self.visit_expr(arena, &arena[*value]);
+                self.resolve_local(name, Access::Write);
+            }
+            // The value first, as jlox resolves it
+            Expr::Set { object, value, .. } => {
+                self.visit_expr(arena, &arena[*value]);
+                self.visit_expr(arena, &arena[*object]);
+            }
+            Expr::This(keyword) => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, msg!("E0405"));
+                    return;
+                }
+                self.resolve_local(keyword, Access::Read);
+            }
+            Expr::Super { keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => self.error(keyword, msg!("E0406")),
+                    ClassType::Class => self.error(keyword, msg!("E0407")),
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(keyword, Access::Read);
+            }
+            Expr::Error(token) => self.error(token, msg!("E0410")),
+            Expr::Logical { left, right, .. } => {
+                self.visit_expr(arena, &arena[*left]);
+                self.in_region(|resolver| resolver.visit_expr(arena, &arena[*right]));
+            }
+            // Parenthesized, `(a < b) < c` is taken to mean what it says
+            Expr::Binary { left, operator, .. } if is_comparison(operator) => {
+                if let Expr::Binary {
+                    operator: first, ..
+                } = &arena[*left]
+                {
+                    if is_comparison(first) {
+                        let (first, second) = (&first.lexeme, &operator.lexeme);
+                        let msg = msg!("E0421", second, first, first, second);
+                        let diagnostic = Diagnostic::at_token(Phase::Resolve, operator, msg);
+                        self.lint(Lint::ChainedComparison, diagnostic);
+                    }
+                }
+                walk_expr(self, arena, expr);
+            }
+            _ => walk_expr(self, arena, expr),
+        }
+    }
+}
+
+/// Whether `operator` is `<`, `<=`, `>` or `>=`.
+fn is_comparison(operator: &Token) -> bool {
+    matches!(
+        operator.token_type,
+        TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual
+    )
+}
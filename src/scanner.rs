@@ -0,0 +1,330 @@
+use std::io::{self, Read};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::diagnostic::{Diagnostic, Diagnostics, Phase};
+use crate::line_index::LineIndex;
+use crate::token::{Lexeme, Token, TokenType};
+
+/// Caps on input size, so hostile or runaway sources produce a clean
+/// diagnostic instead of exhausting memory or the stack.
+#[derive(Clone)]
+pub struct Limits {
+    pub max_source_size: usize, // in bytes
+    pub max_tokens: usize,
+    pub max_literal_length: usize, // in bytes, for string and number literals
+    pub max_nesting_depth: usize,  // see `Parser::set_max_depth`
+    pub max_call_depth: usize,     // calls in progress, see `Interpreter::set_limits`
+    pub max_call_stack: usize,     // in bytes of the Rust stack, for the tree-walker's calls
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_source_size: 16 * 1024 * 1024,
+            max_tokens: 1_000_000,
+            max_literal_length: 64 * 1024,
+            max_nesting_depth: 256,
+            max_call_depth: 1024,
+            // A call to a function whose body nests deeply takes hundreds
+            // of kilobytes of stack in a debug build, so fewer than
+            // `max_call_depth` of them can fill `lox::STACK_SIZE`. The rest
+            // is left for whatever the innermost call does without calling
+            // further.
+            max_call_stack: crate::STACK_SIZE / 2,
+        }
+    }
+}
+
+impl Limits {
+    /// Reads all of `source`, but never more than one byte past
+    /// `max_source_size`, so a source that's too large is known to be
+    /// without reading the rest of it.
+    pub fn read_source(&self, source: impl Read) -> io::Result<Vec<u8>> {
+        // Any size a `usize` can hold fits, even the largest
+        let limit = (self.max_source_size as u64).saturating_add(1);
+        let mut bytes = Vec::new();
+        source.take(limit).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Turns source text into the tokens the parser and `tokenize` work on.
+pub struct Scanner<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    index: LineIndex,
+    limits: &'a Limits,
+    /// Whether `switch`, `case` and `default` are keywords
+    extensions: bool,
+    /// Every lexical error found so far
+    pub diagnostics: Diagnostics,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str, limits: &'a Limits) -> Self {
+        Scanner {
+            source,
+            chars: source.char_indices().peekable(),
+            index: LineIndex::new(source),
+            limits,
+            extensions: false,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Makes the words reserved by `--extensions` scan as keywords rather
+    /// than identifiers.
+    pub fn set_extensions(&mut self, extensions: bool) {
+        self.extensions = extensions;
+    }
+
+    /// Scans the whole source, always ending with an EOF token. Bad characters
+    /// and strings are reported to `diagnostics` and skipped; exceeding one of the
+    /// limits records an error and stops scanning early.
+    pub fn scan_tokens(&mut self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        while let Some((start, ch)) = self.chars.next() {
+            let token_type = match self.scan_token(start, ch) {
+                Ok(Some(token_type)) => token_type,
+                Ok(None) => continue,
+                Err(diagnostic) => {
+                    self.diagnostics.report(diagnostic);
+                    break;
+                }
+            };
+
+            if tokens.len() >= self.limits.max_tokens {
+                let diagnostic = self.error_at(start, msg!("E0105", self.limits.max_tokens));
+                self.diagnostics.report(diagnostic);
+                break;
+            }
+            tokens.push(self.make_token(token_type, start));
+        }
+
+        let (line, column) = self.index.line_col(self.source.len());
+        tokens.push(Token {
+            token_type: TokenType::Eof,
+            lexeme: Lexeme::new(&TokenType::Eof, ""),
+            line,
+            column,
+            span: self.source.len()..self.source.len(),
+        });
+        tokens
+    }
+
+    /// Scans the token that starts with `ch` at byte `start`. Returns `None`
+    /// for whitespace, comments and errors scanning can recover from, and
+    /// `Err` when a literal is longer than the limit allows.
+    fn scan_token(&mut self, start: usize, ch: char) -> Result<Option<TokenType>, Diagnostic> {
+        use TokenType::*;
+
+        let token_type = match ch {
+            '(' => LeftParen,
+            ')' => RightParen,
+            '{' => LeftBrace,
+            '}' => RightBrace,
+            '[' => LeftBracket,
+            ']' => RightBracket,
+            ',' => Comma,
+            ':' => Colon,
+            '.' => Dot,
+            '-' if self.match_char('>') => Arrow,
+            '-' => Minus,
+            '+' => Plus,
+            ';' => Semicolon,
+            '*' => Star,
+            '%' => Percent,
+            '/' => {
+                if self.chars.next_if(|&(_, next)| next == '/').is_some() {
+                    // Comment runs to the end of the line
+                    while self.chars.next_if(|&(_, next)| next != '\n').is_some() {}
+                    return Ok(None);
+                }
+                if self.match_char('*') {
+                    self.block_comment(start);
+                    return Ok(None);
+                }
+                Slash
+            }
+
+            // These can all be followed by '=' to form a two-char operator
+            '!' if self.match_char('=') => BangEqual,
+            '!' => Bang,
+            '=' if self.match_char('=') => EqualEqual,
+            '=' => Equal,
+            '<' if self.match_char('=') => LessEqual,
+            '<' => Less,
+            '>' if self.match_char('=') => GreaterEqual,
+            '>' => Greater,
+
+            '"' => return self.string(start),
+            '0'..='9' => return self.number(start),
+            'a'..='z' | 'A'..='Z' | '_' => {
+                while self
+                    .chars
+                    .next_if(|&(_, next)| next.is_alphanumeric() || next == '_')
+                    .is_some()
+                {}
+                let text = &self.source[start..self.position()];
+                TokenType::keyword(text)
+                    .or_else(|| {
+                        self.extensions
+                            .then(|| TokenType::extension_keyword(text))
+                            .flatten()
+                    })
+                    .unwrap_or(Identifier)
+            }
+
+            ' ' | '\t' | '\r' | '\n' => return Ok(None),
+
+            _ => {
+                self.error(start, msg!("E0101", ch));
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(token_type))
+    }
+
+    /// Skips the rest of a `/* ... */` comment that opened at `start`.
+    /// Comments nest, so every `/*` inside needs its own `*/`.
+    fn block_comment(&mut self, start: usize) {
+        let mut depth = 1;
+        while let Some((_, ch)) = self.chars.next() {
+            if ch == '/' && self.match_char('*') {
+                depth += 1;
+            } else if ch == '*' && self.match_char('/') {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+
+        self.error(start, msg!("E0106"));
+    }
+
+    /// Scans the rest of a string literal whose opening quote is at `start`.
+    /// Strings can span lines. The token's value has escape sequences
+    /// replaced; its lexeme keeps them as written.
+    fn string(&mut self, start: usize) -> Result<Option<TokenType>, Diagnostic> {
+        let mut value = String::new();
+
+        while let Some((offset, ch)) = self.chars.next_if(|&(_, next)| next != '"') {
+            if ch != '\\' {
+                value.push(ch);
+            } else {
+                match self.chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, other)) => {
+                        // Kept as written, so the rest of the string still scans
+                        self.error(offset, msg!("E0107", other));
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    // The string is unterminated, which is reported below
+                    None => {}
+                }
+            }
+
+            if self.position() - start > self.limits.max_literal_length {
+                let msg = msg!("E0104", self.limits.max_literal_length);
+                return Err(self.error_at(start, msg));
+            }
+        }
+
+        if !self.match_char('"') {
+            // Hit the end of the source before the closing quote. Reported
+            // at the opening quote, which is where the mistake usually is
+            self.error(start, msg!("E0102"));
+            return Ok(None);
+        }
+        Ok(Some(TokenType::StringLit(value)))
+    }
+
+    /// Scans the rest of a number literal that starts at `start`.
+    fn number(&mut self, start: usize) -> Result<Option<TokenType>, Diagnostic> {
+        self.digits();
+        // A '.' only belongs to the number when a digit follows it, so
+        // `123.sqrt()` is a method call and `123.` a number then a dot
+        let fraction_follows = self.peek_next().is_some_and(|next| next.is_ascii_digit());
+        if fraction_follows && self.match_char('.') {
+            self.digits();
+        }
+
+        let lexeme = &self.source[start..self.position()];
+        if lexeme.len() > self.limits.max_literal_length {
+            let msg = msg!("E0103", self.limits.max_literal_length);
+            return Err(self.error_at(start, msg));
+        }
+        // Digits with at most one '.' between them always parse, too large
+        // ones as infinity. Still reported rather than unwrapped, so no
+        // input can make the scanner panic
+        match lexeme.parse() {
+            Ok(value) => Ok(Some(TokenType::Number(value))),
+            Err(_) => {
+                let msg = msg!("E0108", lexeme);
+                self.error(start, msg);
+                Ok(None)
+            }
+        }
+    }
+
+    fn digits(&mut self) {
+        while self
+            .chars
+            .next_if(|&(_, next)| next.is_ascii_digit())
+            .is_some()
+        {}
+    }
+
+    /// Consumes the next character if it is `expected`.
+    fn match_char(&mut self, expected: char) -> bool {
+        self.chars.next_if(|&(_, next)| next == expected).is_some()
+    }
+
+    /// The character after the next one, without consuming anything.
+    fn peek_next(&mut self) -> Option<char> {
+        let position = self.position();
+        self.source[position..].chars().nth(1)
+    }
+
+    /// Byte offset of the next unscanned character.
+    fn position(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(offset, _)) => offset,
+            None => self.source.len(),
+        }
+    }
+
+    /// Reports an error at byte `offset` that scanning can carry on after.
+    fn error(&mut self, offset: usize, message: String) {
+        let diagnostic = self.error_at(offset, message);
+        self.diagnostics.report(diagnostic);
+    }
+
+    /// An error at byte `offset`, for the caller to report.
+    fn error_at(&self, offset: usize, message: String) -> Diagnostic {
+        let (line, column) = self.index.line_col(offset);
+        Diagnostic::error(Phase::Scan, line, column, message)
+    }
+
+    /// Builds the token running from `start` to the current position.
+    fn make_token(&mut self, token_type: TokenType, start: usize) -> Token {
+        let (line, column) = self.index.line_col(start);
+        let end = self.position();
+        Token {
+            lexeme: Lexeme::new(&token_type, &self.source[start..end]),
+            token_type,
+            line,
+            column,
+            span: start..end,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::io;
+
+use crate::diagnostic::{Diagnostic, Diagnostics};
+
+/// How running a command over a source ended. Errors are carried as values
+/// so callers never have to scrape stderr; `main` maps each kind to a process
+/// exit code.
+#[derive(Debug)]
+pub enum ExitStatus {
+    Success,
+    /// Succeeded, with warnings to show.
+    Warnings(Vec<Diagnostic>),
+    /// Bad command line: unknown command or option, missing files.
+    UsageError(String),
+    /// The source couldn't be compiled: it was unreadable as text, or
+    /// scanning, parsing, resolving or compiling found errors. Holds every
+    /// diagnostic that was reported.
+    CompileError(Vec<Diagnostic>),
+    /// Evaluation failed, at the position in the diagnostic.
+    RuntimeError(Diagnostic),
+    /// The source could not be read.
+    Io(io::Error),
+    /// `fmt --check` found the source isn't formatted.
+    Unformatted,
+    /// `check-tests` found the source doesn't do what its comments expect.
+    /// Holds a description of each difference.
+    Mismatch(Vec<String>),
+}
+
+impl ExitStatus {
+    /// A `CompileError` when errors were reported, `Warnings` when only
+    /// warnings were and otherwise `Success`.
+    pub fn from_diagnostics(diagnostics: Diagnostics) -> Self {
+        if diagnostics.has_errors() {
+            ExitStatus::CompileError(diagnostics.into_vec())
+        } else if !diagnostics.is_empty() {
+            ExitStatus::Warnings(diagnostics.into_vec())
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    /// The sysexits-style code the CLI exits with.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ExitStatus::Success | ExitStatus::Warnings(_) => 0,
+            ExitStatus::UsageError(_) => 64,
+            ExitStatus::CompileError(_) => 65,
+            ExitStatus::RuntimeError(_) => 70,
+            ExitStatus::Io(_) => 66,
+            ExitStatus::Unformatted | ExitStatus::Mismatch(_) => 1,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! The strings the tree-walker and the VM compute. Cloning one never
+//! copies its text, and short ones are interned: there's one copy of each
+//! per thread, so comparing two is comparing pointers. Joining two long
+//! strings doesn't copy them either: it makes a rope, a node pointing at
+//! both, and the text is only put together once, the first time something
+//! reads it. Building a string with `s = s + piece` in a loop is linear in
+//! its length that way rather than quadratic, as long as nothing reads it
+//! on every iteration.
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// Joins that make a string shorter than this copy it right away, since a
+/// rope costs more than copying a few bytes.
+const MIN_ROPE_LEN: usize = 256;
+
+/// Strings up to this many bytes are interned. Names, keys and the pieces
+/// a program builds its output from mostly are.
+const MAX_INTERNED_LEN: usize = 32;
+
+/// How many strings the interner holds before it first drops the ones
+/// nothing else holds any more.
+const MIN_INTERNER_LIMIT: usize = 1024;
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner {
+        strings: HashSet::new(),
+        limit: MIN_INTERNER_LIMIT,
+    });
+}
+
+/// A Lox string.
+#[derive(Clone)]
+pub struct LoxString(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    /// Interned if it's no longer than `MAX_INTERNED_LEN`
+    Flat(Rc<str>),
+    Rope(Rc<Rope>),
+}
+
+/// Two strings joined, which become one piece of text when first read.
+struct Rope {
+    /// What's joined, left then right, until the text is put together
+    parts: Cell<Option<(LoxString, LoxString)>>,
+    /// In bytes
+    len: usize,
+    text: OnceCell<Box<str>>,
+}
+
+/// The short strings of a thread.
+struct Interner {
+    strings: HashSet<Rc<str>>,
+    /// How many it holds before dropping the unused ones again. Twice as
+    /// many as were left the last time, so dropping them takes constant
+    /// time per string interned.
+    limit: usize,
+}
+
+impl Interner {
+    fn intern(&mut self, text: &str, owned: impl FnOnce() -> Rc<str>) -> Rc<str> {
+        if let Some(interned) = self.strings.get(text) {
+            return interned.clone();
+        }
+        if self.strings.len() >= self.limit {
+            self.strings.retain(|string| Rc::strong_count(string) > 1);
+            self.limit = MIN_INTERNER_LIMIT.max(self.strings.len() * 2);
+        }
+        let interned = owned();
+        self.strings.insert(interned.clone());
+        interned
+    }
+}
+
+fn flat(text: &str, owned: impl FnOnce() -> Rc<str>) -> LoxString {
+    if text.len() > MAX_INTERNED_LEN {
+        return LoxString(Repr::Flat(owned()));
+    }
+    LoxString(Repr::Flat(
+        INTERNER.with(|interner| interner.borrow_mut().intern(text, owned)),
+    ))
+}
+
+impl LoxString {
+    /// `self` followed by `other`.
+    pub fn concat(&self, other: &LoxString) -> LoxString {
+        let len = self.len() + other.len();
+        if len < MIN_ROPE_LEN {
+            let mut text = String::with_capacity(len);
+            text.push_str(self);
+            text.push_str(other);
+            return LoxString::from(text);
+        }
+        LoxString(Repr::Rope(Rc::new(Rope {
+            parts: Cell::new(Some((self.clone(), other.clone()))),
+            len,
+            text: OnceCell::new(),
+        })))
+    }
+
+    /// The text, put together if it's a rope no one has read yet.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Flat(text) => text,
+            Repr::Rope(rope) => rope.text(),
+        }
+    }
+
+    /// The length in bytes, known without putting a rope together.
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Repr::Flat(text) => text.len(),
+            Repr::Rope(rope) => rope.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Rope {
+    fn text(&self) -> &str {
+        if let Some(text) = self.text.get() {
+            return text;
+        }
+        // Walked with a stack of its own rather than recursion: a string
+        // built by a loop is a rope as deep as the loop ran. The left part
+        // of each rope is on top of its right.
+        let mut text = String::with_capacity(self.len);
+        let (left, right) = self.parts.take().expect("an unread rope has parts");
+        let mut pending = vec![right, left];
+        while let Some(part) = pending.pop() {
+            match part {
+                LoxString(Repr::Flat(flat)) => text.push_str(&flat),
+                LoxString(Repr::Rope(rope)) => match rope.text.get() {
+                    Some(flat) => text.push_str(flat),
+                    None => {
+                        // Others may still hold the rope, so its parts stay
+                        let parts = rope.parts.take();
+                        let (left, right) = parts.clone().expect("an unread rope has parts");
+                        rope.parts.set(parts);
+                        pending.push(right);
+                        pending.push(left);
+                    }
+                },
+            }
+        }
+        self.text.get_or_init(|| text.into_boxed_str())
+    }
+}
+
+/// Unlinks the parts one rope at a time, since dropping a rope as deep as
+/// a loop ran by recursion would overflow the stack.
+impl Drop for Rope {
+    fn drop(&mut self) {
+        let mut pending: Vec<LoxString> = self
+            .parts
+            .take()
+            .map_or_else(Vec::new, |(left, right)| vec![left, right]);
+        while let Some(part) = pending.pop() {
+            let LoxString(Repr::Rope(rope)) = part else {
+                continue;
+            };
+            // Taken apart here if this was the last reference, so it drops
+            // with no parts left to recurse into
+            if let Ok(rope) = Rc::try_unwrap(rope) {
+                if let Some((left, right)) = rope.parts.take() {
+                    pending.push(left);
+                    pending.push(right);
+                }
+            }
+        }
+    }
+}
+
+impl Deref for LoxString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &LoxString) -> bool {
+        match (&self.0, &other.0) {
+            (Repr::Flat(l), Repr::Flat(r)) if Rc::ptr_eq(l, r) => true,
+            // Both interned, so they'd be the same string if they were equal
+            (Repr::Flat(l), Repr::Flat(r))
+                if l.len() <= MAX_INTERNED_LEN && r.len() <= MAX_INTERNED_LEN =>
+            {
+                false
+            }
+            (Repr::Rope(l), Repr::Rope(r)) if Rc::ptr_eq(l, r) => true,
+            _ => self.len() == other.len() && self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for LoxString {}
+
+impl Hash for LoxString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(text: String) -> Self {
+        flat(&text, || text.as_str().into())
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(text: &str) -> Self {
+        flat(text, || text.into())
+    }
+}
+
+impl fmt::Display for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl fmt::Debug for LoxString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,60 @@
+/// Edit distance between two strings, counted in chars: the number of
+/// insertions, deletions, substitutions and adjacent transpositions needed to
+/// turn one into the other (optimal string alignment distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Rows of the distance matrix for the previous two and current prefixes of `a`
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            // Swapped neighbours ("ture" for "true") count as one edit
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Returns the candidate closest to `name` if it is near enough to be a
+/// plausible typo. Exact matches are not typos and are never suggested.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // Allow roughly one mistake per three characters, but always at least
+    // one, and never so many that nothing of `name` is left
+    let len = name.chars().count();
+    let max_distance = (len / 3).max(1).min(len.saturating_sub(1));
+
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// `message`, about `name` not being defined, followed by a suggestion of
+/// the closest of `candidates` if one is near enough.
+pub fn did_you_mean<'a>(
+    message: String,
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> String {
+    match closest(name, candidates) {
+        Some(candidate) => format!("{} {}", message, msg!("E0203", candidate)),
+        None => message,
+    }
+}
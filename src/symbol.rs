@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned string: text stored once in a process-wide table, so two
+/// symbols with the same text are the same pointer. Comparing and hashing
+/// symbols compares and hashes the pointer, so looking a name up doesn't
+/// touch its characters, and reading the text takes no lock.
+///
+/// Interned text is never freed, so only names and punctuation are
+/// interned: the table grows with the distinct names in the sources read,
+/// not with their literals or the values programs build at runtime.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+/// Shared between threads, since `main` runs files on several of them.
+/// Only interning locks it.
+fn interner() -> &'static Mutex<HashSet<&'static str>> {
+    static INTERNER: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    /// The symbol for `text`, adding it to the table if it's new.
+    pub fn intern(text: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap();
+        if let Some(&text) = interner.get(text) {
+            return Symbol(text);
+        }
+        let text: &'static str = Box::leak(text.into());
+        interner.insert(text);
+        Symbol(text)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.0, state)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Symbol::intern(text)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
@@ -0,0 +1,139 @@
+//! Switches the terminal standard input is between the settings it has
+//! and the ones the REPL edits lines in, by calling the C library's
+//! `tcgetattr` and `tcsetattr`. Only on Linux and macOS, whose `struct
+//! termios` layouts this knows; elsewhere `Terminal::new` finds none and
+//! the REPL reads whole lines.
+
+use std::io;
+
+/// Standard input's terminal. Dropping it puts back the settings it had,
+/// however the REPL ends, a panic included.
+pub struct Terminal {
+    original: sys::Termios,
+    raw: sys::Termios,
+    is_raw: bool,
+}
+
+impl Terminal {
+    /// The terminal standard input is, or `None` if it isn't one or its
+    /// settings can't be changed here.
+    pub fn new() -> Option<Terminal> {
+        let original = sys::get().ok()?;
+        Some(Terminal {
+            raw: original.raw()?,
+            original,
+            is_raw: false,
+        })
+    }
+
+    /// Makes keys arrive one at a time, unechoed, with Ctrl-C as a key
+    /// rather than a signal, or puts back the settings the terminal had.
+    /// Does nothing if it's already that way.
+    pub fn set_raw(&mut self, raw: bool) -> io::Result<()> {
+        if raw == self.is_raw {
+            return Ok(());
+        }
+        sys::set(if raw { &self.raw } else { &self.original })?;
+        self.is_raw = raw;
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Nothing more can be done about it failing here
+        _ = self.set_raw(false);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::io;
+    use std::mem::size_of;
+    use std::os::raw::c_int;
+
+    /// `struct termios`, only looked into for its local modes. Larger than
+    /// any platform's.
+    #[repr(C, align(8))]
+    #[derive(Clone, Copy)]
+    pub struct Termios([u8; 128]);
+
+    /// `tcflag_t`
+    #[cfg(target_os = "linux")]
+    type Flags = u32;
+    #[cfg(target_os = "macos")]
+    type Flags = u64;
+
+    /// The offset of `c_lflag`, after the input, output and control modes
+    const LOCAL_MODES: usize = 3 * size_of::<Flags>();
+
+    /// Whether the local modes have the values BSD gave them, as on macOS
+    /// and on Linux for PowerPC, rather than System V's
+    const BSD_MODES: bool = cfg!(any(
+        target_os = "macos",
+        target_arch = "powerpc",
+        target_arch = "powerpc64"
+    ));
+    const ISIG: Flags = if BSD_MODES { 0x80 } else { 0o1 };
+    const ICANON: Flags = if BSD_MODES { 0x100 } else { 0o2 };
+    const ECHO: Flags = 0o10;
+
+    const STDIN: c_int = 0;
+    const TCSANOW: c_int = 0;
+
+    extern "C" {
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, actions: c_int, termios: *const Termios) -> c_int;
+    }
+
+    pub fn get() -> io::Result<Termios> {
+        let mut termios = Termios([0; 128]);
+        // SAFETY: `termios` has room for the struct `tcgetattr` fills in
+        if unsafe { tcgetattr(STDIN, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(termios)
+    }
+
+    pub fn set(termios: &Termios) -> io::Result<()> {
+        // SAFETY: `termios` is what `tcgetattr` filled in, or a copy with
+        // only its local modes changed
+        if unsafe { tcsetattr(STDIN, TCSANOW, termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    impl Termios {
+        /// These settings without line editing, echo or signal keys.
+        pub fn raw(&self) -> Option<Termios> {
+            let mut raw = *self;
+            let field = &mut raw.0[LOCAL_MODES..LOCAL_MODES + size_of::<Flags>()];
+            let modes = Flags::from_ne_bytes(field.try_into().ok()?);
+            field.copy_from_slice(&(modes & !(ISIG | ICANON | ECHO)).to_ne_bytes());
+            Some(raw)
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::io;
+
+    #[derive(Clone, Copy)]
+    pub struct Termios;
+
+    pub fn get() -> io::Result<Termios> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn set(_: &Termios) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    impl Termios {
+        pub fn raw(&self) -> Option<Termios> {
+            None
+        }
+    }
+}
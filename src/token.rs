@@ -0,0 +1,264 @@
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::rc::Rc;
+
+use crate::symbol::Symbol;
+
+/// Reserved words in Lox and the token types they scan as.
+pub const KEYWORDS: [(&str, TokenType); 19] = [
+    ("and", TokenType::And),
+    ("break", TokenType::Break),
+    ("class", TokenType::Class),
+    ("continue", TokenType::Continue),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("import", TokenType::Import),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
+/// Reserved words only with `--extensions`, so strict Lox programs can
+/// still use them as names.
+pub const EXTENSION_KEYWORDS: [(&str, TokenType); 8] = [
+    ("case", TokenType::Case),
+    ("catch", TokenType::Catch),
+    ("default", TokenType::Default),
+    ("finally", TokenType::Finally),
+    ("switch", TokenType::Switch),
+    ("throw", TokenType::Throw),
+    ("try", TokenType::Try),
+    ("with", TokenType::With),
+];
+
+/// Every kind of token the scanner produces.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenType {
+    // Single chars
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Colon,
+    Dot,
+    Semicolon,
+    Minus,
+    Plus,
+    Slash,
+    Star,
+    Percent,
+    // One or two chars
+    /// `->`, before a function's return type
+    Arrow,
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    // Literals
+    Number(f64),
+    StringLit(String),
+    True,
+    False,
+    Nil,
+    Identifier,
+    // Keywords
+    And,
+    Break,
+    Case,
+    Catch,
+    Class,
+    Continue,
+    Default,
+    Else,
+    Finally,
+    For,
+    Fun,
+    If,
+    Import,
+    Or,
+    Print,
+    Return,
+    Super,
+    Switch,
+    This,
+    Throw,
+    Try,
+    Var,
+    While,
+    With,
+
+    Eof,
+}
+
+impl TokenType {
+    /// The token type of a reserved word, or `None` for a plain identifier.
+    pub fn keyword(text: &str) -> Option<TokenType> {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == text)
+            .map(|(_, token_type)| token_type.clone())
+    }
+
+    /// The token type of a word reserved only with `--extensions`.
+    pub fn extension_keyword(text: &str) -> Option<TokenType> {
+        EXTENSION_KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == text)
+            .map(|(_, token_type)| token_type.clone())
+    }
+}
+
+/// The `LEFT_PAREN` style name `tokenize` prints for a token type.
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TokenType::*;
+
+        let name = match self {
+            LeftParen => "LEFT_PAREN",
+            RightParen => "RIGHT_PAREN",
+            LeftBrace => "LEFT_BRACE",
+            RightBrace => "RIGHT_BRACE",
+            LeftBracket => "LEFT_BRACKET",
+            RightBracket => "RIGHT_BRACKET",
+            Comma => "COMMA",
+            Colon => "COLON",
+            Dot => "DOT",
+            Minus => "MINUS",
+            Plus => "PLUS",
+            Semicolon => "SEMICOLON",
+            Slash => "SLASH",
+            Star => "STAR",
+            Percent => "PERCENT",
+            Arrow => "ARROW",
+            Bang => "BANG",
+            BangEqual => "BANG_EQUAL",
+            Equal => "EQUAL",
+            EqualEqual => "EQUAL_EQUAL",
+            Greater => "GREATER",
+            GreaterEqual => "GREATER_EQUAL",
+            Less => "LESS",
+            LessEqual => "LESS_EQUAL",
+            Identifier => "IDENTIFIER",
+            StringLit(_) => "STRING",
+            Number(_) => "NUMBER",
+            And => "AND",
+            Break => "BREAK",
+            Case => "CASE",
+            Catch => "CATCH",
+            Class => "CLASS",
+            Continue => "CONTINUE",
+            Default => "DEFAULT",
+            Else => "ELSE",
+            False => "FALSE",
+            Finally => "FINALLY",
+            For => "FOR",
+            Fun => "FUN",
+            If => "IF",
+            Import => "IMPORT",
+            Nil => "NIL",
+            Or => "OR",
+            Print => "PRINT",
+            Return => "RETURN",
+            Super => "SUPER",
+            Switch => "SWITCH",
+            This => "THIS",
+            Throw => "THROW",
+            True => "TRUE",
+            Try => "TRY",
+            Var => "VAR",
+            While => "WHILE",
+            With => "WITH",
+            Eof => "EOF",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A token scanned from the source
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub lexeme: Lexeme, // the exact text
+    pub line: usize,
+    pub column: usize,
+    /// Byte offsets of the lexeme in the source
+    pub span: Range<usize>,
+}
+
+/// The text of a token as written. Names, keywords and punctuation are
+/// interned, so the interpreter can look names up by symbol. Literals are
+/// kept as they are: there's no end to the different ones sources can
+/// hold, and interned text is never freed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Lexeme {
+    Interned(Symbol),
+    Literal(Rc<str>),
+}
+
+impl Lexeme {
+    /// The lexeme of a token of type `token_type` written as `text`.
+    pub fn new(token_type: &TokenType, text: &str) -> Self {
+        match token_type {
+            TokenType::Number(_) | TokenType::StringLit(_) => Lexeme::Literal(text.into()),
+            _ => Lexeme::Interned(Symbol::intern(text)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self
+    }
+
+    /// The symbol names are looked up by. Literals aren't names, so the
+    /// interning here is only for completeness.
+    pub fn symbol(&self) -> Symbol {
+        match self {
+            Lexeme::Interned(symbol) => *symbol,
+            Lexeme::Literal(text) => Symbol::intern(text),
+        }
+    }
+}
+
+impl Deref for Lexeme {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Lexeme::Interned(symbol) => symbol.as_str(),
+            Lexeme::Literal(text) => text,
+        }
+    }
+}
+
+impl PartialEq<&str> for Lexeme {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl fmt::Debug for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &**self)
+    }
+}
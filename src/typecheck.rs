@@ -0,0 +1,849 @@
+//! `typecheck`: finds operations that can only fail, like `"a" - 1`,
+//! calling a number or calling a function with the wrong number of
+//! arguments, without running the program.
+//!
+//! The checker follows the program in order, keeping the type of each
+//! variable as far as it knows it. Where control flow joins, after an `if`
+//! or around a loop, a variable whose type differs between the ways there
+//! becomes unknown, and nothing is reported about unknown types, so what is
+//! reported fails whenever it runs.
+//!
+//! Variables, parameters and return values can be annotated with a type,
+//! as in `var x: number = 1;` or `fun f(a: string) -> bool { ... }`.
+//! Assigning, passing or returning something else is reported, and where
+//! the checker doesn't know better it takes the annotation's word for what
+//! a variable holds or a call returns. The types are `nil`, `bool`,
+//! `number`, `string`, `list`, `map`, `function` and `class`; any other
+//! name, like that of a class, stands for anything.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+use crate::ast::{Expr, ExprArena, ExprId, FunctionDecl, Literal, Stmt};
+use crate::diagnostic::{Diagnostic, Diagnostics, Phase, Severity};
+use crate::environment::Environment;
+use crate::function::LoxCallable;
+use crate::interpreter::Interpreter;
+use crate::module::module_name;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::status::ExitStatus;
+use crate::symbol::Symbol;
+use crate::token::{Token, TokenType};
+use crate::value::Value;
+use crate::visit::{self, Visitor};
+use crate::{scan_tokens, Options, Output};
+
+/// Reports the operations in `source` that can only fail, as warnings, or
+/// as errors with `options.strict`. The resolver's errors come first, since
+/// the types of code it rejects mean nothing.
+pub fn typecheck(source: &str, options: &Options, _out: &mut Output) -> ExitStatus {
+    let tokens = match scan_tokens(source, options) {
+        Ok(tokens) => tokens,
+        Err(status) => return status,
+    };
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(options.limits.max_nesting_depth);
+    let Some(statements) = parser.parse_program() else {
+        return ExitStatus::from_diagnostics(parser.diagnostics);
+    };
+
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new(&mut interpreter);
+    resolver.resolve(&parser.arena, &statements);
+    if resolver.diagnostics.has_errors() {
+        return ExitStatus::from_diagnostics(resolver.diagnostics);
+    }
+
+    let severity = match options.strict {
+        true => Severity::Error,
+        false => Severity::Warning,
+    };
+    let mut checker = Checker::new(interpreter.environment(), severity, options);
+    checker.check(&parser.arena, &statements);
+
+    // A return is checked after the expression it returns
+    let mut diagnostics = checker.diagnostics.into_vec();
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    ExitStatus::from_diagnostics(diagnostics.into_iter().collect::<Diagnostics>())
+}
+
+/// What the checker knows about a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Nil,
+    Bool,
+    Number,
+    String,
+    List,
+    Map,
+    /// A function, by where its signature is in `Checker::signatures`
+    Function(usize),
+    /// A class, with the number of arguments it takes if known: it could
+    /// inherit `init` from a class the checker doesn't know
+    Class(Option<usize>),
+    /// Anything at all, about which nothing is reported
+    Unknown,
+}
+
+impl Type {
+    /// The type an annotation names, if it's one the checker knows.
+    fn named(annotation: &Token) -> Type {
+        match annotation.lexeme.as_str() {
+            "nil" => Type::Nil,
+            "bool" | "boolean" => Type::Bool,
+            "number" => Type::Number,
+            "string" => Type::String,
+            "list" => Type::List,
+            "map" => Type::Map,
+            "function" => Type::Function(ANY_FUNCTION),
+            "class" => Type::Class(None),
+            _ => Type::Unknown,
+        }
+    }
+
+    fn of_literal(literal: &Literal) -> Type {
+        match literal {
+            Literal::Nil => Type::Nil,
+            Literal::Bool(_) => Type::Bool,
+            Literal::Number(_) => Type::Number,
+            Literal::Str(_) => Type::String,
+        }
+    }
+
+    /// Whether a value of this type can be used where `expected` is.
+    fn fits(self, expected: Type) -> bool {
+        match (self, expected) {
+            (Type::Unknown, _) | (_, Type::Unknown) => true,
+            (Type::Function(_), Type::Function(_)) | (Type::Class(_), Type::Class(_)) => true,
+            _ => self == expected,
+        }
+    }
+
+    /// The type of a value that's one of `self` or `other`.
+    fn join(self, other: Type) -> Type {
+        if self == other {
+            self
+        } else {
+            Type::Unknown
+        }
+    }
+}
+
+/// The names `type()` gives values of each type.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Nil => "nil",
+            Type::Bool => "boolean",
+            Type::Number => "number",
+            Type::String => "string",
+            Type::List => "list",
+            Type::Map => "map",
+            Type::Function(_) => "function",
+            Type::Class(_) => "class",
+            Type::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What a function takes and returns.
+struct Signature {
+    /// `None` for any function, as an annotation of `function` means
+    arity: Option<usize>,
+    params: Vec<Type>,
+    returns: Type,
+}
+
+/// The signature of the `function` annotation.
+const ANY_FUNCTION: usize = 0;
+
+/// What the checker knows about a variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Variable {
+    /// The type of what it holds at this point
+    ty: Type,
+    /// Its annotation
+    declared: Option<Type>,
+}
+
+/// The variables in scope, innermost scope last. The first scope is the
+/// globals.
+type Scopes = Vec<HashMap<Symbol, Variable>>;
+
+/// What's known after reaching a point one way or another: the variables
+/// of the scopes both ways share, with the types they agree on.
+fn merge(a: &Scopes, b: &Scopes) -> Scopes {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            a.iter()
+                .map(|(name, &variable)| {
+                    let ty = b
+                        .get(name)
+                        .map_or(Type::Unknown, |other| other.ty.join(variable.ty));
+                    (*name, Variable { ty, ..variable })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The type an optional annotation names.
+fn annotated(annotation: &Option<Token>) -> Type {
+    annotation.as_ref().map_or(Type::Unknown, Type::named)
+}
+
+struct Checker {
+    scopes: Scopes,
+    /// Where the scopes of the function being checked start
+    function_scope: usize,
+    /// The scopes at each `break` and `continue` of the loop being checked,
+    /// which reach its end or its next iteration along with its body's
+    jumps: Vec<Scopes>,
+    bindings: Bindings,
+    /// The signatures `Type::Function` refers to, `ANY_FUNCTION` first
+    signatures: Vec<Signature>,
+    /// Where the signature of each function declaration checked so far
+    /// is, so checking one again, as in a loop, gives the same type
+    declared_signatures: HashMap<*const FunctionDecl, usize>,
+    native_signatures: HashMap<Symbol, usize>,
+    /// What the function being checked is annotated to return
+    returns: Type,
+    /// The natives, which are all the globals before the program runs
+    natives: Rc<RefCell<Environment>>,
+    coerce_strings: bool,
+    severity: Severity,
+    /// Off while working out the types a loop settles on, so its body is
+    /// reported once, with them
+    reporting: bool,
+    diagnostics: Diagnostics,
+}
+
+impl Checker {
+    fn new(natives: Rc<RefCell<Environment>>, severity: Severity, options: &Options) -> Self {
+        Checker {
+            scopes: vec![HashMap::new()],
+            function_scope: 0,
+            jumps: Vec::new(),
+            bindings: Bindings::default(),
+            signatures: vec![Signature {
+                arity: None,
+                params: Vec::new(),
+                returns: Type::Unknown,
+            }],
+            declared_signatures: HashMap::new(),
+            native_signatures: HashMap::new(),
+            returns: Type::Unknown,
+            natives,
+            coerce_strings: options.coerce_strings,
+            severity,
+            reporting: true,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    fn check(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        self.bindings.visit_stmts(arena, statements);
+        self.statements(arena, statements);
+    }
+
+    fn report(&mut self, token: &Token, message: String) {
+        if self.reporting {
+            self.diagnostics.report(Diagnostic {
+                severity: self.severity,
+                ..Diagnostic::at_token(Phase::Typecheck, token, message)
+            });
+        }
+    }
+
+    fn declare(&mut self, name: &Token, ty: Type, declared: Option<Type>) {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.insert(name.lexeme.symbol(), Variable { ty, declared });
+    }
+
+    /// The type of a function declared as `function`.
+    fn signature(&mut self, function: &FunctionDecl) -> Type {
+        let key: *const FunctionDecl = function;
+        if let Some(&index) = self.declared_signatures.get(&key) {
+            return Type::Function(index);
+        }
+        self.signatures.push(Signature {
+            arity: Some(function.params.len()),
+            params: function.param_types.iter().map(annotated).collect(),
+            returns: annotated(&function.return_type),
+        });
+        let index = self.signatures.len() - 1;
+        self.declared_signatures.insert(key, index);
+        Type::Function(index)
+    }
+
+    /// The type `name` has where it's read.
+    fn variable(&mut self, name: &Token) -> Type {
+        let symbol = name.lexeme.symbol();
+        let found = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| Some((depth, *scope.get(&symbol)?)));
+        let ty = match found {
+            // Assignments in the function are followed in order, but a
+            // closure's can happen whenever it's called
+            Some((depth, variable)) if depth >= self.function_scope => {
+                match self.bindings.assigned_by_closures.contains(&symbol) {
+                    true => Type::Unknown,
+                    false => variable.ty,
+                }
+            }
+            // The function can be called whenever, after the variable
+            // changes, unless it never does
+            Some((_, variable)) => match self.bindings.count(symbol) {
+                1 => variable.ty,
+                _ => Type::Unknown,
+            },
+            None if self.bindings.count(symbol) == 0 => self.native(name),
+            None => Type::Unknown,
+        };
+        match (ty, found) {
+            (Type::Unknown, Some((_, variable))) => variable.declared.unwrap_or(Type::Unknown),
+            _ => ty,
+        }
+    }
+
+    /// The type of the native `name`, if there is one.
+    fn native(&mut self, name: &Token) -> Type {
+        let symbol = name.lexeme.symbol();
+        if let Some(&index) = self.native_signatures.get(&symbol) {
+            return Type::Function(index);
+        }
+        let Ok(Value::Native(native)) = self.natives.borrow().get(name) else {
+            return Type::Unknown;
+        };
+        self.signatures.push(Signature {
+            arity: Some(native.arity()),
+            params: Vec::new(),
+            returns: Type::Unknown,
+        });
+        let index = self.signatures.len() - 1;
+        self.native_signatures.insert(symbol, index);
+        Type::Function(index)
+    }
+
+    /// Records that `name` now holds a `ty`, reporting it if its annotation
+    /// says otherwise.
+    fn assign(&mut self, name: &Token, ty: Type) {
+        let symbol = name.lexeme.symbol();
+        let Some(variable) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&symbol))
+        else {
+            return;
+        };
+        variable.ty = ty;
+        if let Some(declared) = variable.declared.filter(|&declared| !ty.fits(declared)) {
+            self.report(name, msg!("E0608", name.lexeme, declared, ty));
+        }
+    }
+
+    /// Makes every variable named in `names` unknown, for code that could
+    /// have stopped after assigning any of them.
+    fn forget(&mut self, names: &HashSet<Symbol>) {
+        for scope in &mut self.scopes {
+            for (name, variable) in scope.iter_mut() {
+                if names.contains(name) {
+                    variable.ty = Type::Unknown;
+                }
+            }
+        }
+    }
+
+    fn statements(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        for stmt in statements {
+            self.statement(arena, stmt);
+        }
+    }
+
+    fn block(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.statements(arena, statements);
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr)
+            | Stmt::Print { value: expr, .. }
+            | Stmt::Throw { value: expr, .. } => {
+                self.expression(arena, *expr);
+            }
+            Stmt::Var {
+                name,
+                annotation,
+                initializer,
+            } => {
+                let declared = annotation.as_ref().map(Type::named);
+                // Without a value, the variable is nil until it's assigned
+                // one, whatever its annotation
+                let ty = match initializer {
+                    Some(value) => self.expression(arena, *value),
+                    None => Type::Nil,
+                };
+                if let Some(declared) = declared.filter(|&declared| !ty.fits(declared)) {
+                    if initializer.is_some() {
+                        self.report(name, msg!("E0608", name.lexeme, declared, ty));
+                    }
+                }
+                self.declare(name, ty, declared);
+            }
+            Stmt::Block(statements) => self.block(arena, statements),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(arena, *condition);
+                let before = self.scopes.clone();
+                self.statement(arena, then_branch);
+                let after_then = mem::replace(&mut self.scopes, before);
+                if let Some(else_branch) = else_branch {
+                    self.statement(arena, else_branch);
+                }
+                self.scopes = merge(&after_then, &self.scopes);
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => self.while_loop(arena, *condition, body, *increment),
+            Stmt::Break(_) | Stmt::Continue(_) => self.jumps.push(self.scopes.clone()),
+            Stmt::Function(declaration) => {
+                let ty = self.signature(declaration);
+                self.declare(&declaration.name, ty, None);
+                self.function(arena, declaration);
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                let init = methods.iter().find(|method| method.name.lexeme == "init");
+                let arity = match init {
+                    Some(init) => Some(init.params.len()),
+                    None if superclass.is_none() && mixins.is_empty() => Some(0),
+                    None => None,
+                };
+                for class in superclass.iter().chain(mixins) {
+                    self.expression(arena, *class);
+                }
+                self.declare(name, Type::Class(arity), None);
+                for method in methods.iter().chain(class_methods) {
+                    self.function(arena, method);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                let ty = match value {
+                    Some(value) => self.expression(arena, *value),
+                    None => Type::Nil,
+                };
+                if !ty.fits(self.returns) {
+                    self.report(keyword, msg!("E0610", ty, self.returns));
+                }
+            }
+            Stmt::Import { path, .. } => {
+                if let TokenType::StringLit(file) = &path.token_type {
+                    if let Some(name) = module_name(file) {
+                        let scope = self.scopes.last_mut().unwrap();
+                        let module = Variable {
+                            ty: Type::Unknown,
+                            declared: None,
+                        };
+                        scope.insert(Symbol::intern(name), module);
+                    }
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                let mut assigned = Assigned::default();
+                assigned.visit_stmts(arena, body);
+                let before = self.scopes.clone();
+                self.block(arena, body);
+                if let Some(catch) = catch {
+                    // The body could have thrown after any of its assignments
+                    let after_body = mem::replace(&mut self.scopes, before);
+                    self.forget(&assigned.names);
+                    self.scopes.push(HashMap::new());
+                    self.declare(&catch.name, Type::Unknown, None);
+                    self.statements(arena, &catch.body);
+                    self.scopes.pop();
+                    self.scopes = merge(&after_body, &self.scopes);
+                }
+                if let Some(finally) = finally {
+                    self.forget(&assigned.names);
+                    self.block(arena, finally);
+                }
+            }
+            Stmt::Switch {
+                subject,
+                cases,
+                default,
+                ..
+            } => {
+                self.expression(arena, *subject);
+                let mut ends = Vec::new();
+                for case in cases {
+                    self.expression(arena, case.value);
+                    let before = self.scopes.clone();
+                    self.block(arena, &case.body);
+                    ends.push(mem::replace(&mut self.scopes, before));
+                }
+                if let Some(default) = default {
+                    self.block(arena, default);
+                }
+                for end in ends {
+                    self.scopes = merge(&end, &self.scopes);
+                }
+            }
+            Stmt::Error(_) => {}
+        }
+    }
+
+    /// Checks the body of a loop with the types its variables settle on
+    /// over any number of iterations, which is also what they are after it.
+    fn while_loop(
+        &mut self,
+        arena: &ExprArena,
+        condition: ExprId,
+        body: &Stmt,
+        increment: Option<ExprId>,
+    ) {
+        let enclosing_jumps = mem::take(&mut self.jumps);
+        let reporting = mem::replace(&mut self.reporting, false);
+        loop {
+            let start = self.scopes.clone();
+            self.iteration(arena, condition, body, increment);
+            self.scopes = merge(&start, &self.scopes);
+            if self.scopes == start {
+                break;
+            }
+        }
+        self.reporting = reporting;
+        let start = self.scopes.clone();
+        self.iteration(arena, condition, body, increment);
+        self.scopes = start;
+        self.jumps = enclosing_jumps;
+    }
+
+    fn iteration(
+        &mut self,
+        arena: &ExprArena,
+        condition: ExprId,
+        body: &Stmt,
+        increment: Option<ExprId>,
+    ) {
+        self.expression(arena, condition);
+        self.statement(arena, body);
+        for jump in mem::take(&mut self.jumps) {
+            self.scopes = merge(&jump, &self.scopes);
+        }
+        if let Some(increment) = increment {
+            self.expression(arena, increment);
+        }
+    }
+
+    /// Checks a function's body where it's declared. Its parameters could
+    /// be anything their annotations allow.
+    fn function(&mut self, arena: &ExprArena, function: &FunctionDecl) {
+        let enclosing_scopes = self.scopes.clone();
+        let enclosing_function_scope = mem::replace(&mut self.function_scope, self.scopes.len());
+        let enclosing_jumps = mem::take(&mut self.jumps);
+        let enclosing_returns = mem::replace(&mut self.returns, annotated(&function.return_type));
+
+        self.scopes.push(HashMap::new());
+        for (param, annotation) in function.params.iter().zip(&function.param_types) {
+            let declared = annotation.as_ref().map(Type::named);
+            self.declare(param, Type::Unknown, declared);
+        }
+        self.statements(arena, &function.body);
+
+        self.scopes = enclosing_scopes;
+        self.function_scope = enclosing_function_scope;
+        self.jumps = enclosing_jumps;
+        self.returns = enclosing_returns;
+    }
+
+    fn expression(&mut self, arena: &ExprArena, id: ExprId) -> Type {
+        match &arena[id] {
+            Expr::Literal(literal) => Type::of_literal(literal),
+            Expr::Grouping(inner) => self.expression(arena, *inner),
+            Expr::Variable(name) => self.variable(name),
+            Expr::Assign { name, value } => {
+                let ty = self.expression(arena, *value);
+                self.assign(name, ty);
+                ty
+            }
+            Expr::Unary { operator, right } => {
+                let right = self.expression(arena, *right);
+                match operator.token_type {
+                    TokenType::Minus => {
+                        if !right.fits(Type::Number) {
+                            self.report(operator, msg!("E0601", operator.lexeme, right));
+                        }
+                        Type::Number
+                    }
+                    _ => Type::Bool,
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expression(arena, *left);
+                let right = self.expression(arena, *right);
+                self.binary(operator, left, right)
+            }
+            Expr::Logical { left, right, .. } => {
+                let left = self.expression(arena, *left);
+                // The right operand might not run
+                let before = self.scopes.clone();
+                let right = self.expression(arena, *right);
+                self.scopes = merge(&before, &self.scopes);
+                left.join(right)
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                let callee = self.expression(arena, *callee);
+                let arguments: Vec<Type> = arguments
+                    .iter()
+                    .map(|argument| self.expression(arena, *argument))
+                    .collect();
+                let (arity, params, returns) = match callee {
+                    Type::Function(index) => {
+                        let signature = &self.signatures[index];
+                        (signature.arity, signature.params.clone(), signature.returns)
+                    }
+                    Type::Class(arity) => (arity, Vec::new(), Type::Unknown),
+                    Type::Unknown => (None, Vec::new(), Type::Unknown),
+                    _ => {
+                        self.report(paren, msg!("E0604", callee));
+                        (None, Vec::new(), Type::Unknown)
+                    }
+                };
+                if let Some(arity) = arity.filter(|&arity| arity != arguments.len()) {
+                    self.report(paren, msg!("E0605", arity, arguments.len()));
+                }
+                for (index, (&argument, &param)) in arguments.iter().zip(&params).enumerate() {
+                    if !argument.fits(param) {
+                        self.report(paren, msg!("E0609", param, index + 1, argument));
+                    }
+                }
+                returns
+            }
+            Expr::Get { object, name } => {
+                let object = self.expression(arena, *object);
+                self.property(name, object);
+                Type::Unknown
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.expression(arena, *object);
+                self.property(name, object);
+                self.expression(arena, *value)
+            }
+            Expr::List { elements, .. } => {
+                for element in elements {
+                    self.expression(arena, *element);
+                }
+                Type::List
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(arena, *key);
+                    self.expression(arena, *value);
+                }
+                Type::Map
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.expression(arena, *object);
+                self.expression(arena, *index);
+                self.indexed(bracket, object);
+                Type::Unknown
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = self.expression(arena, *object);
+                self.expression(arena, *index);
+                self.indexed(bracket, object);
+                self.expression(arena, *value)
+            }
+            Expr::This(_) | Expr::Super { .. } | Expr::Error(_) => Type::Unknown,
+        }
+    }
+
+    /// The type of a binary operation on values of types `left` and
+    /// `right`, reporting it if it can't work on them.
+    fn binary(&mut self, operator: &Token, left: Type, right: Type) -> Type {
+        use Type::{Number, String, Unknown};
+
+        match operator.token_type {
+            TokenType::EqualEqual | TokenType::BangEqual => Type::Bool,
+            TokenType::Plus => match (left, right) {
+                (Number, Number) => Number,
+                (String, String) => String,
+                (String, _) | (_, String) if self.coerce_strings => String,
+                (Unknown, _) | (_, Unknown) => Unknown,
+                _ => {
+                    self.report(operator, msg!("E0603", left, right));
+                    Unknown
+                }
+            },
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                match (left, right) {
+                    (Number, Number) | (String, String) | (Unknown, _) | (_, Unknown) => {}
+                    _ => self.report(operator, msg!("E0611", operator.lexeme, left, right)),
+                }
+                Type::Bool
+            }
+            _ => {
+                if !left.fits(Number) || !right.fits(Number) {
+                    self.report(operator, msg!("E0602", operator.lexeme, left, right));
+                }
+                Number
+            }
+        }
+    }
+
+    /// Reports reading or setting `name` on a value of type `object` if it
+    /// can't have properties.
+    fn property(&mut self, name: &Token, object: Type) {
+        if matches!(object, Type::Nil | Type::Bool | Type::Number) {
+            self.report(name, msg!("E0606", name.lexeme, object));
+        }
+    }
+
+    fn indexed(&mut self, bracket: &Token, object: Type) {
+        if !matches!(
+            object,
+            Type::List | Type::Map | Type::String | Type::Unknown
+        ) {
+            self.report(bracket, msg!("E0607", object));
+        }
+    }
+}
+
+/// How often each name is bound anywhere in the program, by a declaration
+/// or an assignment, and which are assigned from functions that don't
+/// declare them. Worked out before checking, since a function can be
+/// called before code after it runs.
+#[derive(Default)]
+struct Bindings {
+    counts: HashMap<Symbol, usize>,
+    assigned_by_closures: HashSet<Symbol>,
+    /// The names declared in each function being walked, innermost last
+    functions: Vec<HashSet<Symbol>>,
+}
+
+impl Bindings {
+    fn count(&self, name: Symbol) -> usize {
+        self.counts.get(&name).copied().unwrap_or(0)
+    }
+
+    fn bind(&mut self, name: &Token) {
+        *self.counts.entry(name.lexeme.symbol()).or_default() += 1;
+    }
+
+    fn declare(&mut self, name: &Token) {
+        self.bind(name);
+        if let Some(function) = self.functions.last_mut() {
+            function.insert(name.lexeme.symbol());
+        }
+    }
+}
+
+impl Visitor for Bindings {
+    fn visit_stmt(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        match stmt {
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } => self.declare(name),
+            Stmt::Function(declaration) => self.declare(&declaration.name),
+            Stmt::Import { path, .. } => {
+                if let TokenType::StringLit(file) = &path.token_type {
+                    if let Some(name) = module_name(file) {
+                        *self.counts.entry(Symbol::intern(name)).or_default() += 1;
+                    }
+                }
+            }
+            Stmt::Try {
+                catch: Some(catch), ..
+            } => self.declare(&catch.name),
+            _ => {}
+        }
+        visit::walk_stmt(self, arena, stmt);
+    }
+
+    fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+        if let Expr::Assign { name, .. } = expr {
+            self.bind(name);
+            let symbol = name.lexeme.symbol();
+            if self
+                .functions
+                .last()
+                .is_some_and(|function| !function.contains(&symbol))
+            {
+                self.assigned_by_closures.insert(symbol);
+            }
+        }
+        visit::walk_expr(self, arena, expr);
+    }
+
+    fn visit_function(&mut self, arena: &ExprArena, function: &FunctionDecl) {
+        let params = function.params.iter().map(|param| param.lexeme.symbol());
+        self.functions.push(params.collect());
+        visit::walk_function(self, arena, function);
+        self.functions.pop();
+    }
+}
+
+/// The names assigned in a list of statements.
+#[derive(Default)]
+struct Assigned {
+    names: HashSet<Symbol>,
+}
+
+impl Visitor for Assigned {
+    fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+        if let Expr::Assign { name, .. } = expr {
+            self.names.insert(name.lexeme.symbol());
+        }
+        visit::walk_expr(self, arena, expr);
+    }
+}
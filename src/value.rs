@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Literal;
+use crate::class::{LoxClass, LoxInstance};
+use crate::exception::Exception;
+use crate::function::LoxFunction;
+use crate::gc::{self, Trace};
+use crate::map::LoxMap;
+use crate::module::Module;
+use crate::native::NativeFunction;
+use crate::string::LoxString;
+
+/// A value the tree-walker computes, stores in a variable or passes around.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    Nil,
+    Str(LoxString),
+    Function(Rc<LoxFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Native(Rc<NativeFunction>),
+    Module(Rc<Module>),
+    Exception(Rc<Exception>),
+    /// Shared, so every variable holding a list sees changes made to it
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap>>),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Lox equality: values of different types are never equal, and objects
+    /// are only equal to themselves.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::Native(l), Value::Native(r)) => Rc::ptr_eq(l, r),
+            (Value::Module(l), Value::Module(r)) => Rc::ptr_eq(l, r),
+            (Value::Exception(l), Value::Exception(r)) => Rc::ptr_eq(l, r),
+            (Value::List(l), Value::List(r)) => Rc::ptr_eq(l, r),
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+
+    /// Where the object this value refers to is, which tells it apart from
+    /// every other object; `None` for `nil`, booleans, numbers and strings.
+    pub(crate) fn identity(&self) -> Option<*const ()> {
+        match self {
+            Value::Number(_) | Value::Bool(_) | Value::Nil | Value::Str(_) => None,
+            Value::Function(function) => Some(Rc::as_ptr(function) as *const ()),
+            Value::Class(class) => Some(Rc::as_ptr(class) as *const ()),
+            Value::Instance(instance) => Some(Rc::as_ptr(instance) as *const ()),
+            Value::Native(native) => Some(Rc::as_ptr(native) as *const ()),
+            Value::Module(module) => Some(Rc::as_ptr(module) as *const ()),
+            Value::Exception(exception) => Some(Rc::as_ptr(exception) as *const ()),
+            Value::List(list) => Some(Rc::as_ptr(list) as *const ()),
+            Value::Map(map) => Some(Rc::as_ptr(map) as *const ()),
+        }
+    }
+
+    /// Calls `edge` with the object this value refers to, if it's one that
+    /// can be part of a cycle.
+    pub(crate) fn trace(&self, edge: &mut dyn FnMut(*const ())) {
+        match self {
+            Value::Function(function) => edge(gc::address(function)),
+            Value::Class(class) => edge(gc::address(class)),
+            Value::Instance(instance) => edge(gc::address(instance)),
+            Value::List(list) => edge(gc::address(list)),
+            Value::Map(map) => edge(gc::address(map)),
+            _ => {}
+        }
+    }
+
+    /// The name the `type()` native reports.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Str(_) => "string",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::Module(_) => "module",
+            Value::Exception(_) => "exception",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+/// The value a literal in the source evaluates to.
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Number(n) => Value::Number(*n),
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Nil => Value::Nil,
+            Literal::Str(s) => Value::Str(s.clone()),
+        }
+    }
+}
+
+/// Formats a value the way Lox prints it: numbers without a trailing `.0`
+/// when they are whole, strings without quotes.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Native(native) => write!(f, "{}", native),
+            Value::Module(module) => write!(f, "{}", module),
+            Value::Exception(exception) => write!(f, "{}", exception),
+            Value::List(list) => print_once(f, list.as_ptr() as *const (), "[...]", |f| {
+                write!(f, "[")?;
+                for (index, element) in list.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }),
+            Value::Map(map) => write!(f, "{}", map.borrow()),
+        }
+    }
+}
+
+impl Trace for RefCell<Vec<Value>> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(list) = self.try_borrow() else {
+            return false;
+        };
+        for value in list.iter() {
+            value.trace(edge);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut list) = self.try_borrow_mut() {
+            let elements = std::mem::take(&mut *list);
+            drop(list);
+            drop(elements);
+        }
+    }
+}
+
+thread_local! {
+    /// The lists and maps being printed right now, innermost last.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Prints a list or map with `print`, or as `repeat` when it is already being
+/// printed further out, so one that contains itself doesn't recurse forever.
+pub(crate) fn print_once(
+    f: &mut fmt::Formatter,
+    container: *const (),
+    repeat: &str,
+    print: impl FnOnce(&mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    if PRINTING.with(|printing| printing.borrow().contains(&container)) {
+        return write!(f, "{}", repeat);
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(container));
+    let result = print(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
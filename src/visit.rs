@@ -0,0 +1,192 @@
+//! Walking the AST without changing it. A pass implements `Visitor`,
+//! overriding the methods for the nodes it cares about, and calls the
+//! `walk_` function of the same node from its override to carry on into
+//! the node's children. The defaults do nothing but walk, so a visitor
+//! that overrides nothing visits every node.
+//!
+//! Children are visited in the order they appear in the source. See `fold`
+//! for passes that rewrite the tree.
+
+use crate::ast::{Expr, ExprArena, FunctionDecl, Stmt};
+
+/// A pass over the AST that reads it. Each method is called for every node
+/// of its kind, with the arena the tree's expressions are in.
+pub trait Visitor {
+    /// The statements of a program, block or body, in order.
+    fn visit_stmts(&mut self, arena: &ExprArena, statements: &[Stmt]) {
+        walk_stmts(self, arena, statements);
+    }
+
+    fn visit_stmt(&mut self, arena: &ExprArena, stmt: &Stmt) {
+        walk_stmt(self, arena, stmt);
+    }
+
+    fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+        walk_expr(self, arena, expr);
+    }
+
+    /// A function, method or getter declaration.
+    fn visit_function(&mut self, arena: &ExprArena, function: &FunctionDecl) {
+        walk_function(self, arena, function);
+    }
+}
+
+pub fn walk_stmts<V: Visitor + ?Sized>(visitor: &mut V, arena: &ExprArena, statements: &[Stmt]) {
+    for stmt in statements {
+        visitor.visit_stmt(arena, stmt);
+    }
+}
+
+/// Visits the expressions, statement lists and functions directly in
+/// `stmt`.
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, arena: &ExprArena, stmt: &Stmt) {
+    match stmt {
+        Stmt::Expression(expr)
+        | Stmt::Print { value: expr, .. }
+        | Stmt::Throw { value: expr, .. } => visitor.visit_expr(arena, &arena[*expr]),
+        Stmt::Var { initializer, .. }
+        | Stmt::Return {
+            value: initializer, ..
+        } => {
+            if let Some(value) = initializer {
+                visitor.visit_expr(arena, &arena[*value]);
+            }
+        }
+        Stmt::Block(statements) => visitor.visit_stmts(arena, statements),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(arena, &arena[*condition]);
+            visitor.visit_stmt(arena, then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(arena, else_branch);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => {
+            visitor.visit_expr(arena, &arena[*condition]);
+            visitor.visit_stmt(arena, body);
+            if let Some(increment) = increment {
+                visitor.visit_expr(arena, &arena[*increment]);
+            }
+        }
+        Stmt::Function(declaration) => visitor.visit_function(arena, declaration),
+        Stmt::Class {
+            superclass,
+            mixins,
+            methods,
+            class_methods,
+            ..
+        } => {
+            if let Some(superclass) = superclass {
+                visitor.visit_expr(arena, &arena[*superclass]);
+            }
+            for mixin in mixins {
+                visitor.visit_expr(arena, &arena[*mixin]);
+            }
+            for method in methods.iter().chain(class_methods) {
+                visitor.visit_function(arena, method);
+            }
+        }
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+            ..
+        } => {
+            visitor.visit_stmts(arena, body);
+            if let Some(catch) = catch {
+                visitor.visit_stmts(arena, &catch.body);
+            }
+            if let Some(finally) = finally {
+                visitor.visit_stmts(arena, finally);
+            }
+        }
+        Stmt::Switch {
+            subject,
+            cases,
+            default,
+            ..
+        } => {
+            visitor.visit_expr(arena, &arena[*subject]);
+            for case in cases {
+                visitor.visit_expr(arena, &arena[case.value]);
+                visitor.visit_stmts(arena, &case.body);
+            }
+            if let Some(default) = default {
+                visitor.visit_stmts(arena, default);
+            }
+        }
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Import { .. } | Stmt::Error(_) => {}
+    }
+}
+
+/// Visits the operands of `expr`.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, arena: &ExprArena, expr: &Expr) {
+    match expr {
+        Expr::Grouping(inner) => visitor.visit_expr(arena, &arena[*inner]),
+        Expr::Assign { value, .. } => visitor.visit_expr(arena, &arena[*value]),
+        Expr::Unary { right, .. } => visitor.visit_expr(arena, &arena[*right]),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            visitor.visit_expr(arena, &arena[*left]);
+            visitor.visit_expr(arena, &arena[*right]);
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            visitor.visit_expr(arena, &arena[*callee]);
+            for argument in arguments {
+                visitor.visit_expr(arena, &arena[*argument]);
+            }
+        }
+        Expr::Get { object, .. } => visitor.visit_expr(arena, &arena[*object]),
+        Expr::Set { object, value, .. } => {
+            visitor.visit_expr(arena, &arena[*object]);
+            visitor.visit_expr(arena, &arena[*value]);
+        }
+        Expr::List { elements, .. } => {
+            for element in elements {
+                visitor.visit_expr(arena, &arena[*element]);
+            }
+        }
+        Expr::Map { entries, .. } => {
+            for (key, value) in entries {
+                visitor.visit_expr(arena, &arena[*key]);
+                visitor.visit_expr(arena, &arena[*value]);
+            }
+        }
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr(arena, &arena[*object]);
+            visitor.visit_expr(arena, &arena[*index]);
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            visitor.visit_expr(arena, &arena[*object]);
+            visitor.visit_expr(arena, &arena[*index]);
+            visitor.visit_expr(arena, &arena[*value]);
+        }
+        Expr::Literal(_)
+        | Expr::Variable(_)
+        | Expr::This(_)
+        | Expr::Super { .. }
+        | Expr::Error(_) => {}
+    }
+}
+
+/// Visits the body of `function`.
+pub fn walk_function<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    arena: &ExprArena,
+    function: &FunctionDecl,
+) {
+    visitor.visit_stmts(arena, &function.body);
+}
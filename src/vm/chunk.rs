@@ -0,0 +1,185 @@
+use super::value::Value;
+
+/// One bytecode instruction. The opcode byte is followed by its operands,
+/// as noted on each variant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// constant index: pushes that constant
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// slot: pushes the local in that slot of the current frame
+    GetLocal,
+    /// slot: stores the top of the stack in that local, leaving it there
+    SetLocal,
+    /// name constant
+    GetGlobal,
+    /// name constant: pops the value into a new global
+    DefineGlobal,
+    /// name constant
+    SetGlobal,
+    /// upvalue index
+    GetUpvalue,
+    /// upvalue index
+    SetUpvalue,
+    /// name constant: replaces an instance with one of its properties
+    GetProperty,
+    /// name constant: pops a value and an instance, sets the field and pushes
+    /// the value back
+    SetProperty,
+    /// name constant: pops the superclass and `this`, pushes the bound method
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Not,
+    Negate,
+    Print,
+    /// 16-bit forward offset
+    Jump,
+    /// 16-bit forward offset, taken if the top of the stack is falsey; leaves
+    /// the condition on the stack
+    JumpIfFalse,
+    /// 16-bit backward offset
+    Loop,
+    /// argument count
+    Call,
+    /// function constant, then an (is_local, index) byte pair per upvalue
+    Closure,
+    /// Moves the local on top of the stack into the heap, then pops it
+    CloseUpvalue,
+    Return,
+    /// name constant: pushes a new class
+    Class,
+    /// Copies the methods of the superclass under the subclass on top of the
+    /// stack into it, then pops the subclass
+    Inherit,
+    /// name constant: pops a closure and adds it to the class under it
+    Method,
+
+    // Superinstructions, each doing the work of a pair of the ones above
+    // that `--op-histogram` finds running back to back most in loops. The
+    // compiler fuses a pair only when no jump lands between them.
+    /// two slots: GetLocal twice
+    GetLocals,
+    /// slot: SetLocal then Pop, as an assignment statement
+    SetLocalPop,
+    /// constant index: Constant then Add
+    AddConstant,
+    /// constant index: Constant then Subtract
+    SubtractConstant,
+    /// constant index: Constant then Less
+    LessConstant,
+    /// slot, then name constant: GetLocal then GetProperty, as `this.x`
+    GetLocalProperty,
+    /// name constant: SetProperty then Pop, as an assignment statement
+    SetPropertyPop,
+    /// 16-bit forward offset: pops the condition and jumps if it was falsey,
+    /// as JumpIfFalse with a Pop where it goes to and where it doesn't
+    PopJumpIfFalse,
+}
+
+/// Every opcode, in declaration order, so a byte can be decoded by index.
+const OPCODES: [OpCode; 47] = {
+    use OpCode::*;
+    [
+        Constant,
+        Nil,
+        True,
+        False,
+        Pop,
+        GetLocal,
+        SetLocal,
+        GetGlobal,
+        DefineGlobal,
+        SetGlobal,
+        GetUpvalue,
+        SetUpvalue,
+        GetProperty,
+        SetProperty,
+        GetSuper,
+        Equal,
+        NotEqual,
+        Greater,
+        GreaterEqual,
+        Less,
+        LessEqual,
+        Add,
+        Subtract,
+        Multiply,
+        Divide,
+        Modulo,
+        Not,
+        Negate,
+        Print,
+        Jump,
+        JumpIfFalse,
+        Loop,
+        Call,
+        Closure,
+        CloseUpvalue,
+        Return,
+        Class,
+        Inherit,
+        Method,
+        GetLocals,
+        SetLocalPop,
+        AddConstant,
+        SubtractConstant,
+        LessConstant,
+        GetLocalProperty,
+        SetPropertyPop,
+        PopJumpIfFalse,
+    ]
+};
+
+// Checked when compiling, so adding an opcode in one place but not the
+// other can't go unnoticed
+const _: () = {
+    let mut i = 0;
+    while i < OPCODES.len() {
+        assert!(OPCODES[i] as usize == i, "OPCODES out of order");
+        i += 1;
+    }
+};
+
+impl OpCode {
+    /// The opcode a byte encodes, or `None` if it isn't one.
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).copied()
+    }
+}
+
+/// A compiled function body: its bytecode, the constants the code refers to
+/// by index, and the source position every byte was compiled from.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    /// Line and column of the token each byte of `code` came from
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, position: (usize, usize)) {
+        self.code.push(byte);
+        self.positions.push(position);
+    }
+
+    /// Adds a constant, returning its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
@@ -0,0 +1,816 @@
+use std::rc::Rc;
+
+use super::chunk::{Chunk, OpCode};
+use super::value::{Function, Value};
+use crate::ast::{Expr, ExprArena, ExprId, FunctionDecl, Literal, Stmt};
+use crate::diagnostic::{Diagnostics, Phase};
+use crate::optimizer::truthiness;
+use crate::token::{Token, TokenType};
+
+/// What kind of function is being compiled, which decides what its slot 0
+/// holds and what a bare `return` gives back.
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// A local variable, living in a stack slot of the function's frame.
+struct Local {
+    name: String,
+    depth: usize,
+    /// Whether a closure captured it, so leaving its scope has to close the
+    /// upvalue rather than just pop it
+    is_captured: bool,
+}
+
+/// Where a closure finds one of its captured variables when it's created:
+/// in a local slot of the enclosing function, or in one of its upvalues.
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+/// The jumps `break` and `continue` left to patch in the innermost loop.
+struct Loop {
+    /// Scope depth the loop itself is at; everything deeper is popped when
+    /// jumping out of the body
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Compiler state for one function being compiled. Nested declarations
+/// push a new one, so the stack of them mirrors the nesting in the source.
+struct FunctionState {
+    function: Function,
+    kind: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+    /// Set once the chunk has run out of constants and that's been
+    /// reported, so the ones after it aren't reported again
+    constants_full: bool,
+    /// Where the last instruction emitted starts, for `fuse`, or `None`
+    /// when a jump lands after it
+    last_op: Option<usize>,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionType, name: Option<String>) -> Self {
+        // Slot 0 holds the function being called, or `this` in a method
+        let receiver = match kind {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Script | FunctionType::Function => "",
+        };
+        FunctionState {
+            function: Function {
+                name,
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+            constants_full: false,
+            last_op: None,
+        }
+    }
+}
+
+/// Compiles a resolved program into the bytecode of its top-level script.
+/// The resolver has already rejected the programs that make no sense, so
+/// the only errors left are running into the bytecode's size limits.
+/// `arena` holds the program's expressions.
+///
+/// Code that can never run isn't compiled: statements after a `return`,
+/// `break` or `continue` in the same block, and the branches a literal
+/// condition rules out, as `--opt` drops them from the tree. `lint`'s
+/// `unreachable-code` warns about the first.
+pub fn compile(arena: &ExprArena, statements: &[Stmt]) -> Result<Function, Diagnostics> {
+    let mut compiler = Compiler {
+        arena,
+        states: vec![FunctionState::new(FunctionType::Script, None)],
+        position: (1, 1),
+        diagnostics: Diagnostics::default(),
+    };
+    for stmt in statements {
+        compiler.statement(stmt);
+    }
+    compiler.emit_return();
+
+    if !compiler.diagnostics.has_errors() {
+        Ok(compiler.states.pop().unwrap().function)
+    } else {
+        Err(compiler.diagnostics)
+    }
+}
+
+struct Compiler<'a> {
+    arena: &'a ExprArena,
+    states: Vec<FunctionState>,
+    /// Line and column of the token being compiled, recorded with every byte
+    /// emitted
+    position: (usize, usize),
+    diagnostics: Diagnostics,
+}
+
+impl Compiler<'_> {
+    fn statement(&mut self, stmt: &Stmt) {
+        // Until something in it says otherwise, what the statement compiles
+        // to is on its own line, not the one before's
+        if let Some(token) = stmt.token(self.arena) {
+            self.at(token);
+        }
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.expression(*expr);
+                self.emit(OpCode::Pop);
+            }
+            Stmt::Print { value, .. } => {
+                self.expression(*value);
+                self.emit(OpCode::Print);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                match initializer {
+                    Some(initializer) => self.expression(*initializer),
+                    None => self.emit(OpCode::Nil),
+                }
+                self.define_variable(name);
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match truthiness(&self.arena[*condition]) {
+                Some(true) => self.statement(then_branch),
+                Some(false) => {
+                    if let Some(else_branch) = else_branch {
+                        self.statement(else_branch);
+                    }
+                }
+                None => self.if_statement(*condition, then_branch, else_branch.as_deref()),
+            },
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                if truthiness(&self.arena[*condition]) != Some(false) {
+                    self.while_loop(*condition, body, *increment);
+                }
+            }
+            Stmt::Break(keyword) => {
+                self.at(keyword);
+                self.discard_loop_locals();
+                let jump = self.emit_jump(OpCode::Jump);
+                self.current_loop().breaks.push(jump);
+            }
+            Stmt::Continue(keyword) => {
+                self.at(keyword);
+                self.discard_loop_locals();
+                let jump = self.emit_jump(OpCode::Jump);
+                self.current_loop().continues.push(jump);
+            }
+            Stmt::Function(declaration) => {
+                // Declared before the body so the function can call itself
+                if self.state().scope_depth > 0 {
+                    self.add_local(&declaration.name);
+                }
+                self.function(declaration, FunctionType::Function);
+                if self.state().scope_depth == 0 {
+                    self.define_global(&declaration.name);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                self.at(keyword);
+                match value {
+                    Some(value) => {
+                        self.expression(*value);
+                        self.emit(OpCode::Return);
+                    }
+                    None => self.emit_return(),
+                }
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+                class_methods,
+            } => {
+                if let Some(getter) = methods.iter().find(|method| method.is_getter) {
+                    self.at(&getter.name);
+                    self.error(msg!("E0507", "getters"));
+                }
+                if let Some(Expr::Variable(mixin)) = mixins.first().map(|&mixin| &self.arena[mixin])
+                {
+                    self.at(mixin);
+                    self.error(msg!("E0507", "mixins"));
+                }
+                if let Some(method) = class_methods.first() {
+                    self.at(&method.name);
+                    self.error(msg!("E0507", "class methods"));
+                }
+                self.class(name, *superclass, methods);
+            }
+            Stmt::Import { keyword, .. } => {
+                self.at(keyword);
+                self.error(msg!("E0506"));
+            }
+            Stmt::Throw { keyword, .. } | Stmt::Try { keyword, .. } => {
+                self.at(keyword);
+                self.error(msg!("E0507", "exceptions"));
+            }
+            Stmt::Switch { keyword, .. } => {
+                self.at(keyword);
+                self.error(msg!("E0507", "switch"));
+            }
+            Stmt::Error(_) => unreachable!("resolver rejects error nodes"),
+        }
+    }
+
+    /// Compiles a block's statements, up to one that jumps out of it.
+    fn statements(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.statement(stmt);
+            if matches!(
+                stmt,
+                Stmt::Return { .. } | Stmt::Break(_) | Stmt::Continue(_)
+            ) {
+                break;
+            }
+        }
+    }
+
+    fn if_statement(&mut self, condition: ExprId, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        self.expression(condition);
+        let then_jump = self.emit_jump(OpCode::PopJumpIfFalse);
+        self.statement(then_branch);
+        match else_branch {
+            Some(else_branch) => {
+                let else_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(then_jump);
+                self.statement(else_branch);
+                self.patch_jump(else_jump);
+            }
+            None => self.patch_jump(then_jump),
+        }
+    }
+
+    /// Also compiles desugared `for` loops, whose increment runs after the
+    /// body and is where `continue` jumps to.
+    fn while_loop(&mut self, condition: ExprId, body: &Stmt, increment: Option<ExprId>) {
+        let loop_start = self.chunk().code.len();
+        self.state().last_op = None;
+        self.expression(condition);
+        let exit_jump = self.emit_jump(OpCode::PopJumpIfFalse);
+
+        let scope_depth = self.state().scope_depth;
+        self.state().loops.push(Loop {
+            scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.statement(body);
+        let finished = self.state().loops.pop().unwrap();
+
+        for jump in finished.continues {
+            self.patch_jump(jump);
+        }
+        if let Some(increment) = increment {
+            self.expression(increment);
+            self.emit(OpCode::Pop);
+        }
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        for jump in finished.breaks {
+            self.patch_jump(jump);
+        }
+    }
+
+    /// Compiles a function's body and emits the code creating its closure.
+    fn function(&mut self, declaration: &FunctionDecl, kind: FunctionType) {
+        self.at(&declaration.name);
+        let name = declaration.name.lexeme.to_string();
+        self.states.push(FunctionState::new(kind, Some(name)));
+        self.begin_scope();
+        for param in &declaration.params {
+            self.add_local(param);
+        }
+        self.state().function.arity = declaration.params.len();
+        self.statements(&declaration.body);
+        self.emit_return();
+
+        let FunctionState {
+            mut function,
+            upvalues,
+            ..
+        } = self.states.pop().unwrap();
+        function.upvalue_count = upvalues.len();
+        self.at(&declaration.name);
+        let constant = self.make_constant(Value::Function(Rc::new(function)));
+        self.emit_with(OpCode::Closure, constant);
+        for upvalue in upvalues {
+            self.emit_byte(u8::from(upvalue.is_local));
+            self.emit_byte(upvalue.index);
+        }
+    }
+
+    fn class(&mut self, name: &Token, superclass: Option<ExprId>, methods: &[Rc<FunctionDecl>]) {
+        self.at(name);
+        let constant = self.identifier_constant(&name.lexeme);
+        if self.state().scope_depth > 0 {
+            self.add_local(name);
+        }
+        self.emit_with(OpCode::Class, constant);
+        if self.state().scope_depth == 0 {
+            self.define_global(name);
+        }
+
+        if let Some(superclass) = superclass {
+            // Methods of a subclass see `super` as a local of a scope of
+            // their own
+            self.expression(superclass);
+            self.begin_scope();
+            self.add_local_named("super");
+            self.at(name);
+            self.named_variable(&name.lexeme);
+            if let Expr::Variable(superclass) = &self.arena[superclass] {
+                self.at(superclass);
+            }
+            self.emit(OpCode::Inherit);
+        }
+
+        // The class stays on the stack while its methods are added to it
+        self.at(name);
+        self.named_variable(&name.lexeme);
+        for method in methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(method, kind);
+            self.at(&method.name);
+            let constant = self.identifier_constant(&method.name.lexeme);
+            self.emit_with(OpCode::Method, constant);
+        }
+        self.emit(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn expression(&mut self, expr: ExprId) {
+        let arena = self.arena;
+        match &arena[expr] {
+            Expr::Literal(literal) => match literal {
+                Literal::Nil => self.emit(OpCode::Nil),
+                Literal::Bool(true) => self.emit(OpCode::True),
+                Literal::Bool(false) => self.emit(OpCode::False),
+                Literal::Number(n) => self.emit_constant(Value::Number(*n)),
+                Literal::Str(s) => self.emit_constant(Value::Str(s.clone())),
+            },
+            Expr::Grouping(inner) => self.expression(*inner),
+            Expr::Variable(name) => {
+                self.at(name);
+                self.named_variable(&name.lexeme);
+            }
+            Expr::Assign { name, value } => {
+                self.expression(*value);
+                self.at(name);
+                self.set_variable(&name.lexeme);
+            }
+            Expr::Unary { operator, right } => {
+                self.expression(*right);
+                self.at(operator);
+                match operator.token_type {
+                    TokenType::Minus => self.emit(OpCode::Negate),
+                    TokenType::Bang => self.emit(OpCode::Not),
+                    _ => unreachable!("parser only builds unary '-' and '!'"),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(*left);
+                self.expression(*right);
+                self.at(operator);
+                let op = match operator.token_type {
+                    TokenType::EqualEqual => OpCode::Equal,
+                    TokenType::BangEqual => OpCode::NotEqual,
+                    TokenType::Greater => OpCode::Greater,
+                    TokenType::GreaterEqual => OpCode::GreaterEqual,
+                    TokenType::Less => OpCode::Less,
+                    TokenType::LessEqual => OpCode::LessEqual,
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    TokenType::Star => OpCode::Multiply,
+                    TokenType::Slash => OpCode::Divide,
+                    TokenType::Percent => OpCode::Modulo,
+                    _ => unreachable!("parser only builds binary operators"),
+                };
+                self.emit(op);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                // The result is whichever operand decided it, so the left
+                // one is only popped when the right one is needed
+                self.expression(*left);
+                self.at(operator);
+                let end_jump = if operator.token_type == TokenType::Or {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    let end_jump = self.emit_jump(OpCode::Jump);
+                    self.patch_jump(else_jump);
+                    end_jump
+                } else {
+                    self.emit_jump(OpCode::JumpIfFalse)
+                };
+                self.emit(OpCode::Pop);
+                self.expression(*right);
+                self.patch_jump(end_jump);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.expression(*callee);
+                for argument in arguments {
+                    self.expression(*argument);
+                }
+                self.at(paren);
+                // The parser caps arguments at 255
+                self.emit_with(OpCode::Call, arguments.len() as u8);
+            }
+            Expr::Get { object, name } => {
+                self.expression(*object);
+                self.at(name);
+                let constant = self.identifier_constant(&name.lexeme);
+                self.emit_with(OpCode::GetProperty, constant);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.expression(*object);
+                self.expression(*value);
+                self.at(name);
+                let constant = self.identifier_constant(&name.lexeme);
+                self.emit_with(OpCode::SetProperty, constant);
+            }
+            Expr::This(keyword) => {
+                self.at(keyword);
+                self.named_variable("this");
+            }
+            Expr::Super { keyword, method } => {
+                self.at(keyword);
+                self.named_variable("this");
+                self.named_variable("super");
+                self.at(method);
+                let constant = self.identifier_constant(&method.lexeme);
+                self.emit_with(OpCode::GetSuper, constant);
+            }
+            Expr::List { bracket, .. } => {
+                self.at(bracket);
+                self.error(msg!("E0507", "lists"));
+            }
+            Expr::Index { bracket, .. } | Expr::SetIndex { bracket, .. } => {
+                self.at(bracket);
+                self.error(msg!("E0507", "indexing"));
+            }
+            Expr::Map { brace, .. } => {
+                self.at(brace);
+                self.error(msg!("E0507", "maps"));
+            }
+            Expr::Error(_) => unreachable!("resolver rejects error nodes"),
+        }
+    }
+
+    /// Emits the code pushing the variable `name`: a local, a captured
+    /// variable of an enclosing function, or else a global.
+    fn named_variable(&mut self, name: &str) {
+        let level = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(level, name) {
+            self.emit_with(OpCode::GetLocal, slot);
+        } else if let Some(index) = self.resolve_upvalue(level, name) {
+            self.emit_with(OpCode::GetUpvalue, index);
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit_with(OpCode::GetGlobal, constant);
+        }
+    }
+
+    /// Emits the code storing the top of the stack in the variable `name`.
+    fn set_variable(&mut self, name: &str) {
+        let level = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(level, name) {
+            self.emit_with(OpCode::SetLocal, slot);
+        } else if let Some(index) = self.resolve_upvalue(level, name) {
+            self.emit_with(OpCode::SetUpvalue, index);
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit_with(OpCode::SetGlobal, constant);
+        }
+    }
+
+    /// The slot of the innermost local called `name` in the function at
+    /// `level` of the nesting.
+    fn resolve_local(&self, level: usize, name: &str) -> Option<u8> {
+        let slot = self.states[level]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)?;
+        Some(slot as u8)
+    }
+
+    /// The upvalue index through which the function at `level` reaches
+    /// `name` in an enclosing function, adding upvalues along the way.
+    fn resolve_upvalue(&mut self, level: usize, name: &str) -> Option<u8> {
+        if level == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(level - 1, name) {
+            self.states[level - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(level, slot, true));
+        }
+        let index = self.resolve_upvalue(level - 1, name)?;
+        Some(self.add_upvalue(level, index, false))
+    }
+
+    fn add_upvalue(&mut self, level: usize, index: u8, is_local: bool) -> u8 {
+        let upvalues = &self.states[level].upvalues;
+        if let Some(existing) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return existing as u8;
+        }
+        if upvalues.len() > u8::MAX as usize {
+            self.error(msg!("E0503"));
+            return 0;
+        }
+        self.states[level]
+            .upvalues
+            .push(UpvalueRef { index, is_local });
+        (self.states[level].upvalues.len() - 1) as u8
+    }
+
+    /// Stores the value just compiled in the variable `name` declares: a new
+    /// local in a block, or a global at the top level.
+    fn define_variable(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(name);
+        } else {
+            self.define_global(name);
+        }
+    }
+
+    fn define_global(&mut self, name: &Token) {
+        self.at(name);
+        let constant = self.identifier_constant(&name.lexeme);
+        self.emit_with(OpCode::DefineGlobal, constant);
+    }
+
+    fn add_local(&mut self, name: &Token) {
+        self.at(name);
+        self.add_local_named(&name.lexeme);
+    }
+
+    fn add_local_named(&mut self, name: &str) {
+        if self.state().locals.len() > u8::MAX as usize {
+            self.error(msg!("E0502"));
+            return;
+        }
+        let depth = self.state().scope_depth;
+        self.state().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    /// Leaves a scope, popping its locals off the stack.
+    fn end_scope(&mut self) {
+        self.state().scope_depth -= 1;
+        let depth = self.state().scope_depth;
+        while self
+            .state()
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > depth)
+        {
+            let local = self.state().locals.pop().unwrap();
+            self.emit_discard(&local);
+        }
+    }
+
+    /// Pops the locals declared inside the innermost loop's body, ahead of
+    /// jumping out of it. The compiler keeps them, since the code after the
+    /// jump still sees them.
+    fn discard_loop_locals(&mut self) {
+        let depth = self.current_loop().scope_depth;
+        let state = self.states.last().unwrap();
+        let count = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .count();
+        let captured: Vec<bool> = state.locals[state.locals.len() - count..]
+            .iter()
+            .rev()
+            .map(|local| local.is_captured)
+            .collect();
+        for is_captured in captured {
+            if is_captured {
+                self.emit(OpCode::CloseUpvalue);
+            } else {
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
+    fn emit_discard(&mut self, local: &Local) {
+        if local.is_captured {
+            self.emit(OpCode::CloseUpvalue);
+        } else {
+            self.emit(OpCode::Pop);
+        }
+    }
+
+    /// Emits a bare return: `this` from an initializer, `nil` otherwise.
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionType::Initializer {
+            self.emit_with(OpCode::GetLocal, 0);
+        } else {
+            self.emit(OpCode::Nil);
+        }
+        self.emit(OpCode::Return);
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_with(OpCode::Constant, constant);
+    }
+
+    /// Adds a constant to the current chunk, returning its index.
+    fn make_constant(&mut self, value: Value) -> u8 {
+        let index = self.chunk().add_constant(value);
+        if index > u8::MAX as usize {
+            if !self.state().constants_full {
+                self.state().constants_full = true;
+                self.error(msg!("E0501"));
+            }
+            return 0;
+        }
+        index as u8
+    }
+
+    /// A string constant for a variable or property name. Reuses an existing
+    /// one, since the same names tend to come up over and over.
+    fn identifier_constant(&mut self, name: &str) -> u8 {
+        let existing = self
+            .chunk()
+            .constants
+            .iter()
+            .position(|constant| matches!(constant, Value::Str(s) if &**s == name));
+        match existing {
+            Some(index) if index <= u8::MAX as usize => index as u8,
+            _ => self.make_constant(Value::Str(name.into())),
+        }
+    }
+
+    /// Emits a jump with a placeholder offset, returning where the offset is
+    /// so `patch_jump` can fill it in.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit(op);
+        self.emit_byte(0xff);
+        self.emit_byte(0xff);
+        self.chunk().code.len() - 2
+    }
+
+    /// Points the jump whose offset is at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        self.state().last_op = None;
+        let jump = self.chunk().code.len() - offset - 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error(msg!("E0504"));
+            return;
+        };
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit(OpCode::Loop);
+        let offset = self.chunk().code.len() - loop_start + 2;
+        let Ok(offset) = u16::try_from(offset) else {
+            self.error(msg!("E0505"));
+            return;
+        };
+        let [high, low] = offset.to_be_bytes();
+        self.emit_byte(high);
+        self.emit_byte(low);
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        if self.fuse(op) {
+            return;
+        }
+        self.state().last_op = Some(self.chunk().code.len());
+        self.emit_byte(op as u8);
+    }
+
+    /// Turns the last instruction into the superinstruction doing its work
+    /// and `op`'s, if there's one, so `op`'s operands follow its own. Not
+    /// when a jump lands between them, which would then land inside it.
+    fn fuse(&mut self, op: OpCode) -> bool {
+        let Some(last) = self.state().last_op else {
+            return false;
+        };
+        let fused = match (OpCode::from_byte(self.chunk().code[last]), op) {
+            (Some(OpCode::GetLocal), OpCode::GetLocal) => OpCode::GetLocals,
+            (Some(OpCode::SetLocal), OpCode::Pop) => OpCode::SetLocalPop,
+            (Some(OpCode::Constant), OpCode::Add) => OpCode::AddConstant,
+            (Some(OpCode::Constant), OpCode::Subtract) => OpCode::SubtractConstant,
+            (Some(OpCode::Constant), OpCode::Less) => OpCode::LessConstant,
+            (Some(OpCode::GetLocal), OpCode::GetProperty) => OpCode::GetLocalProperty,
+            (Some(OpCode::SetProperty), OpCode::Pop) => OpCode::SetPropertyPop,
+            _ => return false,
+        };
+        let position = self.position;
+        let chunk = self.chunk();
+        chunk.code[last] = fused as u8;
+        // A runtime error is reported at the last byte read, so an operator
+        // failing points at itself rather than at its constant
+        if matches!(op, OpCode::Add | OpCode::Subtract | OpCode::Less) {
+            chunk.positions[last..].fill(position);
+        }
+        true
+    }
+
+    fn emit_with(&mut self, op: OpCode, operand: u8) {
+        self.emit(op);
+        self.emit_byte(operand);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let position = self.position;
+        self.chunk().write(byte, position);
+    }
+
+    /// Attributes the code emitted next to `token`.
+    fn at(&mut self, token: &Token) {
+        self.position = (token.line, token.column);
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state().function.chunk
+    }
+
+    fn current_loop(&mut self) -> &mut Loop {
+        self.state()
+            .loops
+            .last_mut()
+            .expect("resolver keeps break and continue inside loops")
+    }
+
+    fn error(&mut self, msg: String) {
+        let (line, column) = self.position;
+        self.diagnostics.error(Phase::Compile, line, column, msg);
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use super::chunk::{Chunk, OpCode};
+use super::value::{Function, Value};
+
+/// Disassembles `function` and then, in the order they appear, every
+/// function declared inside it.
+pub fn disassemble_function(function: &Function, out: &mut String) {
+    disassemble_chunk(&function.chunk, &function.to_string(), out);
+    for constant in &function.chunk.constants {
+        if let Value::Function(inner) = constant {
+            out.push('\n');
+            disassemble_function(inner, out);
+        }
+    }
+}
+
+/// Writes every instruction in `chunk` under a `== name ==` header, one per
+/// line, in the format of clox's disassembler: the byte offset, the source
+/// line (`|` when it's the same as the previous instruction's), the opcode
+/// and its operands.
+pub fn disassemble_chunk(chunk: &Chunk, name: &str, out: &mut String) {
+    writeln!(out, "== {} ==", name).unwrap();
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+}
+
+/// Writes the instruction at `offset`, returning the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    write!(out, "{:04} ", offset).unwrap();
+    let line = chunk.positions[offset].0;
+    if offset > 0 && line == chunk.positions[offset - 1].0 {
+        out.push_str("   | ");
+    } else {
+        write!(out, "{:4} ", line).unwrap();
+    }
+
+    let byte = chunk.code[offset];
+    let Some(op) = OpCode::from_byte(byte) else {
+        writeln!(out, "Unknown opcode {}", byte).unwrap();
+        return offset + 1;
+    };
+
+    let name = op_name(op);
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method
+        | OpCode::AddConstant
+        | OpCode::SubtractConstant
+        | OpCode::LessConstant
+        | OpCode::SetPropertyPop => {
+            let constant = chunk.code[offset + 1];
+            let value = &chunk.constants[constant as usize];
+            writeln!(out, "{:<16} {:4} '{}'", name, constant, value).unwrap();
+            offset + 2
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::SetLocalPop
+        | OpCode::Call => {
+            writeln!(out, "{:<16} {:4}", name, chunk.code[offset + 1]).unwrap();
+            offset + 2
+        }
+        OpCode::GetLocals => {
+            let (first, second) = (chunk.code[offset + 1], chunk.code[offset + 2]);
+            writeln!(out, "{:<16} {:4} {:4}", name, first, second).unwrap();
+            offset + 3
+        }
+        OpCode::GetLocalProperty => {
+            let (slot, constant) = (chunk.code[offset + 1], chunk.code[offset + 2]);
+            let value = &chunk.constants[constant as usize];
+            writeln!(out, "{:<16} {:4} {:4} '{}'", name, slot, constant, value).unwrap();
+            offset + 3
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::PopJumpIfFalse | OpCode::Loop => {
+            let jump = u16::from_be_bytes([chunk.code[offset + 1], chunk.code[offset + 2]]);
+            let next = offset + 3;
+            let target = if op == OpCode::Loop {
+                next - jump as usize
+            } else {
+                next + jump as usize
+            };
+            writeln!(out, "{:<16} {:4} -> {}", name, offset, target).unwrap();
+            next
+        }
+        OpCode::Closure => {
+            let constant = chunk.code[offset + 1];
+            let value = &chunk.constants[constant as usize];
+            writeln!(out, "{:<16} {:4} {}", name, constant, value).unwrap();
+
+            let Value::Function(function) = value else {
+                return offset + 2;
+            };
+            let mut offset = offset + 2;
+            for _ in 0..function.upvalue_count {
+                let kind = if chunk.code[offset] == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                let index = chunk.code[offset + 1];
+                writeln!(
+                    out,
+                    "{:04}    |                     {} {}",
+                    offset, kind, index
+                )
+                .unwrap();
+                offset += 2;
+            }
+            offset
+        }
+        _ => {
+            writeln!(out, "{}", name).unwrap();
+            offset + 1
+        }
+    }
+}
+
+/// clox's name for an opcode, e.g. `OP_GET_LOCAL` for `GetLocal`.
+pub(crate) fn op_name(op: OpCode) -> String {
+    let mut name = String::from("OP");
+    for ch in format!("{:?}", op).chars() {
+        if ch.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(ch.to_ascii_uppercase());
+    }
+    name
+}
@@ -0,0 +1,83 @@
+//! Counts for `run --backend=vm --op-histogram`: how many times each opcode
+//! ran, and each pair that ran one right after the other in the same call,
+//! which is where a superinstruction doing the work of both would save a
+//! trip through dispatch.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::chunk::OpCode;
+use super::debug::op_name;
+
+/// How many of the most frequent pairs the table lists
+const PAIRS_SHOWN: usize = 10;
+
+/// One row of `Histogram::table`.
+fn row(table: &mut String, name: &str, count: &str, share: &str) {
+    writeln!(table, "{:<40} {:>12} {:>7}", name, count, share).unwrap();
+}
+
+/// The opcodes run so far, see `Vm::set_histogram`.
+pub struct Histogram {
+    ops: Vec<u64>,
+    pairs: HashMap<(u8, u8), u64>,
+    /// The last opcode run, with how many calls were in progress
+    previous: Option<(u8, usize)>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            ops: vec![0; 256],
+            pairs: HashMap::new(),
+            previous: None,
+        }
+    }
+}
+
+impl Histogram {
+    /// Counts the opcode `byte`, run with `depth` calls in progress. It only
+    /// makes a pair with the one before if that ran in the same call.
+    pub fn record(&mut self, byte: u8, depth: usize) {
+        self.ops[byte as usize] += 1;
+        if let Some((previous, previous_depth)) = self.previous {
+            if previous_depth == depth {
+                *self.pairs.entry((previous, byte)).or_default() += 1;
+            }
+        }
+        self.previous = Some((byte, depth));
+    }
+
+    /// The counts as two tables under headers, most frequent first: every
+    /// opcode that ran, and the pairs that ran most.
+    pub fn table(&self) -> String {
+        let total: u64 = self.ops.iter().sum();
+        let mut table = String::new();
+        let share = |count: u64| format!("{:.2}%", count as f64 * 100.0 / total.max(1) as f64);
+        let name = |byte: u8| OpCode::from_byte(byte).map_or_else(|| byte.to_string(), op_name);
+
+        let header = msg!("E0059");
+        let columns: Vec<&str> = header.split_whitespace().collect();
+        row(&mut table, columns[0], columns[1], columns[2]);
+        let mut ops: Vec<(u8, u64)> = (0..=u8::MAX)
+            .map(|byte| (byte, self.ops[byte as usize]))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        ops.sort_by_key(|&(byte, count)| (std::cmp::Reverse(count), byte));
+        for (byte, count) in ops {
+            row(&mut table, &name(byte), &count.to_string(), &share(count));
+        }
+
+        let header = msg!("E0060");
+        let columns: Vec<&str> = header.split_whitespace().collect();
+        table.push('\n');
+        row(&mut table, columns[0], columns[1], columns[2]);
+        let mut pairs: Vec<(&(u8, u8), &u64)> = self.pairs.iter().collect();
+        pairs.sort_by_key(|&(pair, &count)| (std::cmp::Reverse(count), *pair));
+        for (&(first, second), &count) in pairs.into_iter().take(PAIRS_SHOWN) {
+            let pair = format!("{} {}", name(first), name(second));
+            row(&mut table, &pair, &count.to_string(), &share(count));
+        }
+        table
+    }
+}
@@ -0,0 +1,751 @@
+//! A second backend in the style of clox: `compiler` turns the resolved AST
+//! into bytecode, which `Vm` runs on a value stack. Selected with
+//! `run --backend=vm`.
+
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod histogram;
+pub mod value;
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::gc;
+use crate::native;
+use crate::scanner::Limits;
+use crate::string::LoxString;
+use crate::suggest;
+use crate::Output;
+use chunk::OpCode;
+use histogram::Histogram;
+use value::{BoundMethod, Class, Closure, Function, Instance, Native, Upvalue, Value};
+
+/// A function call in progress.
+struct CallFrame {
+    closure: Rc<Closure>,
+    /// Index of the next byte to run in the closure's chunk
+    ip: usize,
+    /// Stack index of the frame's slot 0, the callee or `this`
+    slots: usize,
+}
+
+/// Stack-based virtual machine running compiled bytecode.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    /// Upvalues still pointing at stack slots, to close when those slots are
+    /// popped
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    /// See `Interpreter::set_coerce_strings`
+    coerce_strings: bool,
+    /// Whether each instruction is written to stderr before it runs, see
+    /// `set_trace`
+    trace: bool,
+    /// Calls deeper than this are a "Stack overflow." runtime error
+    max_frames: usize,
+    /// The opcodes run so far, if counting them, see `set_histogram`
+    histogram: Option<Box<Histogram>>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            coerce_strings: false,
+            trace: false,
+            max_frames: Limits::default().max_call_depth,
+            histogram: None,
+        };
+        vm.define_standard_library();
+        vm
+    }
+
+    pub fn set_coerce_strings(&mut self, coerce_strings: bool) {
+        self.coerce_strings = coerce_strings;
+    }
+
+    /// Makes more than `frames` calls in progress a "Stack overflow."
+    /// runtime error, rather than `Limits::max_call_depth`'s default.
+    pub fn set_max_frames(&mut self, frames: usize) {
+        self.max_frames = frames;
+    }
+
+    /// Writes the stack and each instruction before it runs to
+    /// `out.stderr`, like clox's `DEBUG_TRACE_EXECUTION`, indented by the
+    /// depth of calls.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Counts every opcode run, and every pair run one after the other,
+    /// for `histogram`.
+    pub fn set_histogram(&mut self, histogram: bool) {
+        self.histogram = histogram.then(Box::default);
+    }
+
+    /// The table of the opcodes run so far, if `set_histogram` is on.
+    pub fn histogram(&self) -> Option<String> {
+        self.histogram.as_ref().map(|histogram| histogram.table())
+    }
+
+    /// Runs a compiled script. A runtime error comes back as a diagnostic at
+    /// the line and column it happened at.
+    pub fn interpret(&mut self, script: Function, out: &mut Output) -> Result<(), Diagnostic> {
+        let closure = Rc::new(Closure {
+            function: Rc::new(script),
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        self.call(closure, 0)
+            .expect("a script takes no arguments and is the first frame");
+
+        let result = self.run(out);
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    /// Runs instructions until the script returns. The loop without
+    /// `--trace` and `--op-histogram` is a separate one, so the usual run
+    /// doesn't test for them before every instruction.
+    fn run(&mut self, out: &mut Output) -> Result<(), Diagnostic> {
+        let result = if self.trace || self.histogram.is_some() {
+            loop {
+                self.before_step(out);
+                match self.step(out) {
+                    Ok(false) => {}
+                    Ok(true) => break Ok(()),
+                    Err(message) => break Err(message),
+                }
+            }
+        } else {
+            loop {
+                match self.step(out) {
+                    Ok(false) => {}
+                    Ok(true) => break Ok(()),
+                    Err(message) => break Err(message),
+                }
+            }
+        };
+        result.map_err(|message| {
+            let frame = self.frame();
+            let chunk = &frame.closure.function.chunk;
+            let (line, column) = chunk.positions[frame.ip - 1];
+            Diagnostic::error(Phase::Runtime, line, column, message)
+        })
+    }
+
+    /// Traces and counts the instruction about to run, as asked to.
+    #[cold]
+    fn before_step(&mut self, out: &mut Output) {
+        if self.trace {
+            self.trace_instruction(out);
+        }
+        if let Some(histogram) = &mut self.histogram {
+            let frame = self.frames.last().expect("code only runs inside a frame");
+            let byte = frame.closure.function.chunk.code[frame.ip];
+            histogram.record(byte, self.frames.len());
+        }
+    }
+
+    /// Writes the stack, bottom first, and the instruction about to run.
+    fn trace_instruction(&self, out: &mut Output) {
+        let indent = "  ".repeat(self.frames.len() - 1);
+        let mut stack = String::new();
+        for value in &self.stack {
+            write!(stack, "[ {} ]", value).unwrap();
+        }
+        let frame = self.frame();
+        let mut instruction = String::new();
+        debug::disassemble_instruction(&frame.closure.function.chunk, frame.ip, &mut instruction);
+        writeln!(out.stderr, "{}          {}", indent, stack).unwrap();
+        write!(out.stderr, "{}{}", indent, instruction).unwrap();
+    }
+
+    /// Runs one instruction. Returns whether the script has finished, or the
+    /// message of a runtime error.
+    #[inline(always)]
+    fn step(&mut self, out: &mut Output) -> Result<bool, String> {
+        let byte = self.read_byte();
+        let op = OpCode::from_byte(byte).expect("compiler only emits valid opcodes");
+
+        match op {
+            OpCode::Constant => {
+                let constant = self.read_constant();
+                self.push(constant);
+            }
+            OpCode::Nil => self.push(Value::Nil),
+            OpCode::True => self.push(Value::Bool(true)),
+            OpCode::False => self.push(Value::Bool(false)),
+            OpCode::Pop => {
+                self.pop();
+            }
+            OpCode::GetLocal => {
+                let slot = self.frame().slots + self.read_byte() as usize;
+                self.push(trusted::at(&self.stack, slot).clone());
+            }
+            OpCode::GetLocals => {
+                let slots = self.frame().slots;
+                let first = slots + self.read_byte() as usize;
+                let second = slots + self.read_byte() as usize;
+                self.push(trusted::at(&self.stack, first).clone());
+                self.push(trusted::at(&self.stack, second).clone());
+            }
+            OpCode::SetLocal => {
+                let slot = self.frame().slots + self.read_byte() as usize;
+                *trusted::at_mut(&mut self.stack, slot) = self.peek(0).clone();
+            }
+            OpCode::SetLocalPop => {
+                let slot = self.frame().slots + self.read_byte() as usize;
+                let value = self.pop();
+                *trusted::at_mut(&mut self.stack, slot) = value;
+            }
+            OpCode::GetGlobal => {
+                let name = self.read_string();
+                match self.globals.get(&*name) {
+                    Some(value) => self.push(value.clone()),
+                    None => return Err(self.undefined(&name)),
+                }
+            }
+            OpCode::DefineGlobal => {
+                let name = self.read_string();
+                let value = self.pop();
+                self.globals.insert(name.to_string(), value);
+            }
+            OpCode::SetGlobal => {
+                let name = self.read_string();
+                let value = self.peek(0).clone();
+                match self.globals.get_mut(&*name) {
+                    Some(global) => *global = value,
+                    None => return Err(self.undefined(&name)),
+                }
+            }
+            OpCode::GetUpvalue => {
+                let index = self.read_byte() as usize;
+                let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                let value = match &*upvalue.borrow() {
+                    Upvalue::Open(slot) => self.stack[*slot].clone(),
+                    Upvalue::Closed(value) => value.clone(),
+                };
+                self.push(value);
+            }
+            OpCode::SetUpvalue => {
+                let index = self.read_byte() as usize;
+                let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                let value = self.peek(0).clone();
+                match &mut *upvalue.borrow_mut() {
+                    Upvalue::Open(slot) => self.stack[*slot] = value,
+                    Upvalue::Closed(closed) => *closed = value,
+                };
+            }
+            OpCode::GetProperty => {
+                let name = self.read_string();
+                let value = get_property(self.pop(), &name)?;
+                self.push(value);
+            }
+            OpCode::GetLocalProperty => {
+                let slot = self.frame().slots + self.read_byte() as usize;
+                let name = self.read_string();
+                let value = get_property(trusted::at(&self.stack, slot).clone(), &name)?;
+                self.push(value);
+            }
+            OpCode::SetProperty => {
+                let value = self.set_property()?;
+                self.push(value);
+            }
+            OpCode::SetPropertyPop => {
+                self.set_property()?;
+            }
+            OpCode::GetSuper => {
+                let name = self.read_string();
+                let Value::Class(superclass) = self.pop() else {
+                    unreachable!("`super` is only ever defined as a class")
+                };
+                let this = self.pop();
+                let method = bind_method(&superclass, &name, this)?;
+                self.push(method);
+            }
+            OpCode::Equal => {
+                let (left, right) = self.pop_pair();
+                self.push(Value::Bool(left.equals(&right)));
+            }
+            OpCode::NotEqual => {
+                let (left, right) = self.pop_pair();
+                self.push(Value::Bool(!left.equals(&right)));
+            }
+            OpCode::Greater => self.comparison(Ordering::is_gt)?,
+            OpCode::GreaterEqual => self.comparison(Ordering::is_ge)?,
+            OpCode::Less => self.comparison(Ordering::is_lt)?,
+            OpCode::LessEqual => self.comparison(Ordering::is_le)?,
+            OpCode::LessConstant => {
+                let constant = self.read_constant();
+                self.push(constant);
+                self.comparison(Ordering::is_lt)?;
+            }
+            OpCode::Add => {
+                let (left, right) = self.pop_pair();
+                let sum = self.add(left, right)?;
+                self.push(sum);
+            }
+            OpCode::AddConstant => {
+                let constant = self.read_constant();
+                let left = self.pop();
+                let sum = self.add(left, constant)?;
+                self.push(sum);
+            }
+            OpCode::Subtract => self.arithmetic(|l, r| l - r)?,
+            OpCode::SubtractConstant => {
+                let constant = self.read_constant();
+                match (self.stack.last_mut(), constant) {
+                    (Some(Value::Number(l)), Value::Number(r)) => *l -= r,
+                    _ => return Err(msg!("E0302")),
+                }
+            }
+            OpCode::Multiply => self.arithmetic(|l, r| l * r)?,
+            OpCode::Divide | OpCode::Modulo => {
+                if let (Value::Number(_), Value::Number(0.0)) = (self.peek(1), self.peek(0)) {
+                    return Err(msg!("E0313"));
+                }
+                if op == OpCode::Divide {
+                    self.arithmetic(|l, r| l / r)?;
+                } else {
+                    self.arithmetic(|l, r| l % r)?;
+                }
+            }
+            OpCode::Not => {
+                let value = self.pop();
+                self.push(Value::Bool(!value.is_truthy()));
+            }
+            OpCode::Negate => match self.pop() {
+                Value::Number(n) => self.push(Value::Number(-n)),
+                _ => return Err(msg!("E0301")),
+            },
+            OpCode::Print => {
+                let value = self.pop();
+                writeln!(out.stdout, "{}", value).unwrap();
+            }
+            OpCode::Jump => {
+                let offset = self.read_u16();
+                self.frame_mut().ip += offset as usize;
+            }
+            OpCode::JumpIfFalse => {
+                let offset = self.read_u16();
+                if !self.peek(0).is_truthy() {
+                    self.frame_mut().ip += offset as usize;
+                }
+            }
+            OpCode::PopJumpIfFalse => {
+                let offset = self.read_u16();
+                if !self.pop().is_truthy() {
+                    self.frame_mut().ip += offset as usize;
+                }
+            }
+            OpCode::Loop => {
+                let offset = self.read_u16();
+                self.frame_mut().ip -= offset as usize;
+            }
+            OpCode::Call => {
+                let argc = self.read_byte() as usize;
+                let callee = self.peek(argc).clone();
+                self.call_value(callee, argc)?;
+            }
+            OpCode::Closure => {
+                let Value::Function(function) = self.read_constant() else {
+                    unreachable!("Closure's operand is always a function")
+                };
+                let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                for _ in 0..function.upvalue_count {
+                    let is_local = self.read_byte() == 1;
+                    let index = self.read_byte() as usize;
+                    let upvalue = if is_local {
+                        self.capture_upvalue(self.frame().slots + index)
+                    } else {
+                        Rc::clone(&self.frame().closure.upvalues[index])
+                    };
+                    upvalues.push(upvalue);
+                }
+                // Without upvalues it refers to nothing a cycle could go through
+                let closure = Closure { function, upvalues };
+                let closure = if closure.upvalues.is_empty() {
+                    Rc::new(closure)
+                } else {
+                    gc::tracked(closure)
+                };
+                self.push(Value::Closure(closure));
+            }
+            OpCode::CloseUpvalue => {
+                self.close_upvalues(self.stack.len() - 1);
+                self.pop();
+            }
+            OpCode::Return => {
+                let result = self.pop();
+                let frame = self.frames.pop().unwrap();
+                self.close_upvalues(frame.slots);
+                self.stack.truncate(frame.slots);
+                if self.frames.is_empty() {
+                    return Ok(true);
+                }
+                self.push(result);
+            }
+            OpCode::Class => {
+                let name = self.read_string();
+                self.push(Value::Class(gc::tracked(RefCell::new(Class {
+                    name: name.to_string(),
+                    methods: HashMap::new(),
+                }))));
+            }
+            OpCode::Inherit => {
+                let Value::Class(superclass) = self.peek(1) else {
+                    return Err(msg!("E0310"));
+                };
+                let Value::Class(subclass) = self.peek(0) else {
+                    unreachable!("Inherit always follows a class")
+                };
+                // Copied down now, so later lookups don't walk the chain.
+                // The subclass's own methods are added after, overriding them.
+                let methods = superclass.borrow().methods.clone();
+                subclass.borrow_mut().methods.extend(methods);
+                self.pop();
+            }
+            OpCode::Method => {
+                let name = self.read_string();
+                let Value::Closure(method) = self.pop() else {
+                    unreachable!("Method's value is always a closure")
+                };
+                let Value::Class(class) = self.peek(0) else {
+                    unreachable!("methods are only added to a class")
+                };
+                class.borrow_mut().methods.insert(name.to_string(), method);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Calls `callee` with the `argc` arguments on top of the stack.
+    fn call_value(&mut self, callee: Value, argc: usize) -> Result<(), String> {
+        let callee_slot = self.stack.len() - argc - 1;
+        match callee {
+            Value::Closure(closure) => self.call(closure, argc),
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), argc)
+            }
+            Value::Class(class) => {
+                let instance = Instance {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                };
+                self.stack[callee_slot] = Value::Instance(gc::tracked(RefCell::new(instance)));
+                let init = class.borrow().methods.get("init").cloned();
+                match init {
+                    Some(init) => self.call(init, argc),
+                    None if argc != 0 => Err(msg!("E0306", 0, argc)),
+                    None => Ok(()),
+                }
+            }
+            Value::Native(native) => {
+                if argc != native.arity {
+                    return Err(msg!("E0306", native.arity, argc));
+                }
+                let result = (native.function)(&self.stack[callee_slot + 1..])?;
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            }
+            _ => Err(msg!("E0305")),
+        }
+    }
+
+    /// Starts running `closure` in a new frame.
+    fn call(&mut self, closure: Rc<Closure>, argc: usize) -> Result<(), String> {
+        if argc != closure.function.arity {
+            return Err(msg!("E0306", closure.function.arity, argc));
+        }
+        if self.frames.len() >= self.max_frames {
+            return Err(msg!("E0314"));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - argc - 1,
+        });
+        Ok(())
+    }
+
+    /// The open upvalue for stack `slot`, shared by every closure capturing
+    /// that variable.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+        let upvalue = gc::tracked(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Closes every open upvalue at or above stack index `from`, copying the
+    /// variable out of the stack before its slot goes away.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let Upvalue::Open(slot) = *upvalue.borrow() else {
+                return false;
+            };
+            if slot < from {
+                return true;
+            }
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    /// `left + right`: numbers added, or strings joined.
+    fn add(&self, left: Value, right: Value) -> Result<Value, String> {
+        Ok(match (left, right) {
+            (Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+            (Value::Str(l), Value::Str(r)) => Value::Str(l.concat(&r)),
+            (Value::Str(l), r) if self.coerce_strings => {
+                Value::Str(l.concat(&r.to_string().into()))
+            }
+            (l, Value::Str(r)) if self.coerce_strings => {
+                Value::Str(LoxString::from(l.to_string()).concat(&r))
+            }
+            _ => return Err(msg!("E0303")),
+        })
+    }
+
+    /// Pops a value and the instance under it, and sets the field the name
+    /// operand names to the value, returning it.
+    fn set_property(&mut self) -> Result<Value, String> {
+        let name = self.read_string();
+        let value = self.pop();
+        let Value::Instance(instance) = self.pop() else {
+            return Err(msg!("E0308"));
+        };
+        instance
+            .borrow_mut()
+            .fields
+            .insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    fn arithmetic(&mut self, op: fn(f64, f64) -> f64) -> Result<(), String> {
+        match self.pop_pair() {
+            (Value::Number(l), Value::Number(r)) => {
+                self.push(Value::Number(op(l, r)));
+                Ok(())
+            }
+            _ => Err(msg!("E0302")),
+        }
+    }
+
+    /// `op` applied to two numbers, or to two strings by their bytes like
+    /// the tree-walker compares them.
+    fn comparison(&mut self, op: fn(Ordering) -> bool) -> Result<(), String> {
+        let ordering = match self.pop_pair() {
+            (Value::Number(l), Value::Number(r)) => l.partial_cmp(&r),
+            (Value::Str(l), Value::Str(r)) => Some(l.as_str().cmp(r.as_str())),
+            _ => return Err(msg!("E0302")),
+        };
+        // NaN is neither less than, equal to nor greater than anything
+        self.push(Value::Bool(ordering.is_some_and(op)));
+        Ok(())
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("code only runs inside a frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames
+            .last_mut()
+            .expect("code only runs inside a frame")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = *trusted::at(&frame.closure.function.chunk.code, frame.ip);
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let code = &frame.closure.function.chunk.code;
+        let bytes = [
+            *trusted::at(code, frame.ip),
+            *trusted::at(code, frame.ip + 1),
+        ];
+        frame.ip += 2;
+        u16::from_be_bytes(bytes)
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_byte() as usize;
+        trusted::at(&self.frame().closure.function.chunk.constants, index).clone()
+    }
+
+    fn read_string(&mut self) -> LoxString {
+        match self.read_constant() {
+            Value::Str(name) => name,
+            _ => unreachable!("name operands are always string constants"),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("compiler keeps the stack balanced")
+    }
+
+    /// Pops the two operands of a binary operator, left first.
+    fn pop_pair(&mut self) -> (Value, Value) {
+        let right = self.pop();
+        let left = self.pop();
+        (left, right)
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        trusted::at(&self.stack, self.stack.len() - 1 - distance)
+    }
+
+    /// The same natives `native::define_standard_library` gives the
+    /// tree-walker.
+    fn define_standard_library(&mut self) {
+        self.define_native("clock", 0, |_| Ok(Value::Number(native::clock())));
+        self.define_native("len", 1, |args| match &args[0] {
+            Value::Str(s) => Ok(Value::Number(s.chars().count() as f64)),
+            _ => Err(msg!("E0311")),
+        });
+        self.define_native("str", 1, |args| Ok(Value::Str(args[0].to_string().into())));
+        self.define_native("num", 1, |args| match &args[0] {
+            Value::Number(n) => Ok(Value::Number(*n)),
+            Value::Str(s) => Ok(native::parse_number(s).map_or(Value::Nil, Value::Number)),
+            _ => Err(msg!("E0312")),
+        });
+        self.define_native("type", 1, |args| Ok(Value::Str(args[0].type_name().into())));
+        self.define_native("input", 0, |_| {
+            Ok(native::read_line().map_or(Value::Nil, |line| Value::Str(line.into())))
+        });
+        self.define_native("assert", 2, |args| {
+            if args[0].is_truthy() {
+                Ok(Value::Nil)
+            } else {
+                Err(msg!("E0334", args[1]))
+            }
+        });
+    }
+
+    /// The error for the global `name` not being defined, suggesting the
+    /// closest of those that are.
+    fn undefined(&self, name: &str) -> String {
+        let names = self.globals.keys().map(String::as_str);
+        suggest::did_you_mean(msg!("E0304", name), name, names)
+    }
+
+    fn define_native(
+        &mut self,
+        name: &'static str,
+        arity: usize,
+        function: fn(&[Value]) -> Result<Value, String>,
+    ) {
+        let native = Native {
+            name,
+            arity,
+            function,
+        };
+        self.globals
+            .insert(name.to_string(), Value::Native(Rc::new(native)));
+    }
+}
+
+/// The property `name` of `object`: a field, or else a method bound to it.
+fn get_property(object: Value, name: &str) -> Result<Value, String> {
+    let Value::Instance(instance) = object else {
+        return Err(msg!("E0307"));
+    };
+    let field = instance.borrow().fields.get(name).cloned();
+    if let Some(value) = field {
+        return Ok(value);
+    }
+    let class = Rc::clone(&instance.borrow().class);
+    if !class.borrow().methods.contains_key(name) {
+        let (class, instance) = (class.borrow(), instance.borrow());
+        let names = instance.fields.keys().chain(class.methods.keys());
+        let names = names.map(String::as_str);
+        return Err(suggest::did_you_mean(msg!("E0309", name), name, names));
+    }
+    bind_method(&class, name, Value::Instance(instance))
+}
+
+/// `class`'s method `name` bound to `receiver`.
+fn bind_method(class: &RefCell<Class>, name: &str, receiver: Value) -> Result<Value, String> {
+    let class = class.borrow();
+    match class.methods.get(name) {
+        Some(method) => Ok(Value::BoundMethod(gc::tracked(BoundMethod {
+            receiver,
+            method: Rc::clone(method),
+        }))),
+        None => {
+            let names = class.methods.keys().map(String::as_str);
+            Err(suggest::did_you_mean(msg!("E0309", name), name, names))
+        }
+    }
+}
+
+/// Indexing into what the compiler makes sure is there: the operands of
+/// every instruction in its chunk, the values on the stack an instruction
+/// reads, the slot of every local. Checked anyway, so a bug in the compiler
+/// is a panic, unless built with the `unchecked` feature, which skips the
+/// bounds checks to go faster and makes such a bug undefined behavior
+/// instead. Pops stay checked: skipping theirs measured slower.
+#[cfg(not(feature = "unchecked"))]
+mod trusted {
+    pub fn at<T>(slice: &[T], index: usize) -> &T {
+        &slice[index]
+    }
+
+    pub fn at_mut<T>(slice: &mut [T], index: usize) -> &mut T {
+        &mut slice[index]
+    }
+}
+
+#[cfg(feature = "unchecked")]
+mod trusted {
+    #[inline(always)]
+    pub fn at<T>(slice: &[T], index: usize) -> &T {
+        // SAFETY: see the module; the compiler never emits code reading
+        // past the end
+        unsafe { slice.get_unchecked(index) }
+    }
+
+    #[inline(always)]
+    pub fn at_mut<T>(slice: &mut [T], index: usize) -> &mut T {
+        // SAFETY: as for `at`
+        unsafe { slice.get_unchecked_mut(index) }
+    }
+}
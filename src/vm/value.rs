@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::chunk::Chunk;
+use crate::gc::{self, Trace};
+use crate::string::LoxString;
+
+/// A value on the VM's stack, in a constant pool or in a variable.
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(LoxString),
+    /// Only ever a constant, wrapped into a `Closure` before code sees it
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Native(Rc<Native>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Lox equality: values of different types are never equal, and objects
+    /// are only equal to themselves.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Closure(l), Value::Closure(r)) => Rc::ptr_eq(l, r),
+            (Value::Native(l), Value::Native(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            (Value::BoundMethod(l), Value::BoundMethod(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+
+    /// Calls `edge` with the object this value refers to, if it's one that
+    /// can be part of a cycle.
+    pub(crate) fn trace(&self, edge: &mut dyn FnMut(*const ())) {
+        match self {
+            Value::Closure(closure) => edge(gc::address(closure)),
+            Value::Class(class) => edge(gc::address(class)),
+            Value::Instance(instance) => edge(gc::address(instance)),
+            Value::BoundMethod(bound) => edge(gc::address(bound)),
+            _ => {}
+        }
+    }
+
+    /// The name the `type()` native reports, the same as the tree-walker's.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Function(_) | Value::Closure(_) | Value::Native(_) | Value::BoundMethod(_) => {
+                "function"
+            }
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+/// Formats a value the way Lox prints it, matching the tree-walker.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Closure(closure) => write!(f, "{}", closure.function),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.borrow().name),
+            Value::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Value::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+        }
+    }
+}
+
+// Written by hand: objects can refer back to themselves, so a derived impl
+// could recurse forever
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{:?}", s),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+/// A compiled function, or the top-level script when it has no name.
+#[derive(Debug, Default)]
+pub struct Function {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+/// A function together with the variables it captured.
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable. Open while the variable is still on the stack, and
+/// closed over a copy of it once its scope ends.
+pub enum Upvalue {
+    /// Index of the variable's stack slot
+    Open(usize),
+    Closed(Value),
+}
+
+/// A function implemented in Rust.
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Result<Value, String>,
+}
+
+pub struct Class {
+    pub name: String,
+    pub methods: HashMap<String, Rc<Closure>>,
+}
+
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<String, Value>,
+}
+
+/// A method read off an instance, remembering the instance as its `this`.
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl Trace for Closure {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        for upvalue in &self.upvalues {
+            edge(gc::address(upvalue));
+        }
+        true
+    }
+}
+
+impl Trace for RefCell<Upvalue> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(upvalue) = self.try_borrow() else {
+            return false;
+        };
+        if let Upvalue::Closed(value) = &*upvalue {
+            value.trace(edge);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut upvalue) = self.try_borrow_mut() {
+            if let Upvalue::Closed(value) = &mut *upvalue {
+                let value = std::mem::replace(value, Value::Nil);
+                drop(upvalue);
+                drop(value);
+            }
+        }
+    }
+}
+
+impl Trace for RefCell<Class> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(class) = self.try_borrow() else {
+            return false;
+        };
+        for method in class.methods.values() {
+            edge(gc::address(method));
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut class) = self.try_borrow_mut() {
+            let methods = std::mem::take(&mut class.methods);
+            drop(class);
+            drop(methods);
+        }
+    }
+}
+
+impl Trace for RefCell<Instance> {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(instance) = self.try_borrow() else {
+            return false;
+        };
+        edge(gc::address(&instance.class));
+        for value in instance.fields.values() {
+            value.trace(edge);
+        }
+        true
+    }
+
+    fn clear(&self) {
+        if let Ok(mut instance) = self.try_borrow_mut() {
+            let fields = std::mem::take(&mut instance.fields);
+            drop(instance);
+            drop(fields);
+        }
+    }
+}
+
+impl Trace for BoundMethod {
+    fn trace(&self, edge: &mut dyn FnMut(*const ())) -> bool {
+        self.receiver.trace(edge);
+        edge(gc::address(&self.method));
+        true
+    }
+}
@@ -0,0 +1,142 @@
+//! Bindings for running Lox in a browser, built with the `wasm` feature
+//! and `wasm-pack`. Commands already collect what they print in an
+//! `Output` instead of writing to stdout, so these hand that back as
+//! strings, along with the errors rendered as the CLI shows them.
+
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::format::Format;
+use crate::scanner::{Limits, Scanner};
+use crate::{json, parser::Parser, ExitStatus, Options, Output};
+
+/// The limits everything here runs with. Lower than the CLI's, which are
+/// for running on a thread with `lox::STACK_SIZE` of stack: in the browser
+/// there's only the wasm stack, 1 MiB unless the module was linked with
+/// more, and the engine's own stack under it, which it doesn't say the
+/// size of.
+pub fn limits() -> Limits {
+    Limits {
+        max_nesting_depth: 64,
+        max_call_depth: 128,
+        max_call_stack: 512 * 1024,
+        ..Limits::default()
+    }
+}
+
+/// The tokens of `source` as JSON, with any scanning errors:
+/// `{"tokens": [...], "errors": ["[line 1, col 5] Error: ...", ...]}`.
+#[wasm_bindgen]
+pub fn tokenize_to_json(source: &str) -> String {
+    let options = Options {
+        format: Format::Json,
+        limits: limits(),
+        ..Options::default()
+    };
+    let mut out = Output::default();
+    let status = crate::tokenize(source, &options, &mut out);
+    // Nothing is printed when scanning gives up, such as at the token limit
+    let tokens = match out.stdout.trim_end() {
+        "" => "[]",
+        tokens => tokens,
+    };
+    format!(r#"{{"tokens":{},"errors":{}}}"#, tokens, errors(&status))
+}
+
+/// `source` parsed as an expression, as JSON, with any errors:
+/// `{"ast": {...}, "errors": [...]}`. The parse is tolerant, so the tree
+/// is there even when there are errors, with `Error` nodes where parsing
+/// failed.
+#[wasm_bindgen]
+pub fn parse_to_json(source: &str) -> String {
+    let limits = limits();
+    let mut scanner = Scanner::new(source, &limits);
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    parser.set_max_depth(limits.max_nesting_depth);
+    let expr = parser.parse_tolerant();
+
+    let mut diagnostics = scanner.diagnostics;
+    diagnostics.extend(parser.diagnostics);
+    let status = ExitStatus::from_diagnostics(diagnostics);
+    format!(
+        r#"{{"ast":{},"errors":{}}}"#,
+        json::expr(&parser.arena, &parser.arena[expr]),
+        errors(&status)
+    )
+}
+
+/// What running a program printed and how it ended.
+#[wasm_bindgen]
+pub struct RunOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+}
+
+#[wasm_bindgen]
+impl RunOutput {
+    #[wasm_bindgen(getter)]
+    pub fn stdout(&self) -> String {
+        self.stdout.clone()
+    }
+
+    /// The errors that stopped the program, one per line
+    #[wasm_bindgen(getter)]
+    pub fn stderr(&self) -> String {
+        self.stderr.clone()
+    }
+
+    /// The code the CLI would exit with: 0, 65 for compile errors or 70
+    /// for a runtime error
+    #[wasm_bindgen(getter, js_name = exitCode)]
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+}
+
+/// Runs `source` as a program, capturing its output. `vm` picks the
+/// bytecode backend instead of the tree-walker.
+#[wasm_bindgen]
+pub fn run_capture_output(source: &str, vm: bool) -> RunOutput {
+    let options = Options {
+        backend: if vm {
+            crate::Backend::Vm
+        } else {
+            crate::Backend::TreeWalk
+        },
+        limits: limits(),
+        ..Options::default()
+    };
+    let mut out = Output::default();
+    let status = crate::run(source, &options, &mut out);
+    for error in diagnostics(&status) {
+        writeln!(out.stderr, "{}", error).unwrap();
+    }
+    RunOutput {
+        stdout: out.stdout,
+        stderr: out.stderr,
+        exit_code: status.exit_code(),
+    }
+}
+
+/// The diagnostics `status` carries, rendered as the CLI prints them.
+fn diagnostics(status: &ExitStatus) -> Vec<String> {
+    match status {
+        ExitStatus::CompileError(diagnostics) | ExitStatus::Warnings(diagnostics) => {
+            diagnostics.iter().map(ToString::to_string).collect()
+        }
+        ExitStatus::RuntimeError(error) => vec![error.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// The diagnostics `status` carries, as a JSON array of strings.
+fn errors(status: &ExitStatus) -> String {
+    let errors: Vec<String> = diagnostics(status)
+        .iter()
+        .map(|error| json::string(error))
+        .collect();
+    format!("[{}]", errors.join(","))
+}
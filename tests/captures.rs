@@ -0,0 +1,20 @@
+use lox::{captures, ExitStatus, Options, Output};
+
+#[test]
+fn functions_list_the_variables_they_capture_and_how() {
+    let source = "fun counter() {\n  var count = 0;\n  var step = 1;\n  fun next() {\n\
+                  count = count + step;\n    fun show() { print count; }\n    return count;\n  }\n\
+                  return next;\n}\nvar global = 1;\nfun get() { return global; }";
+    let mut out = Output::default();
+    assert!(matches!(
+        captures(source, &Options::default(), &mut out),
+        ExitStatus::Success
+    ));
+    assert_eq!(
+        out.stdout,
+        "counter (line 1) captures nothing.\n\
+         next (line 4) captures:\n  count, declared on line 2, mutated\n  step, declared on line 3, read-only\n\
+         show (line 6) captures:\n  count, declared on line 2, read-only\n\
+         get (line 12) captures nothing.\n"
+    );
+}
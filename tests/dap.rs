@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+use lox::dap::serve;
+use lox::lsp::json::Json;
+use lox::Options;
+
+/// A sink the test can still read after handing it to the adapter.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serves `requests`, each a command and its arguments written as JSON, to
+/// the debug adapter. Returns every message it wrote back.
+fn session(requests: &[(&str, String)]) -> Vec<Json> {
+    let input: String = requests
+        .iter()
+        .enumerate()
+        .map(|(seq, (command, arguments))| {
+            let body = format!(
+                r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+                seq + 1,
+                command,
+                arguments
+            );
+            format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+        })
+        .collect();
+    let output = Captured::default();
+    serve(Cursor::new(input), output.clone(), &Options::default()).unwrap();
+
+    let mut rest = String::from_utf8(output.0.borrow().clone()).unwrap();
+    let mut replies = Vec::new();
+    while let Some((header, after)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header["Content-Length: ".len()..].parse().unwrap();
+        replies.push(Json::parse(&after[..length]).unwrap());
+        rest = after[length..].to_string();
+    }
+    replies
+}
+
+/// The body of the response to the request numbered `seq`.
+fn body(replies: &[Json], seq: usize) -> &Json {
+    let seq = Json::Number(seq as f64);
+    let reply = replies
+        .iter()
+        .find(|reply| reply.get("request_seq") == Some(&seq))
+        .unwrap();
+    assert_eq!(reply.get("success"), Some(&Json::Bool(true)), "{}", reply);
+    reply.get("body").unwrap()
+}
+
+/// Each event sent, with its body's `reason`, `output` or `exitCode`.
+fn events(replies: &[Json]) -> Vec<(String, String)> {
+    replies
+        .iter()
+        .filter(|reply| reply.get("type").and_then(Json::as_str) == Some("event"))
+        .map(|event| {
+            let body = event.get("body").unwrap();
+            let detail = ["reason", "output", "exitCode"]
+                .iter()
+                .find_map(|key| body.get(key))
+                .map_or_else(String::new, |detail| match detail {
+                    Json::String(text) => text.clone(),
+                    other => other.to_string(),
+                });
+            let name = event.get("event").and_then(Json::as_str).unwrap();
+            (name.to_string(), detail)
+        })
+        .collect()
+}
+
+/// The `name = value` of each of the variables in a `variables` body.
+fn variables(body: &Json) -> Vec<String> {
+    let Some(Json::Array(variables)) = body.get("variables") else {
+        panic!("{}", body);
+    };
+    variables
+        .iter()
+        .map(|variable| {
+            let field = |key| variable.get(key).and_then(Json::as_str).unwrap();
+            format!("{} = {}", field("name"), field("value"))
+        })
+        .collect()
+}
+
+#[test]
+fn an_editor_stops_at_a_breakpoint_looks_around_and_steps() {
+    let path = env::temp_dir().join(format!("dap_{}.lox", std::process::id()));
+    let source =
+        "var a = 1;\nfun f(n) {\n  var m = n * 2;\n  print m;\n}\nf(a + 1);\nprint \"done\";";
+    fs::write(&path, source).unwrap();
+    let program = Json::String(path.display().to_string());
+    let replies = session(&[
+        ("initialize", r#"{"adapterID":"lox"}"#.to_string()),
+        ("launch", format!(r#"{{"program":{}}}"#, program)),
+        (
+            "setBreakpoints",
+            format!(
+                r#"{{"source":{{"path":{}}},"breakpoints":[{{"line":4}}]}}"#,
+                program
+            ),
+        ),
+        ("configurationDone", "{}".to_string()),
+        ("stackTrace", r#"{"threadId":1}"#.to_string()),
+        ("scopes", r#"{"frameId":0}"#.to_string()),
+        ("variables", r#"{"variablesReference":1}"#.to_string()),
+        ("variables", r#"{"variablesReference":2}"#.to_string()),
+        ("next", r#"{"threadId":1}"#.to_string()),
+        ("stackTrace", r#"{"threadId":1}"#.to_string()),
+        ("continue", r#"{"threadId":1}"#.to_string()),
+        ("disconnect", "{}".to_string()),
+    ]);
+    fs::remove_file(&path).unwrap();
+
+    let event = |name: &str, detail: &str| (name.to_string(), detail.to_string());
+    assert_eq!(
+        events(&replies),
+        [
+            event("initialized", ""),
+            event("stopped", "breakpoint"),
+            event("output", "4\n"),
+            event("stopped", "step"),
+            event("output", "done\n"),
+            event("exited", "0"),
+            event("terminated", ""),
+        ]
+    );
+    let verified = body(&replies, 3).get("breakpoints").unwrap().to_string();
+    assert_eq!(verified, r#"[{"verified":true,"line":4}]"#);
+
+    let Some(Json::Array(frames)) = body(&replies, 5).get("stackFrames") else {
+        panic!("{:?}", replies);
+    };
+    let frames: Vec<(&str, usize)> = frames
+        .iter()
+        .map(|frame| {
+            let name = frame.get("name").and_then(Json::as_str).unwrap();
+            (name, frame.get("line").and_then(Json::as_usize).unwrap())
+        })
+        .collect();
+    assert_eq!(frames, [("f", 4), ("<script>", 6)]);
+
+    let scopes = body(&replies, 6).get("scopes").unwrap().to_string();
+    assert!(scopes.contains(r#""name":"Locals""#), "{}", scopes);
+    assert_eq!(variables(body(&replies, 7)), ["m = 4", "n = 2"]);
+    assert_eq!(variables(body(&replies, 8)), ["a = 1", "f = <fn f>"]);
+
+    // `next` stepped out of `f`, which had nothing after the `print`
+    let frames = body(&replies, 10).get("stackFrames").unwrap().to_string();
+    assert!(frames.contains(r#""line":7"#), "{}", frames);
+}
+
+#[test]
+fn requests_that_need_a_stopped_program_fail_before_it_runs() {
+    let replies = session(&[
+        ("initialize", "{}".to_string()),
+        ("stackTrace", r#"{"threadId":1}"#.to_string()),
+        ("launch", r#"{"program":"/no/such/file.lox"}"#.to_string()),
+        ("evaluate", r#"{"expression":"1"}"#.to_string()),
+    ]);
+    let failures: Vec<&str> = replies
+        .iter()
+        .filter(|reply| reply.get("success") == Some(&Json::Bool(false)))
+        .map(|reply| reply.get("message").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(
+        failures,
+        [
+            "stackTrace needs the program stopped.",
+            "Failed to read file /no/such/file.lox",
+            "Unknown request: evaluate",
+        ]
+    );
+}
@@ -0,0 +1,52 @@
+use lox::format::Format;
+use lox::{doc, ExitStatus, Options, Output};
+
+const SOURCE: &str = "/// Adds two numbers.\n///\n/// Both of them.\nfun add(a, b) { return a + b; }\n\n\
+                      /// Not about anything: a blank line follows.\n\nclass Point {\n  /// Makes one.\n  init(x) {}\n}\n\
+                      var x = 1; /// After code, so not about `f`\nfun f() {}";
+
+fn documented(options: &Options) -> String {
+    let mut out = Output::default();
+    let status = doc(SOURCE, options, &mut out);
+    assert!(matches!(status, ExitStatus::Success), "{:?}", status);
+    out.stdout
+}
+
+#[test]
+fn doc_comments_are_listed_with_their_declarations() {
+    assert_eq!(
+        documented(&Options::default()),
+        "## `fun add(a, b)`\n\nLine 4\n\nAdds two numbers.\n\nBoth of them.\n\n\
+         ## `class Point`\n\nLine 8\n\n### `init(x)`\n\nLine 10\n\nMakes one.\n\n\
+         ## `fun f()`\n\nLine 13\n\n"
+    );
+}
+
+#[test]
+fn json_has_a_field_for_each_part() {
+    let options = Options {
+        format: Format::Json,
+        ..Options::default()
+    };
+    assert_eq!(
+        documented(&options),
+        "[{\"kind\":\"function\",\"name\":\"add\",\"signature\":\"fun add(a, b)\",\"line\":4,\
+         \"doc\":\"Adds two numbers.\\n\\nBoth of them.\"},\
+         {\"kind\":\"class\",\"name\":\"Point\",\"signature\":\"class Point\",\"line\":8,\"doc\":null,\
+         \"methods\":[{\"kind\":\"method\",\"name\":\"init\",\"signature\":\"init(x)\",\"line\":10,\
+         \"doc\":\"Makes one.\"}]},\
+         {\"kind\":\"function\",\"name\":\"f\",\"signature\":\"fun f()\",\"line\":13,\"doc\":null}]\n"
+    );
+}
+
+#[test]
+fn class_methods_are_listed_where_they_are_declared() {
+    let source = "class A {\n  class make() {}\n  plain() {}\n  class other() {}\n}";
+    let mut out = Output::default();
+    doc(source, &Options::default(), &mut out);
+    assert_eq!(
+        out.stdout,
+        "## `class A`\n\nLine 1\n\n### `class make()`\n\nLine 2\n\n\
+         ### `plain()`\n\nLine 3\n\n### `class other()`\n\nLine 4\n\n"
+    );
+}
@@ -0,0 +1,74 @@
+use lox::expect::Expectations;
+use lox::{check_tests, Backend, ExitStatus, Options, Output};
+
+/// The differences `check-tests` finds in `source` on the tree-walker, or
+/// none if it passes.
+fn differences(source: &str) -> Vec<String> {
+    match check_tests(source, &Options::default(), &mut Output::default()) {
+        ExitStatus::Success => Vec::new(),
+        ExitStatus::Mismatch(differences) => differences,
+        status => panic!("{:?}", status),
+    }
+}
+
+#[test]
+fn a_test_that_does_what_it_says_passes() {
+    let source = "print 1; // expect: 1\nprint \"a\"; // expect: a\n\
+                  print nil + 1; // expect runtime error: Operands must be two numbers or two strings.";
+    assert_eq!(differences(source), Vec::<String>::new());
+    assert_eq!(
+        differences("var a = ; // Error at ';': Expected expression."),
+        Vec::<String>::new()
+    );
+    assert_eq!(differences("// nontest\nprint 1;"), Vec::<String>::new());
+}
+
+#[test]
+fn wrong_output_is_a_mismatch() {
+    assert_eq!(
+        differences("print 1; // expect: 1\nprint 3; // expect: 2\nprint 4;"),
+        [
+            "Expected output '2' (line 2), got '3'.",
+            "Unexpected output '4'.",
+        ]
+    );
+    assert_eq!(
+        differences("print 1; // expect runtime error: Stack overflow."),
+        [
+            "Unexpected output '1'.",
+            "Expected runtime error 'Stack overflow.' (line 1) did not happen.",
+        ]
+    );
+}
+
+#[test]
+fn an_expectation_nothing_meets_is_a_mismatch() {
+    assert_eq!(
+        differences("print 1; // expect: 1\n// expect: 2"),
+        ["Expected output '2' (line 2), got nothing."]
+    );
+    assert_eq!(
+        differences("print 1; // Error at 'print': Nothing wrong here."),
+        [
+            "Unexpected output '1'.",
+            "Expected error not reported: [line 1] Error at 'print': Nothing wrong here.",
+        ]
+    );
+}
+
+#[test]
+fn line_prefixed_errors_only_apply_to_their_backend() {
+    let source = "// [line 3] Error at 'a': One.\n// [java line 4] Error at 'b': Two.\n\
+                  // [c line 5] Error at 'c': Three.";
+    assert_eq!(
+        Expectations::parse(source, Backend::TreeWalk).errors,
+        ["[line 3] Error at 'a': One.", "[line 4] Error at 'b': Two."]
+    );
+    assert_eq!(
+        Expectations::parse(source, Backend::Vm).errors,
+        [
+            "[line 3] Error at 'a': One.",
+            "[line 5] Error at 'c': Three."
+        ]
+    );
+}
@@ -0,0 +1,79 @@
+use lox::scanner::{Limits, Scanner};
+use lox::token::TokenType;
+use lox::{fmt, print_ast, ExitStatus, Options, Output};
+
+/// Programs covering what the formatter lays out: blocks, bodies with and
+/// without braces, comments, blank lines, classes, maps and `switch`.
+const PROGRAMS: [&str; 6] = [
+    "var a=1;var b = -a*(2+3);print a<b and !(b==nil);",
+    "fun add(a,b){return a+b;}\n\n\n// Twice over\nprint add(1,2); /* inline */ print add(\n3, 4);",
+    "class A<B{init(x){this.x=x;}get(){return super.get()+this.x;}}\nprint A(1).get();",
+    "for(var i=0;i<3;i=i+1)if(i==1)continue;else print i;\nwhile(false){}",
+    "var m={\"a\":1,2:[3,4]};switch(m[\"a\"]){case 1:print \"one\";default:{print m;}}",
+    "if (a) if (b) print 1; else print 2;\nif (a) { print 1; } else if (b) print 2; else { print 3; }",
+];
+
+fn options() -> Options {
+    Options {
+        extensions: true,
+        ..Options::default()
+    }
+}
+
+fn formatted(source: &str) -> String {
+    let mut out = Output::default();
+    let status = fmt(source, &options(), &mut out);
+    assert!(matches!(status, ExitStatus::Success), "{:?}", status);
+    out.stdout
+}
+
+fn token_types(source: &str) -> Vec<TokenType> {
+    let limits = Limits::default();
+    let mut scanner = Scanner::new(source, &limits);
+    scanner.set_extensions(true);
+    let tokens = scanner.scan_tokens();
+    tokens.into_iter().map(|token| token.token_type).collect()
+}
+
+fn ast(source: &str) -> String {
+    let mut out = Output::default();
+    let status = print_ast(source, &options(), &mut out);
+    assert!(matches!(status, ExitStatus::Success), "{:?}", status);
+    out.stdout
+}
+
+#[test]
+fn formatting_twice_changes_nothing_more() {
+    for program in PROGRAMS {
+        let once = formatted(program);
+        assert_eq!(formatted(&once), once, "{}", program);
+
+        let mut out = Output::default();
+        let check = Options {
+            check: true,
+            ..options()
+        };
+        assert!(matches!(fmt(&once, &check, &mut out), ExitStatus::Success));
+    }
+}
+
+#[test]
+fn formatting_keeps_the_tokens_and_the_tree() {
+    for program in PROGRAMS {
+        let once = formatted(program);
+        assert_eq!(token_types(&once), token_types(program), "{}", once);
+        assert_eq!(ast(&once), ast(program), "{}", once);
+    }
+}
+
+#[test]
+fn else_stays_with_the_if_it_belongs_to() {
+    assert_eq!(
+        formatted("if (a)\n  if (b) print 1;\nelse print 2;"),
+        "if (a) if (b) print 1; else print 2;\n"
+    );
+    assert_eq!(
+        formatted("if (a) {\nprint 1;\n}\nelse {\nprint 2;\n}"),
+        "if (a) {\n  print 1;\n} else {\n  print 2;\n}\n"
+    );
+}
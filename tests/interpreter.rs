@@ -0,0 +1,676 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+
+use lox::debugger::Debugger;
+use lox::interpreter::Interpreter;
+use lox::value::Value;
+
+/// A sink the test can still read after handing it to the interpreter.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn run_prints_to_output_and_keeps_globals_between_sources() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    interpreter
+        .run("fun greet(name) { var greeting = \"hi \" + name; return greeting; }")
+        .unwrap();
+    interpreter.run("print greet(\"there\");").unwrap();
+    assert_eq!(captured.text(), "hi there\n");
+}
+
+#[test]
+fn run_reports_runtime_errors_as_diagnostics() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Captured::default());
+    interpreter.run("var first = true;").unwrap();
+
+    // Positions are in the source that failed, not counting earlier ones
+    let errors = interpreter.run("print 1;\nprint -\"a\";").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].line, errors[0].column), (2, 7));
+    assert_eq!(errors[0].span, Some(15..16));
+}
+
+#[test]
+fn undefined_names_suggest_locals_in_scope_too() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Captured::default());
+    let errors = interpreter
+        .run("fun f() {\n  var total = 0;\n  { print totl; }\n}\nf();")
+        .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Undefined variable 'totl'. Did you mean 'total'?"
+    );
+
+    // A class's own methods, on the class and through `super`
+    let errors = interpreter
+        .run("class A { class make() {} }\nA.mkae();")
+        .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Undefined property 'mkae'. Did you mean 'make'?"
+    );
+    let errors = interpreter
+        .run("class B { size() {} }\nclass C < B { m() { super.szie(); } }\nC().m();")
+        .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Undefined property 'szie'. Did you mean 'size'?"
+    );
+}
+
+#[test]
+fn registered_closures_convert_arguments_and_results() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.register("add", |a: f64, b: f64| a + b);
+    interpreter.register("shout", |s: String| s.to_uppercase());
+
+    interpreter
+        .run("print add(1, 2); print shout(\"hi\");")
+        .unwrap();
+    assert_eq!(captured.text(), "3\nHI\n");
+
+    let errors = interpreter.run("add(1, \"2\");").unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Expected a number for argument 2 but got string."
+    );
+}
+
+#[test]
+fn call_runs_a_lox_function_by_name() {
+    let mut interpreter = Interpreter::new();
+    interpreter.run("fun twice(n) { return n * 2; }").unwrap();
+
+    let result = interpreter.call("twice", [21.0]).unwrap();
+    assert!(result.equals(&Value::Number(42.0)));
+
+    let error = interpreter.call("missing", [1.0]).unwrap_err();
+    assert_eq!(error.message, "Undefined variable 'missing'.");
+    assert_eq!(error.line, 0);
+}
+
+#[test]
+fn imports_run_once_in_their_own_namespace() {
+    let dir = std::env::temp_dir().join(format!("lox-import-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("counter.lox"),
+        "print \"loaded\"; var count = 0; fun next() { count = count + 1; return count; }",
+    )
+    .unwrap();
+
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.set_path(dir.join("main.lox"));
+    interpreter
+        .run("import \"counter.lox\"; import \"counter.lox\"; var count = 10; print counter.next(); print counter.next(); print count;")
+        .unwrap();
+
+    // Another file by the same name would rebind it
+    let errors = interpreter
+        .run("import \"counter.lox\";\nimport \"other/counter.lox\";")
+        .unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(captured.text(), "loaded\n1\n2\n10\n");
+    assert_eq!(
+        errors[0].message,
+        "Module name 'counter' is already imported from \"counter.lox\"."
+    );
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn lists_are_shared_and_bounds_checked() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.register("sum", |xs: Vec<f64>| xs.iter().sum::<f64>());
+
+    interpreter
+        .run("var xs = [1, 2]; var ys = xs; ys.push(3); xs[0] = 10; print xs; print sum(xs);")
+        .unwrap();
+    assert_eq!(captured.text(), "[10, 2, 3]\n15\n");
+
+    let errors = interpreter.run("print xs[3];").unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Index 3 is out of bounds for a list of length 3."
+    );
+
+    interpreter
+        .run("xs.push(xs); print xs; print [xs];")
+        .unwrap();
+    assert_eq!(
+        captured.text(),
+        "[10, 2, 3]\n15\n[10, 2, 3, [...]]\n[[10, 2, 3, [...]]]\n"
+    );
+}
+
+#[test]
+fn maps_keep_insertion_order_and_compare_keys_by_value() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    interpreter
+        .run("var m = {\"b\": 1, 0: 2}; m[\"a\"] = 3; m[-0] = 4; print m; print m.remove(\"b\"); print m.keys(); print m.has(\"b\");")
+        .unwrap();
+    assert_eq!(captured.text(), "{b: 1, 0: 4, a: 3}\n1\n[0, a]\nfalse\n");
+
+    interpreter
+        .run("m[\"self\"] = m; m[1] = [m]; print m;")
+        .unwrap();
+    assert!(captured
+        .text()
+        .ends_with("{0: 4, a: 3, self: {...}, 1: [{...}]}\n"));
+}
+
+#[test]
+fn any_value_but_nan_is_a_map_key() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    interpreter
+        .run("class P {}\nvar p = P(); var q = P(); var l = [1];\nvar m = {nil: \"nil\", true: \"true\", p: \"p\", l: \"l\"};\np.x = 1; l.push(2);\nprint m[nil]; print m[true]; print m[p]; print m[l]; print m.has(q); print m.has([1, 2]);")
+        .unwrap();
+    assert_eq!(captured.text(), "nil\ntrue\np\nl\nfalse\nfalse\n");
+
+    let errors = interpreter
+        .run("var big = 1; while (big * 10 > big) big = big * 10; m[big * 10 - big * 10] = 1;")
+        .unwrap_err();
+    assert!(
+        errors[0].message.starts_with("NaN can't be a map key"),
+        "{}",
+        errors[0].message
+    );
+}
+
+#[test]
+fn string_methods_count_characters_not_bytes() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    interpreter
+        .run("var s = \"añb,c\"; print s[1]; print s.len(); print s.substr(1, 2).upper(); print s.split(\",\");")
+        .unwrap();
+    assert_eq!(captured.text(), "ñ\n5\nÑB\n[añb, c]\n");
+}
+
+#[test]
+fn strings_built_in_long_loops_read_and_drop_whole() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    // Deep enough that walking the joins by recursion would overflow
+    interpreter
+        .run("var s = \"\"; var half; for (var i = 0; i < 200000; i = i + 1) { s = s + str(i % 10); if (i == 99999) half = s; }\nprint len(s); print len(half); print s.substr(99995, 10); print half == s.substr(0, 100000);")
+        .unwrap();
+    interpreter.run("s = nil; half = nil;").unwrap();
+    assert_eq!(captured.text(), "200000\n100000\n5678901234\ntrue\n");
+}
+
+#[test]
+fn strings_are_equal_however_they_were_made() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    // More short strings than the interner keeps before it drops unused ones
+    interpreter
+        .run("var m = {}; for (var i = 0; i < 5000; i = i + 1) { m[\"k\" + str(i)] = i; var unused = \"u\" + str(i); }\nvar found = 0; for (var i = 0; i < 5000; i = i + 1) if (m[\"k\" + str(i)] == i) found = found + 1;\nprint found; print \"ab\" == \"a\" + \"b\"; print \"ab\" == \"a\" + \"c\"; var a = \"\"; var b = \"\"; for (var i = 0; i < 300; i = i + 1) { a = a + \"x\"; b = b + \"x\"; } print a + \"y\" == b + \"y\"; print a == b + \"y\";")
+        .unwrap();
+    assert_eq!(captured.text(), "5000\ntrue\nfalse\ntrue\nfalse\n");
+}
+
+#[test]
+fn cycles_are_freed_once_nothing_reaches_them() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Captured::default());
+    interpreter
+        .run("class Node {}\nfun pair() { var a = Node(); var b = Node(); a.other = b; b.other = a; return a; }\nfun counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }")
+        .unwrap();
+
+    let node = match interpreter.call("pair", Vec::<Value>::new()).unwrap() {
+        Value::Instance(node) => Rc::downgrade(&node),
+        value => panic!("{}", value),
+    };
+    let next = match interpreter.call("counter", Vec::<Value>::new()).unwrap() {
+        Value::Function(next) => Rc::downgrade(&next),
+        value => panic!("{}", value),
+    };
+    // Only kept by the cycles they're in
+    assert!(node.upgrade().is_some() && next.upgrade().is_some());
+    assert!(lox::gc::collect() > 0);
+    assert!(node.upgrade().is_none() && next.upgrade().is_none());
+
+    // Collected along the way, without being asked to
+    interpreter
+        .run("for (var i = 0; i < 50000; i = i + 1) { pair(); counter()(); }")
+        .unwrap();
+    assert!(lox::gc::live() < 20000, "{}", lox::gc::live());
+}
+
+#[test]
+fn switch_runs_only_the_matching_case_with_extensions() {
+    let source = "fun name(n) { switch (n) { case 1: return \"one\"; case 2: print \"two\"; default: return \"many\"; } return \"done\"; }\nprint name(1); print name(2); print name(3);";
+
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.set_extensions(true);
+    interpreter.run(source).unwrap();
+    assert_eq!(captured.text(), "one\ntwo\ndone\nmany\n");
+
+    // Without them, `switch` is an identifier and this doesn't parse
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.run(source).is_err());
+}
+
+#[test]
+fn getters_run_on_access_and_class_methods_are_inherited() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter
+        .run(
+            "class Shape { class unit() { return this(1); } init(size) { this.size = size; } area { return this.size * this.size; } }\n\
+             class Square < Shape {}\n\
+             print Square.unit().area; print Square(3).area;",
+        )
+        .unwrap();
+    assert_eq!(captured.text(), "1\n9\n");
+}
+
+#[test]
+fn mixins_come_before_the_superclass_and_must_not_conflict() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.set_extensions(true);
+    interpreter
+        .run(
+            "class Base { name() { return \"base\"; } }\n\
+             class Named { name() { return \"named\"; } }\n\
+             class Other { name() { return \"other\"; } }\n\
+             class A < Base with Named {}\n\
+             print A().name();",
+        )
+        .unwrap();
+    assert_eq!(captured.text(), "named\n");
+
+    let errors = interpreter.run("class B with Named, Other {}").unwrap_err();
+    assert_eq!((errors[0].line, errors[0].column), (1, 21));
+
+    // Strict Lox can still use the word as a name
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.run("var with = 1; print with;").unwrap();
+    assert_eq!(captured.text(), "named\n1\n");
+}
+
+#[test]
+fn runtime_errors_are_caught_as_exceptions_and_finally_always_runs() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.set_extensions(true);
+    interpreter
+        .run(
+            "fun check() { try { return nope; } catch (e) { print e.message; print e.line; } finally { print \"done\"; } }\n\
+             check();\n\
+             try { throw Exception(\"mine\"); } catch (e) { print e.message + \" \" + str(e.line); }",
+        )
+        .unwrap();
+    assert_eq!(
+        captured.text(),
+        "Undefined variable 'nope'.\n1\ndone\nmine 3\n"
+    );
+
+    let errors = interpreter.run("throw 42;").unwrap_err();
+    assert_eq!(errors[0].message, "Uncaught exception: 42");
+
+    // Strict Lox can still use the words as names
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter
+        .run("var try = 1; fun throw(catch) { return catch; } print throw(try);")
+        .unwrap();
+    assert!(captured.text().ends_with("mine 3\n1\n"));
+}
+
+#[test]
+fn runtime_errors_in_functions_carry_the_calls_they_happened_in() {
+    let mut interpreter = Interpreter::new();
+    let errors = interpreter
+        .run(
+            "fun inner() { return 1 + nil; }\n\
+             fun outer() {\n  inner();\n}\n\
+             outer();",
+        )
+        .unwrap_err();
+    let trace: Vec<_> = errors[0]
+        .trace
+        .iter()
+        .map(|frame| (frame.function.as_str(), frame.line))
+        .collect();
+    assert_eq!(trace, [("inner", 1), ("outer", 3), ("<script>", 5)]);
+    assert_eq!(errors[0].trace[0].to_string(), "  at inner (<script>:1)");
+
+    let errors = interpreter.run("print 1 + nil;").unwrap_err();
+    assert!(errors[0].trace.is_empty());
+}
+
+#[test]
+fn runaway_recursion_is_a_catchable_stack_overflow() {
+    // The frames it takes to get there need more than a test thread's stack
+    let run = || {
+        let captured = Captured::default();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(captured.clone());
+        interpreter.set_extensions(true);
+
+        let errors = interpreter
+            .run("fun r(n) {\n  return r(n + 1);\n}\nr(0);")
+            .unwrap_err();
+        assert_eq!(errors[0].message, "Stack overflow.");
+        assert_eq!(errors[0].line, 2);
+        assert!(errors[0]
+            .to_string()
+            .contains("  at r (<script>:2)\n  ... the call above repeated"));
+
+        interpreter
+            .run("try { r(0); } catch (error) { print error; }")
+            .unwrap();
+        assert_eq!(captured.text(), "<exception Stack overflow.>\n");
+    };
+    thread::Builder::new()
+        .stack_size(lox::STACK_SIZE)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn tail_calls_reuse_the_stack_and_leave_the_trace_with_tco() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    interpreter.set_tco(true);
+    interpreter.set_extensions(true);
+
+    // Far deeper than the stack would go a call at a time
+    interpreter
+        .run(
+            "fun count(n) { if (n == 0) return \"done\"; return count(n - 1); }\n\
+             print count(200000);",
+        )
+        .unwrap();
+    assert_eq!(captured.text(), "done\n");
+
+    // `middle` ended when it made its tail call, but a call in a `try`
+    // isn't one
+    let errors = interpreter
+        .run(
+            "fun inner() { return 1 + nil; }\n\
+             fun middle() { return inner(); }\n\
+             fun outer() {\n  try { return middle(); } finally {}\n}\n\
+             outer();",
+        )
+        .unwrap_err();
+    let trace: Vec<_> = errors[0]
+        .trace
+        .iter()
+        .map(|frame| (frame.function.as_str(), frame.line))
+        .collect();
+    assert_eq!(trace, [("inner", 1), ("outer", 4), ("<script>", 6)]);
+}
+
+#[test]
+fn debugger_stops_at_breakpoints_and_shows_variables() {
+    let source = "var a = 1;\nfun f(n) {\n  print n;\n}\nf(a + 1);";
+    let commands = "b 3\nc\np n\nc\n";
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    let debugger = Debugger::new(
+        source,
+        "<script>".to_string(),
+        commands.as_bytes(),
+        captured.clone(),
+    );
+    interpreter.set_debugger(debugger);
+    interpreter.run(source).unwrap();
+    assert_eq!(
+        captured.text(),
+        "Stopped at <script>:1\n    1 | var a = 1;\n(lox) Breakpoint set at line 3.\n\
+         (lox) Stopped at <script>:3\n    3 |   print n;\n(lox) n = 2\n(lox) 2\n"
+    );
+}
+
+#[test]
+fn opt_folds_constants_and_drops_dead_branches() {
+    let source = "print 2 * 3 + 1;\nif (false) print \"a\"; else print \"b\";\nwhile (nil) print 1;\nprint 1 / 0;";
+    let options = lox::Options {
+        optimize: true,
+        ..Default::default()
+    };
+    let mut out = lox::Output::default();
+    lox::print_ast(source, &options, &mut out);
+    assert_eq!(
+        out.stdout,
+        "(print 7.0)\n(print \"b\")\n(print (/ 1.0 0.0))\n"
+    );
+}
+
+#[test]
+fn opt_removes_unreachable_code_and_unused_local_functions() {
+    let source =
+        "fun f() {\n  fun unused() {}\n  var x = 1;\n  return x;\n  print x;\n}\nprint f();";
+    let options = lox::Options {
+        optimize: true,
+        opt_verbose: true,
+        ..Default::default()
+    };
+    let mut out = lox::Output::default();
+    lox::run(source, &options, &mut out);
+    assert_eq!(
+        out.stderr,
+        "[line 2] Removed the unused function 'unused'.\n\
+         [line 4] Removed the unreachable code after 'return'.\n"
+    );
+}
+
+#[test]
+fn visitors_reach_nodes_nested_anywhere() {
+    use lox::ast::{Expr, ExprArena};
+    use lox::visit::{walk_expr, Visitor};
+
+    /// Collects the name of every function called.
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl Visitor for Calls {
+        fn visit_expr(&mut self, arena: &ExprArena, expr: &Expr) {
+            if let Expr::Call { callee, .. } = expr {
+                if let Expr::Variable(name) = &arena[*callee] {
+                    self.0.push(name.lexeme.to_string());
+                }
+            }
+            walk_expr(self, arena, expr);
+        }
+    }
+
+    let source = "fun f() { if (a()) { return [b(c())]; } }\nclass K { m() { d(); } }";
+    let tokens = lox::scanner::Scanner::new(source, &Default::default()).scan_tokens();
+    let mut parser = lox::parser::Parser::new(tokens);
+    let statements = parser.parse_program().unwrap();
+    let mut calls = Calls::default();
+    calls.visit_stmts(&parser.arena, &statements);
+    assert_eq!(calls.0, ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn annotations_dont_change_what_runs() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+
+    // Only `typecheck` reads them, so values of other types go through
+    let source = "var n: number = \"one\";\nfun twice(s: string) -> string { return s + s; }\n\
+                  print n;\nprint twice(n);";
+    interpreter.run(source).unwrap();
+    assert_eq!(captured.text(), "one\noneone\n");
+}
+
+#[test]
+fn filesystem_natives_are_only_there_when_allowed() {
+    let dir = std::env::temp_dir().join(format!("lox_fs_{}", std::process::id()));
+    let path = dir.display().to_string().replace('\\', "/");
+    let source = format!(
+        "var dir = \"{}\";\nprint exists(dir);\nmkdir(dir + \"/b\");\nmkdir(dir + \"/a\");\n\
+         print listDir(dir);\nremove(dir + \"/a\");\nprint listDir(dir);\n\
+         remove(dir + \"/b\");\nremove(dir);\nprint exists(dir);\nremove(dir);",
+        path
+    );
+
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    let errors = interpreter.run(&source).unwrap_err();
+    assert_eq!(errors[0].message, "Undefined variable 'exists'.");
+
+    lox::native::define_fs(&mut interpreter);
+    let errors = interpreter.run(&source).unwrap_err();
+    assert_eq!(captured.text(), "false\n[a, b]\n[b]\nfalse\n");
+    // Removing what isn't there fails
+    assert!(
+        errors[0]
+            .message
+            .starts_with(&format!("Can't remove '{}': ", path)),
+        "{}",
+        errors[0].message
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_runs_a_program_and_returns_what_it_wrote() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    let source = "var r = exec(\"sh\", [\"-c\", \"echo out; echo err >&2; exit 3\"]);\n\
+                  print r;\nexec(\"no-such-program\", []);";
+    let errors = interpreter.run(source).unwrap_err();
+    assert_eq!(errors[0].message, "Undefined variable 'exec'.");
+
+    lox::native::define_exec(&mut interpreter);
+    let errors = interpreter.run(source).unwrap_err();
+    assert_eq!(captured.text(), "{stdout: out\n, stderr: err\n, code: 3}\n");
+    assert!(errors[0]
+        .message
+        .starts_with("Can't run 'no-such-program': "));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_natives_match_find_and_replace() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    let source = r#"print match("(\\w+)@(\\w+)?\\.com", "mail bob@.com now");
+print match("x", "abc");
+print findAll("[0-9]+", "a1 b22 c333");
+print replace("(\\w+) (?<last>\\w+)", "hello world", "${last} $1");
+match("(", "a");"#;
+    let errors = interpreter.run(source).unwrap_err();
+    assert_eq!(
+        captured.text(),
+        "[bob@.com, bob, nil]\nnil\n[1, 22, 333]\nworld hello\n"
+    );
+    assert_eq!(
+        errors[0].message,
+        "Invalid regular expression '(': unclosed group"
+    );
+}
+
+#[cfg(feature = "net")]
+#[test]
+fn fetch_returns_the_status_and_body_of_a_get() {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    // Answers once with a length and once in chunks, saying what was asked
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        for response in [
+            "HTTP/1.1 200 OK\r\nContent-Length: {len}\r\n\r\n{body}",
+            "HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n{len:x}\r\n{body}\r\n0\r\n\r\n",
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            // The whole head, so closing doesn't reset the connection
+            let head: Vec<String> = BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .collect();
+            let body = &head[0];
+            let response = response
+                .replace("{len}", &body.len().to_string())
+                .replace("{len:x}", &format!("{:x}", body.len()))
+                .replace("{body}", body);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    lox::native::define_net(&mut interpreter);
+    let source = format!(
+        "print fetch(\"http://127.0.0.1:{port}/data?x=1\");\n\
+         print fetch(\"http://127.0.0.1:{port}\")[\"status\"];\n\
+         fetch(\"https://example.com\");"
+    );
+    let errors = interpreter.run(&source).unwrap_err();
+    server.join().unwrap();
+    assert_eq!(
+        captured.text(),
+        "{status: 200, body: GET /data?x=1 HTTP/1.1}\n404\n"
+    );
+    assert_eq!(
+        errors[0].message,
+        "Can't fetch 'https://example.com': only http:// URLs are supported."
+    );
+}
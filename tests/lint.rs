@@ -0,0 +1,131 @@
+use lox::lint::{Level, Lint, LintLevels};
+use lox::{lint, ExitStatus, Options, Output};
+
+/// What a source that no lint fires on is reported for.
+const NONE: [&str; 0] = [];
+
+/// The names of the lints `source` is reported for, in order, with every
+/// lint a warning.
+fn lints(source: &str) -> Vec<String> {
+    match lint(source, &Options::default(), &mut Output::default()) {
+        ExitStatus::Success => Vec::new(),
+        ExitStatus::Warnings(warnings) => warnings
+            .into_iter()
+            .map(|warning| {
+                let name = warning.message.rsplit_once(" [").unwrap().1;
+                name.trim_end_matches(']').to_string()
+            })
+            .collect(),
+        status => panic!("{:?}", status),
+    }
+}
+
+#[test]
+fn unused_variable() {
+    assert_eq!(lints("fun f() { var x = 1; }"), ["unused-variable"]);
+    assert_eq!(lints("fun f() { var x = 1; print x; }\nvar g = 1;"), NONE);
+}
+
+#[test]
+fn unused_parameter() {
+    assert_eq!(lints("fun f(a, b) { print a; }"), ["unused-parameter"]);
+    assert_eq!(lints("fun f(a, b) { print a + b; }"), NONE);
+}
+
+#[test]
+fn unreachable_code() {
+    assert_eq!(
+        lints("fun f() { return 1; print 2; }"),
+        ["unreachable-code"]
+    );
+    assert_eq!(lints("fun f(a) { if (a) return 1; print 2; }"), NONE);
+}
+
+#[test]
+fn shadowing() {
+    assert_eq!(
+        lints("fun f(a) { { var a = 1; print a; } print a; }"),
+        ["shadowing"]
+    );
+    assert_eq!(lints("fun f(a) { { var b = 1; print b; } print a; }"), NONE);
+}
+
+#[test]
+fn unused_assignment() {
+    assert_eq!(
+        lints("fun f() { var a = 1; print a; a = 2; }"),
+        ["unused-assignment"]
+    );
+    assert_eq!(
+        lints("fun f() { var a = 1; print a; a = 2; print a; }"),
+        NONE
+    );
+}
+
+#[test]
+fn unused_assignment_overwritten_before_it_is_read() {
+    // An initializer, then an assignment, replaced before anything reads them
+    assert_eq!(
+        lints("{ var x = 1; x = 2; print x; }"),
+        ["unused-assignment"]
+    );
+    assert_eq!(
+        lints("fun f() { var x; x = 1; x = 2; print x; }"),
+        ["unused-assignment"]
+    );
+    assert_eq!(
+        lints("fun f(c) { var x; if (c) x = 1; x = 2; print x; }"),
+        ["unused-assignment"]
+    );
+    // Not if the old value can still be read: on another branch, after a
+    // jump, in a `catch` or in the next run of a loop
+    assert_eq!(
+        lints("fun f(c) { var x = 1; if (c) x = 2; print x; }"),
+        NONE
+    );
+    assert_eq!(
+        lints("fun f(c) { var x = 1; if (c) x = 2; else x = 3; print x; }"),
+        NONE
+    );
+    assert_eq!(
+        lints("fun f(c) { var x = 1; c and (x = 2); print x; }"),
+        NONE
+    );
+    assert_eq!(
+        lints("fun f(c) { var x; while (c) { x = 1; if (c) break; x = 2; print x; } print x; }"),
+        NONE
+    );
+    assert_eq!(
+        lints("fun f() { var x = 0; while (x < 3) { x = 1; } x = 2; print x; }"),
+        NONE
+    );
+}
+
+#[test]
+fn chained_comparison() {
+    assert_eq!(
+        lints("var a = 1; var b = 2; var c = 3;\nprint a < b < c;\nprint a >= b <= c;"),
+        ["chained-comparison", "chained-comparison"]
+    );
+    assert_eq!(
+        lints("var a = 1; var b = 2; var c = 3;\nprint a < b and b < c;\nprint (a < b) == true;"),
+        NONE
+    );
+}
+
+#[test]
+fn levels_turn_lints_off_or_into_errors() {
+    let source = "fun f(a) { var x = 1; }";
+    let mut levels = LintLevels::default();
+    levels.set(Lint::UnusedParameter, Level::Allow);
+    levels.set(Lint::UnusedVariable, Level::Deny);
+    let options = Options {
+        lint_levels: levels,
+        ..Options::default()
+    };
+    let ExitStatus::CompileError(errors) = lint(source, &options, &mut Output::default()) else {
+        panic!("expected the denied lint to fail");
+    };
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.ends_with("[unused-variable]"));
+}
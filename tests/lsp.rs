@@ -0,0 +1,136 @@
+use std::io::Cursor;
+
+use lox::lsp::json::Json;
+use lox::lsp::serve;
+
+const URI: &str = "file:///test.lox";
+
+/// Serves `messages`, written as JSON, to the language server. Returns what
+/// it exited with and every message it wrote back.
+fn session(messages: &[String]) -> (i32, Vec<Json>) {
+    let input: String = messages
+        .iter()
+        .map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body))
+        .collect();
+    let mut output = Vec::new();
+    let code = serve(&mut Cursor::new(input), &mut output).unwrap();
+
+    let mut rest = String::from_utf8(output).unwrap();
+    let mut replies = Vec::new();
+    while let Some((header, after)) = rest.split_once("\r\n\r\n") {
+        let length: usize = header["Content-Length: ".len()..].parse().unwrap();
+        replies.push(Json::parse(&after[..length]).unwrap());
+        rest = after[length..].to_string();
+    }
+    (code, replies)
+}
+
+fn request(id: usize, method: &str, params: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+        id, method, params
+    )
+}
+
+fn notification(method: &str, params: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#,
+        method, params
+    )
+}
+
+fn did_open(text: &str) -> String {
+    let text = Json::String(text.to_string());
+    let params = format!(
+        r#"{{"textDocument":{{"uri":"{}","languageId":"lox","version":1,"text":{}}}}}"#,
+        URI, text
+    );
+    notification("textDocument/didOpen", &params)
+}
+
+fn at(id: usize, method: &str, line: usize, character: usize) -> String {
+    let params = format!(
+        r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#,
+        URI, line, character
+    );
+    request(id, method, &params)
+}
+
+/// The `result` of the response to request `id`.
+fn result(replies: &[Json], id: usize) -> &Json {
+    let id = Json::Number(id as f64);
+    let reply = replies.iter().find(|reply| reply.get("id") == Some(&id));
+    reply.and_then(|reply| reply.get("result")).unwrap()
+}
+
+fn position(json: &Json) -> (usize, usize) {
+    let line = json.get("line").and_then(Json::as_usize).unwrap();
+    let character = json.get("character").and_then(Json::as_usize).unwrap();
+    (line, character)
+}
+
+#[test]
+fn a_session_answers_what_an_editor_asks() {
+    let source = "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);\nprint 1 +;";
+    let (code, replies) = session(&[
+        request(1, "initialize", r#"{"capabilities":{}}"#),
+        notification("initialized", "{}"),
+        did_open(source),
+        at(2, "textDocument/hover", 3, 7),
+        at(3, "textDocument/definition", 3, 7),
+        at(4, "textDocument/hover", 3, 0),
+        request(5, "shutdown", "null"),
+        notification("exit", "null"),
+    ]);
+    assert_eq!(code, 0);
+
+    let capabilities = result(&replies, 1).get("capabilities").unwrap();
+    assert_eq!(capabilities.get("hoverProvider"), Some(&Json::Bool(true)));
+    assert_eq!(
+        capabilities.get("definitionProvider"),
+        Some(&Json::Bool(true))
+    );
+
+    // didOpen publishes the syntax error on the last line
+    let published = replies
+        .iter()
+        .find(|reply| {
+            reply.get("method").and_then(Json::as_str) == Some("textDocument/publishDiagnostics")
+        })
+        .and_then(|reply| reply.get("params"))
+        .unwrap();
+    assert_eq!(published.get("uri").and_then(Json::as_str), Some(URI));
+    let Some(Json::Array(diagnostics)) = published.get("diagnostics") else {
+        panic!("{}", published);
+    };
+    assert_eq!(diagnostics.len(), 1);
+    let start = diagnostics[0]
+        .get("range")
+        .and_then(|range| range.get("start"));
+    assert_eq!(start.map(position), Some((4, 9)));
+    let message = diagnostics[0]
+        .get("message")
+        .and_then(Json::as_str)
+        .unwrap();
+    assert!(message.contains("Expected expression."), "{}", message);
+
+    let hover = result(&replies, 2).get("contents").unwrap();
+    let value = hover.get("value").and_then(Json::as_str).unwrap();
+    assert_eq!(value, "```lox\nfun add(a, b)\n```");
+
+    let definition = result(&replies, 3);
+    assert_eq!(definition.get("uri").and_then(Json::as_str), Some(URI));
+    let range = definition.get("range").unwrap();
+    assert_eq!(range.get("start").map(position), Some((0, 4)));
+    assert_eq!(range.get("end").map(position), Some((0, 7)));
+
+    // `print` is a keyword, with nothing to show
+    assert_eq!(result(&replies, 4), &Json::Null);
+}
+
+#[test]
+fn exiting_without_shutting_down_is_an_error() {
+    let (code, replies) = session(&[notification("exit", "null")]);
+    assert_eq!(code, 1);
+    assert_eq!(replies, []);
+}
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::ffi::{c_int, c_void};
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::slice;
+
+use lox::interpreter::Interpreter;
+use lox::plugin::{self, PluginApi, PluginValue, API_VERSION, KIND_NUMBER, KIND_OTHER};
+
+/// A sink the test can still read after handing it to the interpreter.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Adds its two arguments, times the number `data` points to.
+unsafe extern "C" fn scaled_sum(
+    data: *mut c_void,
+    args: *const PluginValue,
+    count: usize,
+    result: *mut PluginValue,
+) -> bool {
+    let args = slice::from_raw_parts(args, count);
+    if args.iter().any(|arg| arg.kind != KIND_NUMBER) {
+        *result = PluginValue::string("scaledSum() takes numbers.");
+        return false;
+    }
+    let scale = *data.cast::<f64>();
+    *result = PluginValue {
+        kind: KIND_NUMBER,
+        number: (args[0].number + args[1].number) * scale,
+        ..PluginValue::NIL
+    };
+    true
+}
+
+/// Whether its argument is a value a plugin only sees printed.
+unsafe extern "C" fn is_other(
+    _: *mut c_void,
+    args: *const PluginValue,
+    _: usize,
+    result: *mut PluginValue,
+) -> bool {
+    let arg = &*args;
+    *result = PluginValue::string(if arg.kind == KIND_OTHER {
+        arg.text().unwrap()
+    } else {
+        "no"
+    });
+    true
+}
+
+static SCALE: f64 = 10.0;
+
+unsafe extern "C" fn init(api: *const PluginApi) -> c_int {
+    let api = &*api;
+    if api.version != API_VERSION {
+        return 1;
+    }
+    let scale = &SCALE as *const f64 as *mut c_void;
+    (api.define)(
+        api.host,
+        b"scaledSum\0".as_ptr().cast(),
+        2,
+        scaled_sum,
+        scale,
+    );
+    let none = std::ptr::null_mut();
+    (api.define)(api.host, b"isOther\0".as_ptr().cast(), 1, is_other, none);
+    0
+}
+
+unsafe extern "C" fn refuse(_: *const PluginApi) -> c_int {
+    3
+}
+
+#[test]
+fn a_plugin_defines_natives_that_get_and_return_values() {
+    let captured = Captured::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(captured.clone());
+    unsafe { plugin::register(&mut interpreter, init) }.unwrap();
+
+    let source = "print scaledSum(1, 2);\nprint isOther([1, 2]);\nprint isOther(\"a\");\n\
+                  scaledSum(1, \"2\");";
+    let errors = interpreter.run(source).unwrap_err();
+    let text = String::from_utf8(captured.0.borrow().clone()).unwrap();
+    assert_eq!(text, "30\n[1, 2]\nno\n");
+    assert_eq!(errors[0].message, "scaledSum() takes numbers.");
+}
+
+#[test]
+fn a_plugin_that_fails_to_load_is_an_error() {
+    let mut interpreter = Interpreter::new();
+    let err = unsafe { plugin::register(&mut interpreter, refuse) }.unwrap_err();
+    assert_eq!(err, "its lox_plugin_init returned 3");
+
+    let err = plugin::load(&mut interpreter, Path::new("no-such-plugin.so")).unwrap_err();
+    assert!(
+        err.starts_with("Can't load plugin 'no-such-plugin.so': "),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,156 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+use lox::highlight::Highlighter;
+use lox::repl::Repl;
+use lox::Options;
+
+/// A sink the test can still read after handing it to the REPL.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+/// `line` highlighted on its own.
+fn highlight(line: &str) -> String {
+    Highlighter::new().highlight("", line)
+}
+
+/// What the REPL prints, writes and reports for `input`, typed on a
+/// terminal if `editing`.
+fn repl(input: &str, editing: bool) -> (String, String, String) {
+    let printed = Captured::default();
+    let written = Captured::default();
+    let errors = Captured::default();
+    let input = Cursor::new(input.as_bytes().to_vec());
+    let mut repl = Repl::new(&Options::default(), input, written.clone());
+    repl.set_output(printed.clone());
+    repl.set_errors(errors.clone());
+    repl.set_editing(editing);
+    repl.run().unwrap();
+    (printed.text(), written.text(), errors.text())
+}
+
+#[test]
+fn keywords_strings_and_numbers_are_colored() {
+    assert_eq!(
+        highlight("var x = \"hi\" + 1.5;"),
+        "\x1b[35mvar\x1b[0m x = \x1b[32m\"hi\"\x1b[0m + \x1b[33m1.5\x1b[0m;"
+    );
+    // A word that only starts like a keyword, and what's in a string or a
+    // comment, isn't one
+    assert_eq!(highlight("variable"), "variable");
+    assert_eq!(highlight("\"if\""), "\x1b[32m\"if\"\x1b[0m");
+    assert_eq!(highlight("// if 1"), "// if 1");
+    // Colored as typed, before the string is closed
+    assert_eq!(
+        highlight("print \"hel"),
+        "\x1b[35mprint\x1b[0m \x1b[32m\"hel\x1b[0m"
+    );
+}
+
+#[test]
+fn extension_keywords_are_colored_only_with_extensions() {
+    let mut highlighter = Highlighter::new();
+    assert_eq!(highlighter.highlight("", "try"), "try");
+    highlighter.set_extensions(true);
+    assert_eq!(highlighter.highlight("", "try"), "\x1b[35mtry\x1b[0m");
+}
+
+#[test]
+fn closing_brackets_with_nothing_to_close_are_dimmed() {
+    assert_eq!(highlight("f(a[1])"), "f(a[\x1b[33m1\x1b[0m])");
+    assert_eq!(highlight("f())"), "f()\x1b[2m)\x1b[0m");
+    assert_eq!(highlight("(]"), "(\x1b[2m]\x1b[0m");
+    // Brackets in strings don't count
+    assert_eq!(highlight("\")\")"), "\x1b[32m\")\"\x1b[0m\x1b[2m)\x1b[0m");
+}
+
+#[test]
+fn earlier_lines_of_a_statement_decide_how_a_line_is_colored() {
+    let highlighter = Highlighter::new();
+    assert_eq!(highlighter.highlight("fun f() {\n", "}"), "}");
+    assert_eq!(highlighter.highlight("", "}"), "\x1b[2m}\x1b[0m");
+    assert_eq!(
+        highlighter.highlight("print \"one\n", "two\";"),
+        "\x1b[32mtwo\"\x1b[0m;"
+    );
+    assert_eq!(
+        highlighter.highlight("/* a\n", "b */ nil"),
+        "b */ \x1b[35mnil\x1b[0m"
+    );
+
+    assert!(highlighter.is_complete("print 1;\n"));
+    assert!(!highlighter.is_complete("fun f() {\n"));
+    assert!(!highlighter.is_complete("print \"one\n"));
+    assert!(!highlighter.is_complete("/* /* */\n"));
+}
+
+#[test]
+fn the_repl_runs_statements_as_they_are_entered() {
+    let input = "var a = 1;\nfun f() {\n  return a + 1;\n}\nprint x;\nprint f();\n";
+    let (printed, written, errors) = repl(input, false);
+    // Going on after an error, with what ran before it still declared
+    assert_eq!(printed, "2\n");
+    assert_eq!(written, "");
+    assert_eq!(errors, "Undefined variable 'x'.\n[line 1, col 7]\n");
+}
+
+#[test]
+fn the_repl_colors_the_line_being_edited() {
+    // "prnt", a backspace over the "nt", then "int 1;" and Enter; then the
+    // up arrow brings it back to run again
+    let (printed, written, _) = repl("prnt\x7f\x7fint 1;\r\x1b[A\r", true);
+    assert_eq!(printed, "1\n1\n");
+    assert!(written.contains("\r\x1b[K> pr"), "{}", written);
+    assert!(
+        written.contains("\r\x1b[K> \x1b[35mprint\x1b[0m \x1b[33m1\x1b[0m;"),
+        "{}",
+        written
+    );
+
+    // Ctrl-C drops the statement being entered
+    let (printed, written, _) = repl("{\rprint 1;\x03print 2;\r", true);
+    assert_eq!(printed, "2\n");
+    assert!(written.contains("\r\x1b[K. "), "{}", written);
+}
+
+#[test]
+fn a_startup_file_declares_what_the_session_can_use() {
+    let path = env::temp_dir().join(format!("loxrc_{}", std::process::id()));
+    fs::write(&path, "fun double(n) { return n * 2; }\nprint nope;").unwrap();
+    let printed = Captured::default();
+    let errors = Captured::default();
+    let input = Cursor::new(b"print double(21);\n".to_vec());
+    let mut repl = Repl::new(&Options::default(), input, Captured::default());
+    repl.set_output(printed.clone());
+    repl.set_errors(errors.clone());
+
+    repl.run_file(&path).unwrap();
+    repl.run().unwrap();
+    fs::remove_file(&path).unwrap();
+    // An error in it is reported, and what came before it still declared
+    assert_eq!(printed.text(), "42\n");
+    assert_eq!(
+        errors.text(),
+        "Undefined variable 'nope'.\n[line 2, col 7]\n"
+    );
+    assert!(repl.run_file(&path).is_err());
+}
@@ -0,0 +1,169 @@
+use std::thread;
+
+use lox::parser::Parser;
+use lox::scanner::{Limits, Scanner};
+use lox::token::TokenType::{self, *};
+
+fn scan(source: &str) -> Vec<TokenType> {
+    let limits = Limits::default();
+    let mut scanner = Scanner::new(source, &limits);
+    let tokens = scanner.scan_tokens();
+    assert!(scanner.diagnostics.is_empty());
+    tokens.into_iter().map(|token| token.token_type).collect()
+}
+
+#[test]
+fn number_with_two_dots_is_number_dot_number() {
+    assert_eq!(scan("1.2.3"), [Number(1.2), Dot, Number(3.0), Eof]);
+}
+
+#[test]
+fn trailing_dot_is_not_part_of_number() {
+    assert_eq!(scan("123."), [Number(123.0), Dot, Eof]);
+}
+
+#[test]
+fn method_call_on_number() {
+    assert_eq!(
+        scan("123.sqrt()"),
+        [Number(123.0), Dot, Identifier, LeftParen, RightParen, Eof]
+    );
+}
+
+#[test]
+fn leading_dot_is_not_part_of_number() {
+    assert_eq!(scan(".5"), [Dot, Number(5.0), Eof]);
+}
+
+#[test]
+fn fraction_keeps_its_lexeme() {
+    let limits = Limits::default();
+    let tokens = Scanner::new("12.50", &limits).scan_tokens();
+    assert_eq!(tokens[0].token_type, Number(12.5));
+    assert_eq!(tokens[0].lexeme, "12.50");
+}
+
+#[test]
+fn string_can_span_lines() {
+    let limits = Limits::default();
+    let mut scanner = Scanner::new("\"one\ntwo\" x", &limits);
+    let tokens = scanner.scan_tokens();
+    assert!(scanner.diagnostics.is_empty());
+    assert_eq!(tokens[0].token_type, StringLit("one\ntwo".to_string()));
+    assert_eq!(tokens[0].line, 1);
+    assert_eq!(tokens[1].line, 2);
+}
+
+#[test]
+fn unterminated_string_reports_its_starting_line() {
+    let limits = Limits::default();
+    let mut scanner = Scanner::new("\n  \"one\ntwo\nthree", &limits);
+    scanner.scan_tokens();
+    let diagnostics = scanner.diagnostics.into_vec();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 3));
+    assert!(diagnostics[0].to_string().starts_with("[line 2, col 3]"));
+}
+
+#[test]
+fn hostile_input_is_reported_not_panicked_on() {
+    let limits = Limits::default();
+    for source in [
+        "\"",
+        "\"\\",
+        "/*",
+        "/* /*",
+        "\0",
+        "é€\u{feff}",
+        "1.",
+        "9".repeat(400).as_str(),
+    ] {
+        let tokens = Scanner::new(source, &limits).scan_tokens();
+        let mut parser = Parser::new(tokens);
+        parser.parse_program();
+    }
+}
+
+#[test]
+fn parser_accepts_tokens_without_eof() {
+    let mut parser = Parser::new(Vec::new());
+    assert_eq!(parser.parse_program().map(|stmts| stmts.len()), Some(0));
+}
+
+#[test]
+fn nesting_too_deep_is_one_error_not_a_stack_overflow() {
+    // Up to the default depth takes more than a test thread's stack in a
+    // debug build
+    let parse = || {
+        let limits = Limits::default();
+        let nested =
+            |levels: usize| format!("print {}1{};", "(".repeat(levels), ")".repeat(levels));
+
+        // The `print` and its value take up a level each
+        let tokens = Scanner::new(&nested(limits.max_nesting_depth - 2), &limits).scan_tokens();
+        assert!(Parser::new(tokens).parse_program().is_some());
+
+        let tokens = Scanner::new(&nested(100_000), &limits).scan_tokens();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse_program().is_none());
+        let diagnostics = parser.diagnostics.into_vec();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .to_string()
+            .ends_with("Error at '(': Expression too deeply nested."));
+
+        // Chains nest as deeply as parentheses do, without the parser recursing
+        for chain in ["1 + 1", "f()", "a.b", "a or b", "x[0]"] {
+            let source = format!("print {}{};", chain, chain[1..].repeat(100_000));
+            let tokens = Scanner::new(&source, &limits).scan_tokens();
+            let mut parser = Parser::new(tokens);
+            assert!(parser.parse_program().is_none(), "{}", chain);
+            assert_eq!(parser.diagnostics.into_vec().len(), 1);
+        }
+        let source = format!("print 1{};", " + 1".repeat(limits.max_nesting_depth - 2));
+        let tokens = Scanner::new(&source, &limits).scan_tokens();
+        assert!(Parser::new(tokens).parse_program().is_some());
+    };
+    thread::Builder::new()
+        .stack_size(lox::STACK_SIZE)
+        .spawn(parse)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn misspelled_keyword_statements_suggest_the_keyword() {
+    let limits = Limits::default();
+    let error = |source: &str| {
+        let tokens = Scanner::new(source, &limits).scan_tokens();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse_program().is_none());
+        parser.diagnostics.into_vec()[0].message.clone()
+    };
+    let hint = "Expected ';' after expression. Did you mean 'while'?";
+    assert_eq!(error("whle (x) print 1;"), hint);
+    assert_eq!(
+        error("retrun 1;"),
+        "Expected ';' after expression. Did you mean 'return'?"
+    );
+    assert_eq!(error("print (x) 1;"), "Expected ';' after value.");
+    assert_eq!(error("f (x) print 1;"), "Expected ';' after expression.");
+}
+
+#[test]
+fn sources_are_read_up_to_one_byte_past_the_size_limit() {
+    let limits = Limits {
+        max_source_size: 3,
+        ..Limits::default()
+    };
+    assert_eq!(limits.read_source(&b"print"[..]).unwrap(), b"prin");
+    assert_eq!(limits.read_source(&b"pr"[..]).unwrap(), b"pr");
+
+    // The largest limit there is doesn't overflow counting that byte
+    let limits = Limits {
+        max_source_size: usize::MAX,
+        ..Limits::default()
+    };
+    assert_eq!(limits.read_source(&b"print"[..]).unwrap(), b"print");
+}
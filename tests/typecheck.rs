@@ -0,0 +1,92 @@
+use lox::{typecheck, ExitStatus, Options, Output};
+
+/// The messages `typecheck` reports for `source`, as warnings.
+fn findings(source: &str) -> Vec<String> {
+    match typecheck(source, &Options::default(), &mut Output::default()) {
+        ExitStatus::Success => Vec::new(),
+        ExitStatus::Warnings(warnings) => warnings
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect(),
+        status => panic!("{:?}", status),
+    }
+}
+
+#[test]
+fn operations_that_can_only_fail_are_reported() {
+    assert_eq!(
+        findings("print \"a\" - 1;\nvar n = 1;\nn();\nfun f(a, b) {}\nf(1);\nprint len(\"a\", 2);"),
+        [
+            "Operands of '-' must be numbers, not string and number.",
+            "Can only call functions and classes, not number.",
+            "Expected 2 arguments but got 1.",
+            "Expected 1 arguments but got 2.",
+        ]
+    );
+    assert_eq!(
+        findings("class A { init(x) {} }\nA();\nvar s = \"a\" + \"b\";\nprint -s;"),
+        [
+            "Expected 1 arguments but got 0.",
+            "Operand of '-' must be a number, not string.",
+        ]
+    );
+    assert_eq!(
+        findings("print \"a\" < \"b\";\nprint \"a\" >= 1;"),
+        ["Operands of '>=' must be two numbers or two strings, not string and number."]
+    );
+}
+
+#[test]
+fn types_that_could_differ_are_not_reported() {
+    let source = "var a = 1;\nif (clock() > 0) a = \"s\";\nprint a - 1;\n\
+                  var b = 1;\nwhile (b < 10) { print b - 1; b = b + 1; }\n\
+                  var c = 1;\nfun change() { c = \"s\"; }\nprint c - 1;\n\
+                  fun g(x) { return x - 1; }\nprint g(\"s\");";
+    assert_eq!(findings(source), Vec::<String>::new());
+}
+
+#[test]
+fn a_loop_is_checked_with_the_types_of_every_iteration() {
+    let source = "var a = 1;\nwhile (true) { print a - 1; a = \"s\"; }";
+    assert_eq!(findings(source), Vec::<String>::new());
+    let source = "var a = \"s\";\nwhile (true) { print a - 1; }";
+    assert_eq!(
+        findings(source),
+        ["Operands of '-' must be numbers, not string and number."]
+    );
+}
+
+#[test]
+fn annotations_are_checked_and_trusted() {
+    let source = "var n: number = \"s\";\nfun f(a: string) -> bool { return a; }\n\
+                  f(1);\nvar b = f(\"a\");\nprint b + 1;\nfun g(x: number) { print -x; }";
+    assert_eq!(
+        findings(source),
+        [
+            "'n' is declared as number but is given string.",
+            "Can't return string from a function declared to return boolean.",
+            "Expected string for argument 1 but got number.",
+            "Operands of '+' must be two numbers or two strings, not boolean and number.",
+        ]
+    );
+    assert_eq!(
+        findings("var a: Point;\nvar b: number;\nb = 2;\nvar f: function = clock;"),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn strict_makes_findings_errors() {
+    let options = Options {
+        strict: true,
+        ..Options::default()
+    };
+    let status = typecheck("print nil + 1;", &options, &mut Output::default());
+    let ExitStatus::CompileError(errors) = status else {
+        panic!("{:?}", status);
+    };
+    assert_eq!(
+        errors[0].message,
+        "Operands of '+' must be two numbers or two strings, not nil and number."
+    );
+}
@@ -0,0 +1,436 @@
+use std::thread;
+
+use lox::{
+    compare_backends, compare_backends_header, debug, disassemble, run, Backend, ExitStatus,
+    Options, Output,
+};
+
+/// What running `source` prints, and the message and line of the runtime
+/// error it stopped with, if any, after checking the VM agrees with the
+/// tree-walker on all of it.
+fn run_both(source: &str) -> (String, Option<(String, usize)>) {
+    let source = source.to_string();
+    // Deep recursion in the tree-walker takes more than a test thread's stack
+    let run_on = move |backend| {
+        let source = source.clone();
+        thread::Builder::new()
+            .stack_size(lox::STACK_SIZE)
+            .spawn(move || {
+                let options = Options {
+                    backend,
+                    ..Options::default()
+                };
+                let mut out = Output::default();
+                let error = match run(&source, &options, &mut out) {
+                    ExitStatus::Success => None,
+                    ExitStatus::RuntimeError(error) => Some((error.message, error.line)),
+                    status => panic!("{:?}", status),
+                };
+                (out.stdout, error)
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    };
+    let tree_walker = run_on(Backend::TreeWalk);
+    assert_eq!(run_on(Backend::Vm), tree_walker);
+    tree_walker
+}
+
+/// The line each instruction of the `<script>` chunk is listed at, with the
+/// `|` of one on the same line as the one before filled in.
+fn lines(source: &str) -> Vec<(String, usize)> {
+    let mut out = Output::default();
+    disassemble(source, &Options::default(), &mut out);
+    let mut line = 0;
+    out.stdout
+        .lines()
+        .skip(1)
+        .take_while(|listing| !listing.is_empty())
+        .map(|listing| {
+            let mut columns = listing.split_whitespace().skip(1);
+            match columns.next().unwrap() {
+                "|" => {}
+                number => line = number.parse().unwrap(),
+            }
+            (columns.next().unwrap().to_string(), line)
+        })
+        .collect()
+}
+
+#[test]
+fn instructions_are_on_the_line_of_their_statement() {
+    let source = "var a = 1;\nprint a;\nprint 2;\n\nprint \"x\";\n{ var b = 3; print b; }";
+    let prints: Vec<usize> = lines(source)
+        .into_iter()
+        .filter(|(op, _)| op == "OP_PRINT")
+        .map(|(_, line)| line)
+        .collect();
+    assert_eq!(prints, [2, 3, 5, 6]);
+
+    let constants: Vec<usize> = lines(source)
+        .into_iter()
+        .filter(|(op, _)| op == "OP_CONSTANT")
+        .map(|(_, line)| line)
+        .collect();
+    assert_eq!(constants, [1, 3, 5, 6]);
+}
+
+#[test]
+fn closures_capture_variables_not_values() {
+    let (stdout, error) = run_both(
+        "fun counter() {\n  var count = 0;\n  fun next() { count = count + 1; return count; }\n  return next;\n}\n\
+         var a = counter();\nvar b = counter();\nprint a(); print a(); print b();\n\
+         var get; var set;\n{\n  var shared = \"before\";\n  fun g() { return shared; }\n  fun s(v) { shared = v; }\n  get = g; set = s;\n}\n\
+         set(\"after\"); print get();\n\
+         for (var i = 0; i < 3; i = i + 1) { fun f() { return i; } print f(); }",
+    );
+    assert_eq!(stdout, "1\n2\n1\nafter\n0\n1\n2\n");
+    assert_eq!(error, None);
+}
+
+#[test]
+fn classes_bind_this_and_call_up_through_super() {
+    let (stdout, error) = run_both(
+        "class A {\n  init(name) { this.name = name; }\n  greet() { return \"A \" + this.name; }\n}\n\
+         class B < A {\n  init(name) { super.init(name + \"!\"); }\n  greet() { return \"B, \" + super.greet(); }\n}\n\
+         var b = B(\"bee\");\nprint b.greet();\nvar greet = b.greet;\nprint greet();\n\
+         print b;\nprint B;\nprint b.init(\"again\") == b;\nprint b.name;",
+    );
+    assert_eq!(
+        stdout,
+        "B, A bee!\nB, A bee!\nB instance\nB\ntrue\nagain!\n"
+    );
+    assert_eq!(error, None);
+}
+
+#[test]
+fn runtime_errors_stop_at_the_same_line() {
+    let cases = [
+        ("print 1;\nprint -\"a\";", "Operand must be a number.", 2),
+        (
+            "var a = 1;\n\nprint a + nil;",
+            "Operands must be two numbers or two strings.",
+            3,
+        ),
+        (
+            "fun f(a) {}\nf(1,\n  2);",
+            "Expected 1 arguments but got 2.",
+            3,
+        ),
+        ("class C {}\nprint C().x;", "Undefined property 'x'.", 2),
+        ("print undefined;", "Undefined variable 'undefined'.", 1),
+        (
+            "fun f() {\n  return g();\n}\nf();",
+            "Undefined variable 'g'.",
+            2,
+        ),
+    ];
+    for (source, message, line) in cases {
+        let (_, error) = run_both(source);
+        assert_eq!(error, Some((message.to_string(), line)), "{}", source);
+    }
+}
+
+#[test]
+fn strings_compare_by_their_characters() {
+    let (stdout, error) = run_both(
+        "print \"apple\" < \"banana\"; print \"b\" <= \"a\"; print \"ab\" > \"a\";\n\
+         print \"Z\" < \"a\"; print \"é\" >= \"z\"; print \"a\" < 1;",
+    );
+    assert_eq!(stdout, "true\nfalse\ntrue\ntrue\ntrue\n");
+    // Anything else keeps the error for numbers
+    assert_eq!(error, Some(("Operands must be numbers.".to_string(), 2)));
+}
+
+#[test]
+fn misspelled_names_suggest_the_closest_defined_one() {
+    let cases = [
+        (
+            "var count = 1;\nprint cuont;",
+            "Undefined variable 'cuont'. Did you mean 'count'?",
+        ),
+        (
+            "print clok();",
+            "Undefined variable 'clok'. Did you mean 'clock'?",
+        ),
+        ("cuont = 2;", "Undefined variable 'cuont'."),
+        (
+            "class P { area() {} }\nvar p = P();\np.width = 1;\nprint p.widht;",
+            "Undefined property 'widht'. Did you mean 'width'?",
+        ),
+        (
+            "class P { area() {} }\nclass Q < P {}\nQ().aera();",
+            "Undefined property 'aera'. Did you mean 'area'?",
+        ),
+        // Nothing close enough
+        ("class P {}\nprint P().x;", "Undefined property 'x'."),
+    ];
+    for (source, message) in cases {
+        let (_, error) = run_both(source);
+        assert_eq!(
+            error.map(|(error, _)| error).as_deref(),
+            Some(message),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn runaway_recursion_is_a_stack_overflow_on_both() {
+    let (stdout, error) = run_both("print \"start\";\nfun r(n) {\n  return r(n + 1);\n}\nr(0);");
+    assert_eq!(stdout, "start\n");
+    assert_eq!(error, Some(("Stack overflow.".to_string(), 3)));
+}
+
+#[test]
+fn running_out_of_constants_is_reported_once_per_chunk() {
+    let numbers: String = (0..300).map(|n| format!("print {};\n", n)).collect();
+    let source = format!("{}fun f() {{\n{}}}", numbers, numbers);
+    let options = Options {
+        backend: Backend::Vm,
+        ..Options::default()
+    };
+    let ExitStatus::CompileError(errors) = run(&source, &options, &mut Output::default()) else {
+        panic!("expected a compile error");
+    };
+    let errors: Vec<(String, usize)> = errors
+        .into_iter()
+        .map(|error| (error.message, error.line))
+        .collect();
+    // The script's 257th constant, then the function's
+    let message = "Too many constants in one chunk.".to_string();
+    assert_eq!(errors, [(message.clone(), 257), (message, 558)]);
+}
+
+#[test]
+fn debugging_needs_the_tree_walker() {
+    let options = Options {
+        backend: Backend::Vm,
+        ..Options::default()
+    };
+    let status = debug("print 1;", &options, &mut Output::default());
+    assert_eq!(status.exit_code(), 64);
+    assert!(
+        matches!(status, ExitStatus::UsageError(message) if message == "debug only works with the tree-walker backend.")
+    );
+}
+
+#[test]
+fn num_only_reads_number_literals() {
+    let (stdout, error) = run_both(
+        "print num(\"12.5\"); print num(\" -3 \"); print num(\"007\");\n\
+         print num(\"inf\"); print num(\"NaN\"); print num(\"1e3\"); print num(\".5\");\n\
+         print num(\"5.\"); print num(\"+1\"); print num(\"\"); print num(\"0x10\");",
+    );
+    assert_eq!(
+        stdout,
+        "12.5\n-3\n7\nnil\nnil\nnil\nnil\nnil\nnil\nnil\nnil\n"
+    );
+    assert_eq!(error, None);
+}
+
+#[test]
+fn code_that_never_runs_isnt_compiled() {
+    let source = "fun f() {\n  return 1;\n  print \"after return\";\n}\n\
+                  if (false) print \"ruled out\"; else print f();\n\
+                  while (false) print \"never\";\n\
+                  while (true) { break; print \"after break\"; }";
+    let ops: Vec<String> = lines(source).into_iter().map(|(op, _)| op).collect();
+    assert_eq!(ops.iter().filter(|op| *op == "OP_PRINT").count(), 1);
+    // Only `while (true)` still tests its condition
+    let tests = ops
+        .iter()
+        .filter(|op| *op == "OP_POP_JUMP_IF_FALSE")
+        .count();
+    assert_eq!(tests, 1, "{:?}", ops);
+
+    let mut out = Output::default();
+    disassemble(source, &Options::default(), &mut out);
+    assert!(!out.stdout.contains("after return"), "{}", out.stdout);
+
+    let (stdout, error) = run_both(source);
+    assert_eq!(stdout, "1\n");
+    assert_eq!(error, None);
+}
+
+#[test]
+fn cycles_are_freed_while_what_is_reachable_stays() {
+    // Each iteration leaves two instances holding each other and a closure
+    // holding itself, and adds a node to a chain that's still in use
+    let source = "class Node {}\n\
+                  fun counter() { var count = 0; fun next() { count = count + 1; return next; } return next; }\n\
+                  var chain = nil;\n\
+                  for (var i = 0; i < 20000; i = i + 1) {\n\
+                    var a = Node(); var b = Node(); a.other = b; b.other = a;\n\
+                    counter()();\n\
+                    var node = Node(); node.value = i; node.next = chain; chain = node;\n\
+                  }\n\
+                  var sum = 0;\n\
+                  while (chain != nil) { sum = sum + chain.value; chain = chain.next; }\n\
+                  print sum;";
+    let (stdout, error) = run_both(source);
+    assert_eq!(stdout, "199990000\n");
+    assert_eq!(error, None);
+
+    let options = Options {
+        backend: Backend::Vm,
+        ..Options::default()
+    };
+    let mut out = Output::default();
+    run(source, &options, &mut out);
+    lox::gc::collect();
+    assert!(lox::gc::live() < 1000, "{}", lox::gc::live());
+}
+
+#[test]
+fn comparing_backends_times_a_program_on_each() {
+    let options = Options {
+        iterations: Some(2),
+        path: Some("fib.lox".into()),
+        ..Options::default()
+    };
+    let source = "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); }\nfib(10);";
+    let mut out = Output::default();
+    assert!(matches!(
+        compare_backends(source, &options, &mut out),
+        ExitStatus::Success
+    ));
+    let header = compare_backends_header();
+    assert_eq!(
+        header.split_whitespace().collect::<Vec<_>>(),
+        ["benchmark", "tree-walker", "vm", "speedup"]
+    );
+    // Lined up under the header, with what it printed left out
+    let columns: Vec<&str> = out.stdout.split_whitespace().collect();
+    assert_eq!((columns.len(), columns[0]), (4, "fib.lox"));
+    assert!(columns[3].ends_with('x'), "{}", out.stdout);
+    assert_eq!(out.stdout.chars().count(), header.chars().count());
+
+    // Failing on either backend fails the comparison
+    let status = compare_backends("print nope;", &options, &mut Output::default());
+    assert!(matches!(status, ExitStatus::RuntimeError(_)));
+}
+
+#[test]
+fn frequent_pairs_run_as_one_instruction_unless_a_jump_lands_between() {
+    let source = "class P {}\nvar p = P();\np.n = 0;\nfun count(p) {\n  \
+                  var q = p;\n  while (p.n < 3) p.n = p.n + 1;\n  q = p;\n  return p.n - q.n + p.n - 1;\n}\nprint count(p);\n\
+                  var x = 5;\nprint 1 < (x or 2);";
+    let ops: Vec<String> = lines(source).into_iter().map(|(op, _)| op).collect();
+    // The `2` is where `x or` jumps to when `x` is true, so comparing with
+    // it can't be one instruction
+    assert!(ops.contains(&"OP_LESS".to_string()), "{:?}", ops);
+    assert!(!ops.contains(&"OP_LESS_CONSTANT".to_string()), "{:?}", ops);
+    assert!(
+        ops.contains(&"OP_SET_PROPERTY_POP".to_string()),
+        "{:?}",
+        ops
+    );
+
+    let mut out = Output::default();
+    disassemble(source, &Options::default(), &mut out);
+    for op in [
+        "OP_GET_LOCAL_PROPERTY",
+        "OP_LESS_CONSTANT",
+        "OP_ADD_CONSTANT",
+        "OP_SUBTRACT_CONSTANT",
+        "OP_POP_JUMP_IF_FALSE",
+        "OP_GET_LOCALS",
+        "OP_SET_LOCAL_POP",
+    ] {
+        assert!(out.stdout.contains(op), "{}", out.stdout);
+    }
+
+    let (stdout, error) = run_both(source);
+    assert_eq!(stdout, "2\ntrue\n");
+    assert_eq!(error, None);
+    // Failing where the operator is, not its constant
+    let (_, error) = run_both("var s = \"a\";\nprint s -\n  1;");
+    assert_eq!(error, Some(("Operands must be numbers.".to_string(), 2)));
+}
+
+#[test]
+fn the_op_histogram_counts_opcodes_and_pairs_run() {
+    let options = Options {
+        backend: Backend::Vm,
+        op_histogram: true,
+        ..Options::default()
+    };
+    let mut out = Output::default();
+    let status = run("for (var i = 0; i < 3; i = i + 1) {}", &options, &mut out);
+    assert_eq!(status.exit_code(), 0, "{}", out.stderr);
+    let counts: Vec<(&str, &str)> = out
+        .stderr
+        .lines()
+        .filter_map(|row| {
+            let mut columns = row.split_whitespace();
+            Some((columns.next()?, columns.next()?))
+        })
+        .collect();
+    assert!(
+        counts.contains(&("OP_LESS_CONSTANT", "4")),
+        "{}",
+        out.stderr
+    );
+    assert!(counts.contains(&("OP_LOOP", "3")), "{}", out.stderr);
+    assert!(
+        out.stderr.contains("OP_GET_LOCAL OP_LESS_CONSTANT"),
+        "{}",
+        out.stderr
+    );
+
+    let status = run(
+        "print 1;",
+        &Options {
+            op_histogram: true,
+            ..Options::default()
+        },
+        &mut out,
+    );
+    assert_eq!(status.exit_code(), 64);
+}
+
+#[test]
+fn heap_stats_show_young_cycles_freed_by_minor_collections() {
+    // Two instances holding each other die young every iteration; the chain
+    // lives on and gets promoted, all but the nodes made since the last
+    // minor collection
+    let source = "class Node {}\n\
+                  var chain = nil;\n\
+                  for (var i = 0; i < 5000; i = i + 1) {\n\
+                    var a = Node(); var b = Node(); a.other = b; b.other = a;\n\
+                    var node = Node(); node.next = chain; chain = node;\n\
+                  }";
+    for backend in [Backend::TreeWalk, Backend::Vm] {
+        let options = Options {
+            backend,
+            heap_stats: true,
+            ..Options::default()
+        };
+        // Dropping the chain at the end takes a deep stack
+        let out = thread::Builder::new()
+            .stack_size(lox::STACK_SIZE)
+            .spawn(move || {
+                let mut out = Output::default();
+                assert_eq!(run(source, &options, &mut out).exit_code(), 0);
+                out
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let numbers: Vec<usize> = out
+            .stderr
+            .split(|ch: char| !ch.is_ascii_digit())
+            .filter_map(|number| number.parse().ok())
+            .collect();
+        let [minor, minor_freed, promoted, _, _, young, old] = numbers[..] else {
+            panic!("{}", out.stderr);
+        };
+        assert!(minor >= 10, "{}", out.stderr);
+        assert!(minor_freed >= 9000, "{}", out.stderr);
+        assert!(promoted >= 4000, "{}", out.stderr);
+        assert!(young + old >= 5000, "{}", out.stderr);
+    }
+}
@@ -0,0 +1,48 @@
+//! The browser bindings, run on the host by `cargo test --features wasm`
+//! and in wasm by `wasm-pack test --node -- --features wasm`, where there's
+//! only the wasm stack to recurse on.
+#![cfg(feature = "wasm")]
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+use lox::wasm::{limits, parse_to_json, run_capture_output};
+
+/// A program that calls itself `depth` times.
+fn recursion(depth: usize) -> String {
+    format!(
+        "fun down(n) {{\n  if (n == 0) return 0;\n  return down(n - 1) + 1;\n}}\nprint down({});",
+        depth
+    )
+}
+
+#[test]
+fn runaway_recursion_is_a_stack_overflow_on_both_backends() {
+    for vm in [false, true] {
+        let output = run_capture_output(&recursion(limits().max_call_depth), vm);
+        assert_eq!(output.stdout(), "");
+        assert!(
+            output.stderr().starts_with("Stack overflow."),
+            "{}",
+            output.stderr()
+        );
+        assert_eq!(output.exit_code(), 70);
+
+        // A debug build takes tens of kilobytes of stack a call, so the
+        // stack runs out long before the calls do, but not this soon
+        let output = run_capture_output(&recursion(10), vm);
+        assert_eq!(output.stderr(), "");
+        assert_eq!(output.stdout(), "10\n");
+    }
+}
+
+#[test]
+fn deep_nesting_is_an_error_well_short_of_the_cli_limit() {
+    let depth = limits().max_nesting_depth + 1;
+    let source = format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+    let output = run_capture_output(&source, false);
+    assert_eq!(output.exit_code(), 65, "{}", output.stderr());
+
+    let json = parse_to_json(&format!("{}1{}", "(".repeat(depth), ")".repeat(depth)));
+    assert!(!json.ends_with(r#""errors":[]}"#), "{}", json);
+}